@@ -0,0 +1 @@
+import("../pkg/index.js").then((wasm) => wasm.worker_main()).catch(console.error);
@@ -0,0 +1,134 @@
+use std::f32::consts::TAU;
+
+use crate::{
+    engine::Renderer,
+    math::{Point, Rect},
+    palette::Color,
+    rng::Rng,
+};
+
+const AMBIENT_SEED: u64 = 0x5EED_A7B1; // 舞う粒の並びを決める種。ステージの乱数とは別に持つ
+const AMBIENT_ALPHA: f32 = 0.5;
+
+/// ステージの背景に舞わせる天気
+#[derive(Clone, Copy, PartialEq)]
+pub enum Weather {
+    Petals, // ゆらゆらと揺れながら落ちる花びら
+    Snow,   // ゆっくり落ちる雪
+    Rain,   // 斜めに速く流れる雨の筋
+}
+
+impl Weather {
+    /// 一度に舞わせる粒の数
+    fn count(self) -> usize {
+        match self {
+            Weather::Petals => 24,
+            Weather::Snow => 48,
+            Weather::Rain => 40,
+        }
+    }
+
+    /// 1フレームに落ちる速さの範囲
+    fn speed(self) -> (f32, f32) {
+        match self {
+            Weather::Petals => (0.6, 1.2),
+            Weather::Snow => (0.3, 0.8),
+            Weather::Rain => (8.0, 12.0),
+        }
+    }
+
+    /// 左右に揺れる幅
+    fn sway(self) -> f32 {
+        match self {
+            Weather::Petals => 12.0,
+            Weather::Snow => 4.0,
+            Weather::Rain => 0.0,
+        }
+    }
+}
+
+/// 舞っている粒1つ。位置はプレイフィールドの左上からの比率で持つ
+#[derive(Clone)]
+struct Mote {
+    x: f32,
+    y: f32,
+    speed: f32, // 1フレームに落ちるピクセル数
+    phase: f32, // 揺れの位相
+}
+
+/// 背景の上に重ねる天気の層。見た目だけのもので、ステージの進行には関わらない。
+#[derive(Clone, Default)]
+pub struct Ambient {
+    weather: Option<Weather>,
+    motes: Vec<Mote>,
+    frame: u32,
+}
+
+impl Ambient {
+    /// 天気を変える。同じ天気なら粒はそのまま舞わせ続ける。
+    pub fn set_weather(&mut self, weather: Option<Weather>) {
+        if self.weather == weather {
+            return;
+        }
+        self.weather = weather;
+        let Some(weather) = weather else {
+            self.motes.clear();
+            return;
+        };
+        // 天気ごとに同じ並びになるよう、毎回同じ種から作る
+        let mut rng = Rng::new(AMBIENT_SEED);
+        let (min, max) = weather.speed();
+        self.motes = (0..weather.count())
+            .map(|_| Mote {
+                x: rng.next_f32(),
+                y: rng.next_f32(),
+                speed: rng.range(min, max),
+                phase: rng.range(0.0, TAU),
+            })
+            .collect();
+    }
+
+    /// `field`はプレイフィールドの範囲。下の端を越えた粒は上に戻す
+    pub fn update(&mut self, field: &Rect) {
+        self.frame = self.frame.wrapping_add(1);
+        for mote in self.motes.iter_mut() {
+            mote.y += mote.speed / field.height;
+            if mote.y > 1.0 {
+                mote.y -= 1.0;
+            }
+        }
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, field: &Rect) {
+        let Some(weather) = self.weather else {
+            return;
+        };
+        renderer.set_color(match weather {
+            Weather::Petals => Color::Ambient,
+            Weather::Snow | Weather::Rain => Color::Border,
+        });
+        renderer.set_alpha(AMBIENT_ALPHA);
+        for mote in self.motes.iter() {
+            let sway = (self.frame as f32 * 0.03 + mote.phase).sin() * weather.sway();
+            let x = field.x + (field.width * mote.x + sway).rem_euclid(field.width);
+            let y = field.y + field.height * mote.y;
+            let at = Point { x, y };
+            match weather {
+                Weather::Petals => renderer.draw_circle(&at, 3.0),
+                Weather::Snow => renderer.draw_circle(&at, 1.5),
+                Weather::Rain => {
+                    // 筋は落ちる向きに少し傾け、プレイフィールドからはみ出さないように縮める
+                    let length = (mote.speed * 2.0).min(y - field.y);
+                    renderer.draw_line(
+                        &Point {
+                            x: (x - length * 0.2).max(field.x),
+                            y: y - length,
+                        },
+                        &at,
+                    );
+                }
+            }
+        }
+        renderer.set_alpha(1.0);
+    }
+}
@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use crate::{
+    ambient::{Ambient, Weather},
+    constants::{per_frame, ARENA_RING_SPEED},
+    engine::Renderer,
+    math::Point,
+    palette::Color,
+    playfield::Playfield,
+    quality::Quality,
+    timeline::{Timed, Timeline},
+};
+
+const LINE_SPACING: f32 = 60.0; // 流れていく横線の間隔
+const LINE_ALPHA: f32 = 0.25;
+const EASING: f32 = per_frame(0.03); // 1フレームで目標の値にどれだけ近づくか（基準の速さで0.03）
+const ARENA_RING_SPACING: f32 = 80.0; // ボス戦の場で広がっていく輪の間隔
+
+/// ステージの進行に合わせて背景に起こす変化
+#[derive(Clone, Copy)]
+pub enum BackgroundChange {
+    ScrollSpeed(f32),         // 背景が流れる速さを変える（1フレームで何ピクセル進むか）
+    Tint(Color, f32),         // 背景全体に色を重ねる（色と不透明度）
+    EnterArena,               // ボス戦の場に入る。流れが止まり、輪が広がる背景になる
+    Weather(Option<Weather>), // 舞わせる天気を変える（`None`で止める）
+}
+
+/// `at`フレーム目に起こす背景の変化
+#[derive(Clone, Copy)]
+pub struct BackgroundEvent {
+    pub at: u32,
+    pub change: BackgroundChange,
+}
+
+impl Timed for BackgroundEvent {
+    fn at(&self) -> u32 {
+        self.at
+    }
+}
+
+/// スクロールする背景。変化は一度に切り替えず、少しずつ目標の値に近づける。
+#[derive(Clone)]
+pub struct Background {
+    timeline: Timeline<BackgroundEvent>,
+    offset: f32, // ここまでに流れた距離
+    speed: f32,
+    target_speed: f32,
+    tint: Color,
+    tint_alpha: f32,
+    target_tint_alpha: f32,
+    arena: f32, // ボス戦の場への切り替わり具合（0から1）
+    arena_entered: bool,
+    ring: f32, // 一番内側の輪の半径
+    ambient: Ambient,
+}
+
+impl Background {
+    pub fn new(events: impl Into<Rc<[BackgroundEvent]>>) -> Self {
+        Self {
+            timeline: Timeline::new(events),
+            offset: 0.0,
+            speed: 0.0,
+            target_speed: 0.0,
+            tint: Color::Background,
+            tint_alpha: 0.0,
+            target_tint_alpha: 0.0,
+            arena: 0.0,
+            arena_entered: false,
+            ring: 0.0,
+            ambient: Ambient::default(),
+        }
+    }
+
+    pub fn apply(&mut self, change: BackgroundChange) {
+        match change {
+            BackgroundChange::ScrollSpeed(speed) => self.target_speed = speed,
+            BackgroundChange::Tint(color, alpha) => {
+                self.tint = color;
+                self.target_tint_alpha = alpha;
+            }
+            BackgroundChange::EnterArena => {
+                self.arena_entered = true;
+                self.target_speed = 0.0;
+            }
+            BackgroundChange::Weather(weather) => self.ambient.set_weather(weather),
+        }
+    }
+
+    /// `frame`はステージ開始からの経過フレーム
+    pub fn update(&mut self, frame: u32, playfield: &Playfield) {
+        while let Some(event) = self.timeline.advance(frame) {
+            self.apply(event.change);
+        }
+
+        self.speed += (self.target_speed - self.speed) * EASING;
+        self.tint_alpha += (self.target_tint_alpha - self.tint_alpha) * EASING;
+        if self.arena_entered {
+            self.arena += (1.0 - self.arena) * EASING;
+            self.ring = (self.ring + ARENA_RING_SPEED) % ARENA_RING_SPACING;
+        }
+        self.offset = (self.offset + self.speed) % LINE_SPACING;
+        self.ambient.update(playfield.rect());
+    }
+
+    /// 品質を下げているときは、流れる線や輪、舞う天気を省いて色だけを重ねる
+    pub fn draw(&self, renderer: &dyn Renderer, playfield: &Playfield, quality: Quality) {
+        let field = playfield.rect();
+        if quality == Quality::High {
+            self.draw_details(renderer, playfield);
+        }
+
+        if self.tint_alpha > 0.0 {
+            renderer.set_color(self.tint);
+            renderer.set_alpha(self.tint_alpha);
+            renderer.fill_rect(field);
+        }
+        renderer.set_alpha(1.0);
+    }
+
+    fn draw_details(&self, renderer: &dyn Renderer, playfield: &Playfield) {
+        let field = playfield.rect();
+
+        // 流れていく横線。ボス戦の場に入ると消えていく
+        renderer.set_color(Color::Border);
+        renderer.set_alpha(LINE_ALPHA * (1.0 - self.arena));
+        let mut y = playfield.top() + self.offset;
+        while y <= playfield.bottom() {
+            renderer.draw_line(
+                &Point {
+                    x: playfield.left(),
+                    y,
+                },
+                &Point {
+                    x: playfield.right(),
+                    y,
+                },
+            );
+            y += LINE_SPACING;
+        }
+
+        // ボス戦の場では、中心から輪が広がり続ける
+        if self.arena > 0.0 {
+            renderer.set_alpha(LINE_ALPHA * self.arena);
+            let center = Point {
+                x: playfield.center_x(),
+                y: playfield.center_y(),
+            };
+            let mut radius = self.ring;
+            while radius < field.width.min(field.height) / 2.0 {
+                renderer.draw_circle(&center, radius.max(1.0));
+                radius += ARENA_RING_SPACING;
+            }
+        }
+
+        self.ambient.draw(renderer, field);
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    constants::{BANNER_HOLD_FRAMES, BANNER_SLIDE_FRAMES},
+    engine::Renderer,
+    math::Point,
+    palette::Color,
+    playfield::Playfield,
+};
+
+const SLIDE_DISTANCE: f32 = 120.0; // 左からどれだけ滑り込んでくるか
+const SUBTITLE_SPACING: f32 = 32.0; // 見出しと小見出しの間隔
+
+/// ステージやボスの始まりに出す見出し。左から滑り込み、しばらく止まってから消えていく。
+#[derive(Clone)]
+pub struct Banner {
+    title: String,
+    subtitle: Option<&'static str>,
+    frame: u32,
+}
+
+impl Banner {
+    pub fn new(title: String, subtitle: Option<&'static str>) -> Self {
+        Self {
+            title,
+            subtitle,
+            frame: 0,
+        }
+    }
+
+    /// 1フレーム進める。表示し終わったら`false`を返す。
+    pub fn update(&mut self) -> bool {
+        self.frame += 1;
+        self.frame < BANNER_SLIDE_FRAMES * 2 + BANNER_HOLD_FRAMES
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, playfield: &Playfield) {
+        let slide = (self.frame as f32 / BANNER_SLIDE_FRAMES as f32).min(1.0);
+        let fade_start = BANNER_SLIDE_FRAMES + BANNER_HOLD_FRAMES;
+        let fade = self.frame.saturating_sub(fade_start) as f32 / BANNER_SLIDE_FRAMES as f32;
+        // 滑り込むときは減速しながら止まる
+        let offset = -SLIDE_DISTANCE * (1.0 - slide) * (1.0 - slide);
+
+        renderer.save();
+        renderer.translate(offset, 0.0);
+        renderer.set_alpha((slide * (1.0 - fade)).clamp(0.0, 1.0));
+        renderer.set_color(Color::Text);
+        let x = playfield.center_x();
+        let y = playfield.top() + playfield.rect().height * 0.35;
+        renderer.draw_large_text_centered(&self.title, &Point { x, y });
+        if let Some(subtitle) = self.subtitle {
+            renderer.draw_text_centered(
+                subtitle,
+                &Point {
+                    x,
+                    y: y + SUBTITLE_SPACING,
+                },
+            );
+        }
+        renderer.restore();
+    }
+}
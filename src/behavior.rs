@@ -0,0 +1,81 @@
+use std::rc::Rc;
+
+use crate::{
+    constants::{frames, per_frame},
+    math::{Point, Vector},
+};
+
+/// イベント列では書けない弾の動き。弾ごとの状態は持たず、`BulletCtx`の値だけで動かす。
+pub trait BulletBehavior {
+    /// 弾が速度の分だけ動いたあとに、毎フレーム呼ばれる
+    fn on_tick(&self, ctx: &mut BulletCtx);
+}
+
+/// 振る舞いが読み書きできる弾の状態
+pub struct BulletCtx<'a> {
+    pub frame: u16, // 弾が生成されてからの経過フレーム
+    pub pos: &'a mut Point,
+    pub vel: &'a mut Vector,
+    pub parent: Option<Parent>, // 弾を撃った敵（もういなければ`None`）
+}
+
+/// 弾を撃った敵の今の動き
+#[derive(Clone, Copy)]
+pub struct Parent {
+    pub pos: Point,
+    pub vel: Vector, // このフレームの速度
+}
+
+/// 名前で選べる振る舞いの一覧。ステージのデータからはこの名前で指定する。
+pub const NAMES: [&str; 2] = ["wobble", "orbit"];
+
+/// 名前から振る舞いを探す
+pub fn find(name: &str) -> Option<Rc<dyn BulletBehavior>> {
+    match name {
+        "wobble" => Some(Rc::new(Wobble {
+            amplitude: 12.0,
+            period: frames(40) as u16,
+        })),
+        "orbit" => Some(Rc::new(Orbit {
+            deg_per_frame: per_frame(1.5),
+        })),
+        _ => None,
+    }
+}
+
+/// 進む向きに対して横に、正弦波で揺れる
+struct Wobble {
+    amplitude: f32, // 揺れの幅（ピクセル）
+    period: u16,    // 1往復のフレーム数
+}
+
+impl BulletBehavior for Wobble {
+    fn on_tick(&self, ctx: &mut BulletCtx) {
+        // 横へのずれが`amplitude * sin`になるように、1フレーム分の差だけ動かす
+        let omega = std::f32::consts::TAU / self.period as f32;
+        let step = self.amplitude * omega * (omega * ctx.frame as f32).cos();
+        let side = ctx.vel.rotate(90.0);
+        if side.length() > 0.0 {
+            *ctx.pos += side.with_length(step);
+        }
+    }
+}
+
+/// 撃った敵の周りを回る。敵が動けば一緒に動き、敵がいなくなればまっすぐ進む。
+struct Orbit {
+    deg_per_frame: f32,
+}
+
+impl BulletBehavior for Orbit {
+    fn on_tick(&self, ctx: &mut BulletCtx) {
+        let Some(parent) = ctx.parent else {
+            return;
+        };
+        // 敵と一緒に動いてから、敵を中心に回る
+        *ctx.pos += parent.vel;
+        let mut pos = parent.pos;
+        pos += Vector::between(&parent.pos, ctx.pos).rotate(self.deg_per_frame);
+        *ctx.pos = pos;
+        *ctx.vel = ctx.vel.rotate(self.deg_per_frame);
+    }
+}
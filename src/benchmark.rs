@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::{
+    constants::ticks,
+    engine::{Game, KeyState, PhaseTimes, Renderer},
+    input::{Actions, Device},
+    layout::Layout,
+    level::Level,
+    math::{Point, Rect},
+    palette::Color,
+    quality::Quality,
+    settings::Settings,
+};
+
+const DEFAULT_COUNTS: [usize; 4] = [500, 1000, 2000, 4000]; // 指定がなければ順に試す弾の数
+const MAX_COUNT: usize = 20_000;
+const BENCHMARK_SEED: u64 = 1;
+const WARMUP_FRAMES: u32 = ticks(2.0); // 弾が場に行き渡るまでは測らない
+const MEASURE_FRAMES: u32 = ticks(5.0);
+
+/// `?benchmark`の値から、試す弾の数を読む。空なら`DEFAULT_COUNTS`、`500,2000`のようにカンマで区切って指定できる
+pub fn parse_counts(text: &str) -> Result<Vec<usize>> {
+    if text.trim().is_empty() {
+        return Ok(DEFAULT_COUNTS.to_vec());
+    }
+    text.split(',')
+        .map(|count| {
+            let count: usize = count
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Invalid bullet count \"{}\"", count))?;
+            if count == 0 || count > MAX_COUNT {
+                return Err(anyhow!("Bullet counts must be 1 to {}", MAX_COUNT));
+            }
+            Ok(count)
+        })
+        .collect()
+}
+
+/// 1つの段で測った時間の合計
+#[derive(Default)]
+struct Totals {
+    frames: u32,     // 測った描画の回数
+    delta_ms: f64,   // 描画の間隔の合計
+    times: [f64; 3], // 更新、描画命令作り、キャンバスへの描画の合計
+}
+
+impl Totals {
+    fn record(&mut self, times: PhaseTimes) {
+        for (total, ms) in
+            self.times
+                .iter_mut()
+                .zip([times.update_ms, times.draw_ms, times.present_ms])
+        {
+            *total += ms as f64;
+        }
+    }
+
+    /// 弾`count`発のときの結果を1行で
+    fn summary(&self, count: usize) -> String {
+        let frames = self.frames.max(1) as f64;
+        let fps = if self.delta_ms > 0.0 {
+            1000.0 * self.frames as f64 / self.delta_ms
+        } else {
+            0.0
+        };
+        format!(
+            "{:>5} bullets: {:5.1} fps  update {:.2}ms  draw {:.2}ms  present {:.2}ms",
+            count,
+            fps,
+            self.times[0] / frames,
+            self.times[1] / frames,
+            self.times[2] / frames
+        )
+    }
+}
+
+/// 隠しの性能測定の画面。URLに`?benchmark`を付けて開くと、ゲームの代わりにこれが始まる。
+/// 決まった数の弾を動きを混ぜて出し続け、数ごとに保てたfpsと、1回の描画あたりの処理の段ごとの時間を
+/// 画面とブラウザのコンソールに出す。結果をそのまま貼ってもらえば、遊ぶ人の環境での重さを比べられる。
+pub struct Benchmark {
+    level: Level,
+    settings: Settings,
+    counts: Vec<usize>,
+    step: usize, // 今試している`counts`の番号
+    frame: u32,  // 今の段で進めたフレーム数
+    totals: Totals,
+    results: Vec<String>,
+}
+
+impl Benchmark {
+    pub fn new(counts: Vec<usize>) -> Self {
+        log!("Benchmark started: {:?} bullets", counts);
+        Self {
+            level: Level::benchmark(BENCHMARK_SEED),
+            settings: Settings::default(),
+            counts,
+            step: 0,
+            frame: 0,
+            totals: Totals::default(),
+            results: Vec::new(),
+        }
+    }
+
+    fn is_measuring(&self) -> bool {
+        self.step < self.counts.len() && self.frame >= WARMUP_FRAMES
+    }
+}
+
+#[async_trait(?Send)]
+impl Game for Benchmark {
+    async fn initialize(&self) -> Result<Box<dyn Game>> {
+        Err(anyhow!("Error: Benchmark is already initialized!"))
+    }
+
+    fn update(&mut self, _keystate: &KeyState) {
+        let Some(&count) = self.counts.get(self.step) else {
+            return;
+        };
+        self.level.fill_bullets(count);
+        self.level.update(&[Actions::default()]);
+        self.frame += 1;
+        if self.frame < WARMUP_FRAMES + MEASURE_FRAMES {
+            return;
+        }
+        let summary = self.totals.summary(count);
+        log!("{}", summary);
+        self.results.push(summary);
+        self.step += 1;
+        self.frame = 0;
+        self.totals = Totals::default();
+        self.level = Level::benchmark(BENCHMARK_SEED);
+        if self.step == self.counts.len() {
+            log!("Benchmark finished:\n{}", self.results.join("\n"));
+        }
+    }
+
+    fn draw(&self, renderer: &dyn Renderer) {
+        renderer.clear(&Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 600.0,
+            height: 600.0,
+        });
+        if self.step < self.counts.len() {
+            self.level.draw(
+                renderer,
+                &self.settings,
+                Device::default(),
+                Quality::High,
+                Layout::Landscape,
+            );
+        }
+
+        renderer.set_color(Color::Text);
+        renderer.draw_text("BENCHMARK", &Point { x: 60.0, y: 60.0 });
+        let current = match self.counts.get(self.step) {
+            Some(&count) if self.is_measuring() => self.totals.summary(count),
+            Some(&count) => format!("{:>5} bullets: warming up", count),
+            None => "Finished. Results are also in the browser console".to_string(),
+        };
+        for (i, line) in self.results.iter().chain([&current]).enumerate() {
+            renderer.draw_text(
+                line,
+                &Point {
+                    x: 60.0,
+                    y: 86.0 + 24.0 * i as f32,
+                },
+            );
+        }
+    }
+
+    fn record_frame_time(&mut self, delta_ms: f32) {
+        if self.is_measuring() {
+            self.totals.frames += 1;
+            self.totals.delta_ms += delta_ms as f64;
+        }
+    }
+
+    fn record_phase_times(&mut self, times: PhaseTimes) {
+        if self.is_measuring() {
+            self.totals.record(times);
+        }
+    }
+}
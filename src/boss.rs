@@ -0,0 +1,212 @@
+//! ボスの攻撃の定義と、それをRustのコードで順に並べて書くための組み立て役。
+//! 組み立てるときに誤りを確かめるので、できあがったスペルの列はそのまま使える。
+
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    constants::ticks,
+    damage::Resistance,
+    item::ItemKind,
+    level::{self, EnemyEvent},
+};
+
+/// ボスの攻撃1つ分
+#[derive(Clone)]
+pub struct Spell {
+    pub name: &'static str,
+    pub frames: u16,                // 制限時間
+    pub survival: bool,             // 耐久スペル（ボスは無敵で、時間切れでのみ終わる）
+    pub health: u32,                // 倒して終わらせるのに必要な傷の量（耐久スペルでは使わない）
+    pub bonus: u64,                 // 被弾もボムもせずに取得したときの得点
+    pub drops: &'static [ItemKind], // 取得したときにボスの位置から落とすアイテム
+    pub guide_frames: u16, // 弾を撃つ何フレーム前から通り道の予告線を出すか（0なら出さない）
+    pub movement: BossMovement,
+    pub resistance: Resistance,   // スペルが始まってから効く守り
+    pub events: Rc<[EnemyEvent]>, // スペル中にボスが起こすイベント
+}
+
+/// スペル中のボスの動き方
+#[derive(Clone, Copy)]
+pub struct BossMovement {
+    pub speed: f32,  // 目的地へ向かう速さ（基準の速さでの1フレームあたり）
+    pub drift: bool, // 上の方の範囲内で、着くたびに次の目的地を乱数で選ぶか
+    pub reposition_every: Option<u16>, // 基準の速さで何フレームごとに一番近いプレイヤーのx座標へ寄るか
+    pub teleport: bool,                // スペルの始まりに、消えてから別の場所に現れるか
+}
+
+impl Default for BossMovement {
+    /// その場から動かない
+    fn default() -> Self {
+        Self {
+            speed: 0.0,
+            drift: false,
+            reposition_every: None,
+            teleport: false,
+        }
+    }
+}
+
+/// スペルの中身。`hp`か`survival`で始め、制限時間と弾幕を足していく
+pub struct Phase {
+    seconds: f32, // 制限時間
+    survival: bool,
+    health: u32,
+    bonus: u64,
+    drops: &'static [ItemKind],
+    guide_seconds: f32,
+    movement: BossMovement,
+    resist: (f32, f32), // 受ける傷の倍率と、それが効く秒数
+    events: Vec<EnemyEvent>,
+}
+
+/// `health`だけ傷を与えれば終わるスペル
+pub fn hp(health: u32) -> Phase {
+    Phase {
+        seconds: 0.0,
+        survival: false,
+        health,
+        bonus: 0,
+        drops: &[],
+        guide_seconds: 0.0,
+        movement: BossMovement::default(),
+        resist: (1.0, 0.0),
+        events: Vec::new(),
+    }
+}
+
+/// ボスが無敵で、時間切れまで避けきれば取得になる耐久スペル
+pub fn survival() -> Phase {
+    Phase {
+        survival: true,
+        ..hp(0)
+    }
+}
+
+impl Phase {
+    /// 制限時間（秒）
+    pub fn timeout(mut self, seconds: f32) -> Self {
+        self.seconds = seconds;
+        self
+    }
+
+    /// 取得したときの得点
+    pub fn bonus(mut self, bonus: u64) -> Self {
+        self.bonus = bonus;
+        self
+    }
+
+    /// 取得したときに落とすアイテム
+    pub fn drops(mut self, drops: &'static [ItemKind]) -> Self {
+        self.drops = drops;
+        self
+    }
+
+    /// 弾を撃つ何秒前から通り道の予告線を出すか
+    pub fn guide(mut self, seconds: f32) -> Self {
+        self.guide_seconds = seconds;
+        self
+    }
+
+    pub fn movement(mut self, movement: BossMovement) -> Self {
+        self.movement = movement;
+        self
+    }
+
+    /// スペルが始まってから`seconds`秒の間、ボスが受ける傷に`scale`を掛ける
+    pub fn resist(mut self, scale: f32, seconds: f32) -> Self {
+        self.resist = (scale, seconds);
+        self
+    }
+
+    /// スペル中にボスが起こすイベント
+    pub fn pattern(mut self, events: Vec<EnemyEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// スペルにする。誤りがあれば`owner`を添えて`problems`に書き足す
+    fn into_spell(self, name: &'static str, owner: &str, problems: &mut Vec<String>) -> Spell {
+        let frames = ticks(self.seconds.max(0.0));
+        let guide_frames = ticks(self.guide_seconds.max(0.0));
+        let (scale, resist_seconds) = self.resist;
+        if name.is_empty() {
+            problems.push(format!("{} has no name", owner));
+        }
+        if frames == 0 {
+            problems.push(format!("{} has no time limit", owner));
+        }
+        if frames > u16::MAX as u32 || guide_frames > u16::MAX as u32 {
+            problems.push(format!("{} lasts too long", owner));
+        }
+        if !self.survival && self.health == 0 {
+            problems.push(format!("{} has no health", owner));
+        }
+        if !(0.0..=1.0).contains(&scale) {
+            problems.push(format!("{} resists with scale {}", owner, scale));
+        }
+        level::validate_enemy_events(owner, &self.events, problems);
+        Spell {
+            name,
+            frames: frames as u16,
+            survival: self.survival,
+            health: self.health,
+            bonus: self.bonus,
+            drops: self.drops,
+            guide_frames: guide_frames as u16,
+            movement: self.movement,
+            resistance: Resistance {
+                scale,
+                frames: ticks(resist_seconds.max(0.0)),
+            },
+            events: self.events.into(),
+        }
+    }
+}
+
+/// ボスが順に使うスペルを並べる
+#[derive(Default)]
+pub struct BossBuilder {
+    spells: Vec<(&'static str, Phase)>,
+}
+
+/// ```text
+/// boss::builder()
+///     .spell("Fan Sign", hp(6000).timeout(20.0).resist(0.5, 1.0).bonus(50_000).pattern(fans))
+///     .spell("Survival Sign", survival().timeout(25.0).pattern(time_stop))
+///     .build()?
+/// ```
+pub fn builder() -> BossBuilder {
+    BossBuilder::default()
+}
+
+impl BossBuilder {
+    /// `name`のスペルを、前に足したスペルの次に使う
+    pub fn spell(mut self, name: &'static str, phase: Phase) -> Self {
+        self.spells.push((name, phase));
+        self
+    }
+
+    /// スペルの列にする。誤りがあれば、どのスペルのどこがおかしいかをすべて並べたエラーを返す
+    pub fn build(self) -> Result<Rc<[Spell]>> {
+        let mut problems = Vec::new();
+        if self.spells.is_empty() {
+            problems.push("the boss has no spells".to_string());
+        }
+        let spells: Rc<[Spell]> = self
+            .spells
+            .into_iter()
+            .enumerate()
+            .map(|(index, (name, phase))| {
+                let owner = format!("spell {} \"{}\"", index, name);
+                phase.into_spell(name, &owner, &mut problems)
+            })
+            .collect();
+        if problems.is_empty() {
+            Ok(spells)
+        } else {
+            Err(anyhow!("Invalid boss:\n{}", problems.join("\n")))
+        }
+    }
+}
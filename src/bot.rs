@@ -0,0 +1,175 @@
+use std::ops::Range;
+
+use crate::{
+    command::Command,
+    component::Collider,
+    constants::{frames, ticks, PLAYER_SPEED},
+    hazard::Hazard,
+    input::Actions,
+    level::Level,
+    math::{Point, Vector},
+    player::{Player, HITBOX_RADIUS},
+    playfield::Playfield,
+    settings::{BombType, Modifiers, ShotType},
+};
+
+const LOOKAHEAD: [u32; 4] = [frames(2), frames(5), frames(9), frames(14)]; // 先を読むフレーム。近いものほど重く見る
+const SAFETY_MARGIN: f32 = 3.0; // 当たり判定に足しておく余裕
+const NEAR_MISS: f32 = 24.0; // これより近くを通る弾は、当たらなくても少し避ける
+const HIT_COST: f32 = 1000.0;
+const HOME_ABOVE_BOTTOM: f32 = 80.0; // 何もなければ戻っていく位置（プレイフィールドの下の端から）
+const HOME_PULL: f32 = 0.002;
+const STRESS_FRAMES: u32 = ticks(600.0); // 耐久試験で、クリアできなければ諦める長さ
+pub const STRESS_SEEDS: Range<u64> = 1..11;
+pub const STRESS_TARGET: f32 = 0.9; // 耐久試験で、クリアしてほしい割合
+
+/// 生き残ることだけを考えて自機を動かす、動作確認用の簡単な操作役。
+/// 動き方の候補を並べ、弾と障害物がまっすぐ進むとして少し先まで読み、一番当たりそうにない動きを選ぶ。
+/// どう動いても次の瞬間に当たりそうなら、ボムを使う。ショットは撃ちっぱなしにする。
+pub fn choose(
+    position: Point,
+    bombs: u8,
+    playfield: &Playfield,
+    bullets: &[(Point, Vector, f32)],
+    hazards: &[Hazard],
+) -> Actions {
+    // 先読みの間に届かない弾は最初に外しておく
+    let reach = PLAYER_SPEED * LOOKAHEAD[LOOKAHEAD.len() - 1] as f32 + NEAR_MISS;
+    let nearby: Vec<_> = bullets
+        .iter()
+        .filter(|(pos, vel, radius)| {
+            let ahead = vel.length() * LOOKAHEAD[LOOKAHEAD.len() - 1] as f32;
+            Vector::between(pos, &position).length() <= reach + ahead + radius
+        })
+        .collect();
+    let home = Point {
+        x: playfield.center_x(),
+        y: playfield.bottom() - HOME_ABOVE_BOTTOM,
+    };
+
+    let mut best = (f32::INFINITY, Actions::default(), false);
+    for dx in [-1, 0, 1] {
+        for dy in [-1, 0, 1] {
+            for focus in [false, true] {
+                let actions = Actions {
+                    up: dy < 0,
+                    down: dy > 0,
+                    left: dx < 0,
+                    right: dx > 0,
+                    shot: true,
+                    focus,
+                    ..Actions::default()
+                };
+                let (vx, vy) = Player::calc_velocity(&actions);
+                let mut risk = 0.0;
+                let mut hit_soon = false;
+                for (step, &frames) in LOOKAHEAD.iter().enumerate() {
+                    let weight = 1.0 / (step + 1) as f32;
+                    let t = frames as f32;
+                    let at = playfield.clamp(Point {
+                        x: position.x + vx * t,
+                        y: position.y + vy * t,
+                    });
+                    for &&(pos, vel, radius) in nearby.iter() {
+                        let mut ahead = pos;
+                        ahead += vel * t;
+                        let collider = Collider {
+                            radius: radius + SAFETY_MARGIN,
+                        };
+                        if collider.overlaps(&ahead, &at, HITBOX_RADIUS) {
+                            risk += HIT_COST * weight;
+                            hit_soon |= step == 0;
+                        } else {
+                            let gap = Vector::between(&ahead, &at).length() - radius;
+                            risk += (NEAR_MISS - gap).max(0.0) / NEAR_MISS * weight;
+                        }
+                    }
+                    for hazard in hazards {
+                        if hazard.reaches(&at, HITBOX_RADIUS + SAFETY_MARGIN, t) {
+                            risk += HIT_COST * weight;
+                            hit_soon |= step == 0;
+                        }
+                    }
+                    risk += Vector::between(&at, &home).length() * HOME_PULL * weight;
+                }
+                if risk < best.0 {
+                    best = (risk, actions, hit_soon);
+                }
+            }
+        }
+    }
+    let (_, mut actions, hit_soon) = best;
+    actions.bomb = hit_soon && bombs > 0;
+    actions
+}
+
+/// 耐久試験の結果
+pub struct StressReport {
+    runs: u64,
+    cleared: u64,
+    deaths: u32, // 全部の回の被弾の合計
+}
+
+impl StressReport {
+    /// クリアできた割合
+    pub fn rate(&self) -> f32 {
+        self.cleared as f32 / self.runs.max(1) as f32
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Bot cleared stage 1 in {} of {} runs ({:.0}%, target {:.0}%: {}), {} deaths in total",
+            self.cleared,
+            self.runs,
+            self.rate() * 100.0,
+            STRESS_TARGET * 100.0,
+            if self.rate() >= STRESS_TARGET {
+                "ok"
+            } else {
+                "FAILED"
+            },
+            self.deaths
+        )
+    }
+}
+
+/// 1人で、ルール変更なしでステージ1を`seeds`のシードごとに操作役に遊ばせ、クリアできた割合を調べる
+pub fn stress_test(seeds: Range<u64>) -> StressReport {
+    let runs = seeds.end.saturating_sub(seeds.start);
+    let mut cleared = 0;
+    let mut deaths = 0;
+    for seed in seeds {
+        let mut level = Level::new(
+            seed,
+            &[0],
+            Playfield::MAIN,
+            Modifiers::default(),
+            BombType::default(),
+            ShotType::default(),
+        );
+        level.apply(Command::ToggleAutoPlay);
+        while level.frame() < STRESS_FRAMES && !level.is_cleared() && !level.is_game_over() {
+            level.update(&[Actions::default()]);
+        }
+        cleared += level.is_cleared() as u64;
+        deaths += level.deaths();
+    }
+    StressReport {
+        runs,
+        cleared,
+        deaths,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 操作役が、決まったシードのステージ1を目標の割合以上クリアできるか。
+    /// 弾幕や敵を変えて急に難しくなりすぎたときに気づけるようにする
+    #[test]
+    fn bot_clears_stage_one() {
+        let report = stress_test(STRESS_SEEDS);
+        assert!(report.rate() >= STRESS_TARGET, "{}", report.summary());
+    }
+}
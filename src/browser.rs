@@ -1,15 +1,36 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    rc::Rc,
+};
+
 use anyhow::{anyhow, Result};
 use futures::Future;
 use wasm_bindgen::{
     closure::{Closure, WasmClosure},
-    JsCast,
+    JsCast, JsValue,
+};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    js_sys::{Array, Function, Object, Reflect},
+    CanvasRenderingContext2d, DedicatedWorkerGlobalScope, Document, Element, Gamepad,
+    GamepadButton, HtmlCanvasElement, HtmlElement, MessageEvent, MouseEvent, Response, Storage,
+    UrlSearchParams, WebSocket, Window, Worker,
 };
-use web_sys::{CanvasRenderingContext2d, Document, HtmlCanvasElement, Window};
 
+const ANNOUNCER_ID: &str = "announcer";
+// 画面には表示せず、スクリーンリーダーにだけ読ませるためのスタイル
+const VISUALLY_HIDDEN: &str =
+    "position:absolute;left:-10000px;width:1px;height:1px;overflow:hidden;";
+
+/// ブラウザの開発者ツールに書き出す。`cargo test`などブラウザの外では標準エラーに書き出す
 macro_rules! log {
-    ($($t:tt)*) => {
+    ($($t:tt)*) => {{
+        #[cfg(target_arch = "wasm32")]
         web_sys::console::log_1(&format!($($t)*).into());
-    };
+        #[cfg(not(target_arch = "wasm32"))]
+        eprintln!($($t)*);
+    }};
 }
 
 pub fn window() -> Result<Window> {
@@ -30,8 +51,50 @@ pub fn canvas() -> Result<HtmlCanvasElement> {
         .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))
 }
 
-pub fn context() -> Result<CanvasRenderingContext2d> {
-    canvas()?
+/// キャンバスの高さを変える。`fit`なら、ページの表示領域に収まるよう縦横の比を保って縮めて見せる
+pub fn resize_canvas(canvas: &HtmlCanvasElement, height: u32, fit: bool) -> Result<()> {
+    canvas.set_height(height);
+    let style = if fit {
+        "max-width: 100vw; max-height: 100vh;"
+    } else {
+        ""
+    };
+    canvas
+        .set_attribute("style", style)
+        .map_err(|err| anyhow!("Could not set canvas style {:#?}", err))
+}
+
+/// ページの表示領域の幅と高さ（CSSピクセル）
+pub fn viewport() -> Result<(f64, f64)> {
+    let window = window()?;
+    let size = |value: std::result::Result<JsValue, JsValue>| {
+        value
+            .ok()
+            .and_then(|value| value.as_f64())
+            .ok_or_else(|| anyhow!("Could not get viewport size"))
+    };
+    Ok((size(window.inner_width())?, size(window.inner_height())?))
+}
+
+/// タッチで操作できる端末か
+pub fn is_touch_device() -> bool {
+    window().is_ok_and(|window| window.navigator().max_touch_points() > 0)
+}
+
+/// ページには加えず、描画先にだけ使うキャンバスを作る
+pub fn create_canvas(width: u32, height: u32) -> Result<HtmlCanvasElement> {
+    let canvas = document()?
+        .create_element("canvas")
+        .map_err(|err| anyhow!("Could not create canvas {:#?}", err))?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    Ok(canvas)
+}
+
+pub fn context_of(canvas: &HtmlCanvasElement) -> Result<CanvasRenderingContext2d> {
+    canvas
         .get_context("2d")
         .map_err(|js_value| anyhow!("Error getting 2d context {:#?}", js_value))?
         .ok_or_else(|| anyhow!("No 2d context found"))?
@@ -44,6 +107,415 @@ pub fn context() -> Result<CanvasRenderingContext2d> {
         })
 }
 
+pub fn local_storage() -> Result<Storage> {
+    window()?
+        .local_storage()
+        .map_err(|err| anyhow!("Error getting localStorage {:#?}", err))?
+        .ok_or_else(|| anyhow!("No localStorage found"))
+}
+
+pub fn storage_get(key: &str) -> Result<Option<String>> {
+    local_storage()?
+        .get_item(key)
+        .map_err(|err| anyhow!("Could not read {} from localStorage {:#?}", key, err))
+}
+
+pub fn storage_set(key: &str, value: &str) -> Result<()> {
+    local_storage()?
+        .set_item(key, value)
+        .map_err(|err| anyhow!("Could not write {} to localStorage {:#?}", key, err))
+}
+
+pub fn storage_remove(key: &str) -> Result<()> {
+    local_storage()?
+        .remove_item(key)
+        .map_err(|err| anyhow!("Could not remove {} from localStorage {:#?}", key, err))
+}
+
+/// ページを閉じたり離れたりするときに`callback`を呼ぶ
+pub fn on_page_hide(callback: impl FnMut() + 'static) -> Result<()> {
+    let onpagehide = closure_wrap(Box::new(callback) as Box<dyn FnMut()>);
+    window()?.set_onpagehide(Some(onpagehide.as_ref().unchecked_ref()));
+    onpagehide.forget();
+    Ok(())
+}
+
+/// キャンバスがクリックされたら、その位置を`callback`に渡す。
+/// ページに収まるよう縮めて見せていても、CSSピクセルからキャンバスの画素の座標に直して渡す
+pub fn on_canvas_click(
+    canvas: &HtmlCanvasElement,
+    mut callback: impl FnMut(f64, f64) + 'static,
+) -> Result<()> {
+    let target = canvas.clone();
+    let onclick = closure_wrap(Box::new(move |event: MouseEvent| {
+        let scale_x = target.width() as f64 / target.client_width().max(1) as f64;
+        let scale_y = target.height() as f64 / target.client_height().max(1) as f64;
+        callback(
+            event.offset_x() as f64 * scale_x,
+            event.offset_y() as f64 * scale_y,
+        );
+    }) as Box<dyn FnMut(MouseEvent)>);
+    canvas.set_onclick(Some(onclick.as_ref().unchecked_ref()));
+    onclick.forget();
+    Ok(())
+}
+
+/// `text`をクリップボードに写す。書き込みは後で終わり、そこで失敗しても知らせない
+pub fn copy_to_clipboard(text: &str) -> Result<()> {
+    let clipboard = Reflect::get(&window()?.navigator(), &"clipboard".into()).unwrap_or_default();
+    if !clipboard.is_object() {
+        return Err(anyhow!("Clipboard is not available"));
+    }
+    call_method(&clipboard, "writeText", &Array::of1(&text.into()))?;
+    Ok(())
+}
+
+/// ゲームパッドの1フレーム分の状態
+pub struct GamepadState {
+    pub buttons: Vec<bool>, // 標準の配置でのボタンごとの押下
+    pub axes: Vec<f64>,     // スティックの傾き（-1から1）
+}
+
+/// ゲームパッドをつながった順の番号で返す。抜かれた番号は`None`になる。
+pub fn gamepads() -> Result<Vec<Option<GamepadState>>> {
+    let pads = window()?
+        .navigator()
+        .get_gamepads()
+        .map_err(|err| anyhow!("Could not get gamepads {:#?}", err))?;
+    Ok(pads
+        .iter()
+        .map(|pad| {
+            let pad = pad
+                .dyn_into::<Gamepad>()
+                .ok()
+                .filter(|pad| pad.connected())?;
+            let buttons = pad
+                .buttons()
+                .iter()
+                .map(|button| {
+                    button
+                        .dyn_into::<GamepadButton>()
+                        .is_ok_and(|button| button.pressed())
+                })
+                .collect();
+            let axes = pad
+                .axes()
+                .iter()
+                .map(|axis| axis.as_f64().unwrap_or(0.0))
+                .collect();
+            Some(GamepadState { buttons, axes })
+        })
+        .collect())
+}
+
+/// `target`のメソッド`name`を呼ぶ。web-sysにまだないAPIを使うためのもの
+fn call_method(target: &JsValue, name: &str, args: &Array) -> Result<JsValue> {
+    Reflect::get(target, &name.into())
+        .ok()
+        .and_then(|method| method.dyn_into::<Function>().ok())
+        .ok_or_else(|| anyhow!("No method {} found", name))?
+        .apply(target, args)
+        .map_err(|err| anyhow!("Error calling {} {:#?}", name, err))
+}
+
+/// 番号`index`のゲームパッドを、強さ`intensity`（0から1）で`duration_ms`だけ震わせる。
+/// `index`が`None`なら、つながっているものすべて。振動に対応していないゲームパッドは何もしない。
+pub fn rumble_gamepads(index: Option<usize>, intensity: f64, duration_ms: u32) -> Result<()> {
+    let pads = window()?
+        .navigator()
+        .get_gamepads()
+        .map_err(|err| anyhow!("Could not get gamepads {:#?}", err))?;
+    for (i, pad) in pads.iter().enumerate() {
+        if index.is_some_and(|index| index != i) || !pad.is_object() {
+            continue;
+        }
+        // 多くのブラウザは`vibrationActuator`、古いものは`hapticActuators`で震わせる
+        let actuator = Reflect::get(&pad, &"vibrationActuator".into()).unwrap_or_default();
+        if actuator.is_object() {
+            let params = Object::new();
+            for (key, value) in [
+                ("duration", duration_ms.into()),
+                ("strongMagnitude", intensity.into()),
+                ("weakMagnitude", intensity.into()),
+            ] {
+                let _ = Reflect::set(&params, &key.into(), &value);
+            }
+            call_method(
+                &actuator,
+                "playEffect",
+                &Array::of2(&"dual-rumble".into(), &params),
+            )?;
+            continue;
+        }
+        let actuators = Reflect::get(&pad, &"hapticActuators".into()).unwrap_or_default();
+        if Array::is_array(&actuators) {
+            for actuator in Array::from(&actuators).iter() {
+                let args = Array::of2(&intensity.into(), &duration_ms.into());
+                call_method(&actuator, "pulse", &args)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 携帯端末を`duration_ms`だけ震わせる。振動に対応していない端末では何もしない
+pub fn vibrate(duration_ms: u32) -> Result<()> {
+    window()?.navigator().vibrate_with_duration(duration_ms);
+    Ok(())
+}
+
+/// スクリーンリーダー向けのARIAライブリージョン。なければ作ってbodyに追加する。
+fn announcer() -> Result<Element> {
+    let document = document()?;
+    if let Some(element) = document.get_element_by_id(ANNOUNCER_ID) {
+        return Ok(element);
+    }
+
+    let element = document
+        .create_element("div")
+        .map_err(|err| anyhow!("Could not create announcer element {:#?}", err))?;
+    element.set_id(ANNOUNCER_ID);
+    for (name, value) in [
+        ("role", "status"),
+        ("aria-live", "polite"),
+        ("style", VISUALLY_HIDDEN),
+    ] {
+        element
+            .set_attribute(name, value)
+            .map_err(|err| anyhow!("Could not set {} on announcer {:#?}", name, err))?;
+    }
+    document
+        .body()
+        .ok_or_else(|| anyhow!("No Body Found"))?
+        .append_child(&element)
+        .map_err(|err| anyhow!("Could not append announcer {:#?}", err))?;
+    Ok(element)
+}
+
+/// `text`をスクリーンリーダーに読み上げさせる
+pub fn announce(text: &str) -> Result<()> {
+    announcer()?.set_text_content(Some(text));
+    Ok(())
+}
+
+/// `text`を`filename`という名前のファイルとしてダウンロードさせる
+pub fn download(filename: &str, mime: &str, text: &str) -> Result<()> {
+    let document = document()?;
+    let link = document
+        .create_element("a")
+        .map_err(|err| anyhow!("Could not create download link {:#?}", err))?;
+    // 中身はデータURLにして渡す。英数字と一部の記号のほかはパーセントエンコードする
+    let encoded: String = text
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+    let href = format!("data:{};charset=utf-8,{}", mime, encoded);
+    for (name, value) in [("href", href.as_str()), ("download", filename)] {
+        link.set_attribute(name, value)
+            .map_err(|err| anyhow!("Could not set {} on download link {:#?}", name, err))?;
+    }
+    let body = document.body().ok_or_else(|| anyhow!("No Body Found"))?;
+    body.append_child(&link)
+        .map_err(|err| anyhow!("Could not append download link {:#?}", err))?;
+    link.clone()
+        .dyn_into::<HtmlElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlElement", element))?
+        .click();
+    link.remove();
+    Ok(())
+}
+
+/// ページのURLのクエリ文字列から`name`の値を取り出す
+pub fn query_param(name: &str) -> Result<Option<String>> {
+    let search = window()?
+        .location()
+        .search()
+        .map_err(|err| anyhow!("Could not read location.search {:#?}", err))?;
+    let params = UrlSearchParams::new_with_str(&search)
+        .map_err(|err| anyhow!("Could not parse query string {:#?}", err))?;
+    Ok(params.get(name))
+}
+
+/// `url`の中身を文字列として読み込む
+pub async fn fetch_text(url: &str) -> Result<String> {
+    let response: Response = JsFuture::from(window()?.fetch_with_str(url))
+        .await
+        .map_err(|err| anyhow!("Could not fetch {} {:#?}", url, err))?
+        .dyn_into()
+        .map_err(|err| anyhow!("Fetch did not return a Response {:#?}", err))?;
+    if !response.ok() {
+        return Err(anyhow!(
+            "Could not fetch {}: status {}",
+            url,
+            response.status()
+        ));
+    }
+    let text = response
+        .text()
+        .map_err(|err| anyhow!("Could not read response from {} {:#?}", url, err))?;
+    JsFuture::from(text)
+        .await
+        .map_err(|err| anyhow!("Could not read response from {} {:#?}", url, err))?
+        .as_string()
+        .ok_or_else(|| anyhow!("Response from {} is not text", url))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SocketState {
+    Connecting,
+    Open,
+    Closed,
+}
+
+/// テキストメッセージだけをやり取りするWebSocket。
+/// 受信したメッセージは溜めておき、ゲームループから`receive`で取り出す。
+pub struct Socket {
+    socket: WebSocket,
+    inbox: Rc<RefCell<VecDeque<String>>>,
+    state: Rc<Cell<SocketState>>,
+    _onopen: Closure<dyn FnMut(JsValue)>,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+    _onclose: Closure<dyn FnMut(JsValue)>,
+}
+
+impl Socket {
+    pub fn connect(url: &str) -> Result<Self> {
+        let socket = WebSocket::new(url)
+            .map_err(|err| anyhow!("Could not connect to {} {:#?}", url, err))?;
+        let inbox = Rc::new(RefCell::new(VecDeque::new()));
+        let state = Rc::new(Cell::new(SocketState::Connecting));
+
+        let open_state = state.clone();
+        let onopen = closure_wrap(Box::new(move |_: JsValue| {
+            open_state.set(SocketState::Open);
+        }) as Box<dyn FnMut(JsValue)>);
+        let message_inbox = inbox.clone();
+        let onmessage = closure_wrap(Box::new(move |event: MessageEvent| {
+            if let Some(text) = event.data().as_string() {
+                message_inbox.borrow_mut().push_back(text);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        let close_state = state.clone();
+        let onclose = closure_wrap(Box::new(move |_: JsValue| {
+            close_state.set(SocketState::Closed);
+        }) as Box<dyn FnMut(JsValue)>);
+
+        socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+        socket.set_onerror(Some(onclose.as_ref().unchecked_ref()));
+
+        Ok(Self {
+            socket,
+            inbox,
+            state,
+            _onopen: onopen,
+            _onmessage: onmessage,
+            _onclose: onclose,
+        })
+    }
+
+    pub fn state(&self) -> SocketState {
+        self.state.get()
+    }
+
+    pub fn send(&self, text: &str) -> Result<()> {
+        self.socket
+            .send_with_str(text)
+            .map_err(|err| anyhow!("Could not send message {:#?}", err))
+    }
+
+    pub fn receive(&self) -> Option<String> {
+        self.inbox.borrow_mut().pop_front()
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = self.socket.close();
+    }
+}
+
+/// Web Workerで動かしているスクリプトとのやりとり。テキストのメッセージだけを送り合う。
+/// 受け取ったメッセージは溜めておき、ゲームループから`receive`で取り出す。
+pub struct WorkerThread {
+    worker: Worker,
+    inbox: Rc<RefCell<VecDeque<String>>>,
+    _onmessage: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl WorkerThread {
+    /// `url`のスクリプトをWeb Workerで動かし始める
+    pub fn spawn(url: &str) -> Result<Self> {
+        let worker =
+            Worker::new(url).map_err(|err| anyhow!("Could not start worker {} {:#?}", url, err))?;
+        let inbox = Rc::new(RefCell::new(VecDeque::new()));
+        let message_inbox = inbox.clone();
+        let onmessage = closure_wrap(Box::new(move |event: MessageEvent| {
+            if let Some(text) = event.data().as_string() {
+                message_inbox.borrow_mut().push_back(text);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        worker.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+        Ok(Self {
+            worker,
+            inbox,
+            _onmessage: onmessage,
+        })
+    }
+
+    pub fn send(&self, text: &str) -> Result<()> {
+        self.worker
+            .post_message(&text.into())
+            .map_err(|err| anyhow!("Could not post message to worker {:#?}", err))
+    }
+
+    pub fn receive(&self) -> Option<String> {
+        self.inbox.borrow_mut().pop_front()
+    }
+}
+
+impl Drop for WorkerThread {
+    fn drop(&mut self) {
+        self.worker.terminate();
+    }
+}
+
+/// Web Workerの中で動いているか（`window`がない）
+pub fn is_worker() -> bool {
+    web_sys::window().is_none()
+}
+
+fn worker_scope() -> Result<DedicatedWorkerGlobalScope> {
+    web_sys::js_sys::global()
+        .dyn_into()
+        .map_err(|_| anyhow!("Not running in a dedicated worker"))
+}
+
+/// Web Workerの中で、メインスレッドからテキストのメッセージが届くたびに`callback`に渡す
+pub fn on_main_thread_message(mut callback: impl FnMut(String) + 'static) -> Result<()> {
+    let onmessage = closure_wrap(Box::new(move |event: MessageEvent| {
+        if let Some(text) = event.data().as_string() {
+            callback(text);
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    worker_scope()?.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+    Ok(())
+}
+
+/// Web Workerの中から、メインスレッドにテキストのメッセージを送る
+pub fn post_to_main_thread(text: &str) -> Result<()> {
+    worker_scope()?
+        .post_message(&text.into())
+        .map_err(|err| anyhow!("Could not post message to main thread {:#?}", err))
+}
+
 pub fn spawn_local<F>(future: F)
 where
     F: Future<Output = ()> + 'static,
@@ -58,6 +530,14 @@ pub fn now() -> Result<f64> {
         .now())
 }
 
+/// 1970年1月1日（UTC）からのミリ秒
+pub fn unix_time() -> Result<f64> {
+    let performance = window()?
+        .performance()
+        .ok_or_else(|| anyhow!("Performance object not found"))?;
+    Ok(performance.time_origin() + performance.now())
+}
+
 pub type LoopClosure = Closure<dyn FnMut(f64)>;
 pub fn request_animation_frame(callback: &LoopClosure) -> Result<i32> {
     window()?
@@ -0,0 +1,82 @@
+use std::collections::VecDeque;
+
+use crate::{
+    constants::{CAMP_PUNISH_FRAMES, CAMP_WARNING_FRAMES},
+    engine::Renderer,
+    math::Point,
+    palette::Color,
+};
+
+const CAMP_SIZE: f32 = 48.0; // この幅の四角に収まる範囲を動いているだけなら、居座っているとみなす
+const WARNING_RADIUS: f32 = 40.0; // 警告の輪の一番大きいときの半径
+
+/// プレイヤーが同じ場所に居座っていないかを、直近の位置の履歴から見張る
+#[derive(Clone, Default)]
+pub struct CampWatch {
+    history: VecDeque<Point>, // 新しいものが後ろ。`CAMP_PUNISH_FRAMES`個まで
+}
+
+impl CampWatch {
+    /// 今の位置を加える。居座りが長く続いて狙い撃ちする時が来たら`true`を返し、履歴を空にする。
+    pub fn update(&mut self, position: Point) -> bool {
+        self.history.push_back(position);
+        if self.history.len() > CAMP_PUNISH_FRAMES as usize {
+            self.history.pop_front();
+        }
+        if self.camped_frames() < CAMP_PUNISH_FRAMES {
+            return false;
+        }
+        self.history.clear();
+        true
+    }
+
+    /// やられて位置が飛んだときなど、それまでの履歴を捨てる
+    pub fn reset(&mut self) {
+        self.history.clear();
+    }
+
+    /// 今の位置から遡って、狭い範囲に収まり続けているフレーム数
+    fn camped_frames(&self) -> u32 {
+        let Some(last) = self.history.back() else {
+            return 0;
+        };
+        let (mut left, mut right, mut top, mut bottom) = (last.x, last.x, last.y, last.y);
+        let mut frames = 0;
+        for point in self.history.iter().rev() {
+            left = left.min(point.x);
+            right = right.max(point.x);
+            top = top.min(point.y);
+            bottom = bottom.max(point.y);
+            if right - left > CAMP_SIZE || bottom - top > CAMP_SIZE {
+                break;
+            }
+            frames += 1;
+        }
+        frames
+    }
+
+    /// 警告を出している間、狙い撃ちまでの近さ（0から1）
+    fn warning(&self) -> Option<f32> {
+        let frames = self.camped_frames();
+        (frames >= CAMP_WARNING_FRAMES).then(|| {
+            (frames - CAMP_WARNING_FRAMES) as f32
+                / (CAMP_PUNISH_FRAMES - CAMP_WARNING_FRAMES) as f32
+        })
+    }
+
+    /// 警告中なら、プレイヤーのまわりで縮んでいく輪と印を描く
+    pub fn draw(&self, renderer: &dyn Renderer, position: &Point) {
+        let Some(progress) = self.warning() else {
+            return;
+        };
+        renderer.set_color(Color::Telegraph);
+        renderer.draw_circle(position, (WARNING_RADIUS * (1.0 - progress)).max(1.0));
+        renderer.draw_text_centered(
+            "!",
+            &Point {
+                x: position.x,
+                y: position.y - WARNING_RADIUS - 4.0,
+            },
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use crate::constants::ticks;
+
+const KILL_SCORE: u64 = 1_000; // 倍率を掛ける前の、敵1体を倒したときの得点
+const CHAIN_WINDOW: u16 = ticks(1.5) as u16; // 倒してからこの間に次を倒せばチェインが続く
+const DECAY_FRAMES: u16 = ticks(0.25) as u16; // 途切れたあと、この間隔でチェインが1つずつ減る
+const MAX_CHAIN: u32 = 40; // 倍率が上がり続けるチェイン数の上限
+
+/// 続けて敵を倒すほど上がる得点の倍率
+#[derive(Clone, Default)]
+pub struct Chain {
+    count: u32,     // 続けて倒した数
+    remaining: u16, // 次のチェインまで、または次に減るまでの残りフレーム数
+}
+
+impl Chain {
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// 倍率の10倍（1チェインごとに0.1ずつ上がり、1.0から5.0まで）
+    pub fn multiplier_tenths(&self) -> u64 {
+        10 + self.count.min(MAX_CHAIN) as u64
+    }
+
+    /// 敵を倒したときに呼び、その敵の得点を返す
+    pub fn kill(&mut self) -> u64 {
+        let score = KILL_SCORE * self.multiplier_tenths() / 10;
+        self.count += 1;
+        self.remaining = CHAIN_WINDOW;
+        score
+    }
+
+    /// 被弾したらチェインは途切れて0に戻る
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn update(&mut self) {
+        if self.count == 0 {
+            return;
+        }
+        self.remaining = self.remaining.saturating_sub(1);
+        if self.remaining == 0 {
+            self.count -= 1;
+            self.remaining = DECAY_FRAMES;
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! 当たり判定の組み合わせ。物を種類（レイヤー）に分け、どのレイヤー同士を確かめるかをここにまとめて決める。
+//! `Level`は重なった組を受け取り、レイヤーの組ごとに反応を決めるだけにする。
+
+use crate::{
+    component::{Collider, Shape},
+    math::Point,
+};
+
+/// 当たり判定を取る物の種類
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    PlayerHurtbox, // 自機の当たり判定
+    PlayerShot,    // 自機の弾
+    EnemyBody,     // 敵の体
+    EnemyShot,     // 敵弾
+    Item,          // アイテムを取れる範囲
+    Terrain,       // ステージに置かれた障害物
+}
+
+/// 当たり判定を取るレイヤーの組。左のレイヤーの物ごとに、右のレイヤーの物と重なっているかを確かめる。
+/// ここにない組は確かめない（敵弾同士や、自機の弾と障害物など）
+const MASK: [(Layer, Layer); 4] = [
+    (Layer::PlayerShot, Layer::EnemyBody),
+    (Layer::PlayerHurtbox, Layer::EnemyShot),
+    (Layer::PlayerHurtbox, Layer::Terrain),
+    (Layer::Item, Layer::PlayerHurtbox),
+];
+
+/// 当たり判定を取る物1つ。`index`は、その種類の物を`Level`の中で並べた列での番号
+#[derive(Clone, Copy)]
+pub struct Body {
+    pub layer: Layer,
+    pub index: usize,
+    pub at: Point,
+    pub shape: Shape,
+}
+
+impl Body {
+    pub fn circle(layer: Layer, index: usize, at: Point, radius: f32) -> Self {
+        Self {
+            layer,
+            index,
+            at,
+            shape: Shape::Circle(Collider { radius }),
+        }
+    }
+
+    fn overlaps(&self, other: &Body) -> bool {
+        match (self.shape, other.shape) {
+            (_, Shape::Circle(collider)) => {
+                self.shape.overlaps(&self.at, &other.at, collider.radius)
+            }
+            (Shape::Circle(collider), _) => {
+                other.shape.overlaps(&other.at, &self.at, collider.radius)
+            }
+            (
+                Shape::Box {
+                    half_width: w1,
+                    half_height: h1,
+                },
+                Shape::Box {
+                    half_width: w2,
+                    half_height: h2,
+                },
+            ) => {
+                (self.at.x - other.at.x).abs() <= w1 + w2
+                    && (self.at.y - other.at.y).abs() <= h1 + h2
+            }
+        }
+    }
+}
+
+/// 重なった2つ。`first`が`MASK`の組の左側
+#[derive(Clone, Copy)]
+pub struct Contact {
+    pub first: Body,
+    pub second: Body,
+}
+
+/// `bodies`のうち、`MASK`にある組のレイヤー同士で重なっているものを返す。
+/// 組ごとに`MASK`の順に、同じ組の中では左側の物の並び順、同じ物については右側の物の並び順に並ぶ
+pub fn contacts(bodies: &[Body]) -> Vec<Contact> {
+    let mut contacts = Vec::new();
+    for (left, right) in MASK {
+        let seconds: Vec<&Body> = bodies.iter().filter(|body| body.layer == right).collect();
+        for first in bodies.iter().filter(|body| body.layer == left) {
+            for &second in seconds.iter() {
+                if first.overlaps(second) {
+                    contacts.push(Contact {
+                        first: *first,
+                        second: *second,
+                    });
+                }
+            }
+        }
+    }
+    contacts
+}
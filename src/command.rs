@@ -0,0 +1,14 @@
+use crate::{math::Point, tweak::Tweaks};
+
+/// ステージを外から直接操作する命令。
+/// デバッグ用のコンソールやエディタのような開発用ツールは、すべてこれを通してステージを変える。
+#[derive(Clone, Copy)]
+pub enum Command {
+    ToggleInvincible,    // 被弾しなくする・元に戻す
+    ToggleAutoPlay,      // 自機を操作役（`bot`）に動かさせる・元に戻す
+    FullPower,           // 全員のボムを最大まで戻す
+    SkipAhead,           // 敵を次のイベントの直前まで進める（ボスができたらボスまで飛ばす）
+    KillAllEnemies,      // 敵をすべて倒す
+    Spawn(usize, Point), // 名前付きパターン（`PATTERNS`の番号）を撃つ敵を置く
+    Tweak(Tweaks),       // 調整値の倍率を変える
+}
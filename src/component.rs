@@ -0,0 +1,329 @@
+//! 敵・弾・アイテムが共通して持つ部品と、それらをまとめて扱う処理。
+//! 種類ごとに同じ計算を書かずに済むように、位置や当たり判定はここの部品で持つ。
+//! 部品を持つ種類は`Components`を実装し、動かす・描く・当たり判定を並べる・傷を与えるといった処理は、
+//! 種類ごとに書かずにこのモジュールの関数（システム）に任せる。新しい種類を足すときは`Components`を実装すればよい。
+
+use std::mem;
+
+use crate::{
+    collision::{Body, Layer},
+    engine::Renderer,
+    math::{Point, Rect, Vector},
+    palette::Color,
+};
+
+const VELOCITY_SCALE: f32 = 8.0; // デバッグ表示で速度ベクトルを何倍の長さで描くか
+
+/// 位置
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub pos: Point,
+}
+
+/// 速度（1フレームあたりの移動量）
+#[derive(Clone, Copy)]
+pub struct Kinematics {
+    pub vel: Vector,
+}
+
+impl Kinematics {
+    pub fn new(vel: Vector) -> Self {
+        Self { vel }
+    }
+
+    /// `speed`倍した速度の分だけ位置を進める
+    pub fn integrate(&self, transform: &mut Transform, speed: f32) {
+        transform.pos += self.vel * speed;
+    }
+}
+
+/// 円の当たり判定
+#[derive(Clone, Copy)]
+pub struct Collider {
+    pub radius: f32,
+}
+
+impl Collider {
+    /// `at`にあるこの円と、`point`を中心とする半径`radius`の円が重なっているか
+    pub fn overlaps(&self, at: &Point, point: &Point, radius: f32) -> bool {
+        let dx = at.x - point.x;
+        let dy = at.y - point.y;
+        let r = self.radius + radius;
+        dx * dx + dy * dy <= r * r
+    }
+
+    /// `transform`の位置にこの円を描く
+    pub fn draw(&self, renderer: &dyn Renderer, transform: &Transform) {
+        renderer.draw_circle(&transform.pos, self.radius);
+    }
+}
+
+/// 円以外も表せる当たり判定の形。位置は`Transform`を中心にする。
+#[derive(Clone, Copy)]
+pub enum Shape {
+    Circle(Collider),
+    Box { half_width: f32, half_height: f32 },
+}
+
+impl Shape {
+    /// `at`にあるこの形と、`point`を中心とする半径`radius`の円が重なっているか
+    pub fn overlaps(&self, at: &Point, point: &Point, radius: f32) -> bool {
+        match self {
+            Shape::Circle(collider) => collider.overlaps(at, point, radius),
+            Shape::Box {
+                half_width,
+                half_height,
+            } => {
+                // 円の中心に一番近い箱の中の点までの距離で比べる
+                let dx = (point.x - at.x).clamp(-half_width, *half_width) + at.x - point.x;
+                let dy = (point.y - at.y).clamp(-half_height, *half_height) + at.y - point.y;
+                dx * dx + dy * dy <= radius * radius
+            }
+        }
+    }
+
+    /// `transform`の位置にこの形を描く
+    pub fn draw(&self, renderer: &dyn Renderer, transform: &Transform) {
+        match self {
+            Shape::Circle(collider) => collider.draw(renderer, transform),
+            Shape::Box {
+                half_width,
+                half_height,
+            } => renderer.draw_rect(&Rect {
+                x: transform.pos.x - half_width,
+                y: transform.pos.y - half_height,
+                width: half_width * 2.0,
+                height: half_height * 2.0,
+            }),
+        }
+    }
+}
+
+/// 体力。0になったら倒される。
+#[derive(Clone, Copy)]
+pub struct Health {
+    pub remaining: u32,
+}
+
+impl Health {
+    /// `amount`だけ減らし、実際に減った量を返す
+    pub fn damage(&mut self, amount: u32) -> u32 {
+        let dealt = amount.min(self.remaining);
+        self.remaining -= dealt;
+        dealt
+    }
+
+    pub fn is_depleted(&self) -> bool {
+        self.remaining == 0
+    }
+}
+
+/// 見た目。当たり判定とは別に、どの形をどの色で描くか
+#[derive(Clone, Copy)]
+pub struct Renderable {
+    pub color: Color,
+    pub shape: Shape,
+}
+
+impl Renderable {
+    pub fn circle(color: Color, radius: f32) -> Self {
+        Self {
+            color,
+            shape: Shape::Circle(Collider { radius }),
+        }
+    }
+
+    /// `transform`の位置に描く
+    pub fn draw(&self, renderer: &dyn Renderer, transform: &Transform) {
+        renderer.set_color(self.color);
+        self.shape.draw(renderer, transform);
+    }
+}
+
+/// 部品を持つもの。システムはこれを通して、種類を問わず部品を読み書きする
+pub trait Components {
+    fn transform(&self) -> &Transform;
+
+    fn transform_mut(&mut self) -> &mut Transform;
+
+    /// 自分で動くなら、その速度と加速度
+    fn kinematics(&self) -> Option<&Kinematics> {
+        None
+    }
+
+    /// 当たり判定の形
+    fn hitbox(&self) -> Shape;
+
+    /// 傷を受けて壊れるなら、その体力
+    fn health_mut(&mut self) -> Option<&mut Health> {
+        None
+    }
+
+    fn renderable(&self) -> &Renderable;
+
+    /// 見た目の上に重ねて描く、種類ごとの飾り（予告の輪など）
+    fn decorate(&self, _renderer: &dyn Renderer) {}
+
+    /// 見た目と飾りを描く
+    fn draw(&self, renderer: &dyn Renderer) {
+        self.renderable().draw(renderer, self.transform());
+        self.decorate(renderer);
+    }
+}
+
+/// 自分で動くものを、`speed`倍した速度の分だけ進める
+pub fn integrate<E: Components>(entities: &mut [E], speed: f32) {
+    for entity in entities.iter_mut() {
+        if let Some(kinematics) = entity.kinematics().copied() {
+            kinematics.integrate(entity.transform_mut(), speed);
+        }
+    }
+}
+
+/// 並んだ順に描く
+pub fn draw<'a, E: Components + 'a>(
+    renderer: &dyn Renderer,
+    entities: impl IntoIterator<Item = &'a E>,
+) {
+    for entity in entities {
+        entity.draw(renderer);
+    }
+}
+
+/// 当たり判定の形と、自分で動くなら速度ベクトルを描く（デバッグ表示）
+pub fn draw_hitboxes<E: Components>(renderer: &dyn Renderer, entities: &[E]) {
+    for entity in entities {
+        draw_hitbox(
+            renderer,
+            entity.transform(),
+            &entity.hitbox(),
+            entity.kinematics().map(|kinematics| kinematics.vel),
+        );
+    }
+}
+
+/// `transform`の位置にある当たり判定の形と、あれば速度`vel`のベクトルを描く（デバッグ表示）
+pub fn draw_hitbox(
+    renderer: &dyn Renderer,
+    transform: &Transform,
+    hitbox: &Shape,
+    vel: Option<Vector>,
+) {
+    hitbox.draw(renderer, transform);
+    if let Some(vel) = vel {
+        let mut end = transform.pos;
+        end += vel * VELOCITY_SCALE;
+        renderer.draw_line(&transform.pos, &end);
+    }
+}
+
+/// `layer`のレイヤーに置く当たり判定。`Body::index`は`entities`の中での番号になる
+pub fn bodies<E: Components>(layer: Layer, entities: &[E]) -> impl Iterator<Item = Body> + '_ {
+    entities
+        .iter()
+        .enumerate()
+        .map(move |(index, entity)| Body {
+            layer,
+            index,
+            at: entity.transform().pos,
+            shape: entity.hitbox(),
+        })
+}
+
+/// 体力を持つものすべてに`amount`だけ傷を与え、壊れたものを取り除いて並んでいた順に返す
+pub fn damage_all<E: Components>(entities: &mut Vec<E>, amount: u32) -> Vec<E> {
+    let mut destroyed = Vec::new();
+    for mut entity in mem::take(entities) {
+        let depleted = entity.health_mut().is_some_and(|health| {
+            health.damage(amount);
+            health.is_depleted()
+        });
+        if depleted {
+            destroyed.push(entity);
+        } else {
+            entities.push(entity);
+        }
+    }
+    destroyed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 部品を持つだけの物
+    struct Probe {
+        transform: Transform,
+        kinematics: Option<Kinematics>,
+        health: Health,
+        renderable: Renderable,
+    }
+
+    impl Probe {
+        fn new(x: f32, vel: Option<Vector>, health: u32) -> Self {
+            Self {
+                transform: Transform {
+                    pos: Point { x, y: 0.0 },
+                },
+                kinematics: vel.map(Kinematics::new),
+                health: Health { remaining: health },
+                renderable: Renderable::circle(Color::Enemy, 1.0),
+            }
+        }
+    }
+
+    impl Components for Probe {
+        fn transform(&self) -> &Transform {
+            &self.transform
+        }
+
+        fn transform_mut(&mut self) -> &mut Transform {
+            &mut self.transform
+        }
+
+        fn kinematics(&self) -> Option<&Kinematics> {
+            self.kinematics.as_ref()
+        }
+
+        fn hitbox(&self) -> Shape {
+            self.renderable.shape
+        }
+
+        fn health_mut(&mut self) -> Option<&mut Health> {
+            Some(&mut self.health)
+        }
+
+        fn renderable(&self) -> &Renderable {
+            &self.renderable
+        }
+    }
+
+    /// 速度を持つものだけが進み、当たり判定は進んだ位置に並ぶ
+    #[test]
+    fn integrate_moves_only_moving_entities() {
+        let mut probes = vec![
+            Probe::new(0.0, Some(Vector::new(2.0, 1.0)), 1),
+            Probe::new(5.0, None, 1),
+        ];
+        integrate(&mut probes, 0.5);
+        let at = bodies(Layer::EnemyBody, &probes)
+            .map(|body| (body.index, body.at.x, body.at.y))
+            .collect::<Vec<_>>();
+        assert_eq!(at, [(0, 1.0, 0.5), (1, 5.0, 0.0)]);
+    }
+
+    /// 壊れたものだけを、並んでいた順を保って取り除く
+    #[test]
+    fn damage_all_removes_depleted_in_order() {
+        let mut probes = vec![
+            Probe::new(0.0, None, 1),
+            Probe::new(1.0, None, 3),
+            Probe::new(2.0, None, 1),
+        ];
+        let destroyed = damage_all(&mut probes, 1);
+        let xs = |probes: &[Probe]| probes.iter().map(|p| p.transform.pos.x).collect::<Vec<_>>();
+        assert_eq!(xs(&destroyed), [0.0, 2.0]);
+        assert_eq!(xs(&probes), [1.0]);
+        assert_eq!(probes[0].health.remaining, 2);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    bot,
+    command::Command,
+    constants::CONSOLE_CURSOR_SPEED,
+    difficulty,
+    engine::Renderer,
+    input::{Actions, TextKey},
+    level::PATTERNS,
+    math::Point,
+    palette::Color,
+    playfield::Playfield,
+};
+
+const CURSOR_SIZE: f32 = 8.0;
+
+/// 開発中だけ使えるチート用のコンソール。開いている間はゲームを止める。
+/// 数字キーで命令を選び、敵を置く位置はカーソルで決める。
+/// 選んでいるパターンの手強さを測って、ブラウザのコンソールに書き出すこともできる。
+pub struct Console {
+    cursor: Point,
+    pattern: usize, // 置く敵が撃つパターン（`PATTERNS`の番号）
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Self {
+            cursor: Point {
+                x: Playfield::MAIN.center_x(),
+                y: Playfield::MAIN.center_y(),
+            },
+            pattern: 0,
+        }
+    }
+
+    /// 開発用のビルドでだけ開ける
+    pub fn is_available() -> bool {
+        cfg!(debug_assertions)
+    }
+
+    /// `held`で押されている方向にカーソルを動かし、押された数字キーに対応する命令を返す
+    pub fn update(&mut self, held: &Actions, typed: Option<TextKey>) -> Option<Command> {
+        let dx = held.right as i8 - held.left as i8;
+        let dy = held.down as i8 - held.up as i8;
+        self.cursor = Playfield::MAIN.clamp(Point {
+            x: self.cursor.x + dx as f32 * CONSOLE_CURSOR_SPEED,
+            y: self.cursor.y + dy as f32 * CONSOLE_CURSOR_SPEED,
+        });
+
+        match typed? {
+            TextKey::Digit(1) => Some(Command::ToggleInvincible),
+            TextKey::Digit(2) => Some(Command::FullPower),
+            TextKey::Digit(3) => Some(Command::SkipAhead),
+            TextKey::Digit(4) => Some(Command::KillAllEnemies),
+            TextKey::Digit(5) => Some(Command::Spawn(self.pattern, self.cursor)),
+            TextKey::Digit(6) => {
+                self.pattern = (self.pattern + 1) % PATTERNS.len();
+                None
+            }
+            TextKey::Digit(7) => {
+                // 別のステージで動かして測るので、遊んでいるステージには触れない
+                log!("{}", difficulty::analyze(self.pattern).report());
+                None
+            }
+            TextKey::Digit(8) => Some(Command::ToggleAutoPlay),
+            TextKey::Digit(9) => {
+                // 何度も最初から遊ばせるので、しばらく止まる
+                log!("{}", bot::stress_test(bot::STRESS_SEEDS).summary());
+                None
+            }
+            _ => None,
+        }
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, invincible: bool, autoplay: bool) {
+        renderer.set_color(Color::Debug);
+        renderer.draw_line(
+            &Point {
+                x: self.cursor.x - CURSOR_SIZE,
+                y: self.cursor.y,
+            },
+            &Point {
+                x: self.cursor.x + CURSOR_SIZE,
+                y: self.cursor.y,
+            },
+        );
+        renderer.draw_line(
+            &Point {
+                x: self.cursor.x,
+                y: self.cursor.y - CURSOR_SIZE,
+            },
+            &Point {
+                x: self.cursor.x,
+                y: self.cursor.y + CURSOR_SIZE,
+            },
+        );
+
+        let lines = [
+            format!("1: invincible [{}]", if invincible { "ON" } else { "OFF" }),
+            "2: full power".to_string(),
+            "3: skip ahead".to_string(),
+            "4: kill all enemies".to_string(),
+            format!("5: spawn \"{}\"", PATTERNS[self.pattern].0),
+            "6: next pattern".to_string(),
+            "7: analyze pattern (browser console)".to_string(),
+            format!("8: auto-play [{}]", if autoplay { "ON" } else { "OFF" }),
+            "9: bot stress test (browser console)".to_string(),
+            "WASD: cursor  `: close".to_string(),
+        ];
+
+        renderer.set_color(Color::Text);
+        renderer.draw_text("CONSOLE", &Point { x: 60.0, y: 60.0 });
+        for (i, line) in lines.iter().enumerate() {
+            renderer.draw_text(
+                line,
+                &Point {
+                    x: 60.0,
+                    y: 86.0 + 24.0 * i as f32,
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! 時間と速さに関わるゲームの調整値。
+//! 値は秒や1秒あたりの量で書き、`TICK_RATE`で1フレームあたりに直して使う。
+//! シミュレーションの速さを変えても、ゲームの難しさは変わらない。
+
+/// 記録や同梱のリプレイの基準になる、シミュレーションの1秒あたりのフレーム数
+pub const STANDARD_TICK_RATE: u32 = 60;
+
+/// シミュレーションの1秒あたりのフレーム数。`high_refresh`の機能を付けてビルドすると、
+/// 高リフレッシュレートの画面で入力の遅れを減らせるよう、基準の倍の速さで進める
+#[cfg(not(feature = "high_refresh"))]
+pub const TICK_RATE: u32 = STANDARD_TICK_RATE;
+#[cfg(feature = "high_refresh")]
+pub const TICK_RATE: u32 = STANDARD_TICK_RATE * 2;
+
+/// 保存データの名前を、このビルドの速さに合わせる。
+/// 基準と違う速さの記録やリプレイは基準のものと比べたり再生したりできないので、`.<フレーム数>hz`を付けて分ける
+pub fn rate_key(key: String) -> String {
+    if TICK_RATE == STANDARD_TICK_RATE {
+        key
+    } else {
+        format!("{}.{}hz", key, TICK_RATE)
+    }
+}
+
+/// 1秒あたりの量（速さなど）を1フレームあたりに直す
+pub const fn per_tick(per_second: f32) -> f32 {
+    per_second / TICK_RATE as f32
+}
+
+/// 1秒あたりの1秒あたりの量（加速度）を1フレームあたりの1フレームあたりに直す
+pub const fn per_tick_squared(per_second_squared: f32) -> f32 {
+    per_second_squared / (TICK_RATE * TICK_RATE) as f32
+}
+
+/// 秒数をフレーム数に直す
+pub const fn ticks(seconds: f32) -> u32 {
+    (seconds * TICK_RATE as f32 + 0.5) as u32
+}
+
+// パターンやステージのデータ（名前付きパターン、`stage!`、弾幕風スクリプト、ボスのスペル）は、
+// 基準の速さでのフレーム数と1フレームあたりの量で書く。`Level`が読むときに次の関数でこのビルドの速さに直す
+const STEPS: u32 = TICK_RATE / STANDARD_TICK_RATE; // 基準の1フレームがこのビルドの何フレームにあたるか
+
+/// 基準の速さでのフレーム数を、このビルドのフレーム数に直す
+pub const fn frames(standard_frames: u32) -> u32 {
+    standard_frames * STEPS
+}
+
+/// 基準の速さでの1フレームあたりの量（速さや角速度）を、このビルドの1フレームあたりに直す
+pub const fn per_frame(per_standard_frame: f32) -> f32 {
+    per_standard_frame / STEPS as f32
+}
+
+/// 基準の速さでの1フレームあたりの1フレームあたりの量（加速度）を、このビルドのものに直す
+pub const fn per_frame_squared(per_standard_frame_squared: f32) -> f32 {
+    per_standard_frame_squared / (STEPS * STEPS) as f32
+}
+
+// プレイヤー
+pub const PLAYER_SPEED: f32 = per_tick(360.0); // 移動の速さ
+pub const PLAYER_BLINK_FRAMES: u8 = ticks(0.5) as u8; // 通常状態の点滅の周期
+pub const RESPAWN_FRAMES: u8 = ticks(2.0) as u8; // 被弾してから操作できるようになるまで
+pub const BOMB_FRAMES: u8 = ticks(1.0) as u8; // ボムが続く長さ
+pub const SHOT_INTERVAL: u8 = ticks(0.1) as u8; // ショットを撃ってから次を撃てるまで
+
+// 敵と弾
+pub const TELEGRAPH_FRAMES: u16 = ticks(0.2) as u16; // 敵が弾を撃つ何フレーム前から予告を出すか
+pub const CAMP_WARNING_FRAMES: u32 = ticks(3.0); // 同じ場所に何フレーム居座ると警告を出すか
+pub const CAMP_PUNISH_FRAMES: u32 = ticks(4.0); // 同じ場所に何フレーム居座ると狙い撃ちするか
+pub const PUNISHER_SPEED: f32 = per_tick(300.0); // 居座ったプレイヤーを狙い撃つ弾の速さ
+pub const FAN_BULLET_SPEED: f32 = per_tick(120.0); // 扇状に撃つ弾の速さ
+pub const AIM_BULLET_SPEED: f32 = per_tick(60.0); // 自機狙いの弾の速さ
+
+// ボス
+pub const MIDBOSS_ARRIVAL_FRAME: u32 = ticks(5.0); // ステージ開始から何フレームで中ボスが出るか
+pub const BOSS_DELAY_FRAMES: u32 = ticks(4.0); // 中ボスがいなくなってから何フレームでボスが出るか
+pub const BOSS_LEAVE_SPEED: f32 = per_tick(240.0); // 時間切れになった中ボスが画面外へ去る速さ
+pub const BOSS_TELEPORT_FRAMES: u16 = ticks(0.5) as u16; // ボスが消えてから別の場所に現れるまで
+pub const DEFEAT_FRAMES: u32 = ticks(3.0); // ボスを倒してからステージクリアになるまで
+pub const DEFEAT_SLOWMO_FRAMES: u32 = ticks(0.75); // ボスを倒した直後、時間をゆっくり進める長さ
+pub const DEFEAT_SLOWMO_SPEED: f32 = 0.3; // そのときの時間の進み方の倍率
+
+// アイテム
+pub const ITEM_FALL_ACCELERATION: f32 = per_tick_squared(180.0);
+pub const ITEM_MAX_FALL_SPEED: f32 = per_tick(120.0);
+
+// 表示
+pub const DEATH_MARKER_FRAMES: u16 = ticks(2.0) as u16; // 被弾した弾の出どころを表示する長さ
+pub const CREDITS_SPEED: f32 = per_tick(30.0); // スタッフロールが上がる速さ
+pub const ARENA_RING_SPEED: f32 = per_tick(30.0); // ボス戦の場の輪が広がる速さ
+pub const BANNER_SLIDE_FRAMES: u32 = ticks(0.5); // ステージやボスの見出しが滑り込む長さと消えていく長さ
+pub const BANNER_HOLD_FRAMES: u32 = ticks(2.0); // 見出しが止まって表示される長さ
+pub const CONSOLE_CURSOR_SPEED: f32 = per_tick(240.0); // 開発用コンソールのカーソルの速さ
+
+// 記録と通信
+pub const HEATMAP_SAVE_INTERVAL: u32 = ticks(10.0); // ヒートマップを保存する間隔
+pub const HASH_INTERVAL: u32 = ticks(1.0); // 対戦中に状態のずれを確かめる間隔
+pub const MAX_REPLAY_FRAMES: usize = ticks(60.0 * 60.0) as usize; // 読み込むリプレイの最大の長さ。再開のときに全部を一度に進めるので、長すぎると固まる
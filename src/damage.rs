@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+
+use crate::{
+    constants::{per_tick, ticks},
+    engine::Renderer,
+    math::Point,
+    palette::Color,
+    playfield::Playfield,
+    quality::Quality,
+};
+
+const NUMBER_FRAMES: u16 = ticks(0.5) as u16; // 与えた傷の数字を表示する長さ
+const NUMBER_RISE: f32 = per_tick(60.0); // 数字が上がっていく速さ
+const DPS_WINDOW: u32 = ticks(1.0); // 秒間の傷を数える範囲
+
+/// 敵が受ける傷を一時的に減らす守り。ボスのスペルの切り替わりや、敵が出てきた直後などに使う
+#[derive(Clone, Copy)]
+pub struct Resistance {
+    pub scale: f32,  // 受ける傷に掛ける倍率（0なら傷を受けない）
+    pub frames: u32, // 効き始めてから守っている長さ
+}
+
+impl Resistance {
+    pub const NONE: Resistance = Resistance {
+        scale: 1.0,
+        frames: 0,
+    };
+
+    /// 効き始めてから`elapsed`フレーム目に受けた`amount`の傷を、守りの分だけ減らす
+    pub fn reduce(&self, amount: u32, elapsed: u32) -> u32 {
+        if elapsed < self.frames {
+            (amount as f32 * self.scale).round() as u32
+        } else {
+            amount
+        }
+    }
+}
+
+/// 敵の上に浮かぶ、与えた傷の数字
+#[derive(Clone)]
+struct DamageNumber {
+    pos: Point,
+    amount: u32,
+    remaining: u16, // 表示を続ける残りフレーム数
+}
+
+/// 与えた傷の記録。練習モードで、数字と直近1秒の合計（DPS）を表示する。
+#[derive(Clone, Default)]
+pub struct DamageMeter {
+    numbers: Vec<DamageNumber>,
+    recent: VecDeque<(u32, u32)>, // 直近に与えた傷（フレーム、量）
+}
+
+impl DamageMeter {
+    /// `frame`フレーム目に`pos`の敵へ`amount`の傷を与えたことを記録する
+    pub fn record(&mut self, frame: u32, pos: Point, amount: u32) {
+        if amount == 0 {
+            return;
+        }
+        self.numbers.push(DamageNumber {
+            pos,
+            amount,
+            remaining: NUMBER_FRAMES,
+        });
+        self.recent.push_back((frame, amount));
+    }
+
+    /// `frame`はステージ開始からの経過フレーム
+    pub fn update(&mut self, frame: u32) {
+        self.numbers.retain_mut(|number| {
+            number.remaining -= 1;
+            number.pos.y -= NUMBER_RISE;
+            number.remaining > 0
+        });
+        while let Some(&(at, _)) = self.recent.front() {
+            if at + DPS_WINDOW > frame {
+                break;
+            }
+            self.recent.pop_front();
+        }
+    }
+
+    /// 直近1秒に与えた傷の合計
+    pub fn dps(&self) -> u32 {
+        self.recent.iter().map(|(_, amount)| amount).sum()
+    }
+
+    /// 品質を下げているときは、浮かぶ数字を省いてDPSだけを出す
+    pub fn draw(&self, renderer: &dyn Renderer, playfield: &Playfield, quality: Quality) {
+        renderer.set_color(Color::Text);
+        if quality == Quality::High {
+            for number in &self.numbers {
+                renderer.set_alpha(number.remaining as f32 / NUMBER_FRAMES as f32);
+                renderer.draw_text_centered(&number.amount.to_string(), &number.pos);
+            }
+        }
+        renderer.set_alpha(1.0);
+        renderer.draw_text(
+            &format!("DPS {}", self.dps()),
+            &Point {
+                x: playfield.left() + 8.0,
+                y: playfield.bottom() - 8.0,
+            },
+        );
+    }
+}
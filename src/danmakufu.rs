@@ -0,0 +1,797 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use crate::{constants::STANDARD_TICK_RATE, level::EnemyEvent, math::Vector, rng::Rng};
+
+const MAIN_LOOP_FRAMES: u32 = 10 * STANDARD_TICK_RATE; // `@MainLoop`を何フレーム分動かすか（スクリプトは基準の速さで書く）
+const MAX_STEPS: u32 = 200_000; // 終わらないスクリプトで固まらないよう、実行する文の数に上限を設ける
+const MAX_SHOTS: usize = 4_000;
+const MAX_DEPTH: u32 = 64; // 入れ子や呼び出しが深すぎてスタックが溢れないよう、深さに上限を設ける
+const RAND_SEED: u64 = 0xDA44_A0FF; // `rand`の種。読み込むたびに同じパターンになるよう決めておく
+
+/// 東方弾幕風のスクリプトの一部を、敵のイベント列に訳す。
+///
+/// 読めるのは、変数、四則演算と比較、`if`、`loop(n)`、`ascent`と`descent`、`wait`と`yield`、
+/// `task`と`function`の定義と呼び出し、`@Initialize`と`@MainLoop`、それに次の関数だけ。
+/// - `CreateShot01`と`CreateShotA1`（`x, y, 速さ, 向き, 弾の絵, 遅れ`）
+/// - `CreateShot02`（`x, y, 速さ, 向き, 加速度, 最後の速さ, 弾の絵, 遅れ`）
+/// - `GetX`、`GetY`、`GetAngleToPlayer`、`sin`、`cos`、`atan2`、`absolute`、`truncate`、`round`、`rand`、`rand_int`
+///
+/// 撃つ位置は`GetX`と`GetY`からのずれで、向きは数か`GetAngleToPlayer`からのずれで書く。
+/// 遊んでいる間にしか決まらない値（自機の位置など）に頼るものは、読み込むときにエラーにする。
+/// 弾の絵は区別しないので読み飛ばす。`@MainLoop`は`MAIN_LOOP_FRAMES`フレーム分だけ動かす。
+pub fn import(source: &str) -> Result<Vec<EnemyEvent>> {
+    let tokens = tokenize(source)?;
+    let script = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    }
+    .script()?;
+    let mut machine = Machine {
+        routines: &script.routines,
+        scopes: vec![HashMap::new()],
+        time: 0,
+        steps: 0,
+        depth: 0,
+        in_main_loop: false,
+        shots: Vec::new(),
+        rng: Rng::new(RAND_SEED),
+    };
+    // 外側の文で作った変数は、スクリプト全体の変数として残す
+    for stmt in script.body.iter() {
+        machine.statement(stmt)?;
+    }
+    machine.block(&script.initialize)?;
+    if !script.main_loop.is_empty() {
+        machine.in_main_loop = true;
+        for frame in 0..MAIN_LOOP_FRAMES {
+            machine.time = frame;
+            machine.block(&script.main_loop)?;
+        }
+    }
+    if machine.shots.is_empty() {
+        return Err(anyhow!("The script fires no shots"));
+    }
+    // 撃つ順に並べる。同じフレームの弾は書いた順のまま
+    machine.shots.sort_by_key(|(at, _)| *at);
+    Ok(machine.shots.into_iter().map(|(_, shot)| shot).collect())
+}
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(f32),
+    Ident(String),
+    Text(String),
+    Symbol(&'static str),
+}
+
+// 長いものから順に試す
+const SYMBOLS: [&str; 31] = [
+    "==", "!=", "<=", ">=", "&&", "||", "++", "--", "+=", "-=", "*=", "/=", "..", "+", "-", "*",
+    "/", "%", "(", ")", "{", "}", "[", "]", ";", ",", "=", "<", ">", "!", "@",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if rest.starts_with("//") || c == '#' {
+            // `#TouhouDanmakufu`などの見出しの行も読み飛ばす
+            rest = rest.split_once('\n').map_or("", |(_, after)| after);
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after
+                .split_once("*/")
+                .map(|(_, after)| after)
+                .ok_or_else(|| anyhow!("Unclosed comment"))?;
+        } else if let Some(after) = rest.strip_prefix('"') {
+            let (text, after) = after
+                .split_once('"')
+                .ok_or_else(|| anyhow!("Unclosed string"))?;
+            tokens.push(Token::Text(text.to_string()));
+            rest = after;
+        } else if c.is_ascii_digit() {
+            // `1..3`の`..`を小数点と読まないように、点の次が数字のときだけ小数にする
+            let mut end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if rest[end..].starts_with('.')
+                && rest[end + 1..].starts_with(|c: char| c.is_ascii_digit())
+            {
+                end += 1 + rest[end + 1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len() - end - 1);
+            }
+            tokens.push(Token::Number(rest[..end].parse()?));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| rest.starts_with(**symbol))
+                .ok_or_else(|| anyhow!("Unexpected character '{}'", c))?;
+            tokens.push(Token::Symbol(symbol));
+            rest = &rest[symbol.len()..];
+        }
+    }
+    Ok(tokens)
+}
+
+enum Expr {
+    Number(f32),
+    Text,
+    Name(String), // 変数か、引数のない関数
+    Call(String, Vec<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Binary(Box<Expr>, &'static str, Box<Expr>),
+}
+
+enum Stmt {
+    Let(String, Expr),
+    Assign(String, Option<&'static str>, Expr), // 演算つきの代入なら、その演算
+    Loop(Expr, Vec<Stmt>),
+    Range {
+        name: String,
+        from: Expr,
+        to: Expr,
+        descending: bool,
+        body: Vec<Stmt>,
+    },
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Wait(Expr),
+    Call(String, Vec<Expr>),
+    Block(Vec<Stmt>),
+}
+
+/// `task`か`function`。`task`は呼んだ側を待たせずに、呼んだ時点から自分の時間で進む
+struct Routine {
+    params: Vec<String>,
+    body: Vec<Stmt>,
+    is_task: bool,
+}
+
+#[derive(Default)]
+struct Script {
+    body: Vec<Stmt>, // どの`@`にも入っていない文
+    initialize: Vec<Stmt>,
+    main_loop: Vec<Stmt>,
+    routines: HashMap<String, Routine>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: u32, // 読んでいる文や式の入れ子の深さ
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unexpected end of script"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(anyhow!("Expected '{}' near token {}", symbol, self.pos + 1))
+        }
+    }
+
+    fn is_ident(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == name)
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            _ => Err(anyhow!("Expected a name near token {}", self.pos)),
+        }
+    }
+
+    fn script(mut self) -> Result<Script> {
+        let mut script = Script::default();
+        self.items(&mut script)?;
+        if self.peek().is_some() {
+            return Err(anyhow!("Unexpected '}}' near token {}", self.pos + 1));
+        }
+        Ok(script)
+    }
+
+    /// `}`か最後まで、定義と文を読む
+    fn items(&mut self, script: &mut Script) -> Result<()> {
+        while self.peek().is_some() && self.peek() != Some(&Token::Symbol("}")) {
+            if self.is_ident("script_enemy_main") {
+                self.pos += 1;
+                self.expect("{")?;
+                self.items(script)?;
+                self.expect("}")?;
+            } else if self.eat("@") {
+                let name = self.ident()?;
+                let body = self.block()?;
+                match name.as_str() {
+                    "Initialize" => script.initialize.extend(body),
+                    "MainLoop" => script.main_loop.extend(body),
+                    // 描画や後片付けは弾の動きに関わらない
+                    _ => {}
+                }
+            } else if self.is_ident("task") || self.is_ident("function") || self.is_ident("sub") {
+                let is_task = self.ident()? == "task";
+                let name = self.ident()?;
+                let mut params = Vec::new();
+                if self.eat("(") {
+                    while !self.eat(")") {
+                        if self.is_ident("let") {
+                            self.pos += 1;
+                        }
+                        params.push(self.ident()?);
+                        self.eat(",");
+                    }
+                }
+                let body = self.block()?;
+                script.routines.insert(
+                    name,
+                    Routine {
+                        params,
+                        body,
+                        is_task,
+                    },
+                );
+            } else {
+                script.body.push(self.statement()?);
+            }
+            self.eat(";");
+        }
+        Ok(())
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>> {
+        self.expect("{")?;
+        let mut body = Vec::new();
+        while !self.eat("}") {
+            body.push(self.statement()?);
+        }
+        Ok(body)
+    }
+
+    /// 入れ子を1段深くして`read`で読む
+    fn nested<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_DEPTH {
+            return Err(anyhow!(
+                "The script is nested too deeply near token {}",
+                self.pos
+            ));
+        }
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn statement(&mut self) -> Result<Stmt> {
+        self.nested(Self::plain_statement)
+    }
+
+    fn plain_statement(&mut self) -> Result<Stmt> {
+        if self.peek() == Some(&Token::Symbol("{")) {
+            return Ok(Stmt::Block(self.block()?));
+        }
+        if self.eat(";") {
+            return Ok(Stmt::Block(Vec::new()));
+        }
+        let name = self.ident()?;
+        let stmt = match name.as_str() {
+            "let" | "real" | "var" => {
+                let name = self.ident()?;
+                let value = if self.eat("=") {
+                    self.expr()?
+                } else {
+                    Expr::Number(0.0)
+                };
+                self.expect(";")?;
+                Stmt::Let(name, value)
+            }
+            "loop" => {
+                if !self.eat("(") {
+                    return Err(anyhow!(
+                        "loop needs a count (endless loops are not supported)"
+                    ));
+                }
+                let count = self.expr()?;
+                self.expect(")")?;
+                Stmt::Loop(count, self.block()?)
+            }
+            "ascent" | "descent" => {
+                let descending = name == "descent";
+                self.expect("(")?;
+                if self.is_ident("let") {
+                    self.pos += 1;
+                }
+                let name = self.ident()?;
+                if self.ident()? != "in" {
+                    return Err(anyhow!("Expected 'in' in {}", name));
+                }
+                let from = self.expr()?;
+                self.expect("..")?;
+                let to = self.expr()?;
+                self.expect(")")?;
+                Stmt::Range {
+                    name,
+                    from,
+                    to,
+                    descending,
+                    body: self.block()?,
+                }
+            }
+            "if" => {
+                self.expect("(")?;
+                let condition = self.expr()?;
+                self.expect(")")?;
+                let then = self.block()?;
+                let otherwise = if self.is_ident("else") {
+                    self.pos += 1;
+                    if self.is_ident("if") {
+                        vec![self.statement()?]
+                    } else {
+                        self.block()?
+                    }
+                } else {
+                    Vec::new()
+                };
+                Stmt::If(condition, then, otherwise)
+            }
+            "yield" => {
+                self.expect(";")?;
+                Stmt::Wait(Expr::Number(1.0))
+            }
+            "wait" => {
+                self.expect("(")?;
+                let frames = self.expr()?;
+                self.expect(")")?;
+                self.expect(";")?;
+                Stmt::Wait(frames)
+            }
+            _ => {
+                let stmt = if self.eat("=") {
+                    Stmt::Assign(name, None, self.expr()?)
+                } else if let Some(op) =
+                    ["+=", "-=", "*=", "/="].into_iter().find(|op| self.eat(op))
+                {
+                    Stmt::Assign(name, Some(&op[..1]), self.expr()?)
+                } else if self.eat("++") {
+                    Stmt::Assign(name, Some("+"), Expr::Number(1.0))
+                } else if self.eat("--") {
+                    Stmt::Assign(name, Some("-"), Expr::Number(1.0))
+                } else if self.peek() == Some(&Token::Symbol("(")) {
+                    Stmt::Call(name, self.args()?)
+                } else {
+                    Stmt::Call(name, Vec::new())
+                };
+                self.expect(";")?;
+                stmt
+            }
+        };
+        Ok(stmt)
+    }
+
+    fn args(&mut self) -> Result<Vec<Expr>> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        while !self.eat(")") {
+            args.push(self.expr()?);
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok(args)
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        self.binary(0)
+    }
+
+    /// 演算子の強さの段ごとに、左から順に結びつける
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        const LEVELS: [&[&str]; 5] = [
+            &["||"],
+            &["&&"],
+            &["==", "!=", "<=", ">=", "<", ">"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        let Some(ops) = LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = ops.iter().find(|op| self.eat(op)) {
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(Box::new(left), op, Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        self.nested(Self::operand)
+    }
+
+    fn operand(&mut self) -> Result<Expr> {
+        if self.eat("-") {
+            return Ok(Expr::Unary("-", Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Unary("!", Box::new(self.unary()?)));
+        }
+        match self.next()? {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Text(_) => Ok(Expr::Text),
+            Token::Symbol("(") => {
+                let inner = self.expr()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::Symbol("(")) => {
+                Ok(Expr::Call(name, self.args()?))
+            }
+            Token::Ident(name) => Ok(Expr::Name(name)),
+            Token::Symbol(symbol) => {
+                Err(anyhow!("Unexpected '{}' near token {}", symbol, self.pos))
+            }
+        }
+    }
+}
+
+/// 値が何からのずれか。敵の位置や自機への向きは遊んでいる間に決まるので、ずれだけを持っておく
+#[derive(Clone, Copy, PartialEq)]
+enum Base {
+    Plain,
+    X,   // `GetX`から
+    Y,   // `GetY`から
+    Aim, // `GetAngleToPlayer`から
+}
+
+#[derive(Clone, Copy)]
+struct Value {
+    base: Base,
+    n: f32,
+}
+
+impl Value {
+    fn plain(n: f32) -> Self {
+        Self {
+            base: Base::Plain,
+            n,
+        }
+    }
+
+    /// ただの数として使う
+    fn number(self) -> Result<f32> {
+        match self.base {
+            Base::Plain => Ok(self.n),
+            _ => Err(anyhow!(
+                "GetX, GetY and GetAngleToPlayer can only be offset by adding or subtracting"
+            )),
+        }
+    }
+}
+
+struct Machine<'a> {
+    routines: &'a HashMap<String, Routine>,
+    scopes: Vec<HashMap<String, Value>>, // 先頭がスクリプト全体の変数
+    time: u32,                           // 今のフレーム
+    steps: u32,
+    depth: u32,         // 動かしている文の入れ子と呼び出しの深さ
+    in_main_loop: bool, // `@MainLoop`の中では、フレームは外側で進める
+    shots: Vec<(u32, EnemyEvent)>,
+    rng: Rng,
+}
+
+impl Machine<'_> {
+    /// 中身のない`loop`でも数えるよう、ブロックに入るたびと文ごとに数える
+    fn step(&mut self) -> Result<()> {
+        self.steps += 1;
+        if self.steps > MAX_STEPS {
+            return Err(anyhow!("The script runs for too long"));
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, body: &[Stmt]) -> Result<()> {
+        self.step()?;
+        if self.depth >= MAX_DEPTH {
+            return Err(anyhow!("The script calls itself too deeply"));
+        }
+        self.depth += 1;
+        self.scopes.push(HashMap::new());
+        let result = body.iter().try_for_each(|stmt| self.statement(stmt));
+        self.scopes.pop();
+        self.depth -= 1;
+        result
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> Result<()> {
+        self.step()?;
+        match stmt {
+            Stmt::Let(name, value) => {
+                let value = self.eval(value)?;
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(name.clone(), value);
+                }
+            }
+            Stmt::Assign(name, op, value) => {
+                let mut value = self.eval(value)?;
+                if let Some(op) = op {
+                    value = binary(self.variable(name)?, op, value)?;
+                }
+                let scope = self
+                    .scopes
+                    .iter_mut()
+                    .rev()
+                    .find(|scope| scope.contains_key(name))
+                    .ok_or_else(|| anyhow!("Unknown variable {}", name))?;
+                scope.insert(name.clone(), value);
+            }
+            Stmt::Loop(count, body) => {
+                for _ in 0..self.eval(count)?.number()?.max(0.0) as u32 {
+                    self.block(body)?;
+                }
+            }
+            Stmt::Range {
+                name,
+                from,
+                to,
+                descending,
+                body,
+            } => {
+                let from = self.eval(from)?.number()? as i32;
+                let to = self.eval(to)?.number()? as i32;
+                for k in 0..to.saturating_sub(from).max(0) {
+                    let i = if *descending { to - 1 - k } else { from + k };
+                    self.scopes
+                        .push(HashMap::from([(name.clone(), Value::plain(i as f32))]));
+                    let result = self.block(body);
+                    self.scopes.pop();
+                    result?;
+                }
+            }
+            Stmt::If(condition, then, otherwise) => {
+                if self.eval(condition)?.number()? != 0.0 {
+                    self.block(then)?;
+                } else {
+                    self.block(otherwise)?;
+                }
+            }
+            Stmt::Wait(frames) => {
+                let frames = self.eval(frames)?.number()?.max(0.0).round() as u32;
+                // `@MainLoop`そのものの`yield`は、次のフレームを外側で進めるので何もしない
+                if !self.in_main_loop {
+                    self.time = self.time.saturating_add(frames);
+                }
+            }
+            Stmt::Call(name, args) => self.call_statement(name, args)?,
+            Stmt::Block(body) => self.block(body)?,
+        }
+        Ok(())
+    }
+
+    fn call_statement(&mut self, name: &str, args: &[Expr]) -> Result<()> {
+        match name {
+            "CreateShot01" | "CreateShotA1" => self.shot(name, args, false),
+            "CreateShot02" => self.shot(name, args, true),
+            _ => {
+                let routine = self
+                    .routines
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Unsupported function {}", name))?;
+                let values = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                // 呼ばれた側からは、呼んだ側の変数は見えない
+                let callers = self.scopes.split_off(1);
+                self.scopes
+                    .push(routine.params.iter().cloned().zip(values).collect());
+                let (time, in_main_loop) = (self.time, self.in_main_loop);
+                self.in_main_loop = false;
+                let result = self.block(&routine.body);
+                self.in_main_loop = in_main_loop;
+                if routine.is_task {
+                    self.time = time;
+                }
+                self.scopes.truncate(1);
+                self.scopes.extend(callers);
+                result
+            }
+        }
+    }
+
+    /// `CreateShot01(x, y, 速さ, 向き, 弾の絵, 遅れ)`か`CreateShot02(x, y, 速さ, 向き, 加速度, 最後の速さ, 弾の絵, 遅れ)`
+    fn shot(&mut self, name: &str, args: &[Expr], accelerates: bool) -> Result<()> {
+        let count = if accelerates { 8 } else { 6 };
+        if args.len() != count {
+            return Err(anyhow!("{} takes {} arguments", name, count));
+        }
+        let x = self.eval(&args[0])?;
+        let y = self.eval(&args[1])?;
+        if x.base != Base::X || y.base != Base::Y {
+            return Err(anyhow!(
+                "{} must fire from GetX() and GetY() plus an offset",
+                name
+            ));
+        }
+        let speed = self.eval(&args[2])?.number()?;
+        let angle = self.eval(&args[3])?;
+        let aimed = match angle.base {
+            Base::Plain => false,
+            Base::Aim => true,
+            _ => return Err(anyhow!("{} angle cannot be a position", name)),
+        };
+        let acceleration = if accelerates {
+            if aimed {
+                return Err(anyhow!("{} cannot accelerate an aimed shot", name));
+            }
+            Some((
+                self.eval(&args[4])?.number()?,
+                self.eval(&args[5])?.number()?,
+            ))
+        } else {
+            None
+        };
+        let delay = self.eval(&args[count - 1])?.number()?.max(0.0).round() as u32;
+        // 敵のイベントは出てきた次のフレームから数えるので、スクリプトの0フレーム目を1にする
+        let at = self.time.saturating_add(delay).saturating_add(1);
+        let at =
+            u16::try_from(at).map_err(|_| anyhow!("{} fires too late (frame {})", name, at))?;
+        if self.shots.len() >= MAX_SHOTS {
+            return Err(anyhow!("The script fires more than {} shots", MAX_SHOTS));
+        }
+        let offset = Vector::new(x.n, y.n);
+        self.shots.push((
+            at as u32,
+            EnemyEvent::shot(at, offset, angle.n, speed, aimed, acceleration),
+        ));
+        Ok(())
+    }
+
+    fn variable(&self, name: &str) -> Result<Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .ok_or_else(|| anyhow!("Unknown variable {}", name))
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<Value> {
+        match expr {
+            Expr::Number(n) => Ok(Value::plain(*n)),
+            Expr::Text => Err(anyhow!("Text can only be used for bullet graphics")),
+            Expr::Name(name) => match self.variable(name) {
+                Ok(value) => Ok(value),
+                Err(_) => self.function(name, &[]),
+            },
+            Expr::Call(name, args) => self.function(name, args),
+            Expr::Unary(op, inner) => {
+                let n = self.eval(inner)?.number()?;
+                Ok(Value::plain(if *op == "-" {
+                    -n
+                } else {
+                    (n == 0.0) as u8 as f32
+                }))
+            }
+            Expr::Binary(left, op, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                binary(left, op, right)
+            }
+        }
+    }
+
+    fn function(&mut self, name: &str, args: &[Expr]) -> Result<Value> {
+        // 弾の絵の名前は数として使わないので、何でもよい
+        if args.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Ok(Value::plain(0.0));
+        }
+        let values = args
+            .iter()
+            .map(|arg| self.eval(arg)?.number())
+            .collect::<Result<Vec<_>>>()?;
+        let arg = |i: usize| {
+            values
+                .get(i)
+                .copied()
+                .ok_or_else(|| anyhow!("{} needs {} arguments", name, i + 1))
+        };
+        let n = match name {
+            "GetX" => {
+                return Ok(Value {
+                    base: Base::X,
+                    n: 0.0,
+                })
+            }
+            "GetY" => {
+                return Ok(Value {
+                    base: Base::Y,
+                    n: 0.0,
+                })
+            }
+            "GetAngleToPlayer" => {
+                return Ok(Value {
+                    base: Base::Aim,
+                    n: 0.0,
+                })
+            }
+            "sin" => arg(0)?.to_radians().sin(),
+            "cos" => arg(0)?.to_radians().cos(),
+            "atan2" => arg(0)?.atan2(arg(1)?).to_degrees(),
+            "absolute" => arg(0)?.abs(),
+            "truncate" | "trunc" => arg(0)?.trunc(),
+            "round" => arg(0)?.round(),
+            "rand" => self.rng.range(arg(0)?, arg(1)?),
+            "rand_int" => self.rng.range(arg(0)?, arg(1)? + 1.0).floor(),
+            _ => return Err(anyhow!("Unsupported function {}", name)),
+        };
+        Ok(Value::plain(n))
+    }
+}
+
+fn binary(left: Value, op: &str, right: Value) -> Result<Value> {
+    // ずれを持つ値は、数を足し引きするか、同じもの同士を引いて数にするかだけができる
+    let base = match (op, left.base, right.base) {
+        (_, Base::Plain, Base::Plain) => Base::Plain,
+        ("+" | "-", base, Base::Plain) => base,
+        ("+", Base::Plain, base) => base,
+        ("-", a, b) if a == b => Base::Plain,
+        _ => {
+            left.number()?;
+            right.number()?;
+            Base::Plain
+        }
+    };
+    let (a, b) = (left.n, right.n);
+    let truth = |value: bool| value as u8 as f32;
+    let n = match op {
+        "+" => a + b,
+        "-" => a - b,
+        "*" => a * b,
+        "/" => a / b,
+        "%" => a % b,
+        "==" => truth(a == b),
+        "!=" => truth(a != b),
+        "<" => truth(a < b),
+        ">" => truth(a > b),
+        "<=" => truth(a <= b),
+        ">=" => truth(a >= b),
+        "&&" => truth(a != 0.0 && b != 0.0),
+        _ => truth(a != 0.0 || b != 0.0),
+    };
+    Ok(Value { base, n })
+}
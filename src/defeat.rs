@@ -0,0 +1,70 @@
+use crate::{
+    constants::{ticks, DEFEAT_FRAMES, DEFEAT_SLOWMO_FRAMES, DEFEAT_SLOWMO_SPEED},
+    engine::Renderer,
+    math::{Point, Vector},
+    palette::Color,
+};
+
+const BODY_RADIUS: f32 = 20.0; // 爆発を散らすボスの体の大きさ（敵の半径と同じ）
+const BLAST_INTERVAL: u32 = ticks(0.1); // 次の爆発までのフレーム数
+const BLAST_FRAMES: u32 = ticks(0.4); // 1つの爆発が広がって消えるまで
+const BLAST_RADIUS: f32 = 36.0; // 爆発が広がりきったときの半径
+const GOLDEN_DEG: f32 = 137.5; // 爆発の位置が偏らないよう、1つごとにこの角度ずつ回す
+
+/// ボスを倒したあと、ステージクリアまでの演出。
+/// 初めのうちは時間をゆっくり進め、ボスの体のあちこちで爆発を続けてから消える。
+#[derive(Clone)]
+pub struct Defeat {
+    position: Point, // 倒したときのボスの位置
+    frame: u32,
+}
+
+impl Defeat {
+    pub fn new(position: Point) -> Self {
+        Self { position, frame: 0 }
+    }
+
+    /// 1フレーム進める。演出が終わったら`false`を返す。
+    pub fn update(&mut self) -> bool {
+        self.frame += 1;
+        self.frame < DEFEAT_FRAMES
+    }
+
+    /// 時間の進み方の倍率。スローモーションの間だけ1より小さい。
+    pub fn time_scale(&self) -> f32 {
+        if self.frame < DEFEAT_SLOWMO_FRAMES {
+            DEFEAT_SLOWMO_SPEED
+        } else {
+            1.0
+        }
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer) {
+        // 爆発が続く間、ボスの体はだんだん薄れていく
+        let blasts_end = DEFEAT_FRAMES - BLAST_FRAMES;
+        let fade = 1.0 - (self.frame as f32 / blasts_end as f32).min(1.0);
+        if fade > 0.0 {
+            renderer.set_color(Color::Enemy);
+            renderer.set_alpha(fade);
+            renderer.draw_circle(&self.position, BODY_RADIUS);
+        }
+
+        renderer.set_color(Color::Explosion);
+        let first = self.frame.saturating_sub(BLAST_FRAMES) / BLAST_INTERVAL;
+        for i in first..=(self.frame / BLAST_INTERVAL) {
+            let start = i * BLAST_INTERVAL;
+            let progress = (self.frame - start) as f32 / BLAST_FRAMES as f32;
+            if start > blasts_end || progress >= 1.0 {
+                continue;
+            }
+            let mut center = self.position;
+            center += Vector::from_deg_and_mag(
+                GOLDEN_DEG * i as f32,
+                BODY_RADIUS * (i % 3 + 1) as f32 / 3.0,
+            );
+            renderer.set_alpha(1.0 - progress);
+            renderer.draw_circle(&center, (BLAST_RADIUS * progress).max(1.0));
+        }
+        renderer.set_alpha(1.0);
+    }
+}
@@ -0,0 +1,62 @@
+use crate::input::{Actions, Device, Prompt, TextKey};
+
+/// 取り返しのつかない操作の前に、今の画面に重ねて出す確認。`action`は確かめている操作。
+/// 誤って決定しないように、最初は「いいえ」を選んでおく。
+pub struct Confirm<A> {
+    message: String,
+    action: A,
+    yes: bool, // 「はい」を選んでいるか
+}
+
+impl<A: Copy> Confirm<A> {
+    pub fn new(message: impl Into<String>, action: A) -> Self {
+        Self {
+            message: message.into(),
+            action,
+            yes: false,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn action(&self) -> A {
+        self.action
+    }
+
+    /// 左右で選び直し、決定したら選んだ方を返す。戻る操作では「いいえ」にする。
+    pub fn update(&mut self, pressed: &Actions, typed: Option<TextKey>) -> Option<bool> {
+        if pressed.pause || pressed.bomb {
+            return Some(false);
+        }
+        if typed == Some(TextKey::Enter) || pressed.shot {
+            return Some(self.yes);
+        }
+        if pressed.left || pressed.right {
+            self.yes = !self.yes;
+        }
+        None
+    }
+
+    /// スクリーンリーダーに読ませる今の状態
+    pub fn describe(&self) -> String {
+        format!("{} {}", self.message, if self.yes { "Yes" } else { "No" })
+    }
+
+    /// 選択肢と操作の案内
+    pub fn hint(&self, device: Device) -> String {
+        let (yes, no) = if self.yes {
+            ("[Yes]", "No")
+        } else {
+            ("Yes", "[No]")
+        };
+        format!(
+            "{}  {}   {}: OK  {}: cancel",
+            yes,
+            no,
+            device.glyph(Prompt::Confirm),
+            device.glyph(Prompt::Back)
+        )
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{
+    constants::ticks,
+    input::Actions,
+    level::{Level, PATTERNS},
+    math::{Point, Vector},
+    playfield::Playfield,
+};
+
+const ANALYSIS_SEED: u64 = 1;
+const ANALYSIS_FRAMES: u32 = ticks(8.0); // パターンを動かす長さ
+const SAMPLE_INTERVAL: u32 = 5; // 弾の様子を測る間隔（フレーム）
+const DENSITY_RADIUS: f32 = 40.0; // 自機のまわりで弾を数える範囲
+const LANE_HEIGHT: f32 = 24.0; // 隙間を測る、自機のいる高さの帯の幅
+const PLAYER_ROWS: [f32; 2] = [60.0, 120.0]; // 自機がよくいる高さ（プレイフィールドの下の端から）
+const PLAYER_COLUMNS: usize = 5; // 自機がよくいる位置として、横に並べる数
+const COMFORTABLE_GAP: f32 = 48.0; // これより狭い隙間は、抜けるのに気を遣う
+
+/// 名前付きパターン1つを、自機を動かさずに決まったシードで動かして測った、弾幕の手強さの目安
+pub struct Analysis {
+    pattern: usize,    // `PATTERNS`の番号
+    mean_density: f32, // 自機がよくいる位置のまわりにある弾の数の平均
+    peak_density: u32, // 同じく、一番多かったとき
+    gap: Option<f32>, // 自機のいる高さの帯で、一番広い隙間が一番狭くなったときの幅（弾が来なければ`None`）
+    speeds: Vec<f32>, // 測ったときに場にあった弾の速さ（小さい順）
+}
+
+impl Analysis {
+    /// 0から10までの、手強さの大まかな見積もり。数が多く、速く、隙間が狭いほど大きい。
+    /// 重みは今あるパターンの手応えに合わせて決めたもので、比べるための目安でしかない。
+    pub fn estimate(&self) -> f32 {
+        let crowd = self.peak_density as f32 * 0.4 + self.mean_density;
+        let speed = self.percentile(0.9);
+        let squeeze = self
+            .gap
+            .map_or(0.0, |gap| (COMFORTABLE_GAP / gap.max(1.0)).min(4.0) - 1.0);
+        (crowd + speed + squeeze.max(0.0) * 1.5).clamp(0.0, 10.0)
+    }
+
+    fn grade(&self) -> &'static str {
+        match self.estimate() {
+            score if score < 2.5 => "Easy",
+            score if score < 5.0 => "Normal",
+            score if score < 7.5 => "Hard",
+            _ => "Lunatic",
+        }
+    }
+
+    /// 速さの分布のうち、小さい方から`ratio`の位置にある値
+    fn percentile(&self, ratio: f32) -> f32 {
+        let last = self.speeds.len().saturating_sub(1);
+        self.speeds
+            .get((last as f32 * ratio).round() as usize)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// コンソールに書き出すための、数行の報告
+    pub fn report(&self) -> String {
+        let gap = match self.gap {
+            Some(gap) => format!("narrowest {:.0}px at the player's rows", gap),
+            None => "no bullets reach the player's rows".to_string(),
+        };
+        [
+            format!(
+                "Pattern \"{}\": difficulty {:.1} ({})",
+                PATTERNS[self.pattern].0,
+                self.estimate(),
+                self.grade()
+            ),
+            format!(
+                "  density  mean {:.2}, peak {} bullets within {:.0}px of the player's usual spots",
+                self.mean_density, self.peak_density, DENSITY_RADIUS
+            ),
+            format!("  gap      {}", gap),
+            format!(
+                "  speed    median {:.1}, 90% {:.1}, max {:.1} px/frame",
+                self.percentile(0.5),
+                self.percentile(0.9),
+                self.percentile(1.0)
+            ),
+        ]
+        .join("\n")
+    }
+}
+
+/// 名前付きパターン（`PATTERNS`の番号）を`ANALYSIS_FRAMES`だけ動かし、`SAMPLE_INTERVAL`ごとに弾の様子を測る
+pub fn analyze(pattern: usize) -> Analysis {
+    let playfield = Playfield::MAIN;
+    let rows = PLAYER_ROWS.map(|above| playfield.bottom() - above);
+    let spots: Vec<Point> = rows
+        .iter()
+        .flat_map(|&y| {
+            (0..PLAYER_COLUMNS).map(move |i| Point {
+                x: playfield.left()
+                    + (playfield.right() - playfield.left()) * (i as f32 + 0.5)
+                        / PLAYER_COLUMNS as f32,
+                y,
+            })
+        })
+        .collect();
+
+    let mut level = Level::pattern_sandbox(ANALYSIS_SEED, pattern);
+    let mut total_density = 0;
+    let mut peak_density = 0;
+    let mut samples = 0;
+    let mut gap: Option<f32> = None;
+    let mut speeds = Vec::new();
+    for frame in 1..=ANALYSIS_FRAMES {
+        level.update(&[Actions::default()]);
+        if frame % SAMPLE_INTERVAL != 0 {
+            continue;
+        }
+        let bullets: Vec<_> = level.bullet_motions().collect();
+        for spot in spots.iter() {
+            let near = bullets
+                .iter()
+                .filter(|(pos, _, radius)| {
+                    let dx = pos.x - spot.x;
+                    let dy = pos.y - spot.y;
+                    let r = DENSITY_RADIUS + radius;
+                    dx * dx + dy * dy <= r * r
+                })
+                .count() as u32;
+            total_density += near;
+            peak_density = peak_density.max(near);
+        }
+        samples += spots.len() as u32;
+        for &row in rows.iter() {
+            if let Some(widest) = widest_gap(&playfield, row, &bullets) {
+                gap = Some(gap.map_or(widest, |gap| gap.min(widest)));
+            }
+        }
+        speeds.extend(bullets.iter().map(|(_, vel, _)| vel.length()));
+    }
+    speeds.sort_by(f32::total_cmp);
+    Analysis {
+        pattern,
+        mean_density: total_density as f32 / samples.max(1) as f32,
+        peak_density,
+        gap,
+        speeds,
+    }
+}
+
+/// 高さ`row`の帯にかかる弾を横一列に並べたときの、弾と弾（または壁）の間の一番広い隙間。帯に弾がなければ`None`
+fn widest_gap(playfield: &Playfield, row: f32, bullets: &[(Point, Vector, f32)]) -> Option<f32> {
+    let mut spans: Vec<(f32, f32)> = bullets
+        .iter()
+        .filter(|(pos, _, radius)| (pos.y - row).abs() <= LANE_HEIGHT / 2.0 + radius)
+        .map(|(pos, _, radius)| (pos.x - radius, pos.x + radius))
+        .collect();
+    if spans.is_empty() {
+        return None;
+    }
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut widest: f32 = 0.0;
+    let mut edge = playfield.left();
+    for (start, end) in spans {
+        widest = widest.max(start - edge);
+        edge = edge.max(end);
+    }
+    Some(widest.max(playfield.right() - edge))
+}
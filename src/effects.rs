@@ -0,0 +1,99 @@
+use crate::{
+    constants::frames, engine::Renderer, palette::Color, playfield::Playfield, settings::Settings,
+};
+
+// 「点滅を減らす」設定のときの上限
+const SAFE_FLASH_ALPHA: f32 = 0.25; // 画面全体のフラッシュの最大不透明度
+pub const SAFE_SHAKE_FACTOR: f32 = 0.25; // 画面揺れの大きさの倍率
+
+/// 画面全体にかかる演出（フラッシュ、揺れ）。点滅を繰り返す演出は使わない
+#[derive(Clone)]
+pub struct Effects {
+    frame: u16,
+    flash: Fade, // 一回きりのフラッシュ
+    shake: Fade, // 画面揺れ（`strength`は揺れ幅のピクセル数）
+}
+
+/// 残りフレームに比例して弱まる演出
+#[derive(Clone, Copy, Default)]
+struct Fade {
+    strength: f32,
+    remaining: u16,
+    duration: u16,
+}
+
+impl Fade {
+    fn new(strength: f32, duration: u16) -> Self {
+        Self {
+            strength,
+            remaining: duration,
+            duration,
+        }
+    }
+
+    fn update(&mut self) {
+        self.remaining = self.remaining.saturating_sub(1);
+    }
+
+    fn current(&self) -> f32 {
+        if self.duration == 0 {
+            return 0.0;
+        }
+        self.strength * self.remaining as f32 / self.duration as f32
+    }
+}
+
+impl Effects {
+    pub fn new() -> Self {
+        Self {
+            frame: 0,
+            flash: Fade::default(),
+            shake: Fade::default(),
+        }
+    }
+
+    /// `duration`は基準の速さでのフレーム数（ほかの演出も同じ）
+    pub fn flash(&mut self, alpha: f32, duration: u16) {
+        self.flash = Fade::new(alpha, frames(duration as u32) as u16);
+    }
+
+    pub fn shake(&mut self, magnitude: f32, duration: u16) {
+        self.shake = Fade::new(magnitude, frames(duration as u32) as u16);
+    }
+
+    pub fn update(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+        self.flash.update();
+        self.shake.update();
+    }
+
+    /// 画面揺れを反映させる。描画の最後に`end_draw`を呼ぶこと。
+    pub fn begin_draw(&self, renderer: &dyn Renderer, settings: &Settings) {
+        renderer.save();
+
+        let mut magnitude = self.shake.current();
+        if settings.reduce_flashing {
+            magnitude *= SAFE_SHAKE_FACTOR;
+        }
+        if magnitude > 0.0 {
+            let t = self.frame as f32 / frames(1) as f32; // 基準の速さでの経過フレーム
+            renderer.translate(magnitude * (t * 2.3).sin(), magnitude * (t * 3.7).cos());
+        }
+    }
+
+    /// フラッシュを重ねて描き、`begin_draw`での変更を元に戻す
+    pub fn end_draw(&self, renderer: &dyn Renderer, settings: &Settings, playfield: &Playfield) {
+        let mut alpha = self.flash.current();
+        if settings.reduce_flashing {
+            alpha = alpha.min(SAFE_FLASH_ALPHA);
+        }
+
+        if alpha > 0.0 {
+            renderer.set_alpha(alpha);
+            renderer.set_color(Color::Flash);
+            renderer.fill_rect(playfield.rect());
+        }
+
+        renderer.restore();
+    }
+}
@@ -0,0 +1,137 @@
+use crate::{
+    constants::CREDITS_SPEED,
+    engine::Renderer,
+    input::{Device, Prompt},
+    math::Point,
+    palette::Color,
+    playfield::Playfield,
+    profile::Profile,
+};
+
+// 最後の行には、もう一度遊ぶための操作の案内を続ける
+const CREDITS: [&str; 3] = ["STAFF", "wasm_game contributors", "Thank you for playing!"];
+const LINE_HEIGHT: f32 = 40.0;
+const CREDITS_TOP: f32 = 240.0; // スタッフロールが止まる位置（プレイフィールドの上の端から）
+const FAST_FORWARD: u32 = 4; // 早送り中は1フレームでこれだけ進める
+
+/// 最後のステージをクリアしたあとの結果とスタッフロール
+pub struct Ending {
+    frame: u32,
+    score: u64,         // クリアボーナスを含めた最終スコア
+    clear_bonus: u64,   // 残機とボムから計算したボーナス
+    one_cc: bool,       // コンティニューなしでクリアしたか
+    ranked: bool,       // 記録対象の条件で遊んだか（1ccの記録を残したか）
+    first_one_cc: bool, // 記録の上で初めての1ccか
+    clears: u32,        // 記録の上でのクリア回数（今回を含む）
+}
+
+impl Ending {
+    /// `profile`は今回のクリアを記録したあとのもの
+    pub fn new(
+        score: u64,
+        clear_bonus: u64,
+        one_cc: bool,
+        ranked: bool,
+        first_one_cc: bool,
+        profile: &Profile,
+    ) -> Self {
+        Self {
+            frame: 0,
+            score,
+            clear_bonus,
+            one_cc,
+            ranked,
+            first_one_cc,
+            clears: profile.clears(),
+        }
+    }
+
+    /// `fast`なら早送りする。スタッフロールが止まったら`true`を返す
+    pub fn update(&mut self, fast: bool) -> bool {
+        let step = if fast { FAST_FORWARD } else { 1 };
+        self.frame = self.frame.saturating_add(step);
+        !self.is_rolling()
+    }
+
+    /// スタッフロールが止まるまでのフレーム数
+    fn rolling_frames() -> u32 {
+        let field = Playfield::MAIN;
+        ((field.bottom() - field.top() - CREDITS_TOP) / CREDITS_SPEED).ceil() as u32
+    }
+
+    /// スタッフロールがまだ上がっている途中か
+    pub fn is_rolling(&self) -> bool {
+        self.frame < Self::rolling_frames()
+    }
+
+    /// スタッフロールを最後まで飛ばす
+    pub fn skip(&mut self) {
+        self.frame = self.frame.max(Self::rolling_frames());
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, device: Device) {
+        let field = Playfield::MAIN;
+        renderer.set_color(Color::Border);
+        renderer.draw_rect(field.rect());
+        renderer.set_color(Color::Text);
+
+        let x = field.center_x();
+        renderer.draw_large_text_centered(
+            "ALL CLEAR",
+            &Point {
+                x,
+                y: field.top() + 60.0,
+            },
+        );
+        renderer.draw_text_centered(
+            &format!("Clear bonus {}", self.clear_bonus),
+            &Point {
+                x,
+                y: field.top() + 110.0,
+            },
+        );
+        renderer.draw_text_centered(
+            &format!("Score {}", self.score),
+            &Point {
+                x,
+                y: field.top() + 140.0,
+            },
+        );
+        let result = match (self.one_cc, self.ranked) {
+            (true, true) if self.first_one_cc => format!("First 1CC!  Clears {}", self.clears),
+            (true, true) => format!("1CC  Clears {}", self.clears),
+            (true, false) => "1CC (unranked)".to_string(),
+            (false, _) => format!("Clears {}", self.clears),
+        };
+        renderer.draw_text_centered(
+            &result,
+            &Point {
+                x,
+                y: field.top() + 170.0,
+            },
+        );
+
+        // スタッフロールは下から上がってきて、結果の下で止まる
+        let top =
+            (field.bottom() - self.frame as f32 * CREDITS_SPEED).max(field.top() + CREDITS_TOP);
+        let hint = if self.is_rolling() {
+            format!(
+                "{} (hold): fast forward  {}: skip",
+                device.glyph(Prompt::FastForward),
+                device.glyph(Prompt::Skip)
+            )
+        } else {
+            format!(
+                "{}: play again  {}: new seed",
+                device.glyph(Prompt::Retry),
+                device.glyph(Prompt::Reseed)
+            )
+        };
+        for (i, line) in CREDITS.iter().copied().chain([hint.as_str()]).enumerate() {
+            let y = top + LINE_HEIGHT * i as f32;
+            if y <= field.bottom() {
+                renderer.draw_text_centered(line, &Point { x, y });
+            }
+        }
+    }
+}
@@ -1,56 +1,165 @@
-use std::{cell::RefCell, collections::HashMap, f64::consts::PI, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    f64::consts::PI,
+    rc::Rc,
+};
 
 use crate::{
-    browser::{self, window, LoopClosure},
+    browser::{self, LoopClosure},
+    constants::TICK_RATE,
+    input,
+    layout::Viewport,
     math::{Point, Rect},
+    palette::{Color, Palette, Theme},
+    render_list::{self, Presenter},
 };
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use futures::channel::mpsc::{unbounded, UnboundedReceiver};
 use wasm_bindgen::{JsCast, JsValue};
-use web_sys::{CanvasRenderingContext2d, KeyboardEvent};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent, Path2d};
 
 #[async_trait(?Send)]
 pub trait Game {
     async fn initialize(&self) -> Result<Box<dyn Game>>;
     fn update(&mut self, keystate: &KeyState);
-    fn draw(&self, renderer: &Renderer);
+    fn draw(&self, renderer: &dyn Renderer);
+
+    /// ゲーム速度の倍率。1.0未満にすると1フレームの間隔が長くなる。
+    fn speed(&self) -> f32 {
+        1.0
+    }
+
+    /// 描画の間隔（ミリ秒）を受け取る。処理が重いかを見るのに使う。
+    fn record_frame_time(&mut self, _delta_ms: f32) {}
+
+    /// 1回の描画の間に、処理の段ごとにかかった時間を受け取る
+    fn record_phase_times(&mut self, _times: PhaseTimes) {}
+
+    /// シミュレーションが遅れているときに、描画を1回おきに飛ばしてよいか
+    fn frame_skip(&self) -> bool {
+        false
+    }
+
+    /// 内部で描く解像度の、キャンバスの大きさに対する倍率（1.0以下）
+    fn render_scale(&self) -> f32 {
+        1.0
+    }
+
+    /// 描いたものを画面にどう並べるか
+    fn viewport(&self) -> Viewport {
+        Viewport::default()
+    }
+}
+
+const FRAME_SIZE: f32 = 1.0 / TICK_RATE as f32 * 1000.0;
+
+/// 1回の描画の間に、処理の段ごとにかかった時間（ミリ秒）
+#[derive(Clone, Copy, Default)]
+pub struct PhaseTimes {
+    pub update_ms: f32,  // その間に進めたフレームすべての更新
+    pub draw_ms: f32,    // 描画命令の並びを作る
+    pub present_ms: f32, // まとめ直してキャンバスに描く（描画を飛ばしたときは0）
 }
 
-const FRAME_SIZE: f32 = 1.0 / 60.0 * 1000.0;
 pub struct GameLoop {
     last_frame: f64,
     accumulated_delta: f32,
+    skipped_draw: bool, // 前回のフレームで描画を飛ばしたか
 }
 type SharedLoopClosure = Rc<RefCell<Option<LoopClosure>>>;
 
 impl GameLoop {
     pub async fn start(game: impl Game + 'static) -> Result<()> {
-        let mut keyevent_receiver = prepare_input()?;
+        let keystate = prepare_input()?;
         let mut game = game.initialize().await?;
         let mut game_loop = GameLoop {
             last_frame: browser::now()?,
             accumulated_delta: 0.0,
+            skipped_draw: false,
         };
 
-        let renderer = Renderer {
-            context: browser::context()?,
-        };
-        renderer.init();
+        let screen = browser::canvas()?;
+        let renderer = CanvasRenderer::new(browser::context_of(&screen)?);
+        let recorder = RecordingRenderer::default();
+        let mut presenter = Presenter::default();
+        let mut offscreen: Option<Offscreen> = None;
+        let mut viewport = Viewport::default();
 
         let f: SharedLoopClosure = Rc::new(RefCell::new(None));
         let g: SharedLoopClosure = f.clone();
 
-        let mut keystate = KeyState::new();
         *g.borrow_mut() = Some(browser::create_raf_closure(move |perf: f64| {
-            process_input(&mut keystate, &mut keyevent_receiver);
-            game_loop.accumulated_delta += (perf - game_loop.last_frame) as f32;
-            while game_loop.accumulated_delta > FRAME_SIZE {
-                game.update(&keystate);
-                game_loop.accumulated_delta -= FRAME_SIZE;
+            let delta = (perf - game_loop.last_frame) as f32;
+            game.record_frame_time(delta);
+            game_loop.accumulated_delta += delta;
+            let frame_size = FRAME_SIZE / game.speed();
+            let mut ticks = 0;
+            // 時間を測れなくても遊べるよう、測れなければ0とみなす
+            let clock = || browser::now().unwrap_or(0.0);
+            let mut times = PhaseTimes::default();
+            let started = clock();
+            while game_loop.accumulated_delta > frame_size {
+                game.update(&keystate.borrow());
+                keystate.borrow_mut().clear_repeats();
+                keystate.borrow_mut().clear_click();
+                game_loop.accumulated_delta -= frame_size;
+                ticks += 1;
             }
             game_loop.last_frame = perf;
-            game.draw(&renderer);
+            let updated = clock();
+            times.update_ms = (updated - started) as f32;
+            // 1回の描画の間に2フレーム以上進めたら遅れている。続けて飛ばすことはしない。
+            let behind = ticks >= 2;
+            if game.frame_skip() && behind && !game_loop.skipped_draw {
+                game_loop.skipped_draw = true;
+            } else {
+                game_loop.skipped_draw = false;
+                // いったん描画命令の並びにして、まとめ直してからキャンバスに描く
+                game.draw(&recorder);
+                let drawn = clock();
+                let scale = game.render_scale();
+                let wanted = game.viewport();
+                if wanted != viewport
+                    || offscreen.as_ref().map_or(1.0, |target| target.scale) != scale
+                {
+                    if wanted.height != viewport.height {
+                        let fit = wanted.height > screen.width() as f32;
+                        if let Err(err) = browser::resize_canvas(&screen, wanted.height as u32, fit)
+                        {
+                            log!("Could not resize canvas: {:#?}", err);
+                        }
+                        // 大きさを変えると線の太さや文字の設定も初めに戻る
+                        renderer.init();
+                    }
+                    viewport = wanted;
+                    renderer.clear_all(&screen);
+                    offscreen = (scale < 1.0)
+                        .then(|| Offscreen::new(scale, &screen))
+                        .transpose()
+                        .unwrap_or_else(|err| {
+                            log!("Could not create offscreen canvas: {:#?}", err);
+                            None
+                        });
+                    // 描画先が変わったので、前の描画との差分ではなくすべて描き直す
+                    presenter = Presenter::default();
+                }
+                let calls = render_list::batch(recorder.take());
+                match &offscreen {
+                    Some(target) => {
+                        target.renderer.set_view(&viewport, target.scale);
+                        presenter.present(calls, &target.renderer);
+                        target.present(&renderer.context, &screen);
+                    }
+                    None => {
+                        renderer.set_view(&viewport, 1.0);
+                        presenter.present(calls, &renderer);
+                    }
+                }
+                times.draw_ms = (drawn - updated) as f32;
+                times.present_ms = (clock() - drawn) as f32;
+            }
+            game.record_phase_times(times);
 
             let _ = browser::request_animation_frame(f.borrow().as_ref().unwrap());
         }));
@@ -64,16 +173,146 @@ impl GameLoop {
     }
 }
 
-pub struct Renderer {
+/// 内部の解像度を下げて描くときの描画先。ページにないキャンバスに縮めて描き、画面のキャンバスに引き伸ばして写す。
+/// 座標は縮める前のままで描けるよう、描画先の座標の変換に倍率を掛けておく。
+struct Offscreen {
+    scale: f32,
+    canvas: HtmlCanvasElement,
+    renderer: CanvasRenderer,
+}
+
+impl Offscreen {
+    fn new(scale: f32, screen: &HtmlCanvasElement) -> Result<Self> {
+        let size = |length: u32| ((length as f32 * scale).round() as u32).max(1);
+        let canvas = browser::create_canvas(size(screen.width()), size(screen.height()))?;
+        let renderer = CanvasRenderer::new(browser::context_of(&canvas)?);
+        Ok(Self {
+            scale,
+            canvas,
+            renderer,
+        })
+    }
+
+    /// 描いたものを画面のキャンバス全体に引き伸ばして写す
+    fn present(&self, screen_context: &CanvasRenderingContext2d, screen: &HtmlCanvasElement) {
+        let (width, height) = (screen.width() as f64, screen.height() as f64);
+        screen_context.save();
+        let _ = screen_context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+        screen_context.set_global_alpha(1.0);
+        screen_context.clear_rect(0.0, 0.0, width, height);
+        let _ = screen_context.draw_image_with_html_canvas_element_and_dw_and_dh(
+            &self.canvas,
+            0.0,
+            0.0,
+            width,
+            height,
+        );
+        screen_context.restore();
+    }
+}
+
+/// 描画先。ブラウザのキャンバスに描くものと、描画命令を記録するだけのものがある。
+pub trait Renderer {
+    fn set_palette(&self, palette: Palette);
+    fn set_theme(&self, theme: Theme);
+    fn clear(&self, rect: &Rect);
+    fn fill_rect(&self, rect: &Rect);
+    fn draw_rect(&self, rect: &Rect);
+    fn draw_line(&self, start: &Point, end: &Point);
+    #[allow(dead_code)]
+    fn draw_triangle(&self, p1: &Point, p2: &Point, p3: &Point);
+    fn draw_circle(&self, center: &Point, radius: f32);
+    /// 同じ色の円をまとめて1回で描く（たくさんの弾を軽く描くため）
+    fn draw_circles(&self, circles: &[(Point, f32)]);
+    fn save(&self);
+    fn restore(&self);
+    fn translate(&self, dx: f32, dy: f32);
+    fn set_alpha(&self, alpha: f32);
+    fn draw_text(&self, text: &str, position: &Point);
+    /// `position`を中心にして文字を描く
+    fn draw_text_centered(&self, text: &str, position: &Point);
+    /// `position`を中心にして大きな文字を描く（目立たせたい表示用）
+    fn draw_large_text_centered(&self, text: &str, position: &Point);
+    /// 線と塗りつぶしの色を、現在のパレットで`color`に対応する色にする
+    fn set_color(&self, color: Color);
+}
+
+/// ブラウザのキャンバスに描く
+pub struct CanvasRenderer {
     context: CanvasRenderingContext2d,
+    palette: Cell<Palette>,
+    theme: Cell<Theme>,
+    circles: RefCell<HashMap<u32, Path2d>>, // 原点を中心にした円のパス。半径を0.5刻みにしたものがキー
 }
 
-impl Renderer {
-    pub fn init(&self) {
+const MAX_CACHED_CIRCLES: usize = 1024;
+
+impl CanvasRenderer {
+    fn new(context: CanvasRenderingContext2d) -> Self {
+        let renderer = Self {
+            context,
+            palette: Cell::new(Palette::default()),
+            theme: Cell::new(Theme::default()),
+            circles: RefCell::new(HashMap::new()),
+        };
+        renderer.init();
+        renderer
+    }
+
+    fn init(&self) {
         self.context.set_line_width(2.0);
+        self.context.set_font("20px sans-serif");
     }
 
-    pub fn clear(&self, rect: &Rect) {
+    /// 論理座標から描画先の座標への変換を`viewport`にする。`scale`は描画先のキャンバスそのものの倍率
+    fn set_view(&self, viewport: &Viewport, scale: f32) {
+        let zoom = (viewport.scale * scale) as f64;
+        let _ = self.context.set_transform(
+            zoom,
+            0.0,
+            0.0,
+            zoom,
+            (viewport.dx * scale).into(),
+            (viewport.dy * scale).into(),
+        );
+    }
+
+    /// 座標の変換にかかわらず、`canvas`全体を消す
+    fn clear_all(&self, canvas: &HtmlCanvasElement) {
+        let _ = self.context.set_transform(1.0, 0.0, 0.0, 1.0, 0.0, 0.0);
+        self.context
+            .clear_rect(0.0, 0.0, canvas.width().into(), canvas.height().into());
+    }
+
+    /// 半径`radius`の円のパスを、作ったことがあれば使い回す
+    fn circle_path(&self, radius: f32) -> Option<Path2d> {
+        let key = (radius * 2.0).round() as u32;
+        let mut circles = self.circles.borrow_mut();
+        if let Some(path) = circles.get(&key) {
+            return Some(path.clone());
+        }
+        let path = Path2d::new().ok()?;
+        path.arc(0.0, 0.0, key as f64 / 2.0, 0.0, 2.0 * PI).ok()?;
+        path.close_path();
+        // 半径が少しずつ変わる円（広がる輪など）で増え続けないようにする
+        if circles.len() >= MAX_CACHED_CIRCLES {
+            circles.clear();
+        }
+        circles.insert(key, path.clone());
+        Some(path)
+    }
+}
+
+impl Renderer for CanvasRenderer {
+    fn set_palette(&self, palette: Palette) {
+        self.palette.set(palette);
+    }
+
+    fn set_theme(&self, theme: Theme) {
+        self.theme.set(theme);
+    }
+
+    fn clear(&self, rect: &Rect) {
         self.context.clear_rect(
             rect.x.into(),
             rect.y.into(),
@@ -82,7 +321,16 @@ impl Renderer {
         );
     }
 
-    pub fn draw_rect(&self, rect: &Rect) {
+    fn fill_rect(&self, rect: &Rect) {
+        self.context.fill_rect(
+            rect.x.into(),
+            rect.y.into(),
+            rect.width.into(),
+            rect.height.into(),
+        );
+    }
+
+    fn draw_rect(&self, rect: &Rect) {
         self.context.stroke_rect(
             rect.x.into(),
             rect.y.into(),
@@ -91,8 +339,7 @@ impl Renderer {
         );
     }
 
-    #[allow(dead_code)]
-    pub fn draw_line(&self, start: &Point, end: &Point) {
+    fn draw_line(&self, start: &Point, end: &Point) {
         self.context.begin_path();
         self.context.move_to(start.x.into(), start.y.into());
         self.context.line_to(end.x.into(), end.y.into());
@@ -100,8 +347,7 @@ impl Renderer {
         self.context.stroke();
     }
 
-    #[allow(dead_code)]
-    pub fn draw_triangle(&self, p1: &Point, p2: &Point, p3: &Point) {
+    fn draw_triangle(&self, p1: &Point, p2: &Point, p3: &Point) {
         self.context.begin_path();
         self.context.move_to(p1.x.into(), p1.y.into());
         self.context.line_to(p2.x.into(), p2.y.into());
@@ -110,7 +356,14 @@ impl Renderer {
         self.context.stroke();
     }
 
-    pub fn draw_circle(&self, center: &Point, radius: f32) {
+    fn draw_circle(&self, center: &Point, radius: f32) {
+        if let Some(path) = self.circle_path(radius) {
+            let (x, y) = (center.x.into(), center.y.into());
+            let _ = self.context.translate(x, y);
+            self.context.stroke_with_path(&path);
+            let _ = self.context.translate(-x, -y);
+            return;
+        }
         self.context.begin_path();
         let _ = self.context.arc(
             center.x.into(),
@@ -123,24 +376,191 @@ impl Renderer {
         self.context.stroke();
     }
 
-    pub fn set_color(&self, str: &str) {
-        self.context.set_stroke_style(&JsValue::from_str(str));
+    fn draw_circles(&self, circles: &[(Point, f32)]) {
+        self.context.begin_path();
+        for (center, radius) in circles {
+            self.context
+                .move_to((center.x + radius).into(), center.y.into());
+            let _ = self.context.arc(
+                center.x.into(),
+                center.y.into(),
+                (*radius).into(),
+                0.0,
+                2.0 * PI,
+            );
+        }
+        self.context.stroke();
+    }
+
+    fn save(&self) {
+        self.context.save();
+    }
+
+    fn restore(&self) {
+        self.context.restore();
+    }
+
+    fn translate(&self, dx: f32, dy: f32) {
+        let _ = self.context.translate(dx.into(), dy.into());
+    }
+
+    fn set_alpha(&self, alpha: f32) {
+        self.context.set_global_alpha(alpha.into());
+    }
+
+    fn draw_text(&self, text: &str, position: &Point) {
+        let _ = self
+            .context
+            .fill_text(text, position.x.into(), position.y.into());
+    }
+
+    fn draw_text_centered(&self, text: &str, position: &Point) {
+        self.context.save();
+        self.context.set_text_align("center");
+        self.draw_text(text, position);
+        self.context.restore();
+    }
+
+    fn draw_large_text_centered(&self, text: &str, position: &Point) {
+        self.context.save();
+        self.context.set_font("36px sans-serif");
+        self.draw_text_centered(text, position);
+        self.context.restore();
+    }
+
+    fn set_color(&self, color: Color) {
+        let css = JsValue::from_str(self.palette.get().themed_css(self.theme.get(), color));
+        self.context.set_stroke_style(&css);
+        self.context.set_fill_style(&css);
     }
 }
 
-enum KeyPress {
-    KeyUp(KeyboardEvent),
-    KeyDown(KeyboardEvent),
+/// 描画命令1つ分
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCall {
+    SetPalette(Palette),
+    SetTheme(Theme),
+    Clear(Rect),
+    FillRect(Rect),
+    DrawRect(Rect),
+    Line(Point, Point),
+    Triangle(Point, Point, Point),
+    Circle(Point, f32),
+    Circles(Vec<(Point, f32)>),
+    Save,
+    Restore,
+    Translate(f32, f32),
+    SetAlpha(f32),
+    Text(String, Point),
+    TextCentered(String, Point),
+    LargeTextCentered(String, Point),
+    SetColor(Color),
+}
+
+/// 描く代わりに描画命令を順に記録する。画面なしで描画の中身を確かめるのに使う。
+#[derive(Default)]
+pub struct RecordingRenderer {
+    calls: RefCell<Vec<DrawCall>>,
+}
+
+impl RecordingRenderer {
+    /// ここまでに記録した描画命令
+    pub fn calls(&self) -> Vec<DrawCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// ここまでに記録した描画命令を取り出して、記録を空にする
+    pub fn take(&self) -> Vec<DrawCall> {
+        self.calls.take()
+    }
+
+    fn record(&self, call: DrawCall) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+impl Renderer for RecordingRenderer {
+    fn set_palette(&self, palette: Palette) {
+        self.record(DrawCall::SetPalette(palette));
+    }
+
+    fn set_theme(&self, theme: Theme) {
+        self.record(DrawCall::SetTheme(theme));
+    }
+
+    fn clear(&self, rect: &Rect) {
+        self.record(DrawCall::Clear(*rect));
+    }
+
+    fn fill_rect(&self, rect: &Rect) {
+        self.record(DrawCall::FillRect(*rect));
+    }
+
+    fn draw_rect(&self, rect: &Rect) {
+        self.record(DrawCall::DrawRect(*rect));
+    }
+
+    fn draw_line(&self, start: &Point, end: &Point) {
+        self.record(DrawCall::Line(*start, *end));
+    }
+
+    fn draw_triangle(&self, p1: &Point, p2: &Point, p3: &Point) {
+        self.record(DrawCall::Triangle(*p1, *p2, *p3));
+    }
+
+    fn draw_circle(&self, center: &Point, radius: f32) {
+        self.record(DrawCall::Circle(*center, radius));
+    }
+
+    fn draw_circles(&self, circles: &[(Point, f32)]) {
+        self.record(DrawCall::Circles(circles.to_vec()));
+    }
+
+    fn save(&self) {
+        self.record(DrawCall::Save);
+    }
+
+    fn restore(&self) {
+        self.record(DrawCall::Restore);
+    }
+
+    fn translate(&self, dx: f32, dy: f32) {
+        self.record(DrawCall::Translate(dx, dy));
+    }
+
+    fn set_alpha(&self, alpha: f32) {
+        self.record(DrawCall::SetAlpha(alpha));
+    }
+
+    fn draw_text(&self, text: &str, position: &Point) {
+        self.record(DrawCall::Text(text.to_string(), *position));
+    }
+
+    fn draw_text_centered(&self, text: &str, position: &Point) {
+        self.record(DrawCall::TextCentered(text.to_string(), *position));
+    }
+
+    fn draw_large_text_centered(&self, text: &str, position: &Point) {
+        self.record(DrawCall::LargeTextCentered(text.to_string(), *position));
+    }
+
+    fn set_color(&self, color: Color) {
+        self.record(DrawCall::SetColor(color));
+    }
 }
 
 pub struct KeyState {
     pressed_keys: HashMap<String, KeyboardEvent>,
+    repeated_keys: HashSet<String>, // 前回の更新からOSのキーリピートが来たキー
+    clicked: Option<Point>, // 前回の更新からキャンバスがクリックされた位置（キャンバスの画素）
 }
 
 impl KeyState {
     pub fn new() -> Self {
         KeyState {
             pressed_keys: HashMap::new(),
+            repeated_keys: HashSet::new(),
+            clicked: None,
         }
     }
 
@@ -148,50 +568,110 @@ impl KeyState {
         self.pressed_keys.contains_key(code)
     }
 
+    /// どれかのキーが押されているか
+    pub fn is_any_pressed(&self) -> bool {
+        !self.pressed_keys.is_empty()
+    }
+
     fn set_pressed(&mut self, code: &str, event: KeyboardEvent) {
         self.pressed_keys.insert(code.into(), event);
     }
 
     fn set_released(&mut self, code: &str) {
-        self.pressed_keys.remove(code.into());
+        self.pressed_keys.remove(code);
+    }
+
+    /// 前回の更新からキーリピートが来たか。押しっぱなしで続けて入力したい文字入力用。
+    pub fn is_repeated(&self, code: &str) -> bool {
+        self.repeated_keys.contains(code)
+    }
+
+    fn set_repeated(&mut self, code: &str) {
+        self.repeated_keys.insert(code.into());
+    }
+
+    fn clear_repeats(&mut self) {
+        self.repeated_keys.clear();
+    }
+
+    /// 前回の更新からキャンバスがクリックされていれば、その位置（キャンバスの画素）。開発用のビルドでだけ記録する
+    pub fn clicked_at(&self) -> Option<Point> {
+        self.clicked
+    }
+
+    fn set_clicked(&mut self, at: Point) {
+        self.clicked = Some(at);
+    }
+
+    fn clear_click(&mut self) {
+        self.clicked = None;
+    }
+
+    fn release_all(&mut self) {
+        self.pressed_keys.clear();
+        self.repeated_keys.clear();
     }
 }
 
-// ブラウザからのキー入力のレシーバーを作る
-fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
-    let (keydown_sender, keyevent_receiver) = unbounded();
-    let keydown_sender = Rc::new(RefCell::new(keydown_sender));
-    let keyup_sender = keydown_sender.clone();
+// ブラウザからのキー入力を直接反映するキーの状態を作る。
+// イベントを溜めずにその場で反映するので、描画が止まっている間（裏のタブなど）に
+// キーのイベントがいくら来ても、覚えておくのは押されているキーの分だけで済む。
+// キーはキャンバスにフォーカスがあるときだけ受け取り、ページの他の部品の入力には口を出さない。
+fn prepare_input() -> Result<Rc<RefCell<KeyState>>> {
+    let keystate = Rc::new(RefCell::new(KeyState::new()));
+    let keydown_state = keystate.clone();
+    let keyup_state = keystate.clone();
+    let blur_state = keystate.clone();
 
     let onkeydown = browser::closure_wrap(Box::new(move |keycode: KeyboardEvent| {
-        let _ = keydown_sender
-            .borrow_mut()
-            .start_send(KeyPress::KeyDown(keycode));
+        // IMEで変換中のキーは文字の入力のためのものなので、ゲームには渡さない
+        if keycode.is_composing() {
+            return;
+        }
+        if input::is_game_key(&keycode.code()) {
+            keycode.prevent_default(); // SpaceやArrowでページがスクロールしないようにする
+        }
+        let mut keystate = keydown_state.borrow_mut();
+        // キーリピートは押されているキーを増やさず、来たことだけを覚えておく
+        if keycode.repeat() {
+            keystate.set_repeated(&keycode.code());
+        } else {
+            keystate.set_pressed(&keycode.code(), keycode);
+        }
     }) as Box<dyn FnMut(KeyboardEvent)>);
+    // 離したことは変換中でも反映する（押したままにならないように）
     let onkeyup = browser::closure_wrap(Box::new(move |keycode: KeyboardEvent| {
-        let _ = keyup_sender
-            .borrow_mut()
-            .start_send(KeyPress::KeyUp(keycode));
+        if input::is_game_key(&keycode.code()) {
+            keycode.prevent_default();
+        }
+        keyup_state.borrow_mut().set_released(&keycode.code());
     }) as Box<dyn FnMut(KeyboardEvent)>);
+    // フォーカスが外れると離したときのイベントが来ないので、押したままにならないよう全部離す
+    let onblur = browser::closure_wrap(Box::new(move || {
+        blur_state.borrow_mut().release_all();
+    }) as Box<dyn FnMut()>);
 
-    window()?.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
-    window()?.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
+    let canvas = browser::canvas()?;
+    // 開発用のビルドでは、クリックした位置を弾幕を置く座標の確かめに使う
+    if cfg!(debug_assertions) {
+        let click_state = keystate.clone();
+        browser::on_canvas_click(&canvas, move |x, y| {
+            click_state.borrow_mut().set_clicked(Point {
+                x: x as f32,
+                y: y as f32,
+            });
+        })?;
+    }
+    canvas.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
+    canvas.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
+    canvas.set_onblur(Some(onblur.as_ref().unchecked_ref()));
+    canvas
+        .focus()
+        .map_err(|err| anyhow!("Error focusing canvas {:#?}", err))?;
 
     onkeydown.forget();
     onkeyup.forget();
+    onblur.forget();
 
-    Ok(keyevent_receiver)
-}
-
-fn process_input(state: &mut KeyState, keyevent_receiver: &mut UnboundedReceiver<KeyPress>) {
-    loop {
-        match keyevent_receiver.try_next() {
-            Ok(None) => break,
-            Err(_) => break,
-            Ok(Some(evt)) => match evt {
-                KeyPress::KeyUp(evt) => state.set_released(&evt.code()),
-                KeyPress::KeyDown(evt) => state.set_pressed(&evt.code(), evt),
-            },
-        }
-    }
+    Ok(keystate)
 }
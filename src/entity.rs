@@ -0,0 +1,46 @@
+/// ステージの中で敵や弾を区別する番号。
+/// 一度使った番号は使い回さないので、相手が消えたあとに持ち続けても別のものを指すことはない。
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId(u32);
+
+impl EntityId {
+    /// ステージに出る前の敵や弾が持つ番号。`EntityIds`はこれを払い出さないので、ステージに出たものと取り違えない
+    pub const UNASSIGNED: Self = Self(0);
+
+    /// 払い出された番号そのもの（開発用の表示に使う）
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+/// ステージの中で番号を払い出す。`EntityId::UNASSIGNED`と重ならないよう1から数える
+#[derive(Clone)]
+pub struct EntityIds {
+    next: u32,
+}
+
+impl Default for EntityIds {
+    fn default() -> Self {
+        Self { next: 1 }
+    }
+}
+
+impl EntityIds {
+    pub fn allocate(&mut self) -> EntityId {
+        let id = EntityId(self.next);
+        self.next += 1;
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 払い出す番号は、ステージに出る前の番号と重ならない
+    #[test]
+    fn allocated_ids_differ_from_unassigned() {
+        let mut ids = EntityIds::default();
+        assert!((0..3).all(|_| ids.allocate() != EntityId::UNASSIGNED));
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{entity::EntityId, item::ItemKind, math::Point};
+
+/// 1フレームの間にステージで起きた出来事。
+/// 起こした側は積むだけにして、得点や演出、読み上げなどはそれぞれの側でまとめて受け取る。
+#[derive(Clone, Copy)]
+pub enum GameEvent {
+    EnemyDamaged(Damage), // 敵の体力は、必ずこの出来事を通して減らす
+    EnemyHurt { position: Point, amount: u32 }, // 守りの分を引いて、敵の体力が実際に減った量
+    EnemyKilled { by: Option<usize> }, // `by`は倒したプレイヤーで、得点の倍率はその位置で決まる
+    PlayerHit { player: usize, position: Point }, // `player`はステージの中でのプレイヤーの番号
+    ShieldBroken { player: usize }, // シールドが被弾を防いで壊れた。残機は減らない
+    BombUsed { player: usize },
+    ItemCollected { player: usize, kind: ItemKind },
+    SpellStarted { name: &'static str }, // ボスのスペルが始まった。スペルの記録に出会った回数を数える
+    SpellCaptured { name: &'static str, bonus: u64 },
+    BossDefeated { position: Point }, // ステージの最後のボスとの戦いが終わった。クリアの演出が始まる
+    CheckpointReached, // カジュアルモードでゲームオーバーになったら、ここからやり直せる
+}
+
+/// 敵に与えた傷
+#[derive(Clone, Copy)]
+pub struct Damage {
+    pub enemy: EntityId,
+    pub amount: u32,
+    pub by: Option<usize>, // 傷を与えたプレイヤー（ステージの中での番号）。チートなどでは`None`
+}
@@ -0,0 +1,29 @@
+//! テストで、作り直した文字列が記録しておいたもの（`fixtures`）と同じかを確かめる
+
+/// `actual`が記録`fixture`と同じか確かめる。`what`は何の記録か。
+/// 違っていれば、最初に食い違った行と、記録を作り直すための`actual`全体を書き出して失敗する
+#[track_caller]
+pub fn assert_matches_fixture(actual: &str, fixture: &str, what: &str) {
+    let mismatch = actual
+        .lines()
+        .zip(fixture.lines())
+        .enumerate()
+        .find(|(_, (actual, expected))| actual != expected);
+    if let Some((index, (actual_line, expected))) = mismatch {
+        println!("Regenerated {}:\n{}", what, actual);
+        panic!(
+            "{} differs from the fixture at line {}:\nexpected: {}\n  actual: {}",
+            what,
+            index + 1,
+            expected,
+            actual_line
+        );
+    }
+    assert_eq!(
+        actual.lines().count(),
+        fixture.lines().count(),
+        "{} has a different number of lines than the fixture:\n{}",
+        what,
+        actual
+    );
+}
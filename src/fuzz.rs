@@ -0,0 +1,185 @@
+//! 保存データや共有されたデータを読み込む口に、正しいデータを少しずつ壊した入力を与える`cargo test`のテスト。
+//! どの入力でも固まったりパニックしたりせずにエラーを返すこと、読めたものは書き出して読み直しても変わらないことを確かめる。
+//! 同じ種から作るので、失敗したときは何度でも同じ入力で再現する。
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use crate::{
+    heatmap::Heatmap,
+    history::History,
+    input::Actions,
+    pack::Pack,
+    profile::Profile,
+    replay::Replay,
+    rng::Rng,
+    settings::{BombType, Modifiers, PlayMode, ShotType},
+    spectate::StreamEvent,
+};
+
+const FUZZ_SEED: u64 = 0xF022_5EED;
+const CASES: u32 = 200; // 読み込み口ごとに試す、壊した入力の数
+const MAX_MUTATIONS: u64 = 4; // 1つの入力に加える変更の最大数
+
+// 壊れた保存データや悪意のある共有データにありそうな文字と、数として読むと範囲を超える語
+const NOISE: [&str; 12] = [
+    "",
+    " ",
+    "\n",
+    ",",
+    ";",
+    "-1",
+    "0",
+    "255",
+    "65536",
+    "4294967296",
+    "18446744073709551616",
+    "NaN",
+];
+
+/// `sample`を壊した入力を`CASES`個読ませる
+fn check<T>(
+    rng: &mut Rng,
+    name: &str,
+    sample: &str,
+    parse: fn(&str) -> Result<T>,
+    serialize: fn(&T) -> String,
+) {
+    for case in 0..CASES {
+        let input = mutate(rng, sample);
+        let Ok(value) = parse(&input) else {
+            continue;
+        };
+        // 書き出したものは、もう一度読んでも同じものに戻るはず
+        let written = serialize(&value);
+        let reread = parse(&written).unwrap_or_else(|err| {
+            panic!(
+                "{} case {}: cannot read back {:?}: {}",
+                name, case, written, err
+            )
+        });
+        assert_eq!(
+            serialize(&reread),
+            written,
+            "{} case {}: {:?} changes when read back",
+            name,
+            case,
+            written
+        );
+    }
+}
+
+/// 文字を切り詰める、雑音を差し込む、一部を消す、一部を繰り返す、のどれかを何回か加える
+fn mutate(rng: &mut Rng, sample: &str) -> String {
+    let mut text = sample.to_string();
+    for _ in 0..=rng.next_u64() % MAX_MUTATIONS {
+        let boundaries: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .collect();
+        let mut pick = || boundaries[(rng.next_u64() % boundaries.len() as u64) as usize];
+        let (a, b) = (pick(), pick());
+        let (from, to) = (a.min(b), a.max(b));
+        match rng.next_u64() % 4 {
+            0 => text.truncate(from),
+            1 => text.insert_str(from, NOISE[(rng.next_u64() % NOISE.len() as u64) as usize]),
+            2 => text.replace_range(from..to, ""),
+            _ => {
+                let repeated = text[from..to].repeat(2);
+                text.replace_range(from..to, &repeated);
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay() {
+        let mut rng = Rng::new(FUZZ_SEED);
+        let replay = Replay::new(
+            12345,
+            PlayMode::CoOp,
+            Modifiers::from_bits(0b101),
+            BombType::ALL[BombType::ALL.len() - 1],
+            ShotType::ALL[ShotType::ALL.len() - 1],
+            (0..120u16)
+                .map(|frame| std::array::from_fn(|player| Actions::from_bits(frame >> player)))
+                .collect(),
+        )
+        .serialize();
+        check(
+            &mut rng,
+            "replay",
+            &replay,
+            Replay::deserialize,
+            Replay::serialize,
+        );
+    }
+
+    #[test]
+    fn heatmap() {
+        let mut rng = Rng::new(FUZZ_SEED);
+        let heatmap = Heatmap::new().serialize();
+        check(
+            &mut rng,
+            "heatmap",
+            &heatmap,
+            Heatmap::deserialize,
+            Heatmap::serialize,
+        );
+    }
+
+    #[test]
+    fn profile() {
+        let mut rng = Rng::new(FUZZ_SEED);
+        check(
+            &mut rng,
+            "profile",
+            "3 1 1200000 800000 ABC XYZ 1",
+            Profile::deserialize,
+            Profile::serialize,
+        );
+    }
+
+    #[test]
+    fn history() {
+        let mut rng = Rng::new(FUZZ_SEED);
+        check(
+            &mut rng,
+            "history",
+            "1 1700000000000 0 0 0 120000 1 2 3 0\n2 1700000100000 1 1 1 550000 1 3 0 1",
+            History::deserialize,
+            History::serialize,
+        );
+    }
+
+    /// パックは書き出すことがないので、パニックしないことだけを確かめる
+    #[test]
+    fn pack() {
+        let mut rng = Rng::new(FUZZ_SEED);
+        let pack = "name sample\ntitle Sample Pack\nroad 20\nwave 1 0.5 0.2 fan\n\
+            script ring\ntask Ring { loop(3) { ascent(i in 0..8) { \
+            CreateShot01(GetX, GetY, 2, GetAngleToPlayer + i * 45, RED01, 0); } wait(20); } }\n\
+            @Initialize { Ring; }\nend\nwave 2.5 0.25 0.3 ring";
+        for _ in 0..CASES {
+            let _ = Pack::parse(&mutate(&mut rng, pack));
+        }
+    }
+
+    /// 配信の受け口は壊れた知らせを読み飛ばすので、パニックしないことだけを確かめる
+    #[test]
+    fn stream() {
+        let mut rng = Rng::new(FUZZ_SEED);
+        for message in ["RUN 1 42 5 1", "I 1,2 3,4 65535,0"] {
+            for _ in 0..CASES {
+                StreamEvent::decode(&mutate(&mut rng, message), &mut VecDeque::new());
+            }
+        }
+    }
+}
@@ -1,15 +1,96 @@
+use std::cell::Cell;
+
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 
 use crate::{
-    engine::{Game, KeyState, Renderer},
-    level::Level,
-    math::Rect,
+    benchmark::{self, Benchmark},
+    browser,
+    command::Command,
+    console::Console,
+    constants::{HEATMAP_SAVE_INTERVAL, TICK_RATE},
+    dialog::Confirm,
+    ending::Ending,
+    engine::{Game, KeyState, PhaseTimes, RecordingRenderer, Renderer},
+    event::GameEvent,
+    haptics,
+    heatmap::Heatmap,
+    history::{History, HistoryView, RunRecord},
+    input::{Actions, Device, Input, Prompt, TextKey, ToolKey, MAX_PLAYERS},
+    item::ItemKind,
+    layout::{self, Layout, Viewport},
+    level::{Level, MemoryUsage, Snapshot, STAGE_ID},
+    math::{Point, Rect},
+    name_entry::{EntryEvent, NameEntry},
+    netplay::{NetStatus, Netplay},
+    options::{MenuEvent, OptionsMenu},
+    pack::Pack,
+    palette::{Color, Theme},
+    playfield::Playfield,
+    profile::{Profile, Scene},
+    quality::{FrameMonitor, Quality},
+    render_list,
+    replay::Replay,
+    rng::Rng,
+    scene::{Layer, SceneStack},
+    settings::{BombType, HeatmapView, Modifiers, PlayMode, Settings, ShotType},
+    spectate::{Broadcaster, Spectator, StreamEvent, WatchStatus},
+    spellbook::{SpellBook, SpellBookEvent, SpellBookView},
+    suspend::Suspend,
+    tas::{Tas, SLOTS},
+    timing::FrameTimings,
+    title::Title,
+    tweak::{TweakPanel, Tweaks},
+    worker::WorkerGame,
 };
 
+const GARBAGE_PER_ENEMY: u32 = 3; // 対戦モードで敵を1体倒すと相手に送る弾の数
+const SNAPSHOT_SAMPLES: u32 = 20; // スナップショットの処理時間を測るときに平均をとる回数
+const STAGE_DATA_URL: &str = "stage1.txt"; // 組み込みのステージの道中を書いたファイル
+
 pub enum StgGame {
     Loading,
-    Loaded(Level),
+    Loaded(Box<Session>),
+}
+
+/// 読み込みが終わってからのゲーム全体の状態
+pub struct Session {
+    mode: PlayMode,       // 今遊んでいる形式（設定を変えてもやり直すまでは変わらない）
+    modifiers: Modifiers, // 今遊んでいるルール変更（同じく、やり直すまでは変わらない）
+    bomb: BombType,       // 今遊んでいるボムの種類（同じく）
+    shot: ShotType,       // 今遊んでいるショットの種類（同じく）
+    level: Level,         // 対戦モードでは1P側
+    rival: Option<Level>, // 対戦モードの2P側
+    input: Input,
+    settings: Settings,
+    overlays: SceneStack<Overlay>, // ゲームの上に重ねている画面。出している間はゲームを止める
+    previous_heatmap: Option<Heatmap>, // 比較用に、前回までのプレイで保存されたヒートマップ
+    netplay: Option<Netplay>,      // ネット越しの協力プレイ中か
+    broadcaster: Option<Broadcaster>, // 観戦者にプレイを配信しているか
+    spectator: Option<Spectator>,  // 誰かのプレイを観戦しているか
+    snapshot_cost: Option<f64>, // スナップショットの保存と復元にかかった時間（ミリ秒、デバッグ表示用）
+    memory: Option<MemoryUsage>, // ステージが使っているメモリの見積もり（デバッグ表示用）
+    tas: Option<Tas>,           // TASモードで遊んでいるか
+    console: Option<Console>,   // チート用のコンソールを開いている間はゲームを止める
+    tweak_panel: Option<TweakPanel>, // 調整値を変える画面を開いている間もゲームを止める
+    tweaks: Tweaks,             // その画面で変えた調整値の倍率。やり直しても残す
+    ending: Option<Ending>,     // 最後のステージをクリアしたあとの結果画面
+    profile: Profile,           // コンテンツパックで遊ぶときは、パックごとの記録
+    pack: Option<Pack>,         // 道中を差し替えるコンテンツパック
+    stage: Option<Pack>, // ステージのデータファイルから読んだ道中（読めなければ組み込みの道中で遊ぶ）
+    history: History,    // これまでに終わったプレイの記録
+    spells: SpellBook,   // これまでに出会ったスペルの記録
+    practice_spell: Option<String>, // スペルの練習をしているなら、そのスペルの名前
+    dump_draws: Cell<bool>, // 次の描画で描画命令を書き出すか
+    suspend: Suspend,
+    resume: Option<Replay>,       // 再開するかを尋ねている中断セーブ
+    title: Option<Title>,         // 遊び始める前のタイトル画面
+    checkpoint: Option<Snapshot>, // 最後に通ったチェックポイントの状態
+    attack: Option<Snapshot>,     // 練習モードで、今のスペルが始まったときの状態
+    continues: u32,               // 今のプレイでチェックポイントから続けた回数
+    frames: FrameMonitor,         // 描画が60fpsに間に合っているか
+    timings: FrameTimings,        // 描画1回ごとにかかった時間の分布
+    layout: Layout,               // 端末の向きに合わせた表示の並べ方
 }
 
 impl StgGame {
@@ -22,36 +103,1678 @@ impl StgGame {
 impl Game for StgGame {
     async fn initialize(&self) -> Result<Box<dyn Game>> {
         match self {
-            StgGame::Loading => Ok(Box::new(StgGame::Loaded(Level::new()))),
+            StgGame::Loading => {
+                // 隠しの性能測定は、ゲームの代わりに始める
+                if let Some(counts) = browser::query_param("benchmark")? {
+                    return Ok(Box::new(Benchmark::new(benchmark::parse_counts(&counts)?)));
+                }
+                // ステージをWeb Workerで進めるモードも、ゲームの代わりに始める
+                if browser::query_param("worker")?.is_some() {
+                    let stage = browser::fetch_text(STAGE_DATA_URL)
+                        .await
+                        .unwrap_or_else(|err| {
+                            log!("Could not load stage data: {:#?}", err);
+                            String::new()
+                        });
+                    return Ok(Box::new(WorkerGame::start(fresh_seed(), stage)?));
+                }
+                let previous_heatmap = Heatmap::load(STAGE_ID).unwrap_or_else(|err| {
+                    log!("Could not load heatmap: {:#?}", err);
+                    None
+                });
+                let history = History::load().unwrap_or_else(|err| {
+                    log!("Could not load run history: {:#?}", err);
+                    History::default()
+                });
+                let spells = SpellBook::load().unwrap_or_else(|err| {
+                    log!("Could not load spell records: {:#?}", err);
+                    SpellBook::default()
+                });
+                let settings = Settings::default();
+                let netplay = connect_netplay().unwrap_or_else(|err| {
+                    log!("Could not start netplay: {:#?}", err);
+                    None
+                });
+                let (mut broadcaster, spectator) = connect_stream().unwrap_or_else(|err| {
+                    log!("Could not start stream: {:#?}", err);
+                    (None, None)
+                });
+                // 通信や配信の相手が同じパックを持っているとは限らないので、パックは一人で遊ぶときだけ読み込む
+                let pack = if netplay.is_none() && spectator.is_none() && broadcaster.is_none() {
+                    load_pack().await.unwrap_or_else(|err| {
+                        log!("Could not load content pack: {:#?}", err);
+                        let _ = browser::announce(
+                            "Could not load the content pack. Playing the built-in stage",
+                        );
+                        None
+                    })
+                } else {
+                    None
+                };
+                // 読み込めなければ、ゲームに組み込んである同じ道中で遊ぶ
+                let stage = load_stage().await.map(Some).unwrap_or_else(|err| {
+                    log!("Could not load stage data: {:#?}", err);
+                    None
+                });
+                let profile = Profile::load(pack.as_ref().map(Pack::name)).unwrap_or_else(|err| {
+                    log!("Could not load profile: {:#?}", err);
+                    Profile::default()
+                });
+                let mode = if netplay.is_some() {
+                    PlayMode::CoOp
+                } else {
+                    settings.mode
+                };
+                let seed = fresh_seed();
+                let modifiers = settings.modifiers;
+                let bomb = settings.bomb;
+                let shot = settings.shot;
+                let (level, rival) = new_match(
+                    seed,
+                    mode,
+                    modifiers,
+                    bomb,
+                    shot,
+                    pack.as_ref(),
+                    stage.as_ref(),
+                );
+                level.validate(STAGE_ID)?;
+                if let Some(broadcaster) = &mut broadcaster {
+                    broadcaster.start_run(seed, mode, modifiers, bomb, shot);
+                }
+                let suspend = Suspend::install(STAGE_ID)?;
+                // 配信や通信をしているときは、途中から始めると相手と食い違うので再開できない。
+                // パックで遊ぶときも、中断したときと同じパックを読み込んだとは限らないので再開しない
+                let resume = if netplay.is_none()
+                    && spectator.is_none()
+                    && broadcaster.is_none()
+                    && pack.is_none()
+                {
+                    suspend.load().unwrap_or_else(|err| {
+                        log!("Could not load suspended run: {:#?}", err);
+                        None
+                    })
+                } else {
+                    None
+                };
+                if let Some(pack) = &pack {
+                    let _ = browser::announce(&format!("Content pack {} loaded", pack.name()));
+                }
+                if resume.is_some() {
+                    let _ = browser::announce(
+                        "Suspended run found. Enter to resume, R to start a new run",
+                    );
+                }
+                // 通信や配信の相手を待たせないよう、タイトル画面は一人で遊ぶときだけ出す
+                let title = (netplay.is_none()
+                    && spectator.is_none()
+                    && broadcaster.is_none()
+                    && resume.is_none())
+                .then(Title::default);
+                let session = Session {
+                    mode,
+                    modifiers,
+                    bomb,
+                    shot,
+                    level,
+                    rival,
+                    input: Input::new(),
+                    settings,
+                    overlays: SceneStack::new(),
+                    previous_heatmap,
+                    netplay,
+                    broadcaster,
+                    spectator,
+                    snapshot_cost: None,
+                    memory: None,
+                    tas: None,
+                    console: None,
+                    tweak_panel: None,
+                    tweaks: Tweaks::default(),
+                    ending: None,
+                    profile,
+                    pack,
+                    stage,
+                    history,
+                    spells,
+                    practice_spell: None,
+                    dump_draws: Cell::new(false),
+                    suspend,
+                    resume,
+                    title,
+                    checkpoint: None,
+                    attack: None,
+                    continues: 0,
+                    frames: FrameMonitor::default(),
+                    timings: FrameTimings::default(),
+                    layout: Layout::detect(),
+                };
+                // 尋ねている間は保存を上書きしないよう、答えるまで記録しない
+                if session.resume.is_none() {
+                    session.track_run(seed);
+                }
+                Ok(Box::new(StgGame::Loaded(Box::new(session))))
+            }
             StgGame::Loaded(_) => Err(anyhow!("Error: Game is already initialized!")),
         }
     }
 
     fn update(&mut self, keystate: &KeyState) {
-        if let StgGame::Loaded(level) = self {
-            level.update(keystate);
+        if let StgGame::Loaded(session) = self {
+            session.update(keystate);
         }
     }
 
-    fn draw(&self, renderer: &Renderer) {
-        let whole_canvas = Rect {
-            x: 0.0,
-            y: 0.0,
-            width: 600.0,
-            height: 600.0,
-        };
+    fn draw(&self, renderer: &dyn Renderer) {
+        // 縦向きではゲームの画面の外の帯にも表示を置くので、画面に映る範囲をすべて消す
+        let whole_canvas = self.viewport().visible();
 
         renderer.clear(&whole_canvas);
 
-        if let StgGame::Loaded(level) = self {
-            renderer.set_color("gray");
-            renderer.draw_rect(&Rect {
-                x: 50.0,
-                y: 30.0,
-                width: 500.0,
-                height: 540.0,
-            });
-            level.draw(renderer);
+        if let StgGame::Loaded(session) = self {
+            session.draw(renderer, &whole_canvas);
+            if session.dump_draws.take() {
+                session.log_draw_calls(&whole_canvas);
+            }
+        }
+    }
+
+    fn speed(&self) -> f32 {
+        match self {
+            StgGame::Loaded(session) => {
+                session.settings.game_speed.factor() * session.level.time_scale()
+            }
+            StgGame::Loading => 1.0,
+        }
+    }
+
+    fn record_frame_time(&mut self, delta_ms: f32) {
+        if let StgGame::Loaded(session) = self {
+            session.frames.record(delta_ms);
+            session.timings.record_interval(delta_ms);
+        }
+    }
+
+    fn viewport(&self) -> Viewport {
+        match self {
+            StgGame::Loaded(session) => session.viewport(),
+            StgGame::Loading => Viewport::default(),
+        }
+    }
+
+    fn render_scale(&self) -> f32 {
+        match self {
+            StgGame::Loaded(session) => session.settings.render_scale.factor(),
+            StgGame::Loading => 1.0,
+        }
+    }
+
+    fn record_phase_times(&mut self, times: PhaseTimes) {
+        if let StgGame::Loaded(session) = self {
+            session.timings.record_phases(times);
+        }
+    }
+
+    fn frame_skip(&self) -> bool {
+        match self {
+            StgGame::Loaded(session) => session.settings.frame_skip,
+            StgGame::Loading => false,
+        }
+    }
+}
+
+impl Session {
+    fn update(&mut self, keystate: &KeyState) {
+        self.layout = Layout::detect();
+        let actions = self.input.update(keystate, &self.settings);
+        let pressed = self.input.just_pressed();
+        if self.settings.show_hitboxes {
+            self.export_timings();
+        }
+        if let (true, Some(clicked)) = (Console::is_available(), keystate.clicked_at()) {
+            self.pick_coordinate(clicked);
+        }
+
+        // ネット越しのプレイ中は相手を待たせないようにオプション画面を開けない
+        if self.netplay.is_some() {
+            self.update_netplay(actions[0], &pressed);
+            return;
+        }
+        // 観戦中は配信された入力だけでステージを進める
+        if self.spectator.is_some() {
+            self.update_watch(&pressed);
+            return;
+        }
+        // 確認は中断セーブの再開の確認にも重ねるので、先に見る
+        if let Some(Overlay::Confirm(_)) = self.overlays.top() {
+            self.update_confirm(&pressed);
+            return;
+        }
+        if self.resume.is_some() {
+            self.update_resume(&pressed);
+            return;
+        }
+        if let Some(title) = &mut self.title {
+            let active = keystate.is_any_pressed() || self.input.held().is_any();
+            // ゲームパッドでは文字入力ができないので、ショットのボタンでも始められる
+            let start = self.input.typed() == Some(TextKey::Enter) || pressed.shot;
+            if title.update(active, start) {
+                self.title = None;
+                let _ = browser::announce("Game start");
+            }
+            return;
+        }
+        if let Some(Overlay::NameEntry(_)) = self.overlays.top() {
+            self.update_name_entry(&pressed);
+            return;
+        }
+        if let Some(broadcaster) = &mut self.broadcaster {
+            broadcaster.poll();
+        }
+
+        if pressed.pause {
+            match self.overlays.top() {
+                // 記録の一覧からはオプション画面に戻る
+                Some(Overlay::History(_) | Overlay::Spells(_)) => {
+                    self.overlays.pop();
+                    let _ = browser::announce("Options");
+                }
+                Some(_) if self.needs_restart() && self.has_progress() => {
+                    self.ask(
+                        "Restart with new settings? This run will be lost.",
+                        Pending::Restart(self.level.seed()),
+                    );
+                }
+                Some(_) => {
+                    self.overlays.pop();
+                    // 遊ぶ形式が変わったときは同じシードで最初からやり直す
+                    if self.needs_restart() {
+                        self.restart(self.level.seed());
+                    }
+                    let _ = browser::announce("Resumed game");
+                }
+                None => {
+                    self.save_heatmap();
+                    let menu = OptionsMenu::new(self.level.seed());
+                    let _ =
+                        browser::announce(&format!("Options. {}", menu.describe(&self.settings)));
+                    self.overlays.push(Overlay::Options(menu));
+                }
+            }
+            return;
+        }
+
+        let updates_game = self.overlays.updates_game();
+        match self.overlays.top_mut() {
+            Some(Overlay::Options(menu)) => match menu.update(
+                &pressed,
+                &self.input.held(),
+                self.input.typed(),
+                &mut self.settings,
+            ) {
+                Some(MenuEvent::Changed) => {
+                    let _ = browser::announce(&menu.describe(&self.settings));
+                }
+                // シードを入れ直したら、スペルの練習はやめて最初から遊ぶ
+                Some(MenuEvent::Restart(seed)) if self.has_progress() => {
+                    self.practice_spell = None;
+                    self.ask(
+                        "Restart with the new seed? This run will be lost.",
+                        Pending::Restart(seed),
+                    );
+                }
+                Some(MenuEvent::Restart(seed)) => {
+                    self.practice_spell = None;
+                    self.restart(seed);
+                    let _ = browser::announce(&format!("Started with seed {}", seed));
+                }
+                Some(MenuEvent::ShowHistory) => {
+                    let view = HistoryView::default();
+                    let _ = browser::announce(&format!(
+                        "Run history. {}",
+                        view.describe(&self.history)
+                    ));
+                    self.overlays.push(Overlay::History(view));
+                }
+                Some(MenuEvent::ShowSpells) => {
+                    let view = SpellBookView::default();
+                    let _ =
+                        browser::announce(&format!("Spell cards. {}", view.describe(&self.spells)));
+                    self.overlays.push(Overlay::Spells(view));
+                }
+                None => {}
+            },
+            Some(Overlay::History(view)) => {
+                if view.update(&pressed, &self.input.held(), &self.history) {
+                    let _ = browser::announce(&view.describe(&self.history));
+                }
+            }
+            Some(Overlay::Spells(view)) => match view.update(
+                &pressed,
+                &self.input.held(),
+                self.input.typed(),
+                &self.spells,
+            ) {
+                Some(SpellBookEvent::Changed) => {
+                    let _ = browser::announce(&view.describe(&self.spells));
+                }
+                // 練習は1人で遊ぶものなので、ネット越しに遊んでいる間は始めない
+                Some(SpellBookEvent::Practice(_))
+                    if self.netplay.is_some() || self.spectator.is_some() =>
+                {
+                    let _ = browser::announce("Spell practice is not available online");
+                }
+                Some(SpellBookEvent::Practice(index)) if self.has_progress() => {
+                    self.ask(
+                        "Practice this spell? This run will be lost.",
+                        Pending::Practice(index),
+                    );
+                }
+                Some(SpellBookEvent::Practice(index)) => self.practice(index),
+                None => {}
+            },
+            // 名前入力と確認は上で扱った。ゲームを止める画面を出している間は進めない
+            _ if !updates_game => {}
+            _ => {
+                self.update_ending(&pressed);
+                let can_retry = self.is_match_over()
+                    || self.ending.is_some()
+                    || (self.settings.practice && self.level.is_showing_death_marker());
+                if can_retry && (pressed.retry || pressed.reseed) {
+                    let seed = if pressed.reseed {
+                        fresh_seed()
+                    } else {
+                        self.level.seed()
+                    };
+                    if !self.retry_attack(seed) {
+                        self.restart(seed);
+                        let _ = browser::announce("Retry");
+                    }
+                    return;
+                }
+
+                if self.ending.is_none() && self.input.tool() == Some(ToolKey::Suspend) {
+                    self.suspend_run();
+                    return;
+                }
+                if self.ending.is_some()
+                    || self.update_console()
+                    || self.update_tweaks()
+                    || !self.update_tas()
+                    || self.is_match_over()
+                {
+                    return;
+                }
+
+                self.step(&actions);
+                self.take_checkpoint();
+                self.take_attack_snapshot();
+                if self.rival.is_none() && self.level.is_cleared() {
+                    self.finish_run();
+                    self.log_run(actions);
+                } else if self.rival.is_none()
+                    && self.level.is_game_over()
+                    && !self.continue_from_checkpoint()
+                {
+                    self.record_score();
+                    self.log_run(actions);
+                }
+                if let Some(tas) = &mut self.tas {
+                    tas.record(actions);
+                }
+                if self.is_match_over() || self.level.is_cleared() {
+                    self.suspend.stop();
+                } else {
+                    self.suspend.record(actions);
+                }
+                if let Some(broadcaster) = &mut self.broadcaster {
+                    broadcaster.tick(&actions);
+                }
+                if self.rival.is_none() && self.level.frame().is_multiple_of(HEATMAP_SAVE_INTERVAL)
+                {
+                    self.save_heatmap();
+                }
+                if self.settings.show_hitboxes && self.level.frame().is_multiple_of(60) {
+                    self.snapshot_cost = self.measure_snapshot_cost();
+                    self.memory = Some(self.level.memory_usage());
+                }
+            }
+        }
+    }
+
+    /// 全員の操作を受け取ってステージを1フレーム進め、起きた出来事を読み上げて振動でも知らせる
+    fn step(&mut self, actions: &[Actions; MAX_PLAYERS]) {
+        self.simulate(actions);
+        self.announce_events();
+        self.record_spells();
+        // 観戦中に震えても、何が起きたのか分からないので震わせない
+        if self.spectator.is_none() {
+            haptics::play(&self.level, &self.settings);
+            if let Some(rival) = &self.rival {
+                haptics::play(rival, &self.settings);
+            }
+        }
+    }
+
+    /// 全員の操作を受け取ってステージを1フレーム進める
+    fn simulate(&mut self, actions: &[Actions; MAX_PLAYERS]) {
+        match &mut self.rival {
+            Some(rival) => {
+                self.level.update(&actions[..1]);
+                rival.update(&actions[1..]);
+
+                // 倒した敵の数に応じて相手に弾を送る
+                let sent = self.level.take_defeated_enemies();
+                let received = rival.take_defeated_enemies();
+                rival.receive_garbage(sent * GARBAGE_PER_ENEMY);
+                self.level.receive_garbage(received * GARBAGE_PER_ENEMY);
+            }
+            None => self.level.update(actions),
+        }
+    }
+
+    /// このフレームでチェックポイントを通ったら、その状態を覚えておく。
+    /// 対戦モードでは2人の状態がそろわないので使わない。
+    fn take_checkpoint(&mut self) {
+        if self.rival.is_none()
+            && self
+                .level
+                .events()
+                .iter()
+                .any(|event| matches!(event, GameEvent::CheckpointReached))
+        {
+            self.checkpoint = Some(self.level.snapshot());
+        }
+    }
+
+    /// 練習モードで1人で遊んでいるなら、スペルが始まるたびにその時の状態を覚えておく
+    fn take_attack_snapshot(&mut self) {
+        if self.settings.practice
+            && self.rival.is_none()
+            && self.netplay.is_none()
+            && self.broadcaster.is_none()
+            && self
+                .level
+                .events()
+                .iter()
+                .any(|event| matches!(event, GameEvent::SpellStarted { .. }))
+        {
+            self.attack = Some(self.level.snapshot());
+        }
+    }
+
+    /// 練習モードで、今のスペルが始まったときの状態に戻してやり直す。`seed`が今と違えば乱数も変える。
+    /// スペルの途中でなければ（道中や、クリアした後なら）戻さずに`false`を返す
+    fn retry_attack(&mut self, seed: u64) -> bool {
+        if !self.settings.practice || self.ending.is_some() {
+            return false;
+        }
+        let Some(attack) = &self.attack else {
+            return false;
+        };
+        self.level.restore(attack);
+        if seed != self.level.seed() {
+            self.level.reseed(seed);
+        }
+        self.overlays.clear();
+        // 戻したことは入力として残らないので、このプレイはもう中断できない
+        self.suspend.stop();
+        let _ = browser::announce("Retry attack");
+        true
+    }
+
+    /// カジュアルモードなら、ゲームオーバーのあと最後のチェックポイントの状態に戻して続ける。
+    /// 戻したら`true`を返す。
+    fn continue_from_checkpoint(&mut self) -> bool {
+        if !self.settings.casual {
+            return false;
+        }
+        let Some(checkpoint) = &self.checkpoint else {
+            return false;
+        };
+        self.level.restore(checkpoint);
+        self.continues += 1;
+        // 戻したことは入力として残らないので、このプレイはもう中断できない
+        self.suspend.stop();
+        let _ = browser::announce("Continuing from checkpoint");
+        true
+    }
+
+    /// 今のプレイを保存して中断し、再開するかを尋ねる
+    fn suspend_run(&mut self) {
+        match self.suspend.save() {
+            Ok(true) => {
+                self.resume = self.suspend.stop();
+                let _ = browser::announce("Run suspended. Enter to resume, R to start a new run");
+            }
+            Ok(false) => {
+                let _ = browser::announce("This run cannot be suspended");
+            }
+            Err(err) => {
+                log!("Could not suspend run: {:#?}", err);
+            }
+        }
+    }
+
+    /// 中断セーブから再開するか、新しく始めるかを選ぶ。どちらを選んでも保存は消す。
+    fn update_resume(&mut self, pressed: &Actions) {
+        // ゲームパッドでは文字入力ができないので、ショットのボタンで決定する
+        let resume = self.input.typed() == Some(TextKey::Enter)
+            || (self.input.device() == Device::Gamepad && pressed.shot);
+        if !resume && !pressed.retry {
+            return;
+        }
+        if !resume {
+            self.ask("Discard the suspended run?", Pending::NewRun);
+            return;
+        }
+        if let Err(err) = self.suspend.discard() {
+            log!("Could not discard suspended run: {:#?}", err);
+        }
+        let Some(replay) = self.resume.take() else {
+            return;
+        };
+        self.resume_run(replay);
+    }
+
+    /// スタッフロールを進める。ショットを押している間は早送りし、ボムで最後まで飛ばす。
+    /// 初めて見るときだけ、飛ばす前に確認する。
+    fn update_ending(&mut self, pressed: &Actions) {
+        let fast = self.input.held().shot;
+        let Some(ending) = &mut self.ending else {
+            return;
+        };
+        if !ending.is_rolling() {
+            return;
+        }
+        if pressed.bomb {
+            if self.profile.has_seen(Scene::Credits) {
+                self.skip_credits();
+            } else {
+                self.ask(
+                    "Skip the credits? You have not seen them yet.",
+                    Pending::SkipCredits,
+                );
+            }
+            return;
+        }
+        if ending.update(fast) {
+            self.mark_seen(Scene::Credits);
+        }
+    }
+
+    fn skip_credits(&mut self) {
+        if let Some(ending) = &mut self.ending {
+            ending.skip();
+        }
+        self.mark_seen(Scene::Credits);
+        let _ = browser::announce("Skipped credits");
+    }
+
+    /// 演出を見たことを記録に残す
+    fn mark_seen(&mut self, scene: Scene) {
+        if self.profile.has_seen(scene) {
+            return;
+        }
+        self.profile.mark_seen(scene);
+        if let Err(err) = self.profile.save(self.pack.as_ref().map(Pack::name)) {
+            log!("Could not save profile: {:#?}", err);
+        }
+    }
+
+    /// `action`の前に確認を出す
+    fn ask(&mut self, message: impl Into<String>, action: Pending) {
+        let confirm = Confirm::new(message, action);
+        let _ = browser::announce(&confirm.describe());
+        self.overlays.push(Overlay::Confirm(confirm));
+    }
+
+    fn update_confirm(&mut self, pressed: &Actions) {
+        let Some(Overlay::Confirm(confirm)) = self.overlays.top_mut() else {
+            return;
+        };
+        match confirm.update(pressed, self.input.typed()) {
+            None if pressed.left || pressed.right => {
+                let _ = browser::announce(&confirm.describe());
+            }
+            None => {}
+            Some(false) => {
+                self.overlays.pop();
+                let _ = browser::announce("Cancelled");
+            }
+            Some(true) => {
+                let action = confirm.action();
+                self.overlays.pop();
+                self.carry_out(action);
+            }
+        }
+    }
+
+    /// 確認が取れた操作を行う
+    fn carry_out(&mut self, action: Pending) {
+        match action {
+            Pending::Restart(seed) => {
+                self.restart(seed);
+                let _ = browser::announce(&format!("Started with seed {}", seed));
+            }
+            Pending::NewRun => {
+                if let Err(err) = self.suspend.discard() {
+                    log!("Could not discard suspended run: {:#?}", err);
+                }
+                self.resume = None;
+                self.restart(fresh_seed());
+                let _ = browser::announce("New run");
+            }
+            Pending::SkipCredits => self.skip_credits(),
+            Pending::Practice(index) => self.practice(index),
+        }
+    }
+
+    /// スペルの記録の`index`番目のスペルを、今のシードで練習し始める
+    fn practice(&mut self, index: usize) {
+        let Some(name) = self.spells.name(index) else {
+            return;
+        };
+        let _ = browser::announce(&format!("Practicing {}", name));
+        self.practice_spell = Some(name.to_string());
+        self.restart(self.level.seed());
+    }
+
+    /// オプション画面で遊ぶ形式を変えたので、閉じたら最初からやり直す必要があるか
+    fn needs_restart(&self) -> bool {
+        self.mode != self.settings.mode
+            || self.modifiers != self.settings.modifiers
+            || self.bomb != self.settings.bomb
+            || self.shot != self.settings.shot
+            || self.tas.is_some() != self.settings.tas
+    }
+
+    /// やり直すと失われる、途中のプレイがあるか
+    fn has_progress(&self) -> bool {
+        self.ending.is_none() && !self.is_match_over() && self.level.frame() > 0
+    }
+
+    /// 中断セーブの入力を最初から流し直して、中断したところまで進める
+    fn resume_run(&mut self, replay: Replay) {
+        self.ending = None;
+        self.tas = None;
+        self.mode = replay.mode();
+        self.modifiers = replay.modifiers();
+        self.bomb = replay.bomb();
+        self.shot = replay.shot();
+        // 設定も合わせておかないと、オプション画面を閉じたときに最初からやり直してしまう
+        self.settings.mode = self.mode;
+        self.settings.modifiers = self.modifiers;
+        self.settings.bomb = self.bomb;
+        self.settings.shot = self.shot;
+        self.settings.tas = false;
+        self.practice_spell = None;
+        (self.level, self.rival) = new_match(
+            replay.seed(),
+            self.mode,
+            self.modifiers,
+            self.bomb,
+            self.shot,
+            self.pack.as_ref(),
+            self.stage.as_ref(),
+        );
+        self.checkpoint = None;
+        self.attack = None;
+        self.continues = 0;
+        for actions in replay.inputs() {
+            self.simulate(actions);
+            self.take_checkpoint();
+            self.take_attack_snapshot();
+        }
+        let _ = browser::announce(&format!(
+            "Resumed run at {} seconds",
+            self.level.frame() / TICK_RATE
+        ));
+        self.suspend.start(replay);
+    }
+
+    /// 中断できるプレイなら、`seed`から始めたものとして入力を記録し始める
+    fn track_run(&self, seed: u64) {
+        if self.netplay.is_some()
+            || self.spectator.is_some()
+            || self.tas.is_some()
+            || self.pack.is_some()
+            || self.practice_spell.is_some()
+        {
+            self.suspend.stop();
+            return;
+        }
+        self.suspend.start(Replay::new(
+            seed,
+            self.mode,
+            self.modifiers,
+            self.bomb,
+            self.shot,
+            Vec::new(),
+        ));
+    }
+
+    /// 画面を見ていなくても分かるように、このフレームで起きた出来事を読み上げる
+    fn announce_events(&self) {
+        let cooperative = self.mode == PlayMode::CoOp;
+        for event in self.level.events() {
+            let message = match *event {
+                GameEvent::EnemyDamaged(_)
+                | GameEvent::EnemyHurt { .. }
+                | GameEvent::EnemyKilled { .. } => continue,
+                GameEvent::PlayerHit { player, .. } if cooperative => {
+                    format!("Player {} hit", player + 1)
+                }
+                GameEvent::PlayerHit { .. } => "Hit".to_string(),
+                GameEvent::ShieldBroken { player } if cooperative => {
+                    format!("Player {} shield broken", player + 1)
+                }
+                GameEvent::ShieldBroken { .. } => "Shield broken".to_string(),
+                GameEvent::BombUsed { player } if cooperative => {
+                    format!("Player {} bomb", player + 1)
+                }
+                GameEvent::BombUsed { .. } => "Bomb".to_string(),
+                GameEvent::ItemCollected { kind, .. } => match kind {
+                    ItemKind::Point => continue,
+                    ItemKind::Bomb => "Bomb item".to_string(),
+                    ItemKind::Life => "Extra life".to_string(),
+                    ItemKind::BombFragment => "Bomb fragment".to_string(),
+                    ItemKind::LifeFragment => "Life fragment".to_string(),
+                    ItemKind::Shield => "Shield".to_string(),
+                },
+                GameEvent::SpellStarted { .. } => continue,
+                GameEvent::SpellCaptured { bonus, .. } => {
+                    format!("Spell captured, bonus {}", bonus)
+                }
+                GameEvent::BossDefeated { .. } => "Boss defeated".to_string(),
+                GameEvent::CheckpointReached if self.settings.casual => "Checkpoint".to_string(),
+                GameEvent::CheckpointReached => continue,
+            };
+            let _ = browser::announce(&message);
+        }
+    }
+
+    /// このフレームで始まったスペルと取得したスペルを記録する。観戦やTAS、チートを使ったプレイでは残さない
+    fn record_spells(&mut self) {
+        if self.spectator.is_some() || self.tas.is_some() || self.level.is_cheated() {
+            return;
+        }
+        let mut changed = false;
+        for event in self.level.events() {
+            match *event {
+                GameEvent::SpellStarted { name } => self.spells.record_seen(name),
+                GameEvent::SpellCaptured { name, .. } => {
+                    self.spells.record_capture(name, self.level.score())
+                }
+                _ => continue,
+            }
+            changed = true;
+        }
+        if changed {
+            if let Err(err) = self.spells.save() {
+                log!("Could not save spell records: {:#?}", err);
+            }
+        }
+    }
+
+    /// TASモードのキーを処理し、このフレームでステージを進めるかを返す
+    fn update_tas(&mut self) -> bool {
+        let Some(tas) = &mut self.tas else {
+            return true;
+        };
+        if let Some(TextKey::Digit(digit)) = self.input.typed() {
+            if digit >= 1 {
+                tas.select(digit as usize - 1);
+            }
+        }
+        match self.input.tool() {
+            Some(ToolKey::Advance) => return true,
+            Some(ToolKey::TogglePause) => tas.toggle_pause(),
+            Some(ToolKey::SaveState) => {
+                tas.save(&self.level, self.rival.as_ref());
+                let _ = browser::announce(&format!("Saved state {}", tas.slot() + 1));
+            }
+            Some(ToolKey::LoadState) => {
+                if tas.load(&mut self.level, self.rival.as_mut()) {
+                    let _ = browser::announce(&format!("Loaded state {}", tas.slot() + 1));
+                }
+                return false;
+            }
+            Some(ToolKey::Export) => {
+                let replay = tas.to_replay();
+                match replay.save(STAGE_ID) {
+                    Ok(()) => {
+                        let _ =
+                            browser::announce(&format!("Saved replay of {} frames", replay.len()));
+                    }
+                    Err(err) => {
+                        log!("Could not save replay: {:#?}", err);
+                    }
+                }
+            }
+            Some(ToolKey::Console)
+            | Some(ToolKey::DumpDraws)
+            | Some(ToolKey::Suspend)
+            | Some(ToolKey::TimingsJson)
+            | Some(ToolKey::TimingsCsv)
+            | Some(ToolKey::Tweaks)
+            | None => {}
+        }
+        !tas.is_paused()
+    }
+
+    /// コンソールの開閉と命令を処理し、コンソールを開いているかを返す
+    fn update_console(&mut self) -> bool {
+        if self.input.tool() == Some(ToolKey::DumpDraws) && Console::is_available() {
+            self.dump_draws.set(true);
+        }
+        if self.input.tool() == Some(ToolKey::Console) && Console::is_available() {
+            self.console = match self.console {
+                Some(_) => None,
+                None => Some(Console::new()),
+            };
+            return true;
+        }
+        let Some(console) = &mut self.console else {
+            return false;
+        };
+        if let Some(command) = console.update(&self.input.held(), self.input.typed()) {
+            // コンソールの命令は入力として残らないので、このプレイはもう中断できない
+            self.level.apply(command);
+            self.suspend.stop();
+        }
+        true
+    }
+
+    /// 調整値の画面の開閉と操作を処理し、画面を開いているかを返す
+    fn update_tweaks(&mut self) -> bool {
+        if self.input.tool() == Some(ToolKey::Tweaks) && Console::is_available() {
+            self.tweak_panel = match self.tweak_panel {
+                Some(_) => None,
+                None => Some(TweakPanel::new(self.tweaks)),
+            };
+            return true;
+        }
+        let Some(panel) = &mut self.tweak_panel else {
+            return false;
+        };
+        if let Some(tweaks) = panel.update(&self.input.just_pressed()) {
+            // コンソールの命令と同じく入力として残らないので、このプレイはもう中断できない
+            self.tweaks = tweaks;
+            self.level.apply(Command::Tweak(tweaks));
+            self.suspend.stop();
+        }
+        true
+    }
+
+    /// 開発用のビルドで、クリックした位置のプレイフィールドでの座標と、一番近い敵か弾を
+    /// ブラウザのコンソールに書き出し、クリップボードにも写す
+    fn pick_coordinate(&self, clicked: Point) {
+        let at = self.viewport().unproject(&clicked);
+        let text = self.level.probe(&at);
+        log!("{}", text);
+        if let Err(err) = browser::copy_to_clipboard(&text) {
+            log!("Could not copy coordinates: {:#?}", err);
+        }
+    }
+
+    /// デバッグ表示中に書き出しのキーが押されたら、描画の時間の分布をファイルとしてダウンロードさせる
+    fn export_timings(&self) {
+        let result = match self.input.tool() {
+            Some(ToolKey::TimingsJson) => browser::download(
+                "frame_timings.json",
+                "application/json",
+                &self.timings.to_json(),
+            ),
+            Some(ToolKey::TimingsCsv) => {
+                browser::download("frame_timings.csv", "text/csv", &self.timings.to_csv())
+            }
+            _ => return,
+        };
+        match result {
+            Ok(()) => {
+                let _ = browser::announce("Exported frame timings");
+            }
+            Err(err) => {
+                log!("Could not export frame timings: {:#?}", err);
+            }
+        }
+    }
+
+    /// スナップショットの保存と復元を何回か繰り返し、1回あたりの時間を測る
+    fn measure_snapshot_cost(&mut self) -> Option<f64> {
+        let start = browser::now().ok()?;
+        for _ in 0..SNAPSHOT_SAMPLES {
+            let snapshot = self.level.snapshot();
+            self.level.restore(&snapshot);
+        }
+        let end = browser::now().ok()?;
+        Some((end - start) / SNAPSHOT_SAMPLES as f64)
+    }
+
+    fn update_watch(&mut self, pressed: &Actions) {
+        let Some(spectator) = &mut self.spectator else {
+            return;
+        };
+        let events = spectator.poll();
+        if spectator.status() == WatchStatus::Ended && pressed.retry {
+            self.spectator = None;
+            self.restart(fresh_seed());
+            let _ = browser::announce("Playing offline");
+            return;
+        }
+
+        for event in events {
+            match event {
+                StreamEvent::Run {
+                    seed,
+                    mode,
+                    modifiers,
+                    bomb,
+                    shot,
+                } => {
+                    self.mode = mode;
+                    self.modifiers = modifiers;
+                    self.bomb = bomb;
+                    self.shot = shot;
+                    (self.level, self.rival) =
+                        new_match(seed, mode, modifiers, bomb, shot, None, self.stage.as_ref());
+                    let _ = browser::announce("New run started");
+                }
+                StreamEvent::Tick(actions) => {
+                    if !self.is_match_over() {
+                        self.step(&actions);
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_netplay(&mut self, local: Actions, pressed: &Actions) {
+        let Some(netplay) = &mut self.netplay else {
+            return;
+        };
+        netplay.poll();
+        // ネット越しのプレイではルール変更やボム、ショットの種類を送り合わないので、いつも標準のルールで遊ぶ
+        if let Some(seed) = netplay.take_start() {
+            (self.level, self.rival) = new_match(
+                seed,
+                PlayMode::CoOp,
+                Modifiers::default(),
+                BombType::default(),
+                ShotType::default(),
+                None,
+                self.stage.as_ref(),
+            );
+            let _ = browser::announce("Partner connected");
+        }
+
+        match netplay.status() {
+            NetStatus::Playing => {
+                let Some(actions) = netplay.tick(local) else {
+                    return;
+                };
+                // やり直しも入力として送り合うので、両方で同じフレームにやり直せる
+                if self.level.is_game_over() {
+                    if let Some(retry) = actions.iter().find(|a| a.retry || a.reseed) {
+                        let seed = if retry.reseed {
+                            Rng::new(self.level.seed()).next_u64()
+                        } else {
+                            self.level.seed()
+                        };
+                        (self.level, self.rival) = new_match(
+                            seed,
+                            PlayMode::CoOp,
+                            Modifiers::default(),
+                            BombType::default(),
+                            ShotType::default(),
+                            None,
+                            self.stage.as_ref(),
+                        );
+                    }
+                } else {
+                    self.level.update(&actions);
+                }
+                netplay.check_sync(&self.level);
+            }
+            NetStatus::Desynced(_) | NetStatus::Disconnected => {
+                if pressed.retry {
+                    self.netplay = None;
+                    self.restart(fresh_seed());
+                    let _ = browser::announce("Playing offline");
+                }
+            }
+            NetStatus::Connecting | NetStatus::WaitingForPeer => {}
+        }
+    }
+
+    /// このフレームを記録用の描画先に描き直し、描画命令をブラウザのコンソールに書き出す
+    fn log_draw_calls(&self, whole_canvas: &Rect) {
+        let recorder = RecordingRenderer::default();
+        self.draw(&recorder, whole_canvas);
+        let calls = recorder.calls();
+        // 別の場所で描くときと同じように、文字列にしてから描き直して中身が変わらないか確かめる
+        let encoded = render_list::encode(&calls);
+        log!(
+            "{} draw calls ({} batched), {} bytes encoded",
+            calls.len(),
+            render_list::batch(calls.clone()).len(),
+            encoded.len()
+        );
+        if let Some(drawn) = render_list::bounds(&calls) {
+            // フレームごとに消すのはこの範囲だけになる
+            let area = drawn
+                .intersection(whole_canvas)
+                .map_or(0.0, |r| r.width * r.height);
+            log!(
+                "Drawn area {:.0}% of the canvas",
+                area / (whole_canvas.width * whole_canvas.height) * 100.0
+            );
+        }
+        match render_list::decode(&encoded) {
+            Ok(decoded) => {
+                let replayed = RecordingRenderer::default();
+                render_list::replay(&decoded, &replayed);
+                log!(
+                    "Render list round trip matches: {}",
+                    replayed.calls() == calls
+                );
+            }
+            Err(err) => {
+                log!("Failed to decode render list: {:#}", err);
+            }
+        }
+        for call in calls {
+            log!("{:?}", call);
+        }
+    }
+
+    fn draw(&self, renderer: &dyn Renderer, whole_canvas: &Rect) {
+        let settings = &self.settings;
+        renderer.set_palette(settings.palette);
+        // スタッフロールはステージの外なので、元の配色に戻す
+        renderer.set_theme(match self.ending {
+            Some(_) => Theme::Plain,
+            None => self.level.theme(),
+        });
+        renderer.set_color(Color::Background);
+        renderer.fill_rect(whole_canvas);
+
+        let (draws_game, overlays) = self.overlays.visible();
+        match () {
+            _ if !draws_game => {}
+            _ if self.title.is_some() => {
+                if let Some(title) = &self.title {
+                    title.draw(renderer, settings, self.input.device(), self.layout);
+                }
+            }
+            _ if self.rival.is_some() => self.draw_versus(renderer),
+            _ if self.ending.is_some() => {
+                if let Some(ending) = &self.ending {
+                    ending.draw(renderer, self.input.device());
+                }
+            }
+            _ => {
+                self.level.draw(
+                    renderer,
+                    settings,
+                    self.input.device(),
+                    self.quality(),
+                    self.layout,
+                );
+                match settings.heatmap {
+                    HeatmapView::Off => {}
+                    HeatmapView::Current => self.level.heatmap().draw(renderer),
+                    HeatmapView::Previous => {
+                        if let Some(heatmap) = &self.previous_heatmap {
+                            heatmap.draw(renderer);
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.resume.is_some() {
+            let device = self.input.device();
+            let hint = format!(
+                "{}: resume  {}: new run",
+                device.glyph(Prompt::Confirm),
+                device.glyph(Prompt::Retry)
+            );
+            draw_banner(renderer, "Suspended run", Some(&hint));
+        }
+        for overlay in overlays {
+            overlay.draw(
+                renderer,
+                settings,
+                &self.history,
+                &self.spells,
+                self.input.device(),
+            );
+        }
+        if let Some(netplay) = &self.netplay {
+            draw_netplay_status(renderer, netplay, self.input.device());
+        }
+        if let Some(spectator) = &self.spectator {
+            draw_watch_status(renderer, spectator, self.input.device());
+        }
+        if let (true, Some(console)) = (draws_game, &self.console) {
+            console.draw(
+                renderer,
+                self.level.is_invincible(),
+                self.level.is_autoplay(),
+            );
+        }
+        if let (true, Some(panel)) = (draws_game, &self.tweak_panel) {
+            panel.draw(renderer);
+        }
+        if let (true, Some(tas)) = (draws_game, &self.tas) {
+            draw_tas_status(renderer, tas);
+        }
+
+        if let (true, Some(cost)) = (settings.show_hitboxes, self.snapshot_cost) {
+            renderer.set_color(Color::Debug);
+            renderer.draw_text(
+                &format!("SNAPSHOT {:.3}ms", cost),
+                &Point { x: 400.0, y: 20.0 },
+            );
+        }
+        // 波が過ぎても数が減らなければ、どこかで片付け忘れている
+        if let (true, Some(memory)) = (settings.show_hitboxes, self.memory) {
+            renderer.set_color(Color::Debug);
+            renderer.draw_text(
+                &format!(
+                    "B{} E{} I{} EV{} {}KB",
+                    memory.bullets,
+                    memory.enemies,
+                    memory.items,
+                    memory.events,
+                    memory.bytes / 1024
+                ),
+                &Point { x: 400.0, y: 40.0 },
+            );
+        }
+
+        if settings.show_hitboxes {
+            renderer.set_color(Color::Debug);
+            renderer.draw_text(
+                &format!("{}ms", self.timings.summary()),
+                &Point { x: 300.0, y: 60.0 },
+            );
+            renderer.draw_text("H: timings JSON  G: CSV", &Point { x: 300.0, y: 80.0 });
+        }
+
+        if self.quality() == Quality::Low {
+            renderer.set_color(Color::Text);
+            renderer.draw_text("LOW FX", &Point { x: 530.0, y: 20.0 });
+        }
+
+        if !settings.game_speed.is_ranked() {
+            renderer.set_color(Color::Text);
+            renderer.draw_text(
+                &format!("SPEED {}%", (settings.game_speed.factor() * 100.0).round()),
+                &Point { x: 460.0, y: 590.0 },
+            );
+        }
+    }
+
+    fn draw_versus(&self, renderer: &dyn Renderer) {
+        let (device, quality) = (self.input.device(), self.quality());
+        self.level
+            .draw(renderer, &self.settings, device, quality, self.layout);
+        if let Some(rival) = &self.rival {
+            rival.draw(renderer, &self.settings, device, quality, self.layout);
+
+            let winner = match (self.level.is_game_over(), rival.is_game_over()) {
+                (true, true) => Some("DRAW"),
+                (false, true) => Some("1P WINS"),
+                (true, false) => Some("2P WINS"),
+                (false, false) => None,
+            };
+            if let Some(winner) = winner {
+                renderer.set_color(Color::Text);
+                renderer.draw_text_centered(winner, &Point { x: 300.0, y: 590.0 });
+            }
+        }
+    }
+
+    /// 縦向きなら、プレイフィールドを画面の幅いっぱいに広げる。
+    /// 対戦モードは2つのプレイフィールドで幅を使い切っているので、広げずに縦の真ん中に置く。
+    fn viewport(&self) -> Viewport {
+        let focus = match self.rival {
+            Some(_) => layout::SCREEN,
+            None => *Playfield::MAIN.rect(),
+        };
+        self.layout.viewport(&focus)
+    }
+
+    /// 設定と処理の重さから決めた、今の描画の品質
+    fn quality(&self) -> Quality {
+        self.frames.quality(self.settings.quality)
+    }
+
+    /// 対戦モードならどちらかが、そうでなければ全員がゲームオーバーになったか
+    fn is_match_over(&self) -> bool {
+        self.level.is_game_over()
+            || self
+                .rival
+                .as_ref()
+                .is_some_and(|rival| rival.is_game_over())
+    }
+
+    /// 最後のステージをクリアしたので、クリアボーナスを与えて結果画面に移る。
+    /// 記録対象の条件で遊んでいれば、クリアしたことを保存する。
+    fn finish_run(&mut self) {
+        let clear_bonus = self.level.award_clear_bonus();
+        let ranked = self.is_ranked();
+        // チェックポイントから続けていなければノーコンティニュー
+        let one_cc = self.continues == 0;
+        // クリアの記録は標準のルールで遊んだときだけ残す
+        let standard = ranked && !self.modifiers.is_custom();
+        let first_one_cc = standard && one_cc && !self.profile.has_one_cc();
+        if standard {
+            self.profile.record_clear(one_cc);
+        }
+        self.record_score();
+        self.save_heatmap();
+        self.ending = Some(Ending::new(
+            self.level.score(),
+            clear_bonus,
+            one_cc,
+            ranked,
+            first_one_cc,
+            &self.profile,
+        ));
+        let _ = browser::announce(&format!("All clear. Score {}", self.level.score()));
+        // 練習やTASでは何度も見ることになるので、スタッフロールは初めから飛ばしておく
+        if self.settings.practice || self.tas.is_some() {
+            if let Some(ending) = &mut self.ending {
+                ending.skip();
+            }
+        }
+    }
+
+    /// 終わったプレイを記録の一覧に加える。最後のフレームまでの入力も一緒に残す。
+    fn log_run(&mut self, actions: [Actions; MAX_PLAYERS]) {
+        // スペルの練習は1回のプレイとしては数えない
+        if self.practice_spell.is_some() {
+            return;
+        }
+        let replay = self.suspend.stop().map(|mut replay| {
+            replay.push(actions);
+            replay
+        });
+        let run = RunRecord::new(
+            self.mode,
+            self.bomb,
+            self.modifiers.is_custom(),
+            self.level.score(),
+            self.level.stage_number(),
+            self.level.progress(),
+            self.level.deaths(),
+        );
+        if let Err(err) = self.history.record(run, replay.as_ref()) {
+            log!("Could not save run history: {:#?}", err);
+        }
+    }
+
+    /// スコアや記録を残してよい条件で遊んでいるか
+    fn is_ranked(&self) -> bool {
+        self.settings.game_speed.is_ranked()
+            && !self.settings.practice
+            && self.practice_spell.is_none()
+            && self.tas.is_none()
+            && !self.level.is_cheated()
+    }
+
+    /// 終わったプレイのスコアを、ルール変更の有無で分けた記録に残す
+    fn record_score(&mut self) {
+        if !self.is_ranked() {
+            return;
+        }
+        let custom = self.modifiers.is_custom();
+        let previous = self.profile.best(custom);
+        if self.profile.record_score(self.level.score(), custom) {
+            let board = if custom {
+                "custom rules"
+            } else {
+                "standard rules"
+            };
+            let _ = browser::announce(&format!("New high score for {}. Enter your name", board));
+            self.overlays.push(Overlay::NameEntry(NameEntry::new(
+                self.level.score(),
+                custom,
+                previous,
+            )));
+        }
+        if let Err(err) = self.profile.save(self.pack.as_ref().map(Pack::name)) {
+            log!("Could not save profile: {:#?}", err);
+        }
+    }
+
+    /// 名前入力を進め、終えたら最高スコアの記録に名前を付けて保存する
+    fn update_name_entry(&mut self, pressed: &Actions) {
+        let Some(Overlay::NameEntry(entry)) = self.overlays.top_mut() else {
+            return;
+        };
+        match entry.update(pressed, &self.input.held(), self.input.typed()) {
+            Some(EntryEvent::Changed) => {
+                let _ = browser::announce(&entry.describe());
+            }
+            Some(EntryEvent::Done(name)) => {
+                self.profile.set_best_name(entry.is_custom(), &name);
+                self.overlays.pop();
+                if let Err(err) = self.profile.save(self.pack.as_ref().map(Pack::name)) {
+                    log!("Could not save profile: {:#?}", err);
+                }
+                let _ = browser::announce(&format!("Saved high score as {}", name));
+            }
+            None => {}
+        }
+    }
+
+    /// 今の設定の形式で、`seed`から最初からやり直す
+    fn restart(&mut self, seed: u64) {
+        self.save_heatmap();
+        self.ending = None;
+        self.overlays.clear();
+        self.checkpoint = None;
+        self.attack = None;
+        self.continues = 0;
+        self.mode = self.settings.mode;
+        self.modifiers = self.settings.modifiers;
+        self.bomb = self.settings.bomb;
+        self.shot = self.settings.shot;
+        let (level, rival) = new_match(
+            seed,
+            self.mode,
+            self.modifiers,
+            self.bomb,
+            self.shot,
+            self.pack.as_ref(),
+            self.stage.as_ref(),
+        );
+        (self.level, self.rival) = match &self.practice_spell {
+            Some(spell) => (
+                level.with_spell_practice(spell),
+                rival.map(|rival| rival.with_spell_practice(spell)),
+            ),
+            None => (level, rival),
+        };
+        if self.tweaks != Tweaks::default() {
+            self.level.apply(Command::Tweak(self.tweaks));
+        }
+        self.tas = self
+            .settings
+            .tas
+            .then(|| Tas::new(seed, self.mode, self.modifiers, self.bomb, self.shot));
+        if let Some(broadcaster) = &mut self.broadcaster {
+            broadcaster.start_run(seed, self.mode, self.modifiers, self.bomb, self.shot);
+        }
+        self.track_run(seed);
+    }
+
+    fn save_heatmap(&self) {
+        // ヒートマップは元のステージのものなので、パックの道中では残さない
+        if self.pack.is_some() {
+            return;
         }
+        if let Err(err) = self.level.heatmap().save(STAGE_ID) {
+            log!("Could not save heatmap: {:#?}", err);
+        }
+    }
+}
+
+/// ゲームの上に重ねる画面
+enum Overlay {
+    Options(OptionsMenu), // ポーズ画面を兼ねる
+    NameEntry(NameEntry),
+    Confirm(Confirm<Pending>),
+    History(HistoryView),  // オプション画面から開く、これまでのプレイの一覧
+    Spells(SpellBookView), // オプション画面から開く、出会ったスペルの一覧
+}
+
+impl Layer for Overlay {
+    // オプション画面と記録の一覧はプレイフィールド全体を使うので、下のゲームは描かない
+    fn draws_below(&self) -> bool {
+        !matches!(
+            self,
+            Overlay::Options(_) | Overlay::History(_) | Overlay::Spells(_)
+        )
+    }
+}
+
+impl Overlay {
+    fn draw(
+        &self,
+        renderer: &dyn Renderer,
+        settings: &Settings,
+        history: &History,
+        spells: &SpellBook,
+        device: Device,
+    ) {
+        match self {
+            Overlay::Options(menu) => {
+                renderer.set_color(Color::Border);
+                renderer.draw_rect(Playfield::MAIN.rect());
+                menu.draw(renderer, settings, device);
+            }
+            Overlay::History(view) => {
+                renderer.set_color(Color::Border);
+                renderer.draw_rect(Playfield::MAIN.rect());
+                view.draw(renderer, history, device);
+            }
+            Overlay::Spells(view) => {
+                renderer.set_color(Color::Border);
+                renderer.draw_rect(Playfield::MAIN.rect());
+                view.draw(renderer, spells, device);
+            }
+            Overlay::NameEntry(entry) => entry.draw(renderer, device),
+            Overlay::Confirm(confirm) => {
+                draw_banner(renderer, confirm.message(), Some(&confirm.hint(device)));
+            }
+        }
+    }
+}
+
+/// 確認してから行う操作
+#[derive(Clone, Copy)]
+enum Pending {
+    Restart(u64),    // オプション画面から、このシードで最初からやり直す
+    NewRun,          // 中断セーブを捨てて新しく始める
+    SkipCredits,     // 初めて見るスタッフロールを飛ばす
+    Practice(usize), // スペルの記録の、この番号のスペルを練習する
+}
+
+/// URLに`?pack=<コンテンツパックのURL>`があれば読み込む
+async fn load_pack() -> Result<Option<Pack>> {
+    let Some(url) = browser::query_param("pack")? else {
+        return Ok(None);
+    };
+    let text = browser::fetch_text(&url).await?;
+    Pack::parse(&text).map(Some)
+}
+
+/// 組み込みのステージの道中を、ステージのデータファイルから読み込む
+async fn load_stage() -> Result<Pack> {
+    let text = browser::fetch_text(STAGE_DATA_URL).await?;
+    Pack::parse(&text)
+}
+
+/// URLに`?netplay=<WebSocketのURL>&slot=<1か2>`があればネット越しの協力プレイを始める
+fn connect_netplay() -> Result<Option<Netplay>> {
+    let Some(url) = browser::query_param("netplay")? else {
+        return Ok(None);
+    };
+    let slot = match browser::query_param("slot")?.as_deref() {
+        Some("2") => 1,
+        _ => 0,
+    };
+    Ok(Some(Netplay::connect(&url, slot, fresh_seed())?))
+}
+
+/// URLに`?broadcast=<WebSocketのURL>`があればプレイを配信し、`?watch=<WebSocketのURL>`があれば観戦する
+fn connect_stream() -> Result<(Option<Broadcaster>, Option<Spectator>)> {
+    if let Some(url) = browser::query_param("watch")? {
+        return Ok((None, Some(Spectator::connect(&url)?)));
+    }
+    match browser::query_param("broadcast")? {
+        Some(url) => Ok((Some(Broadcaster::connect(&url)?), None)),
+        None => Ok((None, None)),
+    }
+}
+
+/// TASモードの状態（選んでいる枠は括弧付き、空の枠は`-`）と操作方法
+fn draw_tas_status(renderer: &dyn Renderer, tas: &Tas) {
+    let slots = (0..SLOTS)
+        .map(|slot| {
+            let label = if tas.is_filled(slot) {
+                (slot + 1).to_string()
+            } else {
+                "-".to_string()
+            };
+            if slot == tas.slot() {
+                format!("[{}]", label)
+            } else {
+                label
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let state = if tas.is_paused() { "PAUSED" } else { "PLAY" };
+    renderer.set_color(Color::Text);
+    renderer.draw_text(
+        &format!("TAS {} F{}  {}", state, tas.frame(), slots),
+        &Point { x: 10.0, y: 590.0 },
+    );
+    if tas.is_paused() {
+        renderer.draw_text(
+            "F: step P: play 1-4: slot O: save L: load X: export",
+            &Point { x: 60.0, y: 560.0 },
+        );
+    }
+}
+
+/// `device`の操作で、オフラインで遊び始める案内
+fn offline_hint(device: Device) -> String {
+    format!("{}: play offline", device.glyph(Prompt::Retry))
+}
+
+fn draw_watch_status(renderer: &dyn Renderer, spectator: &Spectator, device: Device) {
+    match spectator.status() {
+        WatchStatus::Watching => {
+            renderer.set_color(Color::Text);
+            renderer.draw_text("WATCHING", &Point { x: 10.0, y: 590.0 });
+        }
+        WatchStatus::Connecting => draw_banner(renderer, "Connecting...", None),
+        WatchStatus::Buffering => draw_banner(renderer, "Buffering...", None),
+        WatchStatus::Ended => {
+            draw_banner(renderer, "Stream ended", Some(&offline_hint(device)));
+        }
+    }
+}
+
+fn draw_netplay_status(renderer: &dyn Renderer, netplay: &Netplay, device: Device) {
+    let message = match netplay.status() {
+        NetStatus::Playing => {
+            renderer.set_color(Color::Text);
+            renderer.draw_text(
+                &format!("NET {}P", netplay.slot() + 1),
+                &Point { x: 10.0, y: 590.0 },
+            );
+            return;
+        }
+        NetStatus::Connecting => "Connecting...".to_string(),
+        NetStatus::WaitingForPeer => "Waiting for partner...".to_string(),
+        NetStatus::Desynced(frame) => format!("Desync detected at frame {}", frame),
+        NetStatus::Disconnected => "Disconnected".to_string(),
+    };
+
+    let hint = matches!(
+        netplay.status(),
+        NetStatus::Desynced(_) | NetStatus::Disconnected
+    )
+    .then(|| offline_hint(device));
+    draw_banner(renderer, &message, hint.as_deref());
+}
+
+/// プレイフィールドの中央に帯を出してメッセージを表示する
+fn draw_banner(renderer: &dyn Renderer, message: &str, hint: Option<&str>) {
+    let field = Playfield::MAIN;
+    renderer.set_color(Color::Background);
+    renderer.fill_rect(&Rect {
+        x: field.left() + 10.0,
+        y: field.center_y() - 45.0,
+        width: field.rect().width - 20.0,
+        height: 90.0,
+    });
+    renderer.set_color(Color::Text);
+    renderer.draw_text_centered(
+        message,
+        &Point {
+            x: field.center_x(),
+            y: field.center_y() - 5.0,
+        },
+    );
+    if let Some(hint) = hint {
+        renderer.draw_text_centered(
+            hint,
+            &Point {
+                x: field.center_x(),
+                y: field.center_y() + 25.0,
+            },
+        );
+    }
+}
+
+/// 新しいプレイのためのシードを時刻から作る
+fn fresh_seed() -> u64 {
+    browser::now()
+        .map(|now| (now * 1000.0) as u64)
+        .unwrap_or_default()
+}
+
+/// 形式に合わせてステージを作る。対戦モードでは2P側も同じシードとルール、ボムとショットの種類で作る。
+/// 道中は`pack`があればそれに、なければ`stage`（ステージのデータファイル）があればそれに差し替える
+fn new_match(
+    seed: u64,
+    mode: PlayMode,
+    modifiers: Modifiers,
+    bomb: BombType,
+    shot: ShotType,
+    pack: Option<&Pack>,
+    stage: Option<&Pack>,
+) -> (Level, Option<Level>) {
+    let (level, rival) = match mode {
+        PlayMode::Single => (
+            Level::new(seed, &[0], Playfield::MAIN, modifiers, bomb, shot),
+            None,
+        ),
+        PlayMode::CoOp => (
+            Level::new(seed, &[0, 1], Playfield::MAIN, modifiers, bomb, shot),
+            None,
+        ),
+        PlayMode::Versus => (
+            Level::new(seed, &[0], Playfield::VERSUS[0], modifiers, bomb, shot),
+            Some(Level::new(
+                seed,
+                &[1],
+                Playfield::VERSUS[1],
+                modifiers,
+                bomb,
+                shot,
+            )),
+        ),
+    };
+    match (pack, stage) {
+        (Some(pack), _) => (
+            level.with_pack(pack),
+            rival.map(|rival| rival.with_pack(pack)),
+        ),
+        (None, Some(stage)) => (
+            level.with_stage(stage),
+            rival.map(|rival| rival.with_stage(stage)),
+        ),
+        (None, None) => (level, rival),
     }
 }
@@ -0,0 +1,76 @@
+use std::f32::consts::PI;
+
+use crate::{
+    engine::Renderer,
+    math::{Point, Vector},
+    palette::Color,
+    playfield::Playfield,
+};
+
+const DASH_LENGTH: f32 = 12.0; // 破線の1つの線の長さ
+const DASH_GAP: f32 = 8.0; // 破線の線と線の間
+const ARC_RADIUS: f32 = 60.0; // 弾の壁を示す弧の、撃つ位置からの距離
+const ARC_SPACING: f32 = 40.0; // 弧を重ねるときの間隔
+const ARC_COUNT: u32 = 3;
+const MAX_ALPHA: f32 = 0.6;
+
+/// ボスの攻撃が来る前に、弾の通り道を破線で示す予告
+#[derive(Clone, Copy)]
+pub enum Guide {
+    // `origin`から`deg`の向きにプレイフィールドの端まで伸びる線
+    Ray {
+        origin: Point,
+        deg: f32,
+    },
+    // `center`の周りに`from_deg`から`to_deg`まで広がる弾の壁
+    Arc {
+        center: Point,
+        from_deg: f32,
+        to_deg: f32,
+    },
+}
+
+impl Guide {
+    /// `progress`は攻撃までの近さ（0で出始め、1で攻撃の直前）。近づくほど濃くなる。
+    pub fn draw(&self, renderer: &dyn Renderer, playfield: &Playfield, progress: f32) {
+        renderer.set_color(Color::Telegraph);
+        renderer.set_alpha(MAX_ALPHA * progress.clamp(0.0, 1.0));
+        match *self {
+            Guide::Ray { origin, deg } => {
+                let dash = Vector::from_deg_and_mag(deg, DASH_LENGTH);
+                let step = Vector::from_deg_and_mag(deg, DASH_LENGTH + DASH_GAP);
+                let mut start = origin;
+                while playfield.contains(&start) {
+                    let mut end = start;
+                    end += dash;
+                    renderer.draw_line(&start, &playfield.clamp(end));
+                    start += step;
+                }
+            }
+            Guide::Arc {
+                center,
+                from_deg,
+                to_deg,
+            } => {
+                // 弧は短い線をつなげて描く。外側の弧ほど1つの線が見込む角度を小さくする。
+                for ring in 0..ARC_COUNT {
+                    let radius = ARC_RADIUS + ARC_SPACING * ring as f32;
+                    let dash_deg = DASH_LENGTH / radius * 180.0 / PI;
+                    let step_deg = (DASH_LENGTH + DASH_GAP) / radius * 180.0 / PI;
+                    let mut deg = from_deg;
+                    while deg < to_deg {
+                        let mut start = center;
+                        start += Vector::from_deg_and_mag(deg, radius);
+                        let mut end = center;
+                        end += Vector::from_deg_and_mag((deg + dash_deg).min(to_deg), radius);
+                        if playfield.contains(&start) && playfield.contains(&end) {
+                            renderer.draw_line(&start, &end);
+                        }
+                        deg += step_deg;
+                    }
+                }
+            }
+        }
+        renderer.set_alpha(1.0);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{
+    browser, effects::SAFE_SHAKE_FACTOR, event::GameEvent, level::Level, settings::Settings,
+};
+
+/// 振動1回分。`player`はゲームパッドの番号で、`None`ならつながっているものすべてを震わせる
+struct Rumble {
+    player: Option<usize>,
+    intensity: f32, // 0から1
+    duration_ms: u32,
+}
+
+/// 出来事ごとの振動。強さと長さは、同じ出来事で起こす画面揺れの大きさ（10ピクセルを1とする）と長さに合わせる。
+/// ボムは画面を揺らさないので、被弾より少し弱くする。
+fn rumble(level: &Level, event: &GameEvent) -> Option<Rumble> {
+    match *event {
+        GameEvent::PlayerHit { player, .. } => Some(Rumble {
+            player: level.player_number(player),
+            intensity: 0.8,
+            duration_ms: 330,
+        }),
+        GameEvent::ShieldBroken { player } => Some(Rumble {
+            player: level.player_number(player),
+            intensity: 0.3,
+            duration_ms: 130,
+        }),
+        GameEvent::BombUsed { player } => Some(Rumble {
+            player: level.player_number(player),
+            intensity: 0.5,
+            duration_ms: 250,
+        }),
+        GameEvent::BossDefeated { .. } => Some(Rumble {
+            player: None,
+            intensity: 1.0,
+            duration_ms: 1500,
+        }),
+        _ => None,
+    }
+}
+
+/// このフレームで`level`に起きた出来事に合わせて、ゲームパッドと携帯端末を震わせる。
+/// 画面揺れを弱める設定では、振動も同じ割合で弱める。
+pub fn play(level: &Level, settings: &Settings) {
+    if settings.no_vibration {
+        return;
+    }
+    for rumble in level
+        .events()
+        .iter()
+        .filter_map(|event| rumble(level, event))
+    {
+        let intensity = if settings.reduce_flashing {
+            rumble.intensity * SAFE_SHAKE_FACTOR
+        } else {
+            rumble.intensity
+        };
+        // 震わせられない環境は珍しくないので、失敗しても知らせない
+        let _ = browser::rumble_gamepads(rumble.player, intensity.into(), rumble.duration_ms);
+        // 携帯端末の振動は強さを選べないので、弱いときは短くする
+        let _ = browser::vibrate((rumble.duration_ms as f32 * intensity) as u32);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::{
+    component::{Collider, Components, Health, Kinematics, Renderable, Shape, Transform},
+    constants::per_tick,
+    engine::Renderer,
+    item::ItemKind,
+    math::{Point, Rect, Vector},
+    palette::Color,
+    playfield::{Playfield, Walls},
+    timeline::Timed,
+};
+
+const ASTEROID_RADIUS: f32 = 24.0;
+const CRATE_HALF_SIZE: f32 = 18.0;
+const HAZARD_SPEED: f32 = per_tick(90.0); // 下へ流れてくる速さ
+const ASTEROID_DROPS: [ItemKind; 3] = [ItemKind::Point, ItemKind::Point, ItemKind::Point];
+const CRATE_DROPS: [ItemKind; 2] = [ItemKind::BombFragment, ItemKind::Point];
+
+/// 敵ではない、ステージに置かれた障害物の種類
+#[derive(Clone, Copy)]
+pub enum HazardKind {
+    Asteroid, // 丸い岩。硬いが得点アイテムを多く落とす
+    Crate,    // 箱。すぐ壊れてボムのかけらを落とす
+}
+
+impl HazardKind {
+    fn shape(self) -> Shape {
+        match self {
+            HazardKind::Asteroid => Shape::Circle(Collider {
+                radius: ASTEROID_RADIUS,
+            }),
+            HazardKind::Crate => Shape::Box {
+                half_width: CRATE_HALF_SIZE,
+                half_height: CRATE_HALF_SIZE,
+            },
+        }
+    }
+
+    /// 壊すのに必要なボムの数
+    fn health(self) -> u32 {
+        match self {
+            HazardKind::Asteroid => 2,
+            HazardKind::Crate => 1,
+        }
+    }
+
+    /// 壊したときに落とすアイテム
+    pub fn drops(self) -> &'static [ItemKind] {
+        match self {
+            HazardKind::Asteroid => &ASTEROID_DROPS,
+            HazardKind::Crate => &CRATE_DROPS,
+        }
+    }
+}
+
+/// 上から流れてくる障害物。触れると被弾し、ボムで壊すとアイテムを落とす。
+#[derive(Clone)]
+pub struct Hazard {
+    kind: HazardKind,
+    transform: Transform,
+    kinematics: Kinematics,
+    shape: Shape,
+    health: Health,
+    renderable: Renderable,
+}
+
+impl Hazard {
+    pub fn new(kind: HazardKind, pos: Point) -> Self {
+        Self {
+            kind,
+            transform: Transform { pos },
+            kinematics: Kinematics::new(Vector::new(0.0, HAZARD_SPEED)),
+            shape: kind.shape(),
+            health: Health {
+                remaining: kind.health(),
+            },
+            renderable: Renderable {
+                color: Color::Hazard,
+                shape: kind.shape(),
+            },
+        }
+    }
+
+    pub fn kind(&self) -> HazardKind {
+        self.kind
+    }
+
+    pub fn pos(&self) -> Point {
+        self.transform.pos
+    }
+
+    /// プレイフィールドの下へ抜けきっていなければ`true`
+    pub fn is_on_field(&self, playfield: &Playfield) -> bool {
+        self.transform.pos.y < playfield.bottom() + ASTEROID_RADIUS.max(CRATE_HALF_SIZE)
+    }
+
+    /// `frames`フレーム後、まっすぐ進み続けたとしたら、`point`を中心とする半径`radius`の円に触れるか
+    pub fn reaches(&self, point: &Point, radius: f32, frames: f32) -> bool {
+        let mut ahead = self.transform.pos;
+        ahead += self.kinematics.vel * frames;
+        self.shape.overlaps(&ahead, point, radius)
+    }
+}
+
+impl Components for Hazard {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    fn kinematics(&self) -> Option<&Kinematics> {
+        Some(&self.kinematics)
+    }
+
+    fn hitbox(&self) -> Shape {
+        self.shape
+    }
+
+    fn health_mut(&mut self) -> Option<&mut Health> {
+        Some(&mut self.health)
+    }
+
+    fn renderable(&self) -> &Renderable {
+        &self.renderable
+    }
+
+    fn decorate(&self, renderer: &dyn Renderer) {
+        // 箱には対角線を、岩には残りの硬さの分だけ内側の輪を描いて見分けやすくする
+        match self.kind {
+            HazardKind::Crate => {
+                let pos = self.transform.pos;
+                let s = CRATE_HALF_SIZE;
+                renderer.draw_line(
+                    &Point {
+                        x: pos.x - s,
+                        y: pos.y - s,
+                    },
+                    &Point {
+                        x: pos.x + s,
+                        y: pos.y + s,
+                    },
+                );
+            }
+            HazardKind::Asteroid => {
+                for i in 1..self.health.remaining {
+                    renderer.draw_circle(
+                        &self.transform.pos,
+                        ASTEROID_RADIUS * (1.0 - 0.3 * i as f32),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// `at`フレーム目に、プレイフィールドの横`x`（0が左端、1が右端）の上から障害物を出す予定
+#[derive(Clone, Copy)]
+pub struct HazardSpawn {
+    pub at: u32,
+    pub kind: HazardKind,
+    pub x: f32,
+}
+
+impl Timed for HazardSpawn {
+    fn at(&self) -> u32 {
+        self.at
+    }
+}
+
+impl HazardSpawn {
+    pub fn spawn(&self, playfield: &Playfield) -> Hazard {
+        let rect = playfield.rect();
+        Hazard::new(
+            self.kind,
+            Point {
+                x: rect.x + rect.width * self.x,
+                y: playfield.top() - CRATE_HALF_SIZE,
+            },
+        )
+    }
+}
+
+/// `at`フレーム目から`frames`フレームかけて、左右の壁をプレイフィールドの幅に対する割合`left`と`right`の厚さまで動かす予定。
+/// 0まで戻せば壁は引っ込む。
+#[derive(Clone, Copy)]
+pub struct WallShift {
+    pub at: u32,
+    pub left: f32,
+    pub right: f32,
+    pub frames: u32,
+}
+
+impl Timed for WallShift {
+    fn at(&self) -> u32 {
+        self.at
+    }
+}
+
+/// 左右から迫り出してプレイフィールドを狭める壁。今の厚さから目標の厚さへ、一定の速さで動かす。
+/// プレイヤーは壁を越えられず、弾は壁に入ると消えるか跳ね返る。
+#[derive(Clone, Default)]
+pub struct SlidingWalls {
+    from: Walls,
+    to: Walls,
+    elapsed: u32, // 今の動きを始めてからのフレーム数
+    frames: u32,  // 今の動きにかけるフレーム数
+}
+
+impl SlidingWalls {
+    /// `shift`に従って動き始める。`width`はプレイフィールドの幅
+    pub fn shift(&mut self, shift: &WallShift, width: f32) {
+        self.from = self.current();
+        self.to = Walls {
+            left: width * shift.left,
+            right: width * shift.right,
+        };
+        self.elapsed = 0;
+        self.frames = shift.frames.max(1);
+    }
+
+    pub fn update(&mut self) {
+        self.elapsed = (self.elapsed + 1).min(self.frames);
+    }
+
+    /// 今の壁の厚さ
+    pub fn current(&self) -> Walls {
+        if self.elapsed >= self.frames {
+            return self.to;
+        }
+        let t = self.elapsed as f32 / self.frames as f32;
+        Walls {
+            left: self.from.left + (self.to.left - self.from.left) * t,
+            right: self.from.right + (self.to.right - self.from.right) * t,
+        }
+    }
+}
+
+/// `playfield`の壁を塗りつぶして描く
+pub fn draw_walls(renderer: &dyn Renderer, playfield: &Playfield) {
+    let rect = playfield.rect();
+    let walls = playfield.walls();
+    renderer.set_color(Color::Hazard);
+    if walls.left > 0.0 {
+        renderer.fill_rect(&Rect {
+            x: rect.x,
+            y: rect.y,
+            width: walls.left,
+            height: rect.height,
+        });
+    }
+    if walls.right > 0.0 {
+        renderer.fill_rect(&Rect {
+            x: playfield.open_right(),
+            y: rect.y,
+            width: walls.right,
+            height: rect.height,
+        });
+    }
+}
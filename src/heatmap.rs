@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    browser,
+    engine::Renderer,
+    math::{Point, Rect},
+    palette::Color,
+};
+
+// プレイフィールドを`CELL_SIZE`四方のマスに区切って記録する
+const FIELD_X: f32 = 50.0;
+const FIELD_Y: f32 = 30.0;
+const CELL_SIZE: f32 = 20.0;
+const COLUMNS: usize = 25; // 500 / 20
+const ROWS: usize = 27; // 540 / 20
+const MAX_ALPHA: f32 = 0.6;
+
+/// プレイヤーがどこにいたか、どこで被弾したかの記録
+#[derive(Clone)]
+pub struct Heatmap {
+    visits: Vec<u32>, // マスごとの滞在フレーム数
+    deaths: Vec<u32>, // マスごとの被弾回数
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Self {
+            visits: vec![0; COLUMNS * ROWS],
+            deaths: vec![0; COLUMNS * ROWS],
+        }
+    }
+
+    fn cell_index(point: &Point) -> usize {
+        let column = ((point.x - FIELD_X) / CELL_SIZE).clamp(0.0, (COLUMNS - 1) as f32) as usize;
+        let row = ((point.y - FIELD_Y) / CELL_SIZE).clamp(0.0, (ROWS - 1) as f32) as usize;
+        row * COLUMNS + column
+    }
+
+    fn cell_rect(index: usize) -> Rect {
+        Rect {
+            x: FIELD_X + (index % COLUMNS) as f32 * CELL_SIZE,
+            y: FIELD_Y + (index / COLUMNS) as f32 * CELL_SIZE,
+            width: CELL_SIZE,
+            height: CELL_SIZE,
+        }
+    }
+
+    pub fn record(&mut self, point: &Point) {
+        let index = Self::cell_index(point);
+        self.visits[index] = self.visits[index].saturating_add(1);
+    }
+
+    pub fn record_death(&mut self, point: &Point) {
+        let index = Self::cell_index(point);
+        self.deaths[index] = self.deaths[index].saturating_add(1);
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer) {
+        let max = self.visits.iter().copied().max().unwrap_or(0);
+        if max > 0 {
+            renderer.set_color(Color::Heat);
+            for (index, &visits) in self.visits.iter().enumerate() {
+                if visits == 0 {
+                    continue;
+                }
+                // 滞在時間の短いマスも見えるように平方根で圧縮する
+                renderer.set_alpha(MAX_ALPHA * (visits as f32 / max as f32).sqrt());
+                renderer.fill_rect(&Self::cell_rect(index));
+            }
+            renderer.set_alpha(1.0);
+        }
+
+        renderer.set_color(Color::Death);
+        for (index, &deaths) in self.deaths.iter().enumerate() {
+            if deaths == 0 {
+                continue;
+            }
+            let rect = Self::cell_rect(index);
+            let center = Point {
+                x: rect.x + CELL_SIZE / 2.0,
+                y: rect.y + CELL_SIZE / 2.0,
+            };
+            renderer.draw_circle(&center, 4.0 + 2.0 * deaths.min(4) as f32);
+        }
+    }
+
+    /// `visits`と`deaths`をそれぞれカンマ区切りにし、`;`でつないだ文字列
+    pub fn serialize(&self) -> String {
+        let join = |cells: &[u32]| {
+            cells
+                .iter()
+                .map(|count| count.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        format!("{};{}", join(&self.visits), join(&self.deaths))
+    }
+
+    pub fn deserialize(text: &str) -> Result<Self> {
+        let parse = |cells: &str| -> Result<Vec<u32>> {
+            let cells = cells
+                .split(',')
+                .map(|count| count.parse::<u32>())
+                .collect::<Result<Vec<_>, _>>()?;
+            if cells.len() != COLUMNS * ROWS {
+                return Err(anyhow!("Heatmap has {} cells", cells.len()));
+            }
+            Ok(cells)
+        };
+        let (visits, deaths) = text
+            .split_once(';')
+            .ok_or_else(|| anyhow!("Malformed heatmap"))?;
+        Ok(Self {
+            visits: parse(visits)?,
+            deaths: parse(deaths)?,
+        })
+    }
+
+    fn storage_key(stage: &str) -> String {
+        format!("heatmap.{}", stage)
+    }
+
+    /// 前回までに保存されたステージのヒートマップを読み込む
+    pub fn load(stage: &str) -> Result<Option<Self>> {
+        browser::storage_get(&Self::storage_key(stage))?
+            .map(|text| Self::deserialize(&text))
+            .transpose()
+    }
+
+    pub fn save(&self, stage: &str) -> Result<()> {
+        browser::storage_set(&Self::storage_key(stage), &self.serialize())
+    }
+}
@@ -0,0 +1,304 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    browser,
+    engine::Renderer,
+    input::{Actions, Device, Prompt},
+    level::Progress,
+    math::Point,
+    menu::ListMenu,
+    palette::Color,
+    replay::Replay,
+    settings::{BombType, PlayMode},
+    version,
+};
+
+const STORAGE_KEY: &str = "history";
+// 保存形式の版
+//   0: 版の印がない
+//   1: 先頭に版の印を付けた。中身は版0と同じ
+const FORMAT_VERSION: u32 = 1;
+const MAX_RUNS: usize = 50; // 残しておくプレイの数
+const MAX_REPLAYS: usize = 5; // 入力まで残しておく、直近のプレイの数
+const VISIBLE_ROWS: usize = 14; // 一覧に一度に出す行の数
+const ROW_HEIGHT: f32 = 24.0;
+const MS_PER_DAY: f64 = 86_400_000.0;
+
+/// 終わったプレイ1回分の記録
+#[derive(Clone)]
+pub struct RunRecord {
+    id: u32,        // 記録した順の通し番号
+    time: f64,      // 終わった日時（1970年1月1日からのミリ秒）
+    mode: PlayMode, // 遊んだ人数と形式
+    bomb: BombType, // 選んでいた自機のボム
+    custom: bool,   // ルール変更ありで遊んだか
+    score: u64,     // 最終スコア
+    stage: u32,     // どのステージで終わったか
+    reached: Progress,
+    deaths: u32,
+    replay: bool, // 入力を残しているか（`replay_key`で読み出せる）
+}
+
+impl RunRecord {
+    pub fn new(
+        mode: PlayMode,
+        bomb: BombType,
+        custom: bool,
+        score: u64,
+        stage: u32,
+        reached: Progress,
+        deaths: u32,
+    ) -> Self {
+        Self {
+            id: 0,
+            time: 0.0,
+            mode,
+            bomb,
+            custom,
+            score,
+            stage,
+            reached,
+            deaths,
+            replay: false,
+        }
+    }
+
+    /// このプレイの入力を残しておく場所
+    fn replay_key(&self) -> String {
+        format!("replay.run{}", self.id)
+    }
+
+    /// 一覧の1行。日付、スコア、どこまで進んだか
+    fn summary(&self) -> String {
+        format!(
+            "{}  {:>9}  {}-{}",
+            format_date(self.time),
+            self.score,
+            self.stage,
+            self.reached.name()
+        )
+    }
+
+    /// 選んでいるプレイの詳しい内容。遊んだ条件と、結果の2行
+    fn details(&self) -> [String; 2] {
+        let custom = if self.custom { " (custom)" } else { "" };
+        let replay = if self.replay {
+            format!("Replay {}", self.replay_key())
+        } else {
+            "No replay".to_string()
+        };
+        [
+            format!(
+                "Players {}  Bomb {}{}",
+                self.mode.name(),
+                self.bomb.name(),
+                custom
+            ),
+            format!("Deaths {}  {}", self.deaths, replay),
+        ]
+    }
+
+    /// `通し番号 日時 形式の番号 ボムの番号 ルール変更(0か1) スコア ステージ 進み具合の番号 被弾数 入力を残したか(0か1)`
+    fn serialize(&self) -> String {
+        let index = |found: Option<usize>| found.unwrap_or(0);
+        format!(
+            "{} {} {} {} {} {} {} {} {} {}",
+            self.id,
+            self.time,
+            index(PlayMode::ALL.iter().position(|mode| *mode == self.mode)),
+            index(BombType::ALL.iter().position(|bomb| *bomb == self.bomb)),
+            self.custom as u8,
+            self.score,
+            self.stage,
+            index(Progress::ALL.iter().position(|p| *p == self.reached)),
+            self.deaths,
+            self.replay as u8
+        )
+    }
+
+    fn deserialize(line: &str) -> Result<Self> {
+        let mut fields = line.split(' ');
+        let mut next = || {
+            fields
+                .next()
+                .ok_or_else(|| anyhow!("Malformed run: {}", line))
+        };
+        let id = next()?.parse()?;
+        let time = next()?.parse()?;
+        let mode = *PlayMode::ALL
+            .get(next()?.parse::<usize>()?)
+            .ok_or_else(|| anyhow!("Unknown play mode in run: {}", line))?;
+        let bomb = *BombType::ALL
+            .get(next()?.parse::<usize>()?)
+            .ok_or_else(|| anyhow!("Unknown bomb in run: {}", line))?;
+        let custom = next()? == "1";
+        let score = next()?.parse()?;
+        let stage = next()?.parse()?;
+        let reached = *Progress::ALL
+            .get(next()?.parse::<usize>()?)
+            .ok_or_else(|| anyhow!("Unknown progress in run: {}", line))?;
+        let deaths = next()?.parse()?;
+        let replay = next()? == "1";
+        Ok(Self {
+            id,
+            time,
+            mode,
+            bomb,
+            custom,
+            score,
+            stage,
+            reached,
+            deaths,
+            replay,
+        })
+    }
+}
+
+/// 終わったプレイの記録。古いものから順に並べ、`MAX_RUNS`を超えたら古いものから消す。
+#[derive(Default)]
+pub struct History {
+    runs: Vec<RunRecord>,
+}
+
+impl History {
+    /// 保存された記録を読み込む。まだなければ空の記録を返す。
+    pub fn load() -> Result<Self> {
+        browser::storage_get(STORAGE_KEY)?
+            .map(|text| Self::deserialize(&text))
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    fn save(&self) -> Result<()> {
+        browser::storage_set(STORAGE_KEY, &self.serialize())
+    }
+
+    /// 版の印に続けて、1行に1回分のプレイを古いものから並べた文字列
+    pub fn serialize(&self) -> String {
+        let body = self
+            .runs
+            .iter()
+            .map(RunRecord::serialize)
+            .collect::<Vec<_>>()
+            .join("\n");
+        version::tag(FORMAT_VERSION, &body)
+    }
+
+    pub fn deserialize(text: &str) -> Result<Self> {
+        let (_, text) = version::split("history", text, FORMAT_VERSION)?;
+        let runs = text
+            .lines()
+            .map(RunRecord::deserialize)
+            .collect::<Result<_>>()?;
+        Ok(Self { runs })
+    }
+
+    /// プレイを今の日時で記録に加えて保存する。`replay`があれば入力も残し、古いプレイの入力は消していく。
+    pub fn record(&mut self, mut run: RunRecord, replay: Option<&Replay>) -> Result<()> {
+        run.id = self.runs.last().map_or(1, |last| last.id.wrapping_add(1));
+        run.time = browser::unix_time()?;
+        if let Some(replay) = replay {
+            browser::storage_set(&run.replay_key(), &replay.serialize())?;
+            run.replay = true;
+        }
+        self.runs.push(run);
+
+        let excess = self.runs.len().saturating_sub(MAX_RUNS);
+        self.runs.drain(..excess);
+        let kept = self.runs.len().saturating_sub(MAX_REPLAYS);
+        for old in self.runs[..kept].iter_mut().filter(|run| run.replay) {
+            browser::storage_remove(&old.replay_key())?;
+            old.replay = false;
+        }
+        self.save()
+    }
+
+    pub fn runs(&self) -> &[RunRecord] {
+        &self.runs
+    }
+}
+
+/// これまでのプレイを新しい順に眺める画面
+#[derive(Default)]
+pub struct HistoryView {
+    list: ListMenu,
+}
+
+impl HistoryView {
+    /// 上下で選ぶプレイを変える。変わったら`true`を返す
+    pub fn update(&mut self, pressed: &Actions, held: &Actions, history: &History) -> bool {
+        let moved = self.list.update(pressed, held, history.runs().len());
+        moved.up || moved.down
+    }
+
+    /// 選んでいるプレイの読み上げ用の文章
+    pub fn describe(&self, history: &History) -> String {
+        match self.selected(history) {
+            Some(run) => format!("{}. {}", run.summary(), run.details().join(". ")),
+            None => "No runs yet".to_string(),
+        }
+    }
+
+    fn selected<'a>(&self, history: &'a History) -> Option<&'a RunRecord> {
+        history.runs().iter().rev().nth(self.list.cursor())
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, history: &History, device: Device) {
+        renderer.set_color(Color::Text);
+        renderer.draw_text("RUN HISTORY", &Point { x: 100.0, y: 100.0 });
+
+        // 選んでいる行が見えるように、一覧を行単位でずらす
+        let first = self.list.cursor().saturating_sub(VISIBLE_ROWS - 1);
+        for (i, run) in history
+            .runs()
+            .iter()
+            .rev()
+            .enumerate()
+            .skip(first)
+            .take(VISIBLE_ROWS)
+        {
+            let y = 140.0 + ROW_HEIGHT * (i - first) as f32;
+            if i == self.list.cursor() {
+                renderer.draw_text(">", &Point { x: 80.0, y });
+            }
+            renderer.draw_text(&run.summary(), &Point { x: 100.0, y });
+        }
+        match self.selected(history) {
+            Some(run) => {
+                for (i, line) in run.details().iter().enumerate() {
+                    let y = 488.0 + ROW_HEIGHT * i as f32;
+                    renderer.draw_text(line, &Point { x: 100.0, y });
+                }
+            }
+            None => renderer.draw_text("No runs yet", &Point { x: 100.0, y: 488.0 }),
+        }
+        renderer.draw_text(
+            &format!(
+                "{}: select  {}: back",
+                device.glyph(Prompt::Change),
+                device.glyph(Prompt::Back)
+            ),
+            &Point { x: 100.0, y: 552.0 },
+        );
+    }
+}
+
+/// 1970年1月1日からのミリ秒を、UTCの`年-月-日`にする
+fn format_date(time: f64) -> String {
+    // 日数から暦の日付を求める（3月始まりの年として数えると、うるう日が年の最後に来る）
+    let days = (time / MS_PER_DAY).floor() as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
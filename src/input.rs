@@ -0,0 +1,408 @@
+use crate::{
+    browser::{self, GamepadState},
+    engine::KeyState,
+    settings::Settings,
+};
+
+pub const MAX_PLAYERS: usize = 2;
+
+/// プレイヤーごとのキー割り当て
+struct KeyBindings {
+    up: &'static str,
+    down: &'static str,
+    left: &'static str,
+    right: &'static str,
+    shot: &'static str,
+    bomb: &'static str,
+    focus: &'static str,
+}
+
+// 2人で同じキーボードを使えるように、1Pは左手側と中央、2Pは右手側に寄せている
+const PLAYER_KEYS: [KeyBindings; MAX_PLAYERS] = [
+    KeyBindings {
+        up: "KeyW",
+        down: "KeyS",
+        left: "KeyA",
+        right: "KeyD",
+        shot: "Space",
+        bomb: "KeyJ",
+        focus: "KeyK",
+    },
+    KeyBindings {
+        up: "ArrowUp",
+        down: "ArrowDown",
+        left: "ArrowLeft",
+        right: "ArrowRight",
+        shot: "Period",
+        bomb: "Comma",
+        focus: "Slash",
+    },
+];
+
+// 以下は全員で共通のキー
+const KEY_PAUSE: &str = "Escape";
+const KEY_RETRY: &str = "KeyR";
+const KEY_RESEED: &str = "KeyN";
+
+// ゲームパッドの標準の配置でのボタンの番号
+const PAD_SHOT: usize = 0; // A
+const PAD_BOMB: usize = 1; // B
+const PAD_RESEED: usize = 2; // X
+const PAD_RETRY: usize = 3; // Y
+const PAD_FOCUS: [usize; 4] = [4, 5, 6, 7]; // 肩のボタンとトリガー
+const PAD_PAUSE: usize = 9; // START
+const PAD_DPAD: [usize; 4] = [12, 13, 14, 15]; // 上下左右
+const PAD_DEADZONE: f64 = 0.5; // スティックをこれより倒したら入力とみなす
+
+/// 最後に使われた入力機器。画面の操作の案内をこれに合わせる。
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Device {
+    #[default]
+    Keyboard,
+    Gamepad,
+}
+
+/// 画面に出す操作の案内
+#[derive(Clone, Copy)]
+pub enum Prompt {
+    Confirm,     // 決定
+    Back,        // 戻る（オプション画面を閉じる）
+    Change,      // 項目の選択と値の変更
+    Retry,       // 同じシードでやり直す
+    Reseed,      // シードを変えてやり直す
+    Pick,        // 名前入力で升目の文字を選ぶ
+    Erase,       // 名前入力で1文字消す
+    FastForward, // 押している間、スタッフロールなどの演出を早送りする
+    Skip,        // 演出を最後まで飛ばす
+}
+
+impl Device {
+    /// `prompt`の操作に使うキーかボタンの名前
+    pub fn glyph(self, prompt: Prompt) -> &'static str {
+        match (self, prompt) {
+            (Device::Keyboard, Prompt::Confirm) => "Enter",
+            (Device::Keyboard, Prompt::Back) => "Esc",
+            (Device::Keyboard, Prompt::Change) => "WASD",
+            (Device::Keyboard, Prompt::Retry) => "R",
+            (Device::Keyboard, Prompt::Reseed) => "N",
+            (Device::Keyboard, Prompt::Pick) => "Space",
+            (Device::Keyboard, Prompt::Erase) => "J",
+            (Device::Keyboard, Prompt::FastForward) => "Space",
+            (Device::Keyboard, Prompt::Skip) => "J",
+            (Device::Gamepad, Prompt::Confirm) => "(A)",
+            (Device::Gamepad, Prompt::Back) => "START",
+            (Device::Gamepad, Prompt::Change) => "D-pad",
+            (Device::Gamepad, Prompt::Retry) => "(Y)",
+            (Device::Gamepad, Prompt::Reseed) => "(X)",
+            (Device::Gamepad, Prompt::Pick) => "(A)",
+            (Device::Gamepad, Prompt::Erase) => "(B)",
+            (Device::Gamepad, Prompt::FastForward) => "(A)",
+            (Device::Gamepad, Prompt::Skip) => "(B)",
+        }
+    }
+
+    /// やり直しの案内（ゲームオーバーや練習モードで出す）
+    pub fn retry_hint(self) -> String {
+        format!(
+            "{}: retry  {}: new seed",
+            self.glyph(Prompt::Retry),
+            self.glyph(Prompt::Reseed)
+        )
+    }
+}
+
+/// 文字入力（シードの入力など）に使うキー
+#[derive(Clone, Copy, PartialEq)]
+pub enum TextKey {
+    Digit(u8),
+    Backspace,
+    Enter,
+}
+
+const TEXT_KEYS: [(&str, TextKey); 12] = [
+    ("Digit0", TextKey::Digit(0)),
+    ("Digit1", TextKey::Digit(1)),
+    ("Digit2", TextKey::Digit(2)),
+    ("Digit3", TextKey::Digit(3)),
+    ("Digit4", TextKey::Digit(4)),
+    ("Digit5", TextKey::Digit(5)),
+    ("Digit6", TextKey::Digit(6)),
+    ("Digit7", TextKey::Digit(7)),
+    ("Digit8", TextKey::Digit(8)),
+    ("Digit9", TextKey::Digit(9)),
+    ("Backspace", TextKey::Backspace),
+    ("Enter", TextKey::Enter),
+];
+
+/// TASモードの操作に使うキー
+#[derive(Clone, Copy, PartialEq)]
+pub enum ToolKey {
+    Advance,     // コマ送り
+    TogglePause, // 止める・動かす
+    SaveState,   // 選んでいる枠に保存する
+    LoadState,   // 選んでいる枠から読み込む
+    Export,      // ここまでの入力をリプレイとして保存する
+    Console,     // チート用のコンソールを開く・閉じる（開発用のビルドのみ）
+    DumpDraws,   // このフレームの描画命令をブラウザのコンソールに書き出す（開発用のビルドのみ）
+    Suspend,     // プレイを中断して保存する
+    TimingsJson, // 描画の時間の分布をJSONで書き出す（デバッグ表示中のみ）
+    TimingsCsv,  // 同じくCSVで書き出す
+    Tweaks,      // 調整値を変える画面を開く・閉じる（開発用のビルドのみ）
+}
+
+const TOOL_KEYS: [(&str, ToolKey); 11] = [
+    ("KeyF", ToolKey::Advance),
+    ("KeyP", ToolKey::TogglePause),
+    ("KeyO", ToolKey::SaveState),
+    ("KeyL", ToolKey::LoadState),
+    ("KeyX", ToolKey::Export),
+    ("Backquote", ToolKey::Console),
+    ("KeyI", ToolKey::DumpDraws),
+    ("KeyU", ToolKey::Suspend),
+    ("KeyH", ToolKey::TimingsJson),
+    ("KeyG", ToolKey::TimingsCsv),
+    ("KeyT", ToolKey::Tweaks),
+];
+
+/// ゲームで使うキーか。ゲームで使うキーはページのスクロールなどに使わせない。
+pub fn is_game_key(code: &str) -> bool {
+    let player_keys = PLAYER_KEYS.iter().flat_map(|keys| {
+        [
+            keys.up, keys.down, keys.left, keys.right, keys.shot, keys.bomb, keys.focus,
+        ]
+    });
+    let common_keys = [KEY_PAUSE, KEY_RETRY, KEY_RESEED];
+    let text_keys = TEXT_KEYS.iter().map(|(code, _)| *code);
+    let tool_keys = TOOL_KEYS.iter().map(|(code, _)| *code);
+    player_keys
+        .chain(common_keys)
+        .chain(text_keys)
+        .chain(tool_keys)
+        .any(|key| key == code)
+}
+
+/// 1フレーム分のプレイヤーの操作
+#[derive(Clone, Copy, Default)]
+pub struct Actions {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    #[allow(dead_code)]
+    pub shot: bool, // ショット
+    pub bomb: bool,   // ボム
+    pub focus: bool,  // 低速移動
+    pub pause: bool,  // オプション画面の開閉
+    pub retry: bool,  // 練習モードでのやり直し
+    pub reseed: bool, // 練習モードでのシードを変えたやり直し
+}
+
+impl Actions {
+    fn from_keys(keystate: &KeyState, keys: &KeyBindings) -> Self {
+        Self {
+            up: keystate.is_pressed(keys.up),
+            down: keystate.is_pressed(keys.down),
+            left: keystate.is_pressed(keys.left),
+            right: keystate.is_pressed(keys.right),
+            shot: keystate.is_pressed(keys.shot),
+            bomb: keystate.is_pressed(keys.bomb),
+            focus: keystate.is_pressed(keys.focus),
+            pause: keystate.is_pressed(KEY_PAUSE),
+            retry: keystate.is_pressed(KEY_RETRY),
+            reseed: keystate.is_pressed(KEY_RESEED),
+        }
+    }
+
+    /// ゲームの進行に関わる操作をビット列にする（ネット対戦で送るため）
+    pub fn to_bits(self) -> u16 {
+        [
+            self.up,
+            self.down,
+            self.left,
+            self.right,
+            self.shot,
+            self.bomb,
+            self.focus,
+            self.retry,
+            self.reseed,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, &on)| bits | (on as u16) << i)
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        let bit = |i: u16| bits & (1 << i) != 0;
+        Self {
+            up: bit(0),
+            down: bit(1),
+            left: bit(2),
+            right: bit(3),
+            shot: bit(4),
+            bomb: bit(5),
+            focus: bit(6),
+            retry: bit(7),
+            reseed: bit(8),
+            pause: false,
+        }
+    }
+
+    fn from_gamepad(pad: &GamepadState) -> Self {
+        let button = |index: usize| pad.buttons.get(index).copied().unwrap_or(false);
+        let axis = |index: usize| pad.axes.get(index).copied().unwrap_or(0.0);
+        Self {
+            up: button(PAD_DPAD[0]) || axis(1) < -PAD_DEADZONE,
+            down: button(PAD_DPAD[1]) || axis(1) > PAD_DEADZONE,
+            left: button(PAD_DPAD[2]) || axis(0) < -PAD_DEADZONE,
+            right: button(PAD_DPAD[3]) || axis(0) > PAD_DEADZONE,
+            shot: button(PAD_SHOT),
+            bomb: button(PAD_BOMB),
+            focus: PAD_FOCUS.iter().any(|&index| button(index)),
+            pause: button(PAD_PAUSE),
+            retry: button(PAD_RETRY),
+            reseed: button(PAD_RESEED),
+        }
+    }
+
+    /// どちらかで押されている操作を押されているとみなす
+    fn merge(&self, other: &Actions) -> Self {
+        Self {
+            up: self.up || other.up,
+            down: self.down || other.down,
+            left: self.left || other.left,
+            right: self.right || other.right,
+            shot: self.shot || other.shot,
+            bomb: self.bomb || other.bomb,
+            focus: self.focus || other.focus,
+            pause: self.pause || other.pause,
+            retry: self.retry || other.retry,
+            reseed: self.reseed || other.reseed,
+        }
+    }
+
+    /// 何か1つでも押されているか
+    pub fn is_any(&self) -> bool {
+        self.to_bits() != 0 || self.pause
+    }
+
+    /// `self`では押されていて`prev`では押されていない操作だけを残す
+    fn rising_edge(&self, prev: &Actions) -> Self {
+        Self {
+            up: self.up && !prev.up,
+            down: self.down && !prev.down,
+            left: self.left && !prev.left,
+            right: self.right && !prev.right,
+            shot: self.shot && !prev.shot,
+            bomb: self.bomb && !prev.bomb,
+            focus: self.focus && !prev.focus,
+            pause: self.pause && !prev.pause,
+            retry: self.retry && !prev.retry,
+            reseed: self.reseed && !prev.reseed,
+        }
+    }
+}
+
+/// キー入力とゲームパッドから操作を導出する。1台目のゲームパッドは1P、2台目は2Pを操作する。
+/// 設定によってはキーを押した瞬間だけを見る必要があるので、前フレームのキー状態を覚えておく。
+pub struct Input {
+    held: [Actions; MAX_PLAYERS], // 今フレームで押されているキー（設定を反映しない生の入力）
+    prev_held: [Actions; MAX_PLAYERS], // 前フレームで押されていたキー
+    focus_latched: [bool; MAX_PLAYERS], // 低速モードの切り替え状態（`toggle_focus`のときのみ使う）
+    text_held: [bool; TEXT_KEYS.len()], // 文字入力キーが押されているか
+    typed: Option<TextKey>,       // このフレームで押し始めた文字入力キー
+    tool_held: [bool; TOOL_KEYS.len()], // TAS用のキーが押されているか
+    tool: Option<ToolKey>,        // このフレームで押し始めたTAS用のキー
+    device: Device,               // 最後に使われた入力機器
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            held: [Actions::default(); MAX_PLAYERS],
+            prev_held: [Actions::default(); MAX_PLAYERS],
+            focus_latched: [false; MAX_PLAYERS],
+            text_held: [false; TEXT_KEYS.len()],
+            typed: None,
+            tool_held: [false; TOOL_KEYS.len()],
+            tool: None,
+            device: Device::default(),
+        }
+    }
+
+    /// キー状態を取り込み、設定を反映したプレイヤーごとのゲーム中の操作を返す
+    pub fn update(&mut self, keystate: &KeyState, settings: &Settings) -> [Actions; MAX_PLAYERS] {
+        self.prev_held = self.held;
+        let pads = browser::gamepads().unwrap_or_default();
+        let mut pad_used = false;
+        for (player, (held, keys)) in self.held.iter_mut().zip(PLAYER_KEYS.iter()).enumerate() {
+            *held = Actions::from_keys(keystate, keys);
+            if let Some(Some(pad)) = pads.get(player) {
+                let pad = Actions::from_gamepad(pad);
+                pad_used |= pad.is_any();
+                *held = held.merge(&pad);
+            }
+        }
+        if pad_used {
+            self.device = Device::Gamepad;
+        } else if keystate.is_any_pressed() {
+            self.device = Device::Keyboard;
+        }
+
+        self.typed = None;
+        for ((code, key), held) in TEXT_KEYS.iter().zip(self.text_held.iter_mut()) {
+            let pressed = keystate.is_pressed(code);
+            // 文字入力では、押しっぱなしにしたときのキーリピートも入力として扱う
+            if pressed && (!*held || keystate.is_repeated(code)) {
+                self.typed = Some(*key);
+            }
+            *held = pressed;
+        }
+
+        self.tool = None;
+        for ((code, key), held) in TOOL_KEYS.iter().zip(self.tool_held.iter_mut()) {
+            let pressed = keystate.is_pressed(code);
+            if pressed && !*held {
+                self.tool = Some(*key);
+            }
+            *held = pressed;
+        }
+
+        std::array::from_fn(|player| {
+            let held = self.held[player];
+            if held.rising_edge(&self.prev_held[player]).focus {
+                self.focus_latched[player] = !self.focus_latched[player];
+            }
+            Actions {
+                shot: settings.autofire || held.shot,
+                focus: if settings.toggle_focus {
+                    self.focus_latched[player]
+                } else {
+                    held.focus
+                },
+                ..held
+            }
+        })
+    }
+
+    /// このフレームで1Pが押している操作（設定を反映しない）
+    pub fn held(&self) -> Actions {
+        self.held[0]
+    }
+
+    /// このフレームで1Pが押し始めた操作（メニュー操作などに使う）
+    pub fn just_pressed(&self) -> Actions {
+        self.held[0].rising_edge(&self.prev_held[0])
+    }
+
+    pub fn typed(&self) -> Option<TextKey> {
+        self.typed
+    }
+
+    pub fn tool(&self) -> Option<ToolKey> {
+        self.tool
+    }
+
+    pub fn device(&self) -> Device {
+        self.device
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    component::{Collider, Components, Kinematics, Renderable, Shape, Transform},
+    constants::{ITEM_FALL_ACCELERATION, ITEM_MAX_FALL_SPEED},
+    engine::Renderer,
+    math::{Point, Vector},
+    palette::Color,
+    player::HITBOX_RADIUS,
+};
+
+const ITEM_RADIUS: f32 = 8.0;
+const PICKUP_RADIUS: f32 = 24.0; // プレイヤーの中心からこの距離まで近づけば取れる
+
+/// 敵が落とすアイテムの種類
+#[derive(Clone, Copy)]
+pub enum ItemKind {
+    Point,        // 得点
+    Bomb,         // ボムが1つ増える
+    Life,         // 残機が1つ増える
+    BombFragment, // 決まった数だけ集めるとボムが1つ増える
+    LifeFragment, // 決まった数だけ集めると残機が1つ増える
+    Shield,       // 1度だけ被弾を防ぐ
+}
+
+impl ItemKind {
+    fn label(self) -> &'static str {
+        match self {
+            ItemKind::Point => "P",
+            ItemKind::Bomb => "B",
+            ItemKind::Life => "L",
+            ItemKind::BombFragment => "b",
+            ItemKind::LifeFragment => "l",
+            ItemKind::Shield => "S",
+        }
+    }
+}
+
+/// 一度上に跳ねてから、下に落ちていくアイテム
+#[derive(Clone)]
+pub struct Item {
+    kind: ItemKind,
+    transform: Transform,
+    kinematics: Kinematics, // 横の速さはだんだん弱まり、縦は下向きに加速して`ITEM_MAX_FALL_SPEED`で止まる
+    // プレイヤーの中心が`PICKUP_RADIUS`に入れば取れるよう、自機の当たり判定の分だけ小さくしておく
+    collider: Collider,
+    renderable: Renderable,
+}
+
+impl Item {
+    /// `pos`から横に`vx`、上に`rise`の速さで飛び出させる
+    pub fn new(kind: ItemKind, pos: Point, vx: f32, rise: f32) -> Self {
+        Self {
+            kind,
+            transform: Transform { pos },
+            kinematics: Kinematics::new(Vector::new(vx, -rise)),
+            collider: Collider {
+                radius: PICKUP_RADIUS - HITBOX_RADIUS,
+            },
+            renderable: Renderable::circle(Color::Item, ITEM_RADIUS),
+        }
+    }
+
+    pub fn kind(&self) -> ItemKind {
+        self.kind
+    }
+
+    pub fn pos(&self) -> Point {
+        self.transform.pos
+    }
+
+    /// 速度を変える。位置は`component::integrate`で進める
+    pub fn fall(&mut self) {
+        let (vx, vy) = self.kinematics.vel.components();
+        self.kinematics.vel = Vector::new(
+            vx * 0.95,
+            (vy + ITEM_FALL_ACCELERATION).min(ITEM_MAX_FALL_SPEED),
+        );
+    }
+}
+
+impl Components for Item {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    fn kinematics(&self) -> Option<&Kinematics> {
+        Some(&self.kinematics)
+    }
+
+    fn hitbox(&self) -> Shape {
+        Shape::Circle(self.collider)
+    }
+
+    fn renderable(&self) -> &Renderable {
+        &self.renderable
+    }
+
+    fn decorate(&self, renderer: &dyn Renderer) {
+        renderer.draw_text_centered(
+            self.kind.label(),
+            &Point {
+                x: self.transform.pos.x,
+                y: self.transform.pos.y + 7.0,
+            },
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! 弾の位置と速度の入れ物と、それをまとめて進める計算。
+//! 位置と速度は成分ごとの配列に並べたままフレームをまたいで持ち、
+//! wasmの`simd128`を有効にしたビルドでは4発ずつ、そうでなければ1発ずつ計算する。
+//! どちらで計算しても結果は1ビットも変わらない。（掛け算と足し算を別々に行うので、リプレイや対戦の同期は崩れない）
+
+use crate::{
+    math::{Point, Vector},
+    playfield::Playfield,
+};
+
+/// 弾1発の位置と速度
+#[derive(Clone, Copy)]
+pub struct Motion {
+    pub pos: Point,
+    pub vel: Vector, // 1フレームあたりの移動量
+}
+
+/// 弾の位置と速度を、成分ごとの配列に並べたもの
+#[derive(Clone, Default)]
+pub struct Lanes {
+    x: Vec<f32>,
+    y: Vec<f32>,
+    vx: Vec<f32>,
+    vy: Vec<f32>,
+}
+
+impl Lanes {
+    pub fn push(&mut self, motion: Motion) {
+        let (vx, vy) = motion.vel.components();
+        self.x.push(motion.pos.x);
+        self.y.push(motion.pos.y);
+        self.vx.push(vx);
+        self.vy.push(vy);
+    }
+
+    pub fn clear(&mut self) {
+        self.x.clear();
+        self.y.clear();
+        self.vx.clear();
+        self.vy.clear();
+    }
+
+    pub fn get(&self, index: usize) -> Motion {
+        Motion {
+            pos: Point {
+                x: self.x[index],
+                y: self.y[index],
+            },
+            vel: Vector::new(self.vx[index], self.vy[index]),
+        }
+    }
+
+    pub fn set(&mut self, index: usize, motion: Motion) {
+        let (vx, vy) = motion.vel.components();
+        self.x[index] = motion.pos.x;
+        self.y[index] = motion.pos.y;
+        self.vx[index] = vx;
+        self.vy[index] = vy;
+    }
+
+    /// `items[i]`と`i`番目の弾の位置と速度を組にして、順に`update`に渡す
+    pub fn update_with<T>(&mut self, items: &mut [T], mut update: impl FnMut(&mut T, &mut Motion)) {
+        let lanes = self
+            .x
+            .iter_mut()
+            .zip(&mut self.y)
+            .zip(self.vx.iter_mut().zip(&mut self.vy));
+        for (item, ((x, y), (vx, vy))) in items.iter_mut().zip(lanes) {
+            let mut motion = Motion {
+                pos: Point { x: *x, y: *y },
+                vel: Vector::new(*vx, *vy),
+            };
+            update(item, &mut motion);
+            (*x, *y) = (motion.pos.x, motion.pos.y);
+            (*vx, *vy) = motion.vel.components();
+        }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Point> + '_ {
+        self.x.iter().zip(&self.y).map(|(&x, &y)| Point { x, y })
+    }
+
+    pub fn velocities(&self) -> impl Iterator<Item = Vector> + '_ {
+        self.vx
+            .iter()
+            .zip(&self.vy)
+            .map(|(&x, &y)| Vector::new(x, y))
+    }
+
+    /// `keep[i]`が`false`の弾を取り除き、残りを前に詰める
+    pub fn retain(&mut self, keep: &[bool]) {
+        if keep.iter().all(|&keep| keep) {
+            return;
+        }
+        for lane in [&mut self.x, &mut self.y, &mut self.vx, &mut self.vy] {
+            let mut keep = keep.iter();
+            lane.retain(|_| *keep.next().unwrap_or(&false));
+        }
+    }
+
+    /// 確保しているメモリの大きさ
+    pub fn capacity_bytes(&self) -> usize {
+        (self.x.capacity() + self.y.capacity() + self.vx.capacity() + self.vy.capacity())
+            * std::mem::size_of::<f32>()
+    }
+
+    /// `speed`倍した速度の分だけ、すべての位置を進める（`Kinematics::integrate`と同じ計算）
+    pub fn integrate(&mut self, speed: f32) {
+        integrate(&mut self.x, &self.vx, speed);
+        integrate(&mut self.y, &self.vy, speed);
+    }
+
+    /// 位置ごとに、プレイフィールドの中にあるか（`Playfield::contains`と同じ判定）を`inside`に書く
+    pub fn inside(&self, playfield: &Playfield, inside: &mut Vec<bool>) {
+        inside.clear();
+        inside.resize(self.x.len(), false);
+        within(
+            &self.x,
+            playfield.open_left(),
+            playfield.open_right(),
+            inside,
+            true,
+        );
+        within(&self.y, playfield.top(), playfield.bottom(), inside, false);
+    }
+}
+
+/// `pos[i] += vel[i] * speed`
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+fn integrate(pos: &mut [f32], vel: &[f32], speed: f32) {
+    use core::arch::wasm32::*;
+
+    let split = pos.len() / 4 * 4;
+    let (head, tail) = pos.split_at_mut(split);
+    let scale = f32x4_splat(speed);
+    for (pos, vel) in head.chunks_exact_mut(4).zip(vel.chunks_exact(4)) {
+        // `chunks_exact`で4つずつ区切っているので、読み書きは範囲に収まる
+        unsafe {
+            let moved = f32x4_add(
+                v128_load(pos.as_ptr() as *const v128),
+                f32x4_mul(v128_load(vel.as_ptr() as *const v128), scale),
+            );
+            v128_store(pos.as_mut_ptr() as *mut v128, moved);
+        }
+    }
+    integrate_scalar(tail, &vel[split..], speed);
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+fn integrate(pos: &mut [f32], vel: &[f32], speed: f32) {
+    integrate_scalar(pos, vel, speed);
+}
+
+fn integrate_scalar(pos: &mut [f32], vel: &[f32], speed: f32) {
+    for (pos, vel) in pos.iter_mut().zip(vel) {
+        *pos += vel * speed;
+    }
+}
+
+/// `values[i]`が`min`以上`max`以下かを`inside[i]`に書く。`first`でなければ、すでに書いてある結果との両方を満たすかを書く
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+fn within(values: &[f32], min: f32, max: f32, inside: &mut [bool], first: bool) {
+    use core::arch::wasm32::*;
+
+    let split = values.len() / 4 * 4;
+    let (low, high) = (f32x4_splat(min), f32x4_splat(max));
+    for (chunk, values) in values[..split].chunks_exact(4).enumerate() {
+        // `chunks_exact`で4つずつ区切っているので、読み込みは範囲に収まる
+        let mask = unsafe {
+            let values = v128_load(values.as_ptr() as *const v128);
+            i32x4_bitmask(v128_and(f32x4_ge(values, low), f32x4_le(values, high)))
+        };
+        for lane in 0..4 {
+            let within = mask & (1 << lane) != 0;
+            let inside = &mut inside[chunk * 4 + lane];
+            *inside = within && (first || *inside);
+        }
+    }
+    within_scalar(&values[split..], min, max, &mut inside[split..], first);
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+fn within(values: &[f32], min: f32, max: f32, inside: &mut [bool], first: bool) {
+    within_scalar(values, min, max, inside, first);
+}
+
+fn within_scalar(values: &[f32], min: f32, max: f32, inside: &mut [bool], first: bool) {
+    for (value, inside) in values.iter().zip(inside) {
+        let within = *value >= min && *value <= max;
+        *inside = within && (first || *inside);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::{Kinematics, Transform};
+
+    /// 弾1発ずつ`Kinematics::integrate`で進めた位置と、ビット単位で一致する
+    #[test]
+    fn integrate_matches_kinematics() {
+        let bullets = (0..37)
+            .map(|i| {
+                let pos = Point {
+                    x: i as f32 * 13.7,
+                    y: 400.0 - i as f32 * 3.1,
+                };
+                (
+                    pos,
+                    Vector::from_deg_and_mag(i as f32 * 23.0, 1.5 + i as f32 * 0.1),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut lanes = Lanes::default();
+        for &(pos, vel) in bullets.iter() {
+            lanes.push(Motion { pos, vel });
+        }
+        lanes.integrate(0.3);
+        for ((pos, vel), actual) in bullets.into_iter().zip(lanes.positions()) {
+            let mut expected = Transform { pos };
+            Kinematics::new(vel).integrate(&mut expected, 0.3);
+            assert_eq!(actual.x.to_bits(), expected.pos.x.to_bits());
+            assert_eq!(actual.y.to_bits(), expected.pos.y.to_bits());
+        }
+    }
+
+    /// 中にあるかの判定が、端にちょうど乗っている位置も含めて`Playfield::contains`と一致する
+    #[test]
+    fn inside_matches_playfield() {
+        let playfield = Playfield::MAIN;
+        let (left, right) = (playfield.open_left(), playfield.open_right());
+        let (top, bottom) = (playfield.top(), playfield.bottom());
+        let points = [
+            Point { x: left, y: top },
+            Point {
+                x: right,
+                y: bottom,
+            },
+            Point {
+                x: left - 0.1,
+                y: top + 30.0,
+            },
+            Point {
+                x: right - 30.0,
+                y: bottom + 0.1,
+            },
+            Point {
+                x: (left + right) / 2.0,
+                y: (top + bottom) / 2.0,
+            },
+        ];
+        let mut lanes = Lanes::default();
+        for pos in points {
+            lanes.push(Motion {
+                pos,
+                vel: Vector::zero(),
+            });
+        }
+        let expected = points.map(|pos| playfield.contains(&pos));
+        let mut inside = Vec::new();
+        lanes.inside(&playfield, &mut inside);
+        assert_eq!(inside, expected);
+        assert_eq!(expected, [true, true, false, false, true]);
+    }
+
+    /// 列のまま進めて中にあるかを確かめる時間と、弾1発ずつ`Kinematics::integrate`と`Playfield::contains`で
+    /// 同じことをする時間を測って書き出す。遅い環境で落ちないよう、時間については何も確かめない。
+    /// `cargo test --release timing -- --ignored --nocapture`で測る。
+    /// wasmの`simd128`の分は`wasm32-wasip1`向けにビルドしたテストをnodeのWASIで動かして測る（READMEを参照）
+    #[test]
+    #[ignore = "prints timings only"]
+    fn lanes_timing() {
+        const BULLETS: usize = 4000;
+        const TICKS: u32 = 10_000;
+        let playfield = Playfield::MAIN;
+        let motions = (0..BULLETS)
+            .map(|i| Motion {
+                pos: Point {
+                    x: playfield.open_left() + (i * 37 % 400) as f32,
+                    y: playfield.top() + (i * 53 % 500) as f32,
+                },
+                vel: Vector::from_deg_and_mag(i as f32 * 7.0, 0.01),
+            })
+            .collect::<Vec<_>>();
+
+        let mut lanes = Lanes::default();
+        for &motion in motions.iter() {
+            lanes.push(motion);
+        }
+        let mut inside = Vec::new();
+        let start = std::time::Instant::now();
+        for _ in 0..TICKS {
+            lanes.integrate(1.0);
+            lanes.inside(&playfield, &mut inside);
+        }
+        let lanes_ns = start.elapsed().as_nanos() as f64 / TICKS as f64;
+
+        let mut bodies = motions
+            .iter()
+            .map(|motion| (Transform { pos: motion.pos }, Kinematics::new(motion.vel)))
+            .collect::<Vec<_>>();
+        let mut kept = Vec::new();
+        let start = std::time::Instant::now();
+        for _ in 0..TICKS {
+            kept.clear();
+            for (transform, kinematics) in bodies.iter_mut() {
+                kinematics.integrate(transform, 1.0);
+                kept.push(playfield.contains(&transform.pos));
+            }
+        }
+        let single_ns = start.elapsed().as_nanos() as f64 / TICKS as f64;
+
+        assert_eq!(inside, kept);
+        println!(
+            "{} bullets per tick (simd128: {}): lanes {:.1}us, one at a time {:.1}us",
+            BULLETS,
+            cfg!(all(target_arch = "wasm32", target_feature = "simd128")),
+            lanes_ns / 1000.0,
+            single_ns / 1000.0
+        );
+    }
+
+    /// 取り除いた後も、残った弾の位置と速度は同じ弾のもののまま並ぶ
+    #[test]
+    fn retain_keeps_components_together() {
+        let mut lanes = Lanes::default();
+        for i in 0..6 {
+            let i = i as f32;
+            lanes.push(Motion {
+                pos: Point { x: i, y: -i },
+                vel: Vector::new(i * 10.0, -i * 10.0),
+            });
+        }
+        lanes.retain(&[true, false, false, true, false, true]);
+        let kept = (0..lanes.positions().count())
+            .map(|index| {
+                let motion = lanes.get(index);
+                (motion.pos.x, motion.pos.y, motion.vel.components())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kept,
+            [
+                (0.0, -0.0, (0.0, -0.0)),
+                (3.0, -3.0, (30.0, -30.0)),
+                (5.0, -5.0, (50.0, -50.0)),
+            ]
+        );
+    }
+}
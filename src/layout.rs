@@ -0,0 +1,91 @@
+use crate::{
+    browser,
+    math::{Point, Rect},
+};
+
+const SCREEN_SIZE: f32 = 600.0; // 横向きの画面（論理座標）の幅と高さ
+const PORTRAIT_HEIGHT: f32 = 800.0; // 縦向きのときの画面の高さ。幅は変えない
+
+/// ゲームが描く論理座標の画面全体
+pub const SCREEN: Rect = Rect {
+    x: 0.0,
+    y: 0.0,
+    width: SCREEN_SIZE,
+    height: SCREEN_SIZE,
+};
+
+/// 画面の並べ方。タッチで操作する端末を縦に持っているときは縦向きにして、
+/// プレイフィールドを画面の幅いっぱいに広げ、残機やスコアをその上下の帯に置く。
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Layout {
+    #[default]
+    Landscape,
+    Portrait,
+}
+
+impl Layout {
+    /// ページの表示領域の縦横の比から選ぶ。マウスやキーボードで遊ぶ端末では、縦長の窓でも横向きのまま
+    pub fn detect() -> Layout {
+        match browser::viewport() {
+            Ok((width, height)) if browser::is_touch_device() && height > width => Layout::Portrait,
+            _ => Layout::Landscape,
+        }
+    }
+
+    /// 論理座標の`focus`を画面の幅に合わせて広げ、縦の真ん中に置くときの画面。横向きなら何もしない
+    pub fn viewport(self, focus: &Rect) -> Viewport {
+        match self {
+            Layout::Landscape => Viewport::default(),
+            Layout::Portrait => {
+                let scale = SCREEN_SIZE / focus.width;
+                Viewport {
+                    height: PORTRAIT_HEIGHT,
+                    scale,
+                    dx: -focus.x * scale,
+                    dy: (PORTRAIT_HEIGHT - focus.height * scale) / 2.0 - focus.y * scale,
+                }
+            }
+        }
+    }
+}
+
+/// 描画先の画面の高さと、ゲームが描く論理座標から画面の座標への変換（拡大してから平行移動）。
+/// ゲームはどちらの向きでも600x600の論理座標で描き、並べ方の違いはこの変換だけで吸収する。
+#[derive(Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub height: f32,
+    pub scale: f32,
+    pub dx: f32,
+    pub dy: f32,
+}
+
+impl Viewport {
+    /// 画面の座標`point`を、ゲームが描く論理座標に戻す
+    pub fn unproject(&self, point: &Point) -> Point {
+        Point {
+            x: (point.x - self.dx) / self.scale,
+            y: (point.y - self.dy) / self.scale,
+        }
+    }
+
+    /// 画面に映る範囲を論理座標で表したもの。横向きなら`SCREEN`と同じ
+    pub fn visible(&self) -> Rect {
+        Rect {
+            x: -self.dx / self.scale,
+            y: -self.dy / self.scale,
+            width: SCREEN_SIZE / self.scale,
+            height: self.height / self.scale,
+        }
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            height: SCREEN_SIZE,
+            scale: 1.0,
+            dx: 0.0,
+            dy: 0.0,
+        }
+    }
+}
@@ -1,50 +1,786 @@
-use std::f32::consts::PI;
+use std::{collections::HashMap, f32::consts::PI, mem::size_of, rc::Rc};
+
+use anyhow::{anyhow, Result};
 
 use crate::{
-    engine::{KeyState, Renderer},
-    math::{Point, Vector},
-    player::Player,
+    ambient::Weather,
+    background::{Background, BackgroundChange, BackgroundEvent},
+    banner::Banner,
+    behavior::{self, BulletBehavior, BulletCtx, Parent},
+    boss::{self, hp, survival, BossMovement, Spell},
+    bot,
+    camping::CampWatch,
+    chain::Chain,
+    collision::{self, Body, Layer},
+    command::Command,
+    component::{self, Collider, Components, Health, Kinematics, Renderable, Shape, Transform},
+    constants::{
+        frames, per_frame, per_frame_squared, per_tick, AIM_BULLET_SPEED, BOSS_DELAY_FRAMES,
+        BOSS_LEAVE_SPEED, BOSS_TELEPORT_FRAMES, DEATH_MARKER_FRAMES, FAN_BULLET_SPEED,
+        MIDBOSS_ARRIVAL_FRAME, PUNISHER_SPEED, TELEGRAPH_FRAMES,
+    },
+    damage::{DamageMeter, Resistance},
+    defeat::Defeat,
+    effects::Effects,
+    engine::Renderer,
+    entity::{EntityId, EntityIds},
+    event::{Damage, GameEvent},
+    guide::Guide,
+    hazard::{self, Hazard, HazardKind, HazardSpawn, SlidingWalls, WallShift},
+    heatmap::Heatmap,
+    input::{Actions, Device, MAX_PLAYERS},
+    item::{Item, ItemKind},
+    lanes::{Lanes, Motion},
+    layout::Layout,
+    math::{Point, Rect, Vector},
+    pack::{Pack, Wave},
+    palette::{Color, Theme},
+    player::{Player, BOMB_FRAGMENTS, LIFE_FRAGMENTS},
+    playfield::Playfield,
+    quality::Quality,
+    risk,
+    rng::Rng,
+    settings::{BombType, Modifiers, Settings, ShotType},
+    shot::PlayerShot,
+    stage::{stage, FIRE_DELAY},
+    timeline::{Timed, Timeline},
+    tweak::Tweaks,
 };
 
-pub struct Level {
-    player: Player,
-    enemies: Vec<Enemy>,
-    bullets: Vec<Bullet>,
-}
+pub const STAGE_ID: &str = "stage1"; // 保存データなどでステージを区別するための名前
+const STAGE_NUMBER: u32 = 1;
+const STAGE_TITLE: &str = "Fan Dance at Dusk"; // ステージの始まりに見出しとして出す
+const STAGE_THEME: Theme = Theme::Dusk; // ステージの色合い
+const BULLET_RADIUS: f32 = 10.0; // 敵弾の半径
+const ENEMY_RADIUS: f32 = 20.0; // 敵の半径
+const ENEMY_HEALTH: u32 = 100; // 雑魚敵や使い魔の体力
+const ITEM_DRAW_RADIUS: f32 = 16.0; // アイテムの絵と文字が収まる半径
+const POINT_ITEM_SCORE: u64 = 10_000;
+const NEAR_SIGHT_RADIUS: f32 = 120.0; // 弾が近くでしか見えないルールで、弾が見える距離
+const CLEAR_BONUS_PER_LIFE: u64 = 200_000; // クリア時に残機1つにつき与える得点
+const CLEAR_BONUS_PER_BOMB: u64 = 50_000; // クリア時にボム1つにつき与える得点
+const GUIDE_MAX_RAYS: u16 = 8; // これより多く撃つ扇は、1発ずつの線ではなく弾の壁として弧で予告する
 
-impl Level {
-    pub fn new() -> Self {
-        Level {
-            player: Player::new(),
-            enemies: vec![Enemy::new(
-                Point { x: 300.0, y: 50.0 },
-                Vector::zero(),
-                vec![
-                    EnemyEvent {
-                        at: 120,
-                        event_ty: EnemyEventType::Nways {
-                            n: 4,
-                            wide_deg: 90.0,
-                            center_deg: 90.0,
+/// 敵のイベント列を作る関数
+type Pattern = fn() -> Vec<EnemyEvent>;
+
+/// 名前を付けた弾幕パターン。開発用のツールから敵に撃たせるのに使う。
+pub const PATTERNS: [(&str, Pattern); 13] = [
+    ("fan", || {
+        vec![EnemyEvent {
+            at: 30,
+            event_ty: EnemyEventType::Nways {
+                n: 8,
+                wide_deg: 120.0,
+                center_deg: 90.0,
+                include_center: false,
+                bullet: BulletSpec::default(),
+            },
+        }]
+    }),
+    ("aimed fan", || {
+        vec![EnemyEvent::aimed_nways(FIRE_DELAY, 5, 40.0)]
+    }),
+    ("gravity well", || {
+        // 全方向に撃った弾が、一番近いプレイヤーに吸い寄せられる
+        vec![EnemyEvent {
+            at: 30,
+            event_ty: EnemyEventType::Nways {
+                n: 12,
+                wide_deg: 330.0,
+                center_deg: 90.0,
+                include_center: false,
+                bullet: BulletSpec::new([
+                    BulletEvent {
+                        at: 30,
+                        event_ty: BulletEventType::SpeedClamp { min: 0.5, max: 2.5 },
+                    },
+                    BulletEvent {
+                        at: 31,
+                        event_ty: BulletEventType::AccelTowards {
+                            target: AccelTarget::Player,
+                            magnitude: 0.05,
+                        },
+                    },
+                    BulletEvent {
+                        at: 240,
+                        event_ty: BulletEventType::AccelTowards {
+                            target: AccelTarget::Point(Point { x: 300.0, y: 300.0 }),
+                            magnitude: -0.05,
                         },
                     },
-                    EnemyEvent {
-                        at: 130,
-                        event_ty: EnemyEventType::AimShot,
+                ]),
+            },
+        }]
+    }),
+    ("mines", || {
+        // 広がった弾が止まって待ち、動き出すときのプレイヤーの位置を狙う
+        vec![EnemyEvent {
+            at: 30,
+            event_ty: EnemyEventType::Nways {
+                n: 8,
+                wide_deg: 315.0,
+                center_deg: 90.0,
+                include_center: false,
+                bullet: BulletSpec::new([BulletEvent {
+                    at: 20,
+                    event_ty: BulletEventType::FreezeThenLaunch {
+                        stop_frames: 20,
+                        wait_frames: 40,
+                        launch: Launch {
+                            direction: LaunchDirection::AtPlayer,
+                            speed: 3.0,
+                        },
                     },
-                    EnemyEvent {
-                        at: 135,
-                        event_ty: EnemyEventType::AimShot,
+                }]),
+            },
+        }]
+    }),
+    ("stop and go", || {
+        // 下向きの扇がその場で止まり、同じ向きに速くなって動き出す
+        vec![EnemyEvent {
+            at: 30,
+            event_ty: EnemyEventType::Nways {
+                n: 5,
+                wide_deg: 60.0,
+                center_deg: 90.0,
+                include_center: false,
+                bullet: BulletSpec::new([BulletEvent {
+                    at: 30,
+                    event_ty: BulletEventType::FreezeThenLaunch {
+                        stop_frames: 0,
+                        wait_frames: 30,
+                        launch: Launch {
+                            direction: LaunchDirection::Keep,
+                            speed: 4.0,
+                        },
                     },
-                    EnemyEvent {
-                        at: 140,
-                        event_ty: EnemyEventType::AimShot,
+                }]),
+            },
+        }]
+    }),
+    ("bouncer", || {
+        // 壁で2回まで跳ね返る扇
+        vec![EnemyEvent {
+            at: 30,
+            event_ty: EnemyEventType::Nways {
+                n: 6,
+                wide_deg: 150.0,
+                center_deg: 90.0,
+                include_center: false,
+                bullet: BulletSpec::default().with_boundary(Boundary::Bounce(2)),
+            },
+        }]
+    }),
+    ("wraparound", || {
+        // 下に抜けた弾が上から1回だけ戻ってくる
+        vec![EnemyEvent {
+            at: 30,
+            event_ty: EnemyEventType::AimedNways {
+                n: 3,
+                wide_deg: 30.0,
+                include_center: true,
+                lead: 0.0,
+                bullet: BulletSpec::default().with_boundary(Boundary::Wrap(1)),
+            },
+        }]
+    }),
+    ("orbiters", || {
+        // 周りを回る2体の使い魔がそれぞれプレイヤーを狙って撃ち、前に付いた1体が扇状に撃つ
+        let shots: Rc<[EnemyEvent]> = (0..4)
+            .map(|i| EnemyEvent {
+                at: 40 + 30 * i,
+                event_ty: EnemyEventType::AimShot,
+            })
+            .collect();
+        let orbiters = [0.0, 180.0].map(|start_deg| FamiliarSpec {
+            offset: Offset::Orbit {
+                radius: 50.0,
+                start_deg,
+                deg_per_frame: 3.0,
+            },
+            events: shots.clone(),
+        });
+        let front = FamiliarSpec {
+            offset: Offset::Fixed(Vector::from_deg_and_mag(90.0, 30.0)),
+            events: Rc::new([EnemyEvent {
+                at: 60,
+                event_ty: EnemyEventType::Nways {
+                    n: 5,
+                    wide_deg: 60.0,
+                    center_deg: 90.0,
+                    include_center: true,
+                    bullet: BulletSpec::default(),
+                },
+            }]),
+        };
+        let familiars: Rc<[FamiliarSpec]> = orbiters.into_iter().chain([front]).collect();
+        vec![EnemyEvent {
+            at: 10,
+            event_ty: EnemyEventType::SummonFamiliars(familiars),
+        }]
+    }),
+    ("carrier", || {
+        // 左右に小さな敵を放ち、放たれた敵は斜めに降りながらプレイヤーを狙って撃つ
+        let shots: Rc<[EnemyEvent]> = (0..3)
+            .map(|i| EnemyEvent {
+                at: 20 + 40 * i,
+                event_ty: EnemyEventType::AimShot,
+            })
+            .collect();
+        [45.0, 135.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, deg)| EnemyEvent {
+                at: 20 + 20 * i as u16,
+                event_ty: EnemyEventType::SpawnEnemy {
+                    definition: EnemySpec {
+                        vel: Vector::from_deg_and_mag(deg, 1.0),
+                        events: shots.clone(),
+                        // 放たれたばかりの間は傷を半分しか受けない
+                        resistance: Resistance {
+                            scale: 0.5,
+                            frames: frames(20),
+                        },
                     },
-                ],
-            )],
-            bullets: vec![Bullet::new(
-                Point { x: 300.0, y: 50.0 },
-                Vector::new(0.0, 4.0),
+                    offset: Vector::zero(),
+                },
+            })
+            .collect()
+    }),
+    ("time stop", || {
+        // 全方位に撃った弾を止め、向きを反対にしてから動き出させる
+        vec![
+            EnemyEvent {
+                at: 10,
+                event_ty: EnemyEventType::Nways {
+                    n: 16,
+                    wide_deg: 337.5, // 端同士が重ならないよう1発分の間を空ける
+                    center_deg: 90.0,
+                    include_center: false,
+                    bullet: BulletSpec::default(),
+                },
+            },
+            EnemyEvent {
+                at: 50,
+                event_ty: EnemyEventType::Level(LevelEvent::FreezeBullets(60)),
+            },
+            EnemyEvent {
+                at: 80,
+                event_ty: EnemyEventType::Level(LevelEvent::RotateAllBullets(180.0)),
+            },
+        ]
+    }),
+    ("aimed", || {
+        (0..3)
+            .map(|i| EnemyEvent {
+                at: 30 + 5 * i,
+                event_ty: EnemyEventType::AimShot,
+            })
+            .collect()
+    }),
+    ("wobble", || {
+        vec![EnemyEvent {
+            at: 30,
+            event_ty: EnemyEventType::AimedNways {
+                n: 3,
+                wide_deg: 40.0,
+                include_center: true,
+                lead: 0.0,
+                bullet: BulletSpec::default().with_behavior("wobble"),
+            },
+        }]
+    }),
+    ("orbit", || {
+        // ゆっくり広がる輪が、撃った敵の周りを回りながら付いていく
+        vec![EnemyEvent {
+            at: 30,
+            event_ty: EnemyEventType::Nways {
+                n: 8,
+                wide_deg: 315.0,
+                center_deg: 90.0,
+                include_center: false,
+                bullet: BulletSpec::default().with_behavior("orbit"),
+            },
+        }]
+    }),
+];
+
+/// このステージの道中の敵。遊ぶときは同じものを書いた`static/stage1.txt`を読み込んで使い、
+/// これは読み込めなかったときと、テストやリプレイの確かめに使う
+fn stage_waves() -> Vec<Wave> {
+    stage! {
+        at 0 => spawn fairy at (250, 20)
+            with nways(4, 90 deg, 90 deg) after 120, aim after 130, aim after 135, aim after 140;
+    }
+}
+
+/// このステージの背景の変化。中ボスに向けて流れを緩め、中ボスのあとは速く流す。
+/// ボス戦の場への切り替えは、ボスが出たときに起こす。
+fn stage_background() -> Vec<BackgroundEvent> {
+    vec![
+        BackgroundEvent {
+            at: 0,
+            change: BackgroundChange::ScrollSpeed(per_tick(60.0)),
+        },
+        // 道中は花びらを、中ボスの間は雪を、ボス戦では雨を舞わせる
+        BackgroundEvent {
+            at: 0,
+            change: BackgroundChange::Weather(Some(Weather::Petals)),
+        },
+        BackgroundEvent {
+            at: MIDBOSS_ARRIVAL_FRAME - frames(60),
+            change: BackgroundChange::ScrollSpeed(per_tick(18.0)),
+        },
+        BackgroundEvent {
+            at: MIDBOSS_ARRIVAL_FRAME - frames(60),
+            change: BackgroundChange::Tint(Color::Enemy, 0.08),
+        },
+        BackgroundEvent {
+            at: MIDBOSS_ARRIVAL_FRAME - frames(60),
+            change: BackgroundChange::Weather(Some(Weather::Snow)),
+        },
+        BackgroundEvent {
+            at: MIDBOSS_ARRIVAL_FRAME + frames(900),
+            change: BackgroundChange::ScrollSpeed(per_tick(150.0)),
+        },
+        BackgroundEvent {
+            at: MIDBOSS_ARRIVAL_FRAME + frames(900),
+            change: BackgroundChange::Tint(Color::Enemy, 0.0),
+        },
+        BackgroundEvent {
+            at: MIDBOSS_ARRIVAL_FRAME + frames(900),
+            change: BackgroundChange::Weather(Some(Weather::Rain)),
+        },
+    ]
+}
+
+/// このステージの障害物。中ボスまでの道中に、左右に振って流す。
+fn stage_hazards() -> Vec<HazardSpawn> {
+    vec![
+        HazardSpawn {
+            at: frames(60),
+            kind: HazardKind::Crate,
+            x: 0.25,
+        },
+        HazardSpawn {
+            at: frames(120),
+            kind: HazardKind::Asteroid,
+            x: 0.7,
+        },
+        HazardSpawn {
+            at: frames(200),
+            kind: HazardKind::Crate,
+            x: 0.55,
+        },
+    ]
+}
+
+/// このステージの壁の動き。中ボスの途中で左右から壁を寄せて避ける幅を狭め、しばらくしたら引っ込める。
+fn stage_walls() -> Vec<WallShift> {
+    vec![
+        WallShift {
+            at: MIDBOSS_ARRIVAL_FRAME + frames(300),
+            left: 0.15,
+            right: 0.15,
+            frames: frames(90),
+        },
+        WallShift {
+            at: MIDBOSS_ARRIVAL_FRAME + frames(600),
+            left: 0.0,
+            right: 0.0,
+            frames: frames(90),
+        },
+    ]
+}
+
+/// このステージの中ボスのスペル
+fn midboss_spells() -> Result<Rc<[Spell]>> {
+    let shots = (0..8)
+        .map(|i| EnemyEvent {
+            at: 40 + 90 * i,
+            event_ty: EnemyEventType::AimedNways {
+                n: 3,
+                wide_deg: 30.0,
+                include_center: true,
+                lead: 0.5,
+                bullet: BulletSpec::default(),
+            },
+        })
+        .collect();
+    boss::builder()
+        .spell(
+            "Midboss",
+            hp(4000)
+                .timeout(15.0)
+                .resist(0.5, 1.0)
+                .drops(&[ItemKind::LifeFragment, ItemKind::LifeFragment])
+                .movement(BossMovement {
+                    speed: 1.0,
+                    drift: true,
+                    ..BossMovement::default()
+                })
+                .pattern(shots),
+        )
+        .build()
+}
+
+/// 中ボスを倒したときに必ず落とすアイテム。シールドはステージでここだけ出る
+const MIDBOSS_DROPS: [ItemKind; 5] = [
+    ItemKind::Point,
+    ItemKind::Bomb,
+    ItemKind::BombFragment,
+    ItemKind::Shield,
+    ItemKind::Point,
+];
+
+/// このステージのボスが順に使うスペル
+fn stage_spells() -> Result<Rc<[Spell]>> {
+    let fans = (0..9)
+        .map(|i| EnemyEvent {
+            at: 60 + 120 * i,
+            event_ty: EnemyEventType::AimedNways {
+                n: 7,
+                wide_deg: 90.0,
+                include_center: true,
+                lead: 0.0,
+                bullet: BulletSpec::default(),
+            },
+        })
+        .collect();
+    // 全方位の弾を撃っては止めて反転させるのを繰り返す。ボスは無敵なので逃げ切るしかない。
+    let time_stop = (0..6)
+        .flat_map(|i| {
+            let at = 30 + 240 * i;
+            [
+                EnemyEvent {
+                    at,
+                    event_ty: EnemyEventType::Nways {
+                        n: 16,
+                        wide_deg: 337.5,
+                        center_deg: 90.0 + 11.25 * i as f32,
+                        include_center: false,
+                        bullet: BulletSpec::default(),
+                    },
+                },
+                EnemyEvent {
+                    at: at + 60,
+                    event_ty: EnemyEventType::Level(LevelEvent::FreezeBullets(60)),
+                },
+                EnemyEvent {
+                    at: at + 90,
+                    event_ty: EnemyEventType::Level(LevelEvent::RotateAllBullets(180.0)),
+                },
+            ]
+        })
+        .collect();
+    boss::builder()
+        .spell(
+            "Fan Sign",
+            hp(6000)
+                .timeout(20.0)
+                .resist(0.5, 1.0)
+                .bonus(50_000)
+                .drops(&[ItemKind::Life])
+                .guide(0.5)
+                .movement(BossMovement {
+                    speed: 1.5,
+                    drift: true,
+                    reposition_every: Some(240),
+                    teleport: false,
+                })
+                .pattern(fans),
+        )
+        .spell(
+            "Survival Sign \"Stopped World\"",
+            survival()
+                .timeout(25.0)
+                .bonus(200_000)
+                .guide(1.0)
+                // 弾を止める間の動きを読みやすくするため、瞬間移動したあとは動かない
+                .movement(BossMovement {
+                    teleport: true,
+                    ..BossMovement::default()
+                })
+                .pattern(time_stop),
+        )
+        .build()
+}
+
+/// `name`のスペルを使うボスと、そのボスのスペルの中での番号
+fn find_spell(name: &str) -> Option<(BossKind, usize)> {
+    BossKind::ALL.into_iter().find_map(|kind| {
+        let spells = kind.spells().ok()?;
+        let index = spells.iter().position(|spell| spell.name == name)?;
+        Some((kind, index))
+    })
+}
+
+/// `name`のスペルがこのステージにあり、練習できるか
+pub fn can_practice_spell(name: &str) -> bool {
+    find_spell(name).is_some()
+}
+
+/// `at`から`kinds`のアイテムを横に散らばるように飛び出させる
+fn drop_items(items: &mut Vec<Item>, kinds: &[ItemKind], at: Point) {
+    let spread = (kinds.len() as f32 - 1.0) / 2.0;
+    for (i, &kind) in kinds.iter().enumerate() {
+        items.push(Item::new(kind, at, (i as f32 - spread) * 0.8, 2.0));
+    }
+}
+
+/// ステージの進行で`at`フレーム目にボスを出す予定
+#[derive(Clone)]
+struct Encounter {
+    at: u32,
+    kind: BossKind,
+}
+
+impl Timed for Encounter {
+    fn at(&self) -> u32 {
+        self.at
+    }
+}
+
+/// ステージのどこまで進んだか
+#[derive(Clone, Copy, PartialEq)]
+pub enum Progress {
+    Road,    // 道中
+    Midboss, // 中ボスまで
+    Boss,    // ボスまで
+    Cleared, // ステージをクリアした
+}
+
+impl Progress {
+    pub const ALL: [Progress; 4] = [
+        Progress::Road,
+        Progress::Midboss,
+        Progress::Boss,
+        Progress::Cleared,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Progress::Road => "Road",
+            Progress::Midboss => "Midboss",
+            Progress::Boss => "Boss",
+            Progress::Cleared => "Clear",
+        }
+    }
+}
+
+/// ボスの種類
+#[derive(Clone, Copy, PartialEq)]
+enum BossKind {
+    Midboss, // 時間切れになると倒されずに去っていき、倒せばアイテムを落とす
+    Boss,
+}
+
+impl BossKind {
+    const ALL: [BossKind; 2] = [BossKind::Midboss, BossKind::Boss];
+
+    fn name(self) -> &'static str {
+        match self {
+            BossKind::Midboss => "midboss",
+            BossKind::Boss => "boss",
+        }
+    }
+
+    /// ボスの登場時に見出しとして出す名前
+    fn title(self) -> &'static str {
+        match self {
+            BossKind::Midboss => "Gatekeeper",
+            BossKind::Boss => "Mistress of Fans",
+        }
+    }
+
+    fn spells(self) -> Result<Rc<[Spell]>> {
+        match self {
+            BossKind::Midboss => midboss_spells(),
+            BossKind::Boss => stage_spells(),
+        }
+    }
+
+    /// 倒したときに落とすアイテム。ボスを倒すとステージが終わるので何も落とさない。
+    fn drops(self) -> &'static [ItemKind] {
+        match self {
+            BossKind::Midboss => &MIDBOSS_DROPS,
+            BossKind::Boss => &[],
+        }
+    }
+}
+
+/// 出現中のボスの進み具合
+#[derive(Clone)]
+struct Boss {
+    kind: BossKind,
+    enemy: EntityId, // ボス本体の敵の番号
+    position: Point, // ボス本体の最後の位置（倒されたときにアイテムを落とす場所）
+    leaving: bool,   // 時間切れで画面外へ去っているところか
+    spells: Rc<[Spell]>,
+    spell: usize,   // 今のスペル番号
+    remaining: u16, // 今のスペルの残りフレーム数
+    failed: bool,   // 今のスペル中に被弾かボムをしたか
+    broken: bool,   // 今のスペルの体力を削りきったか
+    destination: Point,
+    teleport: Option<(Point, u16)>, // 瞬間移動の行き先と、終わるまでの残りフレーム数
+}
+
+impl Boss {
+    fn spell(&self) -> &Spell {
+        &self.spells[self.spell]
+    }
+
+    /// 今のスペルが始まってからの経過フレーム数
+    fn elapsed(&self) -> u16 {
+        self.spell().frames - self.remaining
+    }
+
+    /// 描画の不透明度。瞬間移動の前半で消えていき、後半で現れる。
+    fn alpha(&self) -> f32 {
+        match self.teleport {
+            Some((_, remaining)) => {
+                let half = BOSS_TELEPORT_FRAMES as f32 / 2.0;
+                (remaining as f32 - half).abs() / half
+            }
+            None => 1.0,
+        }
+    }
+}
+
+/// ステージの状態はすべてここに持つ（乱数の状態やフレーム数も含む）。
+/// 変化しないデータは`Rc`で共有しているので、丸ごと複製しても安い。
+#[derive(Clone)]
+pub struct Level {
+    frame: u32,           // ステージ開始からの経過フレーム
+    playfield: Playfield, // 壁が迫り出していれば、その分だけ狭まっている
+    players: Vec<Player>,
+    camping: Vec<CampWatch>, // プレイヤーごとの居座りの見張り（居座りを咎めるルールのときだけ使う）
+    score: u64,              // 全プレイヤーで共有するスコア
+    defeated_enemies: u32,   // まだ`take_defeated_enemies`で取り出していない撃破数
+    enemies: Vec<Enemy>,
+    ids: EntityIds,
+    bullets: Bullets,
+    bullet_freeze: u16, // 弾が止まっている残りフレーム数
+    shots: Vec<PlayerShot>,
+    boss: Option<Boss>,
+    script: Timeline<Encounter>, // ボスが出る予定
+    hazards: Vec<Hazard>,
+    hazard_script: Timeline<HazardSpawn>, // 障害物が出る予定
+    waves: Timeline<Wave>,                // 道中の敵が出る予定
+    walls: SlidingWalls,
+    wall_script: Timeline<WallShift>, // 壁が動く予定
+    items: Vec<Item>,
+    background: Background,
+    defeat: Option<Defeat>,        // ボスを倒してからクリアまでの演出
+    reached: Progress,             // ここまでに出たボス
+    deaths: u32,                   // 全プレイヤーの被弾の合計
+    cleared: bool,                 // ボスとの戦いを終えてステージをクリアしたか
+    cheated: bool,                 // チートを1度でも使ったか
+    practice_spell: Option<usize>, // スペルの練習で繰り返す、ボスのスペルの中での番号
+    modifiers: Modifiers,
+    events: Vec<GameEvent>, // このフレームで起きた出来事
+    dispatched: usize,      // `events`のうち、ステージの中で反映し終えた数
+    effects: Effects,
+    damage: DamageMeter, // 与えた傷の記録（練習モードで表示する）
+    chain: Chain,
+    banner: Option<Banner>, // 表示中のステージやボスの見出し
+    heatmap: Heatmap,
+    rng: Rng,
+    death_marker: Option<DeathMarker>, // 直前に被弾した弾の情報（練習モード用）
+    invincible: bool,                  // チートで被弾しない状態か
+    autoplay: bool,                    // チートで自機を操作役に動かさせているか
+    tweaks: Tweaks,                    // 開発用の画面で変えた調整値の倍率
+}
+
+/// 被弾した弾がどこから来たかの表示
+#[derive(Clone)]
+struct DeathMarker {
+    origin: Point,            // 弾が生成された位置
+    hit: Point,               // 被弾した位置
+    bullet: EntityId,         // 被弾した弾
+    source: Option<EntityId>, // 弾を撃った敵
+    remaining: u16,           // 表示を続ける残りフレーム数
+}
+
+impl Level {
+    /// `player_numbers`はこのステージで遊ぶプレイヤーの番号（0なら1P）
+    pub fn new(
+        seed: u64,
+        player_numbers: &[usize],
+        playfield: Playfield,
+        modifiers: Modifiers,
+        bomb: BombType,
+        shot: ShotType,
+    ) -> Self {
+        // プレイヤーは下の方に、横に100ずつ間をあけて並べる
+        let spread = (player_numbers.len() as f32 - 1.0) / 2.0;
+        let players = player_numbers
+            .iter()
+            .enumerate()
+            .map(|(i, &number)| {
+                let spawn = Point {
+                    x: playfield.center_x() + (i as f32 - spread) * 100.0,
+                    y: playfield.bottom() - 95.0,
+                };
+                Player::new(number, spawn, playfield, modifiers, bomb, shot)
+            })
+            .collect();
+        let enemy_spawn = Point {
+            x: playfield.center_x(),
+            y: playfield.top() + 20.0,
+        };
+
+        let mut level = Level {
+            frame: 0,
+            playfield,
+            camping: vec![CampWatch::default(); player_numbers.len()],
+            players,
+            score: 0,
+            defeated_enemies: 0,
+            enemies: Vec::new(),
+            ids: EntityIds::default(),
+            bullet_freeze: 0,
+            boss: None,
+            script: Timeline::new([Encounter {
+                at: MIDBOSS_ARRIVAL_FRAME,
+                kind: BossKind::Midboss,
+            }]),
+            hazards: Vec::new(),
+            hazard_script: Timeline::new(stage_hazards()),
+            waves: Timeline::new(stage_waves()),
+            walls: SlidingWalls::default(),
+            wall_script: Timeline::new(stage_walls()),
+            items: Vec::new(),
+            background: Background::new(stage_background()),
+            defeat: None,
+            reached: Progress::Road,
+            deaths: 0,
+            cleared: false,
+            cheated: false,
+            practice_spell: None,
+            modifiers,
+            events: Vec::new(),
+            dispatched: 0,
+            bullets: Bullets::default(),
+            shots: Vec::new(),
+            effects: Effects::new(),
+            damage: DamageMeter::default(),
+            chain: Chain::default(),
+            banner: Some(Banner::new(
+                format!("Stage {} \u{2014} {}", STAGE_NUMBER, STAGE_TITLE),
+                None,
+            )),
+            heatmap: Heatmap::new(),
+            rng: Rng::new(seed),
+            death_marker: None,
+            invincible: false,
+            autoplay: false,
+            tweaks: Tweaks::default(),
+        };
+        level.bullets.push(
+            Bullet::new(
+                enemy_spawn,
                 Vector::zero(),
                 vec![
                     BulletEvent {
@@ -64,115 +800,1929 @@ impl Level {
                         event_ty: BulletEventType::SetVel(Vector::new(-0.3, 0.0)),
                     },
                 ],
-            )],
+            ),
+            Vector::new(0.0, per_frame(4.0)),
+        );
+        level.register_bullets(0);
+        level
+    }
+
+    /// 道中の敵の出し方を、ステージのデータファイル（`static/stage1.txt`）から読んだものに差し替える。
+    /// 障害物や見出し、中ボスが出るフレームは元のステージのまま
+    pub fn with_stage(mut self, stage: &Pack) -> Self {
+        self.waves = Timeline::new(stage.waves());
+        self
+    }
+
+    /// 道中をコンテンツパックのものに差し替える。元の道中の敵と弾、障害物は出さず、パックの長さだけ道中を続けてから中ボスを出す。
+    pub fn with_pack(mut self, pack: &Pack) -> Self {
+        self.enemies.clear();
+        self.bullets.clear();
+        self.hazard_script = Timeline::new([]);
+        self.wall_script = Timeline::new([]);
+        self.script = Timeline::new([Encounter {
+            at: pack.road_frames(),
+            kind: BossKind::Midboss,
+        }]);
+        self.waves = Timeline::new(pack.waves());
+        self.banner = Some(Banner::new(pack.title().to_string(), None));
+        self
+    }
+
+    /// 道中と障害物を省き、`name`のスペルだけを使うボスがすぐに出るステージにする。
+    /// そのスペルは終わるたびに始めからやり直す。`name`のスペルがなければ元のステージのまま
+    pub fn with_spell_practice(mut self, name: &str) -> Self {
+        let Some((kind, index)) = find_spell(name) else {
+            return self;
+        };
+        self.enemies.clear();
+        self.bullets.clear();
+        self.hazard_script = Timeline::new([]);
+        self.wall_script = Timeline::new([]);
+        self.waves = Timeline::new([]);
+        self.script = Timeline::new([Encounter { at: 0, kind }]);
+        self.practice_spell = Some(index);
+        self
+    }
+
+    /// 道中もボスも障害物もなく、プレイヤーが被弾しないステージ
+    fn sandbox(seed: u64) -> Self {
+        let mut level = Level::new(
+            seed,
+            &[0],
+            Playfield::MAIN,
+            Modifiers::default(),
+            BombType::default(),
+            ShotType::default(),
+        );
+        level.enemies.clear();
+        level.bullets.clear();
+        level.script = Timeline::new([]);
+        level.waves = Timeline::new([]);
+        level.hazard_script = Timeline::new([]);
+        level.wall_script = Timeline::new([]);
+        level.invincible = true;
+        level
+    }
+
+    /// 名前付きパターン（`PATTERNS`の番号）を一度だけ撃つ敵を1体置いた、道中もボスも障害物もないステージ。
+    /// プレイヤーは動かさず被弾もしないので、パターンの動きだけを確かめられる。
+    pub fn pattern_sandbox(seed: u64, pattern: usize) -> Self {
+        let playfield = Playfield::MAIN;
+        let mut level = Self::sandbox(seed);
+        let (_, events) = PATTERNS[pattern];
+        let at = Point {
+            x: playfield.center_x(),
+            y: playfield.top() + 100.0,
+        };
+        level.add_enemy(Enemy::new(at, Vector::zero(), events()));
+        level
+    }
+
+    /// 性能を測るための、弾だけを出し続けるステージ。弾は`fill_bullets`で足す
+    pub fn benchmark(seed: u64) -> Self {
+        Self::sandbox(seed)
+    }
+
+    /// 場の弾が`count`発になるまで、動きの違う弾を順に混ぜてプレイフィールドの上の方から撃ち足す
+    pub fn fill_bullets(&mut self, count: usize) {
+        let specs = [
+            BulletSpec::default(),
+            BulletSpec::default().with_boundary(Boundary::Bounce(2)),
+            BulletSpec::default().with_boundary(Boundary::Wrap(1)),
+            BulletSpec::default().with_behavior("wobble"),
+            BulletSpec::new([
+                BulletEvent {
+                    at: 30,
+                    event_ty: BulletEventType::AccelTowards {
+                        target: AccelTarget::Player,
+                        magnitude: 0.03,
+                    },
+                },
+                BulletEvent {
+                    at: 31,
+                    event_ty: BulletEventType::SpeedClamp { min: 0.5, max: 2.5 },
+                },
+            ]),
+        ];
+        let from = self.bullets.len();
+        for i in from..count {
+            let pos = Point {
+                x: self
+                    .rng
+                    .range(self.playfield.left() + 20.0, self.playfield.right() - 20.0),
+                y: self.playfield.top() + self.rng.range(10.0, 120.0),
+            };
+            let vel = Vector::from_deg_and_mag(
+                self.rng.range(20.0, 160.0),
+                per_frame(self.rng.range(1.0, 3.0)),
+            );
+            self.bullets.push(specs[i % specs.len()].spawn(pos), vel);
+        }
+        self.register_bullets(from.min(self.bullets.len()));
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
+    /// 乱数だけを`seed`から始め直す（練習でスペルを別の乱数でやり直すため）
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    /// 敵に番号を付けてステージに出す
+    fn add_enemy(&mut self, mut enemy: Enemy) -> EntityId {
+        enemy.id = self.ids.allocate();
+        let id = enemy.id;
+        self.enemies.push(enemy);
+        id
+    }
+
+    /// `from`番目から後ろの、新しく出た弾に番号を付ける
+    fn register_bullets(&mut self, from: usize) {
+        for bullet in self.bullets.states[from..].iter_mut() {
+            bullet.id = self.ids.allocate();
+        }
+    }
+
+    /// 使い魔を親の位置に合わせて動かす。親がいなくなった使い魔は一緒に消える。
+    /// 使い魔は親より後に出るので、前から順に見ていけば親の位置はもう決まっている。
+    fn attach_familiars(&mut self) {
+        let mut positions = HashMap::new();
+        self.enemies.retain_mut(|enemy| {
+            if let Some(attachment) = &enemy.attachment {
+                let Some(parent) = positions.get(&attachment.parent) else {
+                    return false;
+                };
+                enemy.transform.pos = attachment.offset.position(parent, enemy.frame);
+            }
+            positions.insert(enemy.id, enemy.transform.pos);
+            true
+        });
+    }
+
+    /// ボスを出して最初のスペルを始める
+    fn start_boss(&mut self, kind: BossKind) {
+        // スペルの定義の誤りは、遊び始める前に`validate`で弾いている
+        let Ok(spells) = kind.spells() else {
+            return;
+        };
+        let spells = match self.practice_spell.and_then(|index| spells.get(index)) {
+            Some(spell) => Rc::from([spell.clone()]),
+            None => spells,
+        };
+        let at = Point {
+            x: self.playfield.center_x(),
+            y: self.playfield.top() + 140.0,
+        };
+        let enemy = self.add_enemy(Enemy::new(at, Vector::zero(), vec![]));
+        if kind == BossKind::Boss {
+            self.background.apply(BackgroundChange::EnterArena);
+        }
+        self.reached = match kind {
+            BossKind::Midboss => Progress::Midboss,
+            BossKind::Boss => Progress::Boss,
+        };
+        self.boss = Some(Boss {
+            kind,
+            enemy,
+            position: at,
+            leaving: false,
+            spells,
+            spell: 0,
+            remaining: 0,
+            failed: false,
+            broken: false,
+            destination: at,
+            teleport: None,
+        });
+        self.begin_spell(0);
+        // 中ボス（ステージの中ほど）とボスの登場をチェックポイントにする
+        self.events.push(GameEvent::CheckpointReached);
+        let spell = self.boss.as_ref().map(|boss| boss.spell().name);
+        self.banner = Some(Banner::new(kind.title().to_string(), spell));
+    }
+
+    /// `index`番目のスペルを始める。前のスペルの弾は消す。
+    fn begin_spell(&mut self, index: usize) {
+        let Some(boss) = &mut self.boss else {
+            return;
+        };
+        boss.spell = index;
+        boss.remaining = boss.spell().frames;
+        boss.failed = false;
+        boss.broken = false;
+        if boss.spell().movement.teleport {
+            let to = Self::boss_area_point(&self.playfield, &mut self.rng);
+            boss.teleport = Some((to, BOSS_TELEPORT_FRAMES));
+        }
+        // 最初のスペルはボスの登場の見出しと一緒に出す
+        if index > 0 {
+            self.banner = Some(Banner::new(boss.spell().name.to_string(), None));
+        }
+        self.events.push(GameEvent::SpellStarted {
+            name: boss.spell().name,
+        });
+        let events = boss.spell().events.clone();
+        if let Some(enemy) = self.enemies.iter_mut().find(|enemy| enemy.id == boss.enemy) {
+            enemy.start_events(events);
+            enemy.guide_frames = boss.spell().guide_frames;
+            enemy.resist(boss.spell().resistance);
+            enemy.health.remaining =
+                (boss.spell().health as f32 * self.tweaks.boss_health).round() as u32;
+        }
+        self.bullets.clear();
+    }
+
+    /// ボスが動き回る範囲（上の方）からランダムに選んだ位置
+    fn boss_area_point(playfield: &Playfield, rng: &mut Rng) -> Point {
+        Point {
+            x: rng.range(playfield.left() + 40.0, playfield.right() - 40.0),
+            y: rng.range(playfield.top() + 110.0, playfield.top() + 200.0),
+        }
+    }
+
+    /// スペルの設定に従ってボスを動かす
+    fn move_boss(&mut self, targets: &[Target]) {
+        let Some(boss) = &mut self.boss else {
+            return;
+        };
+        let Some(enemy) = self.enemies.iter_mut().find(|enemy| enemy.id == boss.enemy) else {
+            return;
+        };
+        let movement = boss.spell().movement;
+        boss.position = enemy.transform.pos;
+
+        if boss.leaving {
+            enemy.kinematics.vel = Vector::new(0.0, -BOSS_LEAVE_SPEED);
+            return;
+        }
+
+        if let Some((to, remaining)) = &mut boss.teleport {
+            *remaining -= 1;
+            if *remaining == BOSS_TELEPORT_FRAMES / 2 {
+                enemy.transform.pos = *to;
+                boss.destination = *to;
+            }
+            enemy.kinematics.vel = Vector::zero();
+            if *remaining == 0 {
+                boss.teleport = None;
+            }
+            return;
+        }
+
+        let elapsed = boss.elapsed();
+        let speed = per_frame(movement.speed);
+        let reposition = movement.reposition_every.is_some_and(|every| {
+            elapsed > 0 && elapsed.is_multiple_of(frames(every as u32) as u16)
+        });
+        let to_destination = Vector::between(&enemy.transform.pos, &boss.destination);
+        if reposition {
+            if let Some(target) = nearest_target(targets, &enemy.transform.pos) {
+                boss.destination.x = target
+                    .position
+                    .x
+                    .clamp(self.playfield.left() + 40.0, self.playfield.right() - 40.0);
+            }
+        } else if movement.drift && to_destination.length() <= speed {
+            boss.destination = Self::boss_area_point(&self.playfield, &mut self.rng);
+        }
+
+        let to_destination = Vector::between(&enemy.transform.pos, &boss.destination);
+        enemy.kinematics.vel = if to_destination.length() <= speed {
+            to_destination
+        } else {
+            to_destination.with_length(speed)
+        };
+    }
+
+    /// スペルの残り時間を進める。時間切れになったら、耐久スペルを被弾もボムもせずに
+    /// 乗り切っていればボーナスを与えて次のスペルに移り、最後のスペルならボスは去る。
+    /// 中ボスは最後のスペルが時間切れになると弾を消して画面外へ去る。
+    fn update_boss(&mut self) {
+        let Some(boss) = &mut self.boss else {
+            return;
+        };
+        let Some(enemy) = self.enemies.iter().find(|enemy| enemy.id == boss.enemy) else {
+            // 倒された
+            if !boss.leaving {
+                drop_items(&mut self.items, boss.kind.drops(), boss.position);
+            }
+            self.end_encounter();
+            return;
+        };
+        if boss.leaving {
+            if enemy.transform.pos.y < self.playfield.top() - ENEMY_RADIUS {
+                let enemy = boss.enemy;
+                self.enemies.retain(|e| e.id != enemy);
+                self.end_encounter();
+            }
+            return;
+        }
+        boss.remaining = boss.remaining.saturating_sub(1);
+        if boss.remaining > 0 {
+            return;
+        }
+        // 耐久スペルは最後まで避けきれば、それ以外は時間内に倒せば取得になる
+        if (boss.spell().survival || boss.broken) && !boss.failed {
+            self.events.push(GameEvent::SpellCaptured {
+                name: boss.spell().name,
+                bonus: boss.spell().bonus,
+            });
+            drop_items(&mut self.items, boss.spell().drops, boss.position);
+        }
+        let next = boss.spell + 1;
+        if next < boss.spells.len() {
+            self.begin_spell(next);
+        } else if self.practice_spell.is_some() {
+            self.begin_spell(0);
+        } else if boss.kind == BossKind::Midboss && !boss.broken {
+            boss.leaving = true;
+            let enemy = boss.enemy;
+            if let Some(enemy) = self.enemies.iter_mut().find(|e| e.id == enemy) {
+                enemy.start_events(Rc::new([]));
+            }
+            self.bullets.clear();
+        } else {
+            // 倒された中ボスは、この次のフレームでアイテムを落とす
+            let enemy = boss.enemy;
+            let defeated = boss.kind == BossKind::Midboss;
+            self.enemies.retain(|e| e.id != enemy);
+            self.bullets.clear();
+            if !defeated {
+                self.end_encounter();
+            }
+        }
+    }
+
+    /// ボスとの戦いを終え、中ボスのあとならボスの出番を決める
+    fn end_encounter(&mut self) {
+        let Some(boss) = self.boss.take() else {
+            return;
+        };
+        if boss.kind == BossKind::Midboss {
+            self.script.push(Encounter {
+                at: self.frame + BOSS_DELAY_FRAMES,
+                kind: BossKind::Boss,
+            });
+        }
+        if boss.kind == BossKind::Boss {
+            self.events.push(GameEvent::BossDefeated {
+                position: boss.position,
+            });
+        }
+    }
+
+    /// ボスを倒した演出を始める。残っている弾はすべて得点アイテムに変えて降らせる
+    fn start_defeat(&mut self, position: Point) {
+        for (_, pos) in self.bullets.iter() {
+            self.items.push(Item::new(ItemKind::Point, pos, 0.0, 1.0));
+        }
+        self.bullets.clear();
+        self.defeat = Some(Defeat::new(position));
+    }
+
+    /// クリアの演出を進め、終わったらステージをクリアにする
+    fn update_defeat(&mut self) {
+        let Some(defeat) = &mut self.defeat else {
+            return;
+        };
+        if !defeat.update() {
+            self.defeat = None;
+            self.cleared = true;
+        }
+    }
+
+    /// 時間の進み方の倍率。ボスを倒した直後のスローモーションの間だけ1より小さい
+    pub fn time_scale(&self) -> f32 {
+        self.defeat.as_ref().map_or(1.0, Defeat::time_scale)
+    }
+
+    /// 封印ボムの輪に入った敵弾を消し、その場所から得点アイテムを出す
+    fn seal_bullets(&mut self) {
+        for player in self.players.iter() {
+            let Some(radius) = player.seal_radius() else {
+                continue;
+            };
+            let center = player.position();
+            let items = &mut self.items;
+            self.bullets.retain(|_, motion| {
+                if Vector::between(&center, &motion.pos).length() > radius {
+                    return true;
+                }
+                items.push(Item::new(ItemKind::Point, motion.pos, 0.0, 1.0));
+                false
+            });
+        }
+    }
+
+    /// アイテムを動かし、プレイヤーが触れたら効果を与える。下に落ちきったものは消す。
+    fn update_items(&mut self) {
+        for item in self.items.iter_mut() {
+            item.fall();
+        }
+        component::integrate(&mut self.items, 1.0);
+        let contacts = collision::contacts(&self.bodies(&[Layer::Item, Layer::PlayerHurtbox]));
+        let mut collected = vec![false; self.items.len()];
+        for contact in contacts.iter() {
+            if collected[contact.first.index] {
+                continue;
+            }
+            collected[contact.first.index] = true;
+            self.events.push(GameEvent::ItemCollected {
+                player: contact.second.index,
+                kind: self.items[contact.first.index].kind(),
+            });
+        }
+        let bottom = self.playfield.bottom();
+        let mut collected = collected.into_iter();
+        self.items
+            .retain(|item| !collected.next().unwrap_or(false) && item.pos().y <= bottom);
+    }
+
+    /// `layers`のレイヤーの物の当たり判定を並べる。自機は被弾もアイテムを取ることもできる間だけ入れる
+    fn bodies(&self, layers: &[Layer]) -> Vec<Body> {
+        let mut bodies = Vec::new();
+        for &layer in layers {
+            match layer {
+                Layer::PlayerHurtbox => bodies.extend(
+                    self.players
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, player)| player.is_alive())
+                        .map(|(index, player)| player.hurtbox(index)),
+                ),
+                Layer::PlayerShot => bodies.extend(component::bodies(layer, &self.shots)),
+                Layer::EnemyBody => bodies.extend(component::bodies(layer, &self.enemies)),
+                Layer::EnemyShot => bodies.extend(self.bullets.bodies(layer)),
+                Layer::Item => bodies.extend(component::bodies(layer, &self.items)),
+                Layer::Terrain => bodies.extend(component::bodies(layer, &self.hazards)),
+            }
+        }
+        bodies
+    }
+
+    /// このフレームで起きた出来事（ステージの外で読み上げなどに使う）
+    pub fn events(&self) -> &[GameEvent] {
+        &self.events
+    }
+
+    /// ステージの中での番号が`index`のプレイヤーの、全体での番号（1Pなら0）
+    pub fn player_number(&self, index: usize) -> Option<usize> {
+        self.players.get(index).map(Player::number)
+    }
+
+    /// まだ反映していない出来事を、得点・演出・記録などに反映する
+    fn dispatch_events(&mut self) {
+        while let Some(&event) = self.events.get(self.dispatched) {
+            self.dispatched += 1;
+            match event {
+                GameEvent::EnemyDamaged(damage) => self.apply_damage(damage),
+                GameEvent::EnemyHurt { position, amount } => {
+                    self.damage.record(self.frame, position, amount)
+                }
+                GameEvent::EnemyKilled { by } => {
+                    self.defeated_enemies += 1;
+                    self.score += self.chain.kill() * self.risk_multiplier(by);
+                }
+                GameEvent::PlayerHit { position, .. } => {
+                    self.deaths += 1;
+                    self.chain.reset();
+                    self.heatmap.record_death(&position);
+                    self.effects.flash(0.8, 12);
+                    self.effects.shake(8.0, 20);
+                    self.fail_spell();
+                }
+                GameEvent::BombUsed { .. } => {
+                    self.effects.flash(0.6, 30);
+                    self.fail_spell();
+                    self.damage_hazards();
+                }
+                GameEvent::ItemCollected { player, kind } => match kind {
+                    ItemKind::Point => {
+                        self.score += POINT_ITEM_SCORE * self.risk_multiplier(Some(player))
+                    }
+                    ItemKind::Bomb => self.players[player].add_bomb(),
+                    ItemKind::Life => self.players[player].add_life(),
+                    ItemKind::BombFragment => self.players[player].add_bomb_fragment(),
+                    ItemKind::LifeFragment => self.players[player].add_life_fragment(),
+                    ItemKind::Shield => self.players[player].add_shield(),
+                },
+                GameEvent::ShieldBroken { .. } => self.effects.flash(0.3, 8),
+                GameEvent::BossDefeated { position } => {
+                    self.effects.flash(0.8, 30);
+                    self.effects.shake(10.0, 90);
+                    self.start_defeat(position);
+                }
+                GameEvent::SpellCaptured { bonus, .. } => {
+                    self.score += bonus;
+                    self.effects.flash(0.4, 20);
+                }
+                GameEvent::SpellStarted { .. } | GameEvent::CheckpointReached => {}
+            }
+        }
+    }
+
+    /// 得点を稼いだプレイヤーが、今リスクゾーンにいれば得点に掛ける倍率。プレイヤーによらない得点なら1
+    fn risk_multiplier(&self, player: Option<usize>) -> u64 {
+        player
+            .and_then(|player| self.players.get(player))
+            .map_or(1, |player| {
+                risk::multiplier(&self.playfield, &player.position())
+            })
+    }
+
+    /// 敵の体力を減らし、なくなったら倒す。
+    /// ボスは倒される代わりに今のスペルが終わり、耐久スペル中や去っていく間は傷を受けない。
+    /// 敵に傷を与える。敵の守りの分を減らしてから体力を減らし、
+    /// 実際に減った量を演出用に、倒したことを得点用に出来事として積む
+    fn apply_damage(&mut self, damage: Damage) {
+        let Damage {
+            enemy: id,
+            amount,
+            by,
+        } = damage;
+        let mut boss = self.boss.as_mut().filter(|boss| boss.enemy == id);
+        if boss
+            .as_ref()
+            .is_some_and(|boss| boss.spell().survival || boss.broken || boss.leaving)
+        {
+            return;
+        }
+        let Some(index) = self.enemies.iter().position(|enemy| enemy.id == id) else {
+            return;
+        };
+        let enemy = &mut self.enemies[index];
+        let amount = enemy
+            .resistance
+            .reduce(amount, enemy.frame - enemy.resisting_since);
+        let dealt = enemy.health.damage(amount);
+        self.events.push(GameEvent::EnemyHurt {
+            position: enemy.transform.pos,
+            amount: dealt,
+        });
+        if !enemy.health.is_depleted() {
+            return;
+        }
+        match &mut boss {
+            Some(boss) => {
+                boss.broken = true;
+                boss.remaining = 1;
+            }
+            None => {
+                self.enemies.remove(index);
+                self.events.push(GameEvent::EnemyKilled { by });
+            }
+        }
+    }
+
+    /// ボムで場の障害物すべてに傷を与え、壊れたものはアイテムを落とす
+    fn damage_hazards(&mut self) {
+        for hazard in component::damage_all(&mut self.hazards, 1) {
+            drop_items(&mut self.items, hazard.kind().drops(), hazard.pos());
+        }
+    }
+
+    /// 今のスペルのボーナスを取れなくする
+    fn fail_spell(&mut self) {
+        if let Some(boss) = &mut self.boss {
+            boss.failed = true;
+        }
+    }
+
+    /// ステージ全体にかかるイベントを起こす
+    fn trigger(&mut self, event: LevelEvent) {
+        match event {
+            LevelEvent::FreezeBullets(duration) => {
+                self.bullet_freeze = self.bullet_freeze.max(frames(duration as u32) as u16);
+            }
+            LevelEvent::RotateAllBullets(deg) => {
+                self.bullets
+                    .update_each(|_, motion| motion.vel = motion.vel.rotate(deg));
+            }
+        }
+    }
+
+    /// 場に出ている弾の位置。撃たれた順に並ぶ
+    #[cfg(test)]
+    pub fn bullet_positions(&self) -> impl Iterator<Item = Point> + '_ {
+        self.bullets.lanes.positions()
+    }
+
+    /// 場に出ている弾の位置、速度、当たり判定の半径。撃たれた順に並ぶ
+    pub fn bullet_motions(&self) -> impl Iterator<Item = (Point, Vector, f32)> + '_ {
+        let lanes = &self.bullets.lanes;
+        self.bullets
+            .states
+            .iter()
+            .zip(lanes.positions().zip(lanes.velocities()))
+            .map(|(bullet, (pos, vel))| (pos, vel, bullet.collider.radius))
+    }
+
+    pub fn is_invincible(&self) -> bool {
+        self.invincible
+    }
+
+    pub fn is_autoplay(&self) -> bool {
+        self.autoplay
+    }
+
+    /// 操作役が`index`番目のプレイヤーに与える操作
+    fn autopilot(&self, index: usize) -> Actions {
+        let bullets: Vec<_> = self.bullet_motions().collect();
+        let player = &self.players[index];
+        bot::choose(
+            player.position(),
+            player.bombs(),
+            &self.playfield,
+            &bullets,
+            &self.hazards,
+        )
+    }
+
+    pub fn apply(&mut self, command: Command) {
+        self.cheated = true;
+        match command {
+            Command::ToggleInvincible => self.invincible = !self.invincible,
+            Command::ToggleAutoPlay => self.autoplay = !self.autoplay,
+            Command::Tweak(tweaks) => self.tweaks = tweaks,
+            Command::FullPower => {
+                for player in self.players.iter_mut() {
+                    player.refill_bombs();
+                }
+            }
+            Command::SkipAhead => {
+                for enemy in self.enemies.iter_mut() {
+                    enemy.skip_to_next_event();
+                }
+            }
+            Command::KillAllEnemies => {
+                // 守りがあっても倒れるだけの傷を与える（ボスは今のスペルが終わる）
+                for enemy in self.enemies.iter() {
+                    self.events.push(GameEvent::EnemyDamaged(Damage {
+                        enemy: enemy.id,
+                        amount: u32::MAX,
+                        by: None,
+                    }));
+                }
+                self.dispatch_events();
+            }
+            Command::Spawn(pattern, at) => {
+                let (_, events) = PATTERNS[pattern];
+                self.add_enemy(Enemy::new(at, Vector::zero(), events()));
+            }
+        }
+    }
+
+    /// 敵と弾のイベント列に、実行時におかしな弾を作ってしまう誤りがないか確かめる。
+    /// 誤りがあれば、どのステージのどの敵・弾のどのイベントかをすべて並べたエラーを返す。
+    pub fn validate(&self, stage: &str) -> Result<()> {
+        let mut problems = Vec::new();
+        for (index, enemy) in self.enemies.iter().enumerate() {
+            validate_enemy_events(
+                &format!("{}: enemy {}", stage, index),
+                enemy.timeline.events(),
+                &mut problems,
+            );
+        }
+        for (index, (bullet, _)) in self.bullets.iter().enumerate() {
+            validate_events(
+                &format!("{}: bullet {}", stage, index),
+                bullet.timeline.events(),
+                BulletEvent::problem,
+                &mut problems,
+            );
+        }
+        let waves = self.waves.events();
+        for (index, wave) in waves.iter().enumerate() {
+            let owner = format!("{}: wave {}", stage, index);
+            if index > 0 && wave.at() < waves[index - 1].at() {
+                problems.push(format!("{} comes before the previous wave", owner));
+            }
+            validate_enemy_events(&owner, &wave.events(), &mut problems);
+        }
+        let background = stage_background();
+        for (index, event) in background.iter().enumerate() {
+            let owner = format!("{}: background event {}", stage, index);
+            if index > 0 && event.at < background[index - 1].at {
+                problems.push(format!("{} comes before the previous event", owner));
+            }
+            match event.change {
+                BackgroundChange::ScrollSpeed(speed) if !speed.is_finite() => {
+                    problems.push(format!("{} scroll speed {} is not a number", owner, speed));
+                }
+                BackgroundChange::Tint(_, alpha) if !(0.0..=1.0).contains(&alpha) => {
+                    problems.push(format!(
+                        "{} tint alpha must be between 0 and 1 (got {})",
+                        owner, alpha
+                    ));
+                }
+                _ => {}
+            }
+        }
+        for kind in [BossKind::Midboss, BossKind::Boss] {
+            if let Err(err) = kind.spells() {
+                problems.push(format!("{}: {}: {:#}", stage, kind.name(), err));
+            }
+        }
+        for (name, pattern) in PATTERNS.iter() {
+            validate_enemy_events(&format!("pattern \"{}\"", name), &pattern(), &mut problems);
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("Invalid stage data:\n{}", problems.join("\n")))
+        }
+    }
+
+    /// 論理座標の`point`を、プレイフィールドの左上からの座標と幅と高さに対する割合で表し、
+    /// 一番近い敵か弾の番号と状態を添えた文にする（開発用のビルドで、弾幕を置く位置を確かめるのに使う）
+    pub fn probe(&self, point: &Point) -> String {
+        let field = &self.playfield;
+        let x = point.x - field.left();
+        let y = point.y - field.top();
+        let mut text = format!(
+            "({:.1}, {:.1}) in playfield, ({:.3}, {:.3}) of its size",
+            x,
+            y,
+            x / field.rect().width,
+            y / field.rect().height
+        );
+        let distance = |pos: &Point| Vector::between(pos, point).length();
+        let enemy = self
+            .enemies
+            .iter()
+            .map(|enemy| (distance(&enemy.transform.pos), enemy))
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        let bullet = (0..self.bullets.len())
+            .map(|index| self.bullets.get(index))
+            .map(|(bullet, motion)| (distance(&motion.pos), (bullet, motion)))
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        match (enemy, bullet) {
+            (Some((gap, enemy)), bullet) if bullet.is_none_or(|(other, _)| gap <= other) => {
+                let boss = self.boss.as_ref().filter(|boss| boss.enemy == enemy.id);
+                text += &format!(
+                    "; nearest enemy {}{} at {:.1} away: frame {}, health {}, speed {:.2}",
+                    enemy.id.value(),
+                    boss.map_or(String::new(), |boss| format!(
+                        " (boss, spell {})",
+                        boss.spell().name
+                    )),
+                    gap,
+                    enemy.frame,
+                    enemy.health.remaining,
+                    enemy.kinematics.vel.length()
+                );
+            }
+            (_, Some((gap, (bullet, motion)))) => {
+                text += &format!(
+                    "; nearest bullet {} at {:.1} away: frame {}, speed {:.2}{}",
+                    bullet.id.value(),
+                    gap,
+                    bullet.frame,
+                    motion.vel.length(),
+                    bullet.source.map_or(String::new(), |source| format!(
+                        ", fired by enemy {}",
+                        source.value()
+                    ))
+                );
+            }
+            _ => {}
+        }
+        text
+    }
+
+    /// 場に出ている実体の数と、その入れ物が確保しているメモリの見積もり
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            bullets: self.bullets.len(),
+            enemies: self.enemies.len(),
+            items: self.items.len(),
+            events: self.events.len(),
+            bytes: self.bullets.capacity_bytes()
+                + self.shots.capacity() * size_of::<PlayerShot>()
+                + self.enemies.capacity() * size_of::<Enemy>()
+                + self.items.capacity() * size_of::<Item>()
+                + self.events.capacity() * size_of::<GameEvent>(),
+        }
+    }
+
+    /// 今の状態を保存する。`restore`で戻せる。
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            level: self.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.clone_from(&snapshot.level);
+    }
+
+    /// `snapshot`の時点に戻し、`inputs`を1フレームずつ与えて進め直す
+    #[allow(dead_code)]
+    pub fn resimulate(&mut self, snapshot: &Snapshot, inputs: &[[Actions; MAX_PLAYERS]]) {
+        self.restore(snapshot);
+        for actions in inputs {
+            self.update(actions);
+        }
+    }
+
+    /// ステージをクリアしたか
+    pub fn is_cleared(&self) -> bool {
+        self.cleared
+    }
+
+    pub fn stage_number(&self) -> u32 {
+        STAGE_NUMBER
+    }
+
+    pub fn progress(&self) -> Progress {
+        if self.cleared {
+            Progress::Cleared
+        } else {
+            self.reached
         }
     }
 
-    pub fn update(&mut self, keystate: &KeyState) {
-        let (vx, vy) = Player::calc_velocity(keystate);
-        self.player.update(vx, vy);
+    pub fn deaths(&self) -> u32 {
+        self.deaths
+    }
+
+    pub fn is_cheated(&self) -> bool {
+        self.cheated
+    }
+
+    pub fn score(&self) -> u64 {
+        self.score
+    }
+
+    /// 残っている残機とボムからクリアボーナスを計算してスコアに加え、その値を返す
+    pub fn award_clear_bonus(&mut self) -> u64 {
+        let bonus = self
+            .players
+            .iter()
+            .map(|player| {
+                player.lives() as u64 * CLEAR_BONUS_PER_LIFE
+                    + player.bombs() as u64 * CLEAR_BONUS_PER_BOMB
+            })
+            .sum();
+        self.score += bonus;
+        bonus
+    }
+
+    /// 被弾直後で、出どころの表示中かどうか
+    pub fn is_showing_death_marker(&self) -> bool {
+        self.death_marker.is_some()
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// 前回呼んでから倒された敵の数を返す（対戦モードで相手に弾を送るのに使う）
+    pub fn take_defeated_enemies(&mut self) -> u32 {
+        std::mem::take(&mut self.defeated_enemies)
+    }
+
+    /// 対戦相手から送られてきた弾を、プレイフィールドの上端からばらまく
+    pub fn receive_garbage(&mut self, count: u32) {
+        let from = self.bullets.len();
+        for _ in 0..count {
+            let pos = Point {
+                x: self
+                    .rng
+                    .range(self.playfield.left() + 20.0, self.playfield.right() - 20.0),
+                y: self.playfield.top() + 10.0,
+            };
+            let deg = self.rng.range(60.0, 120.0);
+            self.bullets.push(
+                Bullet::new(pos, Vector::zero(), vec![]),
+                Vector::from_deg_and_mag(deg, per_frame(2.0)),
+            );
+        }
+        self.register_bullets(from);
+    }
+
+    /// 全員の残機がなくなったか
+    pub fn is_game_over(&self) -> bool {
+        self.players.iter().all(|player| player.is_out())
+    }
+
+    /// このステージの色合い
+    pub fn theme(&self) -> Theme {
+        STAGE_THEME
+    }
 
-        if keystate.is_pressed("KeyJ") {
-            self.player.bomb();
+    pub fn heatmap(&self) -> &Heatmap {
+        &self.heatmap
+    }
+
+    /// 2つの環境で同じ状態になっているかを比べるための値（FNV-1a）
+    pub fn state_hash(&self) -> u64 {
+        let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+        let mut mix = |value: u64| {
+            hash ^= value;
+            hash = hash.wrapping_mul(0x0100_0000_01B3);
+        };
+        mix(self.frame as u64);
+        for player in &self.players {
+            let position = player.position();
+            mix(position.x.to_bits() as u64);
+            mix(position.y.to_bits() as u64);
+            mix(player.lives() as u64);
         }
+        for pos in self.bullets.lanes.positions() {
+            mix(pos.x.to_bits() as u64);
+            mix(pos.y.to_bits() as u64);
+        }
+        hash
+    }
 
+    /// `actions`はプレイヤーごとの操作
+    pub fn update(&mut self, actions: &[Actions]) {
+        self.frame += 1;
+        self.events.clear();
+        self.dispatched = 0;
+        while let Some(encounter) = self.script.advance(self.frame) {
+            self.start_boss(encounter.kind);
+        }
+        while let Some(spawn) = self.hazard_script.advance(self.frame) {
+            self.hazards.push(spawn.spawn(&self.playfield));
+        }
+        while let Some(shift) = self.wall_script.advance(self.frame) {
+            self.walls.shift(&shift, self.playfield.rect().width);
+        }
+        self.walls.update();
+        self.playfield = self.playfield.with_walls(self.walls.current());
+        let playfield = self.playfield;
+        for player in self.players.iter_mut() {
+            player.set_playfield(playfield);
+        }
+        component::integrate(&mut self.hazards, 1.0);
+        self.hazards.retain(|hazard| hazard.is_on_field(&playfield));
+        while let Some(wave) = self.waves.advance(self.frame) {
+            self.add_enemy(Enemy::new(
+                wave.position(&playfield),
+                wave.velocity(),
+                wave.events(),
+            ));
+        }
+
+        // 操作役に任せている間は、渡された操作の代わりに操作役の操作を使う
+        let autopilot: Vec<Actions>;
+        let actions = if self.autoplay {
+            autopilot = (0..self.players.len())
+                .map(|index| self.autopilot(index))
+                .collect();
+            &autopilot[..]
+        } else {
+            actions
+        };
+        for (index, (player, actions)) in self.players.iter_mut().zip(actions.iter()).enumerate() {
+            let (vx, vy) = Player::calc_velocity(actions);
+            player.update(vx * self.tweaks.player_speed, vy * self.tweaks.player_speed);
+
+            if actions.bomb && player.bomb() {
+                self.events.push(GameEvent::BombUsed { player: index });
+            }
+            if actions.shot && player.shoot() {
+                self.shots.extend(PlayerShot::volley(
+                    index,
+                    player.position(),
+                    player.shot_type(),
+                ));
+            }
+        }
+
+        let targets = self.targets();
+        for enemy in self.enemies.iter_mut() {
+            enemy.frame += 1;
+        }
+        component::integrate(&mut self.enemies, 1.0);
+        self.attach_familiars();
+        let mut deferred = Deferred::default();
+        let from = self.bullets.len();
         for enemy in self.enemies.iter_mut() {
-            enemy.update(&mut self.bullets, &self.player);
+            enemy.fire(&mut self.bullets, &mut deferred, &targets);
+        }
+        self.register_bullets(from);
+        for enemy in deferred.enemies {
+            self.add_enemy(enemy);
+        }
+        for event in deferred.level_events {
+            self.trigger(event);
+        }
+
+        // 弾が止められている間は、新しく撃たれた弾も含めてその場で止める
+        if self.bullet_freeze > 0 {
+            self.bullet_freeze -= 1;
+        } else {
+            let parents: HashMap<EntityId, Parent> = self
+                .enemies
+                .iter()
+                .map(|enemy| {
+                    let parent = Parent {
+                        pos: enemy.transform.pos,
+                        vel: enemy.kinematics.vel,
+                    };
+                    (enemy.id, parent)
+                })
+                .collect();
+            self.bullets
+                .update_each(|bullet, motion| bullet.steer(motion, &targets));
+            // 速さはルール変更や開発用の調整で変わる
+            self.bullets
+                .integrate(self.modifiers.bullet_speed() * self.tweaks.bullet_speed);
+            self.bullets
+                .update_each(|bullet, motion| bullet.react(motion, &parents));
+            // 振る舞いが位置を動かすことがあるので、中にあるかは進めた後の位置で確かめる
+            self.bullets.settle(&self.playfield);
+        }
+
+        if let Some(marker) = &mut self.death_marker {
+            marker.remaining -= 1;
+            if marker.remaining == 0 {
+                self.death_marker = None;
+            }
+        }
+
+        self.seal_bullets();
+
+        let playfield = self.playfield;
+        let enemies = self
+            .enemies
+            .iter()
+            .map(|enemy| (enemy.id, enemy.transform.pos))
+            .collect::<Vec<_>>();
+        for shot in self.shots.iter_mut() {
+            shot.steer(&enemies);
+        }
+        component::integrate(&mut self.shots, 1.0);
+        self.shots.retain_mut(|shot| shot.settle(&playfield));
+        let contacts = collision::contacts(&self.bodies(&[
+            Layer::PlayerShot,
+            Layer::EnemyBody,
+            Layer::PlayerHurtbox,
+            Layer::EnemyShot,
+            Layer::Terrain,
+        ]));
+
+        // 自機の弾は当たった敵に傷を与え、貫ける数を使い切ると消える
+        for contact in contacts
+            .iter()
+            .filter(|contact| contact.first.layer == Layer::PlayerShot)
+        {
+            let shot = &mut self.shots[contact.first.index];
+            let enemy = self.enemies[contact.second.index].id;
+            if let Some(amount) = shot.strike(enemy) {
+                self.events.push(GameEvent::EnemyDamaged(Damage {
+                    enemy,
+                    amount,
+                    by: Some(shot.owner),
+                }));
+            }
+        }
+        self.shots.retain(|shot| !shot.is_spent());
+
+        // プレイヤーごとに敵弾や障害物との衝突判定。シールドが防いだ被弾では、残機もボムも減らない
+        for (index, player) in self.players.iter_mut().enumerate() {
+            if player.is_protected() || self.invincible {
+                continue;
+            }
+            let position = player.position();
+            let touched = |layer: Layer| {
+                contacts.iter().find(|contact| {
+                    contact.first.layer == Layer::PlayerHurtbox
+                        && contact.first.index == index
+                        && contact.second.layer == layer
+                })
+            };
+            if let Some(contact) = touched(Layer::EnemyShot) {
+                let (bullet, motion) = self.bullets.get(contact.second.index);
+                if player.hit() {
+                    self.death_marker = Some(DeathMarker {
+                        origin: bullet.origin,
+                        hit: motion.pos,
+                        bullet: bullet.id,
+                        source: bullet.source,
+                        remaining: DEATH_MARKER_FRAMES,
+                    });
+                    self.events.push(GameEvent::PlayerHit {
+                        player: index,
+                        position,
+                    });
+                } else {
+                    self.events.push(GameEvent::ShieldBroken { player: index });
+                }
+                continue;
+            }
+            // 障害物は弾と違って、触れても消えずに流れていく
+            if touched(Layer::Terrain).is_some() {
+                if player.hit() {
+                    self.events.push(GameEvent::PlayerHit {
+                        player: index,
+                        position,
+                    });
+                } else {
+                    self.events.push(GameEvent::ShieldBroken { player: index });
+                }
+            }
+        }
+
+        for player in self.players.iter().filter(|player| !player.is_out()) {
+            self.heatmap.record(&player.position());
+        }
+        if self.modifiers.anti_camping {
+            self.punish_camping();
+        }
+        self.move_boss(&targets);
+        // スペルの時間切れより前に、このフレームの被弾やボムを反映しておく
+        self.dispatch_events();
+        self.update_defeat();
+        self.update_boss();
+        self.update_items();
+        self.dispatch_events();
+        self.background.update(self.frame, &self.playfield);
+        self.effects.update();
+        self.damage.update(self.frame);
+        self.chain.update();
+        if let Some(banner) = &mut self.banner {
+            if !banner.update() {
+                self.banner = None;
+            }
+        }
+    }
+
+    /// 狭い範囲に居座り続けたプレイヤーを、プレイフィールドの上の端から狙い撃つ
+    fn punish_camping(&mut self) {
+        let first = self.bullets.len();
+        for (player, watch) in self.players.iter().zip(self.camping.iter_mut()) {
+            if !player.is_alive() {
+                watch.reset();
+                continue;
+            }
+            let position = player.position();
+            if watch.update(position) {
+                let from = Point {
+                    x: position.x,
+                    y: self.playfield.top(),
+                };
+                let vel = Vector::between(&from, &position).with_length(PUNISHER_SPEED);
+                self.bullets
+                    .push(Bullet::new(from, Vector::zero(), vec![]), vel);
+            }
+        }
+        self.register_bullets(first);
+    }
+
+    /// 敵が狙う相手。残機の残っているプレイヤー全員
+    fn targets(&self) -> Vec<Target> {
+        self.players
+            .iter()
+            .filter(|player| !player.is_out())
+            .map(|player| Target {
+                position: player.position(),
+                velocity: player.velocity(),
+            })
+            .collect()
+    }
+
+    /// `device`は操作の案内に使う入力機器、`quality`は描画の品質、`layout`は表示の並べ方
+    pub fn draw(
+        &self,
+        renderer: &dyn Renderer,
+        settings: &Settings,
+        device: Device,
+        quality: Quality,
+        layout: Layout,
+    ) {
+        renderer.set_color(Color::Border);
+        renderer.draw_rect(self.playfield.rect());
+
+        self.effects.begin_draw(renderer, settings);
+        self.background.draw(renderer, &self.playfield, quality);
+        risk::draw(renderer, &self.playfield);
+        hazard::draw_walls(renderer, &self.playfield);
+        component::draw(renderer, &self.hazards);
+        for player in self.players.iter() {
+            player.draw(renderer);
+        }
+        component::draw(renderer, &self.shots);
+        for (player, watch) in self.players.iter().zip(self.camping.iter()) {
+            watch.draw(renderer, &player.position());
+        }
+        // 予告線は敵や弾の下に敷く
+        let targets = self.targets();
+        for enemy in self.enemies.iter() {
+            enemy.draw_guides(renderer, &self.playfield, &targets);
+        }
+        for enemy in self
+            .enemies
+            .iter()
+            .filter(|enemy| enemy.is_drawn(&self.playfield))
+        {
+            let boss = self.boss.as_ref().filter(|boss| boss.enemy == enemy.id);
+            if let Some(boss) = boss {
+                renderer.set_alpha(boss.alpha());
+            }
+            enemy.draw(renderer);
+            if boss.is_some() {
+                renderer.set_alpha(1.0);
+            }
+        }
+        if let Some(defeat) = &self.defeat {
+            defeat.draw(renderer);
+        }
+        component::draw(
+            renderer,
+            self.items
+                .iter()
+                .filter(|item| self.playfield.is_drawn(&item.pos(), ITEM_DRAW_RADIUS)),
+        );
+        let bullets = self
+            .bullets
+            .iter()
+            .filter(|(bullet, pos)| bullet.is_drawn(pos, &self.playfield) && self.is_visible(pos));
+        match quality {
+            Quality::High => bullets.for_each(|(bullet, pos)| bullet.draw(renderer, pos)),
+            Quality::Low => {
+                let bullets: Vec<(&Bullet, Point)> = bullets.collect();
+                let circles: Vec<(Point, f32)> = bullets
+                    .iter()
+                    .map(|(_, pos)| (*pos, BULLET_RADIUS))
+                    .collect();
+                renderer.set_color(Color::EnemyBullet);
+                renderer.draw_circles(&circles);
+                for (bullet, pos) in bullets {
+                    bullet.draw_telegraph(renderer, &pos);
+                }
+            }
+        }
+        if settings.show_hitboxes {
+            self.draw_hitboxes(renderer);
+        }
+        if settings.practice {
+            self.draw_death_marker(renderer, device);
+            self.damage.draw(renderer, &self.playfield, quality);
+        }
+        self.effects.end_draw(renderer, settings, &self.playfield);
+        if let Some(banner) = &self.banner {
+            banner.draw(renderer, &self.playfield);
+        }
+        self.draw_hud(renderer, device, layout);
+    }
+
+    /// プレイフィールドの上に残機・ボム・スコアを表示する。2人の場合は左右に分ける。
+    /// かけらを集めている途中なら、残機やボムのあとに集めた数を出す。シールドを持っていれば最後に「S」を付ける。
+    /// 縦向きではプレイフィールドの上にスコアだけを残し、残機・ボムとチェインの倍率は下の帯に移す。
+    fn draw_hud(&self, renderer: &dyn Renderer, device: Device, layout: Layout) {
+        let field = &self.playfield;
+        let (status_y, chain_at) = match layout {
+            Layout::Landscape => (
+                22.0,
+                Point {
+                    x: field.right() - 130.0,
+                    y: field.bottom() - 8.0,
+                },
+            ),
+            Layout::Portrait => (
+                field.bottom() + 24.0,
+                Point {
+                    x: field.center_x() - 65.0,
+                    y: field.bottom() + 50.0,
+                },
+            ),
+        };
+        renderer.set_color(Color::Text);
+        let fragments = |count: u8, needed: u8| {
+            if count > 0 {
+                format!("+{}/{}", count, needed)
+            } else {
+                String::new()
+            }
+        };
+        for (index, player) in self.players.iter().enumerate() {
+            let x = if index == 0 {
+                field.left()
+            } else {
+                field.right() - 150.0
+            };
+            renderer.draw_text(
+                &format!(
+                    "{}P L{}{} B{}{}{}",
+                    player.number() + 1,
+                    player.lives(),
+                    fragments(player.life_fragments(), LIFE_FRAGMENTS),
+                    player.bombs(),
+                    fragments(player.bomb_fragments(), BOMB_FRAGMENTS),
+                    if player.is_shielded() { " S" } else { "" }
+                ),
+                &Point { x, y: status_y },
+            );
+        }
+        renderer.draw_text_centered(
+            &format!("{}", self.score),
+            &Point {
+                x: field.center_x(),
+                y: 22.0,
+            },
+        );
+
+        // 2チェインから、今の倍率を右下に出す
+        if self.chain.count() >= 2 {
+            let tenths = self.chain.multiplier_tenths();
+            renderer.draw_text(
+                &format!(
+                    "{} chain x{}.{}",
+                    self.chain.count(),
+                    tenths / 10,
+                    tenths % 10
+                ),
+                &chain_at,
+            );
+        }
+
+        if let Some(boss) = self.boss.as_ref().filter(|boss| !boss.leaving) {
+            self.draw_spell(renderer, boss);
+        }
+
+        if self.is_game_over() {
+            renderer.set_color(Color::Background);
+            renderer.fill_rect(&Rect {
+                x: field.left() + 10.0,
+                y: field.center_y() - 45.0,
+                width: field.rect().width - 20.0,
+                height: 90.0,
+            });
+            renderer.set_color(Color::Text);
+            renderer.draw_text_centered(
+                "GAME OVER",
+                &Point {
+                    x: field.center_x(),
+                    y: field.center_y() - 5.0,
+                },
+            );
+            renderer.draw_text_centered(
+                &device.retry_hint(),
+                &Point {
+                    x: field.center_x(),
+                    y: field.center_y() + 25.0,
+                },
+            );
+        }
+    }
+
+    /// 弾が近くでしか見えないルールでは、生き残っているプレイヤーの近くの弾だけを描く
+    fn is_visible(&self, pos: &Point) -> bool {
+        !self.modifiers.near_sight
+            || self
+                .players
+                .iter()
+                .filter(|player| !player.is_out())
+                .any(|player| {
+                    let position = player.position();
+                    let dx = pos.x - position.x;
+                    let dy = pos.y - position.y;
+                    dx * dx + dy * dy <= NEAR_SIGHT_RADIUS * NEAR_SIGHT_RADIUS
+                })
+    }
+
+    /// スペル名と残り時間、ボーナスを取れるかを表示する。
+    /// 耐久スペルは時間切れまで耐えるしかないので、残り時間を大きく出す。
+    fn draw_spell(&self, renderer: &dyn Renderer, boss: &Boss) {
+        let field = &self.playfield;
+        let spell = boss.spell();
+        let seconds = boss.remaining.div_ceil(60);
+        renderer.set_color(Color::Text);
+        renderer.draw_text_centered(
+            spell.name,
+            &Point {
+                x: field.center_x(),
+                y: field.top() + 24.0,
+            },
+        );
+        if spell.survival {
+            renderer.draw_large_text_centered(
+                &seconds.to_string(),
+                &Point {
+                    x: field.center_x(),
+                    y: field.top() + 64.0,
+                },
+            );
+        } else {
+            renderer.draw_text_centered(
+                &seconds.to_string(),
+                &Point {
+                    x: field.center_x(),
+                    y: field.top() + 50.0,
+                },
+            );
+        }
+        // 時間切れで終わったふつうのスペルにはボーナスがないので、耐久スペルのときだけ出す
+        if spell.survival {
+            let bonus = if boss.failed {
+                "Bonus failed".to_string()
+            } else {
+                format!("Bonus {}", spell.bonus)
+            };
+            renderer.draw_text_centered(
+                &bonus,
+                &Point {
+                    x: field.center_x(),
+                    y: field.top() + 92.0,
+                },
+            );
+        }
+    }
+
+    /// 被弾した弾の軌跡と、それを撃った敵を強調する
+    fn draw_death_marker(&self, renderer: &dyn Renderer, device: Device) {
+        if let Some(marker) = &self.death_marker {
+            renderer.set_color(Color::Death);
+            renderer.draw_line(&marker.origin, &marker.hit);
+            renderer.draw_circle(&marker.hit, BULLET_RADIUS);
+            // 被弾した弾がまだ残っていれば、その後どこへ行ったかもつなげる
+            if let Some((_, pos)) = self
+                .bullets
+                .iter()
+                .find(|(bullet, _)| bullet.id == marker.bullet)
+            {
+                renderer.draw_line(&marker.hit, &pos);
+                renderer.draw_circle(&pos, BULLET_RADIUS);
+            }
+            if let Some(enemy) = marker
+                .source
+                .and_then(|id| self.enemies.iter().find(|enemy| enemy.id == id))
+            {
+                renderer.draw_circle(&enemy.transform.pos, enemy.collider.radius + 8.0);
+            }
+            renderer.set_color(Color::Text);
+            renderer.draw_text_centered(
+                &device.retry_hint(),
+                &Point {
+                    x: self.playfield.center_x(),
+                    y: self.playfield.bottom() - 30.0,
+                },
+            );
+        }
+    }
+
+    /// 全ての当たり判定と弾の速度ベクトルを描く
+    fn draw_hitboxes(&self, renderer: &dyn Renderer) {
+        renderer.set_color(Color::Debug);
+        for player in self.players.iter() {
+            player.draw_hitbox(renderer);
+        }
+        component::draw_hitboxes(renderer, &self.enemies);
+        self.bullets.draw_hitboxes(renderer);
+        component::draw_hitboxes(renderer, &self.shots);
+        component::draw_hitboxes(renderer, &self.items);
+        component::draw_hitboxes(renderer, &self.hazards);
+    }
+}
+
+/// 敵のイベント列と、それが撃つ弾のイベント列を確かめる
+pub fn validate_enemy_events(owner: &str, events: &[EnemyEvent], problems: &mut Vec<String>) {
+    validate_events(owner, events, EnemyEvent::problem, problems);
+    for (index, event) in events.iter().enumerate() {
+        if let EnemyEventType::Nways { bullet, .. }
+        | EnemyEventType::AimedNways { bullet, .. }
+        | EnemyEventType::Shot { bullet, .. } = &event.event_ty
+        {
+            validate_events(
+                &format!("{}, event {} bullets", owner, index),
+                &bullet.events,
+                BulletEvent::problem,
+                problems,
+            );
+            if let Some(name) = bullet.behavior.as_deref() {
+                if behavior::find(name).is_none() {
+                    problems.push(format!(
+                        "{}, event {}: unknown bullet behavior \"{}\" (known: {})",
+                        owner,
+                        index,
+                        name,
+                        behavior::NAMES.join(", ")
+                    ));
+                }
+            }
+        }
+        if let EnemyEventType::SummonFamiliars(familiars) = &event.event_ty {
+            for (familiar, spec) in familiars.iter().enumerate() {
+                validate_enemy_events(
+                    &format!("{}, event {} familiar {}", owner, index, familiar),
+                    &spec.events,
+                    problems,
+                );
+            }
         }
+        if let EnemyEventType::SpawnEnemy { definition, .. } = &event.event_ty {
+            validate_enemy_events(
+                &format!("{}, event {} spawned enemy", owner, index),
+                &definition.events,
+                problems,
+            );
+        }
+    }
+}
 
-        for bullet in self.bullets.iter_mut() {
-            bullet.update();
+/// イベント列の誤りを`problems`に書き足す。
+/// `at`は1以上で、前のイベントより前であってはならない（同じフレームのイベントはまとめて起こる）。
+fn validate_events<E: Timed>(
+    owner: &str,
+    events: &[E],
+    problem: fn(&E) -> Option<String>,
+    problems: &mut Vec<String>,
+) {
+    let mut previous = 0;
+    for (index, event) in events.iter().enumerate() {
+        let at = event.at();
+        let context = format!("{}, event {} (at {})", owner, index, at);
+        if at == 0 {
+            problems.push(format!("{}: `at` must be 1 or later", context));
+        } else if at < previous {
+            problems.push(format!(
+                "{}: `at` must not be earlier than the previous event (at {})",
+                context, previous
+            ));
         }
+        if let Some(problem) = problem(event) {
+            problems.push(format!("{}: {}", context, problem));
+        }
+        previous = previous.max(at);
+    }
+}
+
+/// `Level::memory_usage`の結果（デバッグ表示用）
+#[derive(Clone, Copy)]
+pub struct MemoryUsage {
+    pub bullets: usize,
+    pub enemies: usize,
+    pub items: usize,
+    pub events: usize, // まだ取り出されていない出来事
+    pub bytes: usize,  // 弾・自機の弾・敵・アイテム・出来事の入れ物が確保している大きさ
+}
+
+/// `Level::snapshot`で保存したステージの状態
+#[derive(Clone)]
+pub struct Snapshot {
+    level: Level,
+}
+
+/// 場に出ている弾。位置と速度は成分ごとの配列（`Lanes`）に並べたままフレームをまたいで持ち、
+/// それ以外の状態は同じ順で`Bullet`の列に並べる
+#[derive(Clone, Default)]
+struct Bullets {
+    states: Vec<Bullet>,
+    lanes: Lanes,
+    inside: Vec<bool>, // プレイフィールドの中にあるかの判定。毎フレーム確保し直さないように使い回す
+}
+
+impl Bullets {
+    fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// `bullet`を撃ち出した位置に、速度`vel`で出す
+    fn push(&mut self, bullet: Bullet, vel: Vector) {
+        self.lanes.push(Motion {
+            pos: bullet.origin,
+            vel,
+        });
+        self.states.push(bullet);
+    }
+
+    fn clear(&mut self) {
+        self.states.clear();
+        self.lanes.clear();
+    }
+
+    /// 撃たれた順に、弾とその位置
+    fn iter(&self) -> impl Iterator<Item = (&Bullet, Point)> {
+        self.states.iter().zip(self.lanes.positions())
+    }
+
+    fn get(&self, index: usize) -> (&Bullet, Motion) {
+        (&self.states[index], self.lanes.get(index))
+    }
+
+    /// 弾を1発ずつ、位置と速度と一緒に書き換える
+    fn update_each(&mut self, update: impl FnMut(&mut Bullet, &mut Motion)) {
+        self.lanes.update_with(&mut self.states, update);
+    }
+
+    /// `keep`が`false`を返した弾を取り除く
+    fn retain(&mut self, mut keep: impl FnMut(&mut Bullet, &mut Motion) -> bool) {
+        self.inside.clear();
+        let inside = &mut self.inside;
+        self.lanes.update_with(&mut self.states, |bullet, motion| {
+            inside.push(keep(bullet, motion))
+        });
+        self.compact();
+    }
+
+    /// `inside`が`false`の弾を取り除く
+    fn compact(&mut self) {
+        if self.inside.iter().all(|&inside| inside) {
+            return;
+        }
+        let mut keep = self.inside.iter();
+        self.states.retain(|_| *keep.next().unwrap_or(&false));
+        self.lanes.retain(&self.inside);
+    }
+
+    /// 速さを`speed`倍して位置をまとめて進める（`simd128`を有効にしたwasmのビルドでは4発ずつ）
+    fn integrate(&mut self, speed: f32) {
+        self.lanes.integrate(speed);
+    }
+
+    /// 画面外に出た弾は、弾ごとの設定に従って跳ね返るか反対側に回り込み、それ以外は消す。
+    /// 中にあるかはまとめて確かめ、外に出た弾だけを1発ずつ処理する
+    fn settle(&mut self, playfield: &Playfield) {
+        self.lanes.inside(playfield, &mut self.inside);
+        for (index, bullet) in self.states.iter_mut().enumerate() {
+            if self.inside[index] {
+                continue;
+            }
+            let mut motion = self.lanes.get(index);
+            self.inside[index] = bullet.handle_boundary(&mut motion, playfield);
+            self.lanes.set(index, motion);
+        }
+        self.compact();
+    }
 
-        // 画面外に飛んで行った弾を消す
-        self.bullets.retain(|bullet| bullet.in_canvas());
+    /// `layer`のレイヤーに置く当たり判定。`Body::index`は撃たれた順での番号になる
+    fn bodies(&self, layer: Layer) -> impl Iterator<Item = Body> + '_ {
+        self.iter()
+            .enumerate()
+            .map(move |(index, (bullet, at))| Body {
+                layer,
+                index,
+                at,
+                shape: Shape::Circle(bullet.collider),
+            })
+    }
 
-        // プレイヤーと敵弾の衝突判定
-        for bullet in self.bullets.iter() {
-            if self.player.is_collided(bullet) {
-                self.player.hit();
-            }
+    /// 当たり判定の円と速度ベクトルを描く（デバッグ表示）
+    fn draw_hitboxes(&self, renderer: &dyn Renderer) {
+        for (index, bullet) in self.states.iter().enumerate() {
+            let motion = self.lanes.get(index);
+            component::draw_hitbox(
+                renderer,
+                &Transform { pos: motion.pos },
+                &Shape::Circle(bullet.collider),
+                Some(motion.vel),
+            );
         }
     }
 
-    pub fn draw(&self, renderer: &Renderer) {
-        self.player.draw(renderer);
-        for enemy in self.enemies.iter() {
-            enemy.draw(renderer);
-        }
-        for bullet in self.bullets.iter() {
-            bullet.draw(renderer);
-        }
+    /// 確保しているメモリの大きさ
+    fn capacity_bytes(&self) -> usize {
+        self.states.capacity() * size_of::<Bullet>()
+            + self.lanes.capacity_bytes()
+            + self.inside.capacity()
     }
 }
 
 #[derive(Clone)]
 pub struct Bullet {
-    frame: u16,                // 弾が生成されてからの経過フレーム
-    pos: Point,                // 位置
-    vel: Vector,               // 速度
-    acc: Vector,               // 加速度
-    events: Vec<BulletEvent>,  // 弾に起こる変化の列（タイミング、イベント）
-    next_event: Option<usize>, // 次に起こるイベント番号
+    frame: u16,               // 弾が生成されてからの経過フレーム
+    origin: Point,            // 生成された位置
+    id: EntityId,             // ステージに出たときに付く番号
+    source: Option<EntityId>, // 弾を撃った敵
+    acc: Vector,              // 毎フレーム速度に足す加速度
+    collider: Collider,
+    phase: BulletPhase,
+    boundary: Boundary,
+    pull: Option<(AccelTarget, f32)>, // 毎フレーム引き寄せられる先と加速度の大きさ
+    behavior: Option<Rc<dyn BulletBehavior>>, // イベント列では書けない動き
+    speed_limit: Option<(f32, f32)>,  // 速さの下限と上限
+    timeline: Timeline<BulletEvent>,  // 弾に起こる変化の列
+    renderable: Renderable,
 }
 
 impl Bullet {
-    pub fn new(pos: Point, vel: Vector, acc: Vector, events: Vec<BulletEvent>) -> Self {
+    /// `pos`から撃ち出す弾。速度は`Bullets::push`で場に出すときに与える
+    pub fn new(pos: Point, acc: Vector, events: impl Into<Rc<[BulletEvent]>>) -> Self {
         Self {
             frame: 0,
-            pos,
-            vel,
+            origin: pos,
+            id: EntityId::UNASSIGNED,
+            source: None,
             acc,
-            next_event: if events.is_empty() { None } else { Some(0) },
-            events,
+            collider: Collider {
+                radius: BULLET_RADIUS,
+            },
+            phase: BulletPhase::Moving,
+            boundary: Boundary::Despawn,
+            pull: None,
+            behavior: None,
+            speed_limit: None,
+            timeline: Timeline::new(events),
+            renderable: Renderable::circle(Color::EnemyBullet, BULLET_RADIUS),
         }
     }
 
-    pub fn update(&mut self) {
-        self.frame += 1;
-
-        self.vel += self.acc;
-
-        self.pos += self.vel;
+    /// 弾を撃った敵を記録する
+    fn with_source(mut self, source: EntityId) -> Self {
+        self.source = Some(source);
+        self
+    }
 
-        if let Some(next_event) = self.next_event {
-            let event = &self.events[next_event];
+    /// 1フレームのうち、位置を進める前の処理。速度を決める
+    fn steer(&mut self, motion: &mut Motion, targets: &[Target]) {
+        self.frame += 1;
 
-            if event.at != self.frame {
-                return;
+        match self.phase {
+            BulletPhase::Moving => self.accelerate(motion, targets),
+            BulletPhase::Stopping {
+                remaining,
+                total,
+                from,
+                wait,
+                launch,
+            } => {
+                // 残りフレームに比例して遅くなり、0になったら止まって待つ
+                let remaining = remaining - 1;
+                motion.vel = from * (remaining as f32 / total as f32);
+                self.phase = if remaining == 0 {
+                    BulletPhase::Waiting {
+                        remaining: wait,
+                        heading: from,
+                        launch,
+                    }
+                } else {
+                    BulletPhase::Stopping {
+                        remaining,
+                        total,
+                        from,
+                        wait,
+                        launch,
+                    }
+                };
+            }
+            BulletPhase::Waiting {
+                remaining,
+                heading,
+                launch,
+            } => {
+                motion.vel = Vector::zero();
+                self.phase = if remaining <= 1 {
+                    motion.vel = launch.velocity(&heading, &motion.pos, targets);
+                    BulletPhase::Moving
+                } else {
+                    BulletPhase::Waiting {
+                        remaining: remaining - 1,
+                        heading,
+                        launch,
+                    }
+                };
             }
+        }
+    }
+
+    /// 1フレームのうち、位置を進めた後の処理。振る舞いとイベント列を進める。
+    /// `parents`は敵の番号ごとの今の動き（振る舞いが撃った敵を参照するのに使う）
+    fn react(&mut self, motion: &mut Motion, parents: &HashMap<EntityId, Parent>) {
+        if let Some(behavior) = &self.behavior {
+            behavior.on_tick(&mut BulletCtx {
+                frame: self.frame,
+                pos: &mut motion.pos,
+                vel: &mut motion.vel,
+                parent: self.source.and_then(|id| parents.get(&id).copied()),
+            });
+        }
 
+        while let Some(event) = self.timeline.advance(self.frame as u32) {
             match event.event_ty {
                 BulletEventType::RotateVel(deg) => {
-                    self.vel = self.vel.rotate(deg);
+                    motion.vel = motion.vel.rotate(deg);
                 }
                 BulletEventType::SetVel(vel) => {
-                    self.vel = vel.clone();
+                    motion.vel = vel * per_frame(1.0);
                 }
                 BulletEventType::SetAcc(acc) => {
-                    self.acc = acc.clone();
+                    self.acc = acc * per_frame_squared(1.0);
+                }
+                BulletEventType::AccelTowards { target, magnitude } => {
+                    self.pull = Some((target, per_frame_squared(magnitude)));
+                }
+                BulletEventType::SpeedClamp { min, max } => {
+                    self.speed_limit = Some((per_frame(min), per_frame(max)));
+                }
+                BulletEventType::FreezeThenLaunch {
+                    stop_frames,
+                    wait_frames,
+                    launch,
+                } => {
+                    let stop_frames = frames(stop_frames as u32) as u16;
+                    let wait_frames = frames(wait_frames as u32) as u16;
+                    self.phase = if stop_frames == 0 {
+                        let heading = motion.vel;
+                        motion.vel = Vector::zero();
+                        BulletPhase::Waiting {
+                            remaining: wait_frames,
+                            heading,
+                            launch,
+                        }
+                    } else {
+                        BulletPhase::Stopping {
+                            remaining: stop_frames,
+                            total: stop_frames,
+                            from: motion.vel,
+                            wait: wait_frames,
+                            launch,
+                        }
+                    };
                 }
             }
+        }
+    }
 
-            self.next_event = if next_event == self.events.len() - 1 {
-                None
-            } else {
-                Some(next_event + 1)
+    /// 加速度と引き寄せを速度に加え、速さを制限する
+    fn accelerate(&mut self, motion: &mut Motion, targets: &[Target]) {
+        motion.vel += self.acc;
+        if let Some((target, magnitude)) = &self.pull {
+            let point = match target {
+                AccelTarget::Point(point) => Some(*point),
+                AccelTarget::Player => nearest_target(targets, &motion.pos).map(|t| t.position),
             };
+            if let Some(point) = point {
+                motion.vel += Vector::between(&motion.pos, &point).with_length(*magnitude);
+            }
+        }
+        // 加速した後に速さを制限する
+        if let Some((min, max)) = self.speed_limit {
+            let speed = motion.vel.length();
+            motion.vel = motion.vel.with_length(speed.clamp(min, max));
+        }
+    }
+
+    /// 撃つときに決めた、画面の端での振る舞い
+    fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    /// プレイフィールドの外に出ていたら`boundary`に従って処理し、弾を残すかを返す
+    fn handle_boundary(&mut self, motion: &mut Motion, playfield: &Playfield) -> bool {
+        if playfield.contains(&motion.pos) {
+            return true;
+        }
+        match &mut self.boundary {
+            Boundary::Despawn => false,
+            Boundary::Bounce(0) | Boundary::Wrap(0) => false,
+            Boundary::Bounce(remaining) => {
+                *remaining -= 1;
+                if motion.pos.x < playfield.open_left() || motion.pos.x > playfield.open_right() {
+                    motion.vel = motion.vel.flip_x();
+                }
+                if motion.pos.y < playfield.top() || motion.pos.y > playfield.bottom() {
+                    motion.vel = motion.vel.flip_y();
+                }
+                motion.pos = playfield.clamp(motion.pos);
+                true
+            }
+            Boundary::Wrap(remaining) => {
+                *remaining -= 1;
+                // 壁が迫り出している間は、壁の内側の端から反対側の端へ回り込む
+                let open = playfield.open_rect();
+                let width = open.width;
+                let height = open.height;
+                if motion.pos.x < playfield.open_left() {
+                    motion.pos.x += width;
+                } else if motion.pos.x > playfield.open_right() {
+                    motion.pos.x -= width;
+                }
+                if motion.pos.y < playfield.top() {
+                    motion.pos.y += height;
+                } else if motion.pos.y > playfield.bottom() {
+                    motion.pos.y -= height;
+                }
+                true
+            }
         }
     }
 
-    pub fn draw(&self, renderer: &Renderer) {
-        renderer.set_color("black");
-        renderer.draw_circle(&self.pos, 10.0);
+    /// `pos`にあるとき、待っている間の輪を含めて画面に見える位置にあるか
+    fn is_drawn(&self, pos: &Point, playfield: &Playfield) -> bool {
+        playfield.is_drawn(pos, self.collider.radius + 5.0)
     }
 
-    pub fn in_canvas(&self) -> bool {
-        self.pos.x <= 550.0 && self.pos.x >= 50.0 && self.pos.y <= 570.0 && self.pos.y >= 30.0
+    /// `pos`にあるこの弾を描く
+    fn draw(&self, renderer: &dyn Renderer, pos: Point) {
+        self.renderable.draw(renderer, &Transform { pos });
+        self.draw_telegraph(renderer, &pos);
     }
 
-    pub fn pos(&self) -> Point {
-        self.pos
+    /// 止まって待っている弾は、脈打つ輪で動き出すことを知らせる
+    fn draw_telegraph(&self, renderer: &dyn Renderer, pos: &Point) {
+        if let BulletPhase::Waiting { .. } = self.phase {
+            renderer.set_color(Color::Telegraph);
+            let pulse = (self.frame as f32 * 0.4).sin();
+            renderer.draw_circle(pos, BULLET_RADIUS + 3.0 + 2.0 * pulse);
+        }
     }
 }
 
@@ -181,98 +2731,1014 @@ pub enum BulletEventType {
     RotateVel(f32),
     SetVel(Vector),
     SetAcc(Vector),
+    // これ以降、毎フレーム`target`に向かって大きさ`magnitude`の加速をする（負なら遠ざかる）
+    AccelTowards {
+        target: AccelTarget,
+        magnitude: f32,
+    },
+    // これ以降、速さを`min`以上`max`以下に保つ
+    SpeedClamp {
+        min: f32,
+        max: f32,
+    },
+    // `stop_frames`かけて止まり、`wait_frames`待ってから`launch`の向きと速さで動き出す
+    FreezeThenLaunch {
+        stop_frames: u16,
+        wait_frames: u16,
+        launch: Launch,
+    },
+}
+
+/// プレイフィールドの端に来た弾の振る舞い
+#[derive(Clone, Copy, Default)]
+pub enum Boundary {
+    #[default]
+    Despawn, // 消える
+    Bounce(u8), // 壁で跳ね返る（残り回数）
+    Wrap(u8),   // 反対側の端に回り込む（残り回数）
+}
+
+/// 敵が撃つ弾の設定
+#[derive(Clone, Default)]
+pub struct BulletSpec {
+    events: Rc<[BulletEvent]>, // 撃った弾それぞれに起こる変化の列
+    boundary: Boundary,
+    behavior: Option<Rc<str>>, // 弾に付ける振る舞いの名前（`behavior::NAMES`のどれか）
+}
+
+impl BulletSpec {
+    fn new(events: impl Into<Rc<[BulletEvent]>>) -> Self {
+        Self {
+            events: events.into(),
+            boundary: Boundary::Despawn,
+            behavior: None,
+        }
+    }
+
+    fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    fn with_behavior(mut self, name: &str) -> Self {
+        self.behavior = Some(name.into());
+        self
+    }
+
+    /// `pos`から`vel`で撃つ弾を作る
+    /// `pos`から撃ち出す弾。速度は`Bullets::push`で与える
+    fn spawn(&self, pos: Point) -> Bullet {
+        let mut bullet =
+            Bullet::new(pos, Vector::zero(), self.events.clone()).with_boundary(self.boundary);
+        bullet.behavior = self.behavior.as_deref().and_then(behavior::find);
+        bullet
+    }
+}
+
+/// 止まって待つ弾の状態
+#[derive(Clone, Copy)]
+enum BulletPhase {
+    Moving,
+    // 止まるまで減速している
+    Stopping {
+        remaining: u16,
+        total: u16,
+        from: Vector, // 減速し始めたときの速度
+        wait: u16,
+        launch: Launch,
+    },
+    // 止まって待っている
+    Waiting {
+        remaining: u16,
+        heading: Vector, // 止まる前の速度
+        launch: Launch,
+    },
 }
 
+/// 止まっていた弾が動き出すときの向きと速さ
+#[derive(Clone, Copy)]
+pub struct Launch {
+    direction: LaunchDirection,
+    speed: f32,
+}
+
+#[derive(Clone, Copy)]
+pub enum LaunchDirection {
+    Keep,     // 止まる前と同じ向き
+    AtPlayer, // 動き出すときに一番近いプレイヤーがいる向き
+    #[allow(dead_code)]
+    Deg(f32),
+}
+
+impl Launch {
+    fn velocity(&self, heading: &Vector, pos: &Point, targets: &[Target]) -> Vector {
+        let speed = per_frame(self.speed);
+        let down = Vector::from_deg_and_mag(90.0, speed);
+        match self.direction {
+            LaunchDirection::Keep if heading.length() > 0.0 => heading.with_length(speed),
+            LaunchDirection::Keep => down,
+            LaunchDirection::AtPlayer => nearest_target(targets, pos).map_or(down, |target| {
+                Vector::between(pos, &target.position).with_length(speed)
+            }),
+            LaunchDirection::Deg(deg) => Vector::from_deg_and_mag(deg, speed),
+        }
+    }
+}
+
+/// 弾を引き寄せる先
+#[derive(Clone, Copy)]
+pub enum AccelTarget {
+    Point(Point),
+    Player, // 一番近いプレイヤー
+}
+
+/// 弾が撃たれてから`at`フレーム目に起こすこと。
+/// フレーム数や速さ、加速度は基準の速さ（`STANDARD_TICK_RATE`）で書き、起こすときにこのビルドの速さに直す
 #[derive(Clone)]
 pub struct BulletEvent {
     at: u16,
     event_ty: BulletEventType,
 }
 
+impl Timed for BulletEvent {
+    fn at(&self) -> u32 {
+        frames(self.at as u32)
+    }
+}
+
+impl BulletEvent {
+    fn problem(&self) -> Option<String> {
+        match &self.event_ty {
+            BulletEventType::RotateVel(deg) if !deg.is_finite() => {
+                Some(format!("RotateVel angle {} is not a number", deg))
+            }
+            BulletEventType::SetVel(vel) if !vel.is_finite() => {
+                Some("SetVel velocity is not a number".to_string())
+            }
+            BulletEventType::SetAcc(acc) if !acc.is_finite() => {
+                Some("SetAcc acceleration is not a number".to_string())
+            }
+            BulletEventType::AccelTowards { target, magnitude } => match target {
+                _ if !magnitude.is_finite() => Some(format!(
+                    "AccelTowards magnitude {} is not a number",
+                    magnitude
+                )),
+                AccelTarget::Point(point) if !(point.x.is_finite() && point.y.is_finite()) => {
+                    Some("AccelTowards point is not a number".to_string())
+                }
+                _ => None,
+            },
+            BulletEventType::FreezeThenLaunch { launch, .. } => {
+                if !(launch.speed.is_finite() && launch.speed >= 0.0) {
+                    Some(format!(
+                        "FreezeThenLaunch speed must be 0 or more (got {})",
+                        launch.speed
+                    ))
+                } else if let LaunchDirection::Deg(deg) = launch.direction {
+                    (!deg.is_finite())
+                        .then(|| format!("FreezeThenLaunch angle {} is not a number", deg))
+                } else {
+                    None
+                }
+            }
+            BulletEventType::SpeedClamp { min, max } => {
+                if !(min.is_finite() && max.is_finite() && 0.0 <= *min && min <= max) {
+                    Some(format!(
+                        "SpeedClamp needs 0 <= min <= max (got min {}, max {})",
+                        min, max
+                    ))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
 struct Enemy {
-    frame: u16,                // 敵が生成されてからの経過フレーム
-    pos: Point,                // 位置
-    vel: Vector,               // 速度
-    events: Vec<EnemyEvent>,   // 弾に起こる変化の列（タイミング、イベント）
-    next_event: Option<usize>, // 次に起こるイベント番号
+    id: EntityId,                   // ステージに出たときに付く番号
+    frame: u32,                     // 敵が生成されてからの経過フレーム
+    attachment: Option<Attachment>, // 使い魔なら、どの敵にどう付いているか
+    transform: Transform,
+    kinematics: Kinematics,
+    collider: Collider,
+    health: Health,
+    timeline: Timeline<EnemyEvent>, // 敵が起こすイベントの列
+    guide_frames: u16,              // 弾を撃つ何フレーム前から予告線を出すか（0なら出さない）
+    resistance: Resistance,
+    resisting_since: u32, // `resistance`が効き始めたフレーム（`frame`と同じ数え方）
+    renderable: Renderable,
 }
 
 impl Enemy {
     pub fn new(pos: Point, vel: Vector, events: Vec<EnemyEvent>) -> Self {
         Self {
+            id: EntityId::UNASSIGNED,
             frame: 0,
-            pos,
-            vel,
-            next_event: if events.is_empty() { None } else { Some(0) },
-            events,
+            attachment: None,
+            transform: Transform { pos },
+            kinematics: Kinematics::new(vel),
+            collider: Collider {
+                radius: ENEMY_RADIUS,
+            },
+            health: Health {
+                remaining: ENEMY_HEALTH,
+            },
+            timeline: Timeline::new(events),
+            guide_frames: 0,
+            resistance: Resistance::NONE,
+            resisting_since: 0,
+            renderable: Renderable::circle(Color::Enemy, ENEMY_RADIUS),
         }
     }
 
-    pub fn update(&mut self, bullets: &mut Vec<Bullet>, player: &Player) {
-        self.frame += 1;
-
-        self.pos += self.vel;
-
-        if let Some(next_event) = self.next_event {
-            let event = &self.events[next_event];
+    /// 今から`resistance`の守りを効かせる
+    fn resist(&mut self, resistance: Resistance) {
+        self.resistance = resistance;
+        self.resisting_since = self.frame;
+    }
 
-            if event.at != self.frame {
-                return;
-            }
+    /// `parent`の敵に付いて動く使い魔を作る
+    fn familiar(parent: &Enemy, spec: &FamiliarSpec) -> Self {
+        Self {
+            attachment: Some(Attachment {
+                parent: parent.id,
+                offset: spec.offset,
+            }),
+            timeline: Timeline::new(spec.events.clone()),
+            ..Self::new(
+                spec.offset.position(&parent.transform.pos, 0),
+                Vector::zero(),
+                vec![],
+            )
+        }
+    }
 
+    /// このフレームのイベントを起こす。新しく出す敵やステージ全体へのイベントは`deferred`に入れる。
+    pub fn fire(&mut self, bullets: &mut Bullets, deferred: &mut Deferred, targets: &[Target]) {
+        let id = self.id;
+        while let Some(event) = self.timeline.advance(self.frame) {
             match &event.event_ty {
                 EnemyEventType::Nways {
                     n,
                     wide_deg,
                     center_deg,
+                    include_center,
+                    bullet,
+                } => {
+                    for deg in fan_degs(*n, *wide_deg, *center_deg, *include_center) {
+                        bullets.push(
+                            bullet.spawn(self.transform.pos).with_source(id),
+                            Vector::from_deg_and_mag(deg, FAN_BULLET_SPEED),
+                        );
+                    }
+                }
+                EnemyEventType::AimedNways {
+                    n,
+                    wide_deg,
+                    include_center,
+                    lead,
+                    bullet,
                 } => {
-                    let step = wide_deg / (*n as f32 - 1.0);
-                    for deg in (0..*n).map(|i| center_deg - wide_deg / 2.0 + step * i as f32) {
-                        bullets.push(Bullet::new(
-                            self.pos,
-                            Vector::from_deg_and_mag(deg, 2.0),
-                            Vector::zero(),
-                            vec![],
-                        ));
+                    let center_deg = self.aim_deg(targets, FAN_BULLET_SPEED, *lead);
+                    for deg in fan_degs(*n, *wide_deg, center_deg, *include_center) {
+                        bullets.push(
+                            bullet.spawn(self.transform.pos).with_source(id),
+                            Vector::from_deg_and_mag(deg, FAN_BULLET_SPEED),
+                        );
+                    }
+                }
+                EnemyEventType::SummonFamiliars(familiars) => {
+                    for spec in familiars.iter() {
+                        deferred.enemies.push(Enemy::familiar(self, spec));
                     }
                 }
+                EnemyEventType::SpawnEnemy { definition, offset } => {
+                    let mut pos = self.transform.pos;
+                    pos += *offset;
+                    deferred.enemies.push(definition.spawn(pos));
+                }
+                EnemyEventType::Level(event) => {
+                    deferred.level_events.push(*event);
+                }
                 EnemyEventType::AimShot => {
-                    let deg = player.get_aim_rad(&self.pos) * 180.0 / PI;
-                    bullets.push(Bullet::new(
-                        self.pos,
-                        Vector::from_deg_and_mag(deg, 1.0),
-                        Vector::zero(),
-                        vec![],
-                    ));
+                    let deg = self.aim_deg(targets, AIM_BULLET_SPEED, 0.0);
+                    bullets.push(
+                        Bullet::new(self.transform.pos, Vector::zero(), vec![]).with_source(id),
+                        Vector::from_deg_and_mag(deg, AIM_BULLET_SPEED),
+                    );
+                }
+                EnemyEventType::Shot {
+                    offset,
+                    deg,
+                    speed,
+                    aimed,
+                    bullet,
+                } => {
+                    let mut pos = self.transform.pos;
+                    pos += *offset;
+                    let speed = per_frame(*speed);
+                    let deg = if *aimed {
+                        self.aim_deg(targets, speed, 0.0) + deg
+                    } else {
+                        *deg
+                    };
+                    bullets.push(
+                        bullet.spawn(pos).with_source(id),
+                        Vector::from_deg_and_mag(deg, speed),
+                    );
                 }
             }
+        }
+    }
+
+    /// 一番近い相手を狙う角度。誰もいなければ真下を向く。
+    /// `lead`が0より大きいと、速さ`speed`の弾が届くまでに相手が動く分を見越して狙う。
+    fn aim_deg(&self, targets: &[Target], speed: f32, lead: f32) -> f32 {
+        nearest_target(targets, &self.transform.pos).map_or(90.0, |target| {
+            let frames = target.distance_squared(&self.transform.pos).sqrt() / speed;
+            let aim = target.predicted(frames * lead);
+            (aim.y - self.transform.pos.y).atan2(aim.x - self.transform.pos.x) * 180.0 / PI
+        })
+    }
+
+    /// イベント列を差し替えて、最初から起こし直す
+    fn start_events(&mut self, events: Rc<[EnemyEvent]>) {
+        self.frame = 0;
+        self.timeline = Timeline::new(events);
+    }
+
+    /// 次のイベントが起こる直前まで時間を進める
+    fn skip_to_next_event(&mut self) {
+        if let Some(at) = self.timeline.next_at() {
+            self.frame = self.frame.max(at.saturating_sub(1));
+        }
+    }
+
+    /// 次に弾を撃つイベントまでの残りフレーム数。使い魔や敵を出すだけのイベントは飛ばす
+    fn frames_until_next_shot(&self) -> Option<u32> {
+        self.timeline
+            .upcoming()
+            .find(|(_, event)| event.event_ty.fires_bullets())
+            .map(|(at, _)| at.saturating_sub(self.frame))
+    }
 
-            self.next_event = if next_event == self.events.len() - 1 {
-                None
+    /// `guide_frames`フレーム以内に撃つ弾の通り道を予告線にする。狙う弾は今の相手の位置から向きを決める。
+    fn guides(&self, targets: &[Target]) -> Vec<(u32, Guide)> {
+        let origin = self.transform.pos;
+        let fan = |n: u16, wide_deg: f32, center_deg: f32, include_center: bool| {
+            if n > GUIDE_MAX_RAYS {
+                vec![Guide::Arc {
+                    center: origin,
+                    from_deg: center_deg - wide_deg / 2.0,
+                    to_deg: center_deg + wide_deg / 2.0,
+                }]
             } else {
-                Some(next_event + 1)
-            };
+                fan_degs(n, wide_deg, center_deg, include_center)
+                    .into_iter()
+                    .map(|deg| Guide::Ray { origin, deg })
+                    .collect()
+            }
+        };
+        self.timeline
+            .upcoming()
+            .map(|(at, event)| (at.saturating_sub(self.frame), event))
+            .take_while(|(remaining, _)| *remaining <= self.guide_frames as u32)
+            .flat_map(|(remaining, event)| {
+                let guides = match &event.event_ty {
+                    EnemyEventType::Nways {
+                        n,
+                        wide_deg,
+                        center_deg,
+                        include_center,
+                        ..
+                    } => fan(*n, *wide_deg, *center_deg, *include_center),
+                    EnemyEventType::AimedNways {
+                        n,
+                        wide_deg,
+                        include_center,
+                        lead,
+                        ..
+                    } => {
+                        let center_deg = self.aim_deg(targets, FAN_BULLET_SPEED, *lead);
+                        fan(*n, *wide_deg, center_deg, *include_center)
+                    }
+                    EnemyEventType::AimShot => vec![Guide::Ray {
+                        origin,
+                        deg: self.aim_deg(targets, AIM_BULLET_SPEED, 0.0),
+                    }],
+                    EnemyEventType::Shot {
+                        offset,
+                        deg,
+                        speed,
+                        aimed,
+                        ..
+                    } => {
+                        let mut origin = origin;
+                        origin += *offset;
+                        let deg = if *aimed {
+                            self.aim_deg(targets, per_frame(*speed), 0.0) + deg
+                        } else {
+                            *deg
+                        };
+                        vec![Guide::Ray { origin, deg }]
+                    }
+                    _ => vec![],
+                };
+                guides.into_iter().map(move |guide| (remaining, guide))
+            })
+            .collect()
+    }
+
+    pub fn draw_guides(&self, renderer: &dyn Renderer, playfield: &Playfield, targets: &[Target]) {
+        if self.guide_frames == 0 {
+            return;
+        }
+        for (remaining, guide) in self.guides(targets) {
+            let progress = 1.0 - remaining as f32 / self.guide_frames as f32;
+            guide.draw(renderer, playfield, progress);
+        }
+    }
+
+    /// 予告の輪を含めて、画面に見える位置にあるか
+    fn is_drawn(&self, playfield: &Playfield) -> bool {
+        playfield.is_drawn(&self.transform.pos, self.collider.radius * 2.0)
+    }
+}
+
+impl Components for Enemy {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    /// 使い魔は親に合わせて動くので、自分では動かない
+    fn kinematics(&self) -> Option<&Kinematics> {
+        self.attachment.is_none().then_some(&self.kinematics)
+    }
+
+    fn hitbox(&self) -> Shape {
+        Shape::Circle(self.collider)
+    }
+
+    fn health_mut(&mut self) -> Option<&mut Health> {
+        Some(&mut self.health)
+    }
+
+    fn renderable(&self) -> &Renderable {
+        &self.renderable
+    }
+
+    /// 弾を撃つ直前に、広がっていく輪で予告する
+    fn decorate(&self, renderer: &dyn Renderer) {
+        if let Some(remaining) = self.frames_until_next_shot() {
+            if remaining <= TELEGRAPH_FRAMES as u32 {
+                let progress = 1.0 - remaining as f32 / TELEGRAPH_FRAMES as f32;
+                renderer.set_color(Color::Telegraph);
+                renderer.draw_circle(&self.transform.pos, self.collider.radius * (1.0 + progress));
+            }
+        }
+    }
+}
+
+/// 使い魔がどの敵に付いているか
+#[derive(Clone, Copy)]
+struct Attachment {
+    parent: EntityId,
+    offset: Offset,
+}
+
+/// 使い魔の親からの位置
+#[derive(Clone, Copy)]
+pub enum Offset {
+    Fixed(Vector), // 親から決まった位置にいる
+    // 親の周りを回る
+    Orbit {
+        radius: f32,
+        start_deg: f32,
+        deg_per_frame: f32,
+    },
+}
+
+impl Offset {
+    fn position(&self, parent: &Point, frame: u32) -> Point {
+        let mut position = *parent;
+        position += match self {
+            Offset::Fixed(offset) => *offset,
+            Offset::Orbit {
+                radius,
+                start_deg,
+                deg_per_frame,
+            } => Vector::from_deg_and_mag(
+                start_deg + per_frame(*deg_per_frame) * frame as f32,
+                *radius,
+            ),
+        };
+        position
+    }
+}
+
+/// 敵が出す使い魔の設定
+#[derive(Clone)]
+pub struct FamiliarSpec {
+    offset: Offset,
+    events: Rc<[EnemyEvent]>, // 使い魔が自分で起こすイベント
+}
+
+/// 敵の更新中には`Level`を直接いじれないので、全員の更新が終わってから反映するもの
+#[derive(Default)]
+struct Deferred {
+    enemies: Vec<Enemy>,           // 新しく出す敵
+    level_events: Vec<LevelEvent>, // ステージ全体にかかるイベント
+}
+
+/// ステージ全体の弾にかかるイベント（ボスの仕掛けなど）
+#[derive(Clone, Copy)]
+pub enum LevelEvent {
+    FreezeBullets(u16),    // すべての弾を指定フレームの間その場に止める
+    RotateAllBullets(f32), // すべての弾の速度の向きを度数法で回す
+}
+
+/// 敵が途中で出す別の敵の設定
+#[derive(Clone)]
+pub struct EnemySpec {
+    vel: Vector,
+    events: Rc<[EnemyEvent]>,
+    resistance: Resistance, // 出てきてから効く守り
+}
+
+impl EnemySpec {
+    fn spawn(&self, pos: Point) -> Enemy {
+        Enemy {
+            timeline: Timeline::new(self.events.clone()),
+            resistance: self.resistance,
+            ..Enemy::new(pos, self.vel * per_frame(1.0), vec![])
         }
     }
+}
+
+/// 敵が狙う相手（残機の残っているプレイヤー）
+pub struct Target {
+    position: Point,
+    velocity: Vector, // 1フレームあたりの移動量
+}
+
+impl Target {
+    fn distance_squared(&self, point: &Point) -> f32 {
+        let dx = self.position.x - point.x;
+        let dy = self.position.y - point.y;
+        dx * dx + dy * dy
+    }
 
-    pub fn draw(&self, renderer: &Renderer) {
-        renderer.set_color("pink");
-        renderer.draw_circle(&self.pos, 20.0);
+    /// 今の速度のまま`frames`フレーム動いたときの位置
+    fn predicted(&self, frames: f32) -> Point {
+        let mut position = self.position;
+        position += self.velocity * frames;
+        position
     }
 }
 
+fn nearest_target<'a>(targets: &'a [Target], point: &Point) -> Option<&'a Target> {
+    targets.iter().min_by(|a, b| {
+        a.distance_squared(point)
+            .total_cmp(&b.distance_squared(point))
+    })
+}
+
+/// `center_deg`を中心に`wide_deg`の幅で`n`発を等間隔に並べた扇の角度。
+/// `n`が偶数のとき、`include_center`なら中心にも1発置き、そうでなければ中心を挟むように並べる。
+fn fan_degs(n: u16, wide_deg: f32, center_deg: f32, include_center: bool) -> Vec<f32> {
+    if n <= 1 {
+        return vec![center_deg; n as usize];
+    }
+    let step = wide_deg / (n as f32 - 1.0);
+    let offset = if include_center && n.is_multiple_of(2) {
+        step / 2.0
+    } else {
+        0.0
+    };
+    (0..n)
+        .map(|i| center_deg - wide_deg / 2.0 + offset + step * i as f32)
+        .collect()
+}
+
 #[derive(Clone)]
 enum EnemyEventType {
     Nways {
         n: u16,
         wide_deg: f32,
         center_deg: f32,
+        include_center: bool, // 偶数発のときに中心にも撃つか
+        bullet: BulletSpec,
+    },
+    // 一番近いプレイヤーに向けて扇状に撃つ
+    AimedNways {
+        n: u16,
+        wide_deg: f32,
+        include_center: bool,
+        lead: f32, // 0なら今の位置を、1なら弾が届くときの予想位置を狙う
+        bullet: BulletSpec,
     },
+    // 親に付いて動く使い魔を出す。親が消えると一緒に消える。
+    SummonFamiliars(Rc<[FamiliarSpec]>),
+    // 自分の位置から`offset`ずらしたところに別の敵を出す
+    SpawnEnemy {
+        definition: EnemySpec,
+        offset: Vector,
+    },
+    // ステージ全体の弾に働きかける
+    Level(LevelEvent),
     AimShot,
+    // 自分の位置から`offset`ずらしたところから、`deg`の向きに速さ`speed`で1発撃つ。
+    // `aimed`なら`deg`は一番近いプレイヤーへの向きからのずれ
+    Shot {
+        offset: Vector,
+        deg: f32,
+        speed: f32,
+        aimed: bool,
+        bullet: BulletSpec,
+    },
+}
+
+impl EnemyEventType {
+    /// 自分で弾を撃つイベントか（撃つ前に予告を出す）
+    fn fires_bullets(&self) -> bool {
+        match self {
+            EnemyEventType::Nways { .. }
+            | EnemyEventType::AimedNways { .. }
+            | EnemyEventType::AimShot
+            | EnemyEventType::Shot { .. } => true,
+            EnemyEventType::SummonFamiliars(_)
+            | EnemyEventType::SpawnEnemy { .. }
+            | EnemyEventType::Level(_) => false,
+        }
+    }
 }
 
+/// 敵が出てから`at`フレーム目に起こすこと。
+/// フレーム数や速さは基準の速さ（`STANDARD_TICK_RATE`）で書き、起こすときにこのビルドの速さに直す
 #[derive(Clone)]
-struct EnemyEvent {
+pub struct EnemyEvent {
     at: u16,
     event_ty: EnemyEventType,
 }
+
+impl Timed for EnemyEvent {
+    fn at(&self) -> u32 {
+        frames(self.at as u32)
+    }
+}
+
+impl EnemyEvent {
+    /// `at`フレーム目に、`center_deg`を中心に`wide_deg`の幅で`n`発撃つ扇
+    pub fn nways(at: u16, n: u16, wide_deg: f32, center_deg: f32) -> Self {
+        Self {
+            at,
+            event_ty: EnemyEventType::Nways {
+                n,
+                wide_deg,
+                center_deg,
+                include_center: false,
+                bullet: BulletSpec::default(),
+            },
+        }
+    }
+
+    /// `at`フレーム目に、一番近いプレイヤーに弾が届くときの予想位置を中心に、`wide_deg`の幅で`n`発撃つ扇
+    pub fn aimed_nways(at: u16, n: u16, wide_deg: f32) -> Self {
+        Self {
+            at,
+            event_ty: EnemyEventType::AimedNways {
+                n,
+                wide_deg,
+                include_center: true,
+                lead: 1.0,
+                bullet: BulletSpec::default(),
+            },
+        }
+    }
+
+    /// `at`フレーム目に、一番近いプレイヤーへ1発撃つ
+    pub fn aim_shot(at: u16) -> Self {
+        Self {
+            at,
+            event_ty: EnemyEventType::AimShot,
+        }
+    }
+
+    /// `at`フレーム目に1発撃つイベント（`EnemyEventType::Shot`）。
+    /// `acceleration`が`(加速度, 最後の速さ)`なら、撃った向きに加速か減速して最後の速さで落ち着く。狙い撃ちの弾には使えない。
+    pub fn shot(
+        at: u16,
+        offset: Vector,
+        deg: f32,
+        speed: f32,
+        aimed: bool,
+        acceleration: Option<(f32, f32)>,
+    ) -> Self {
+        let events = match acceleration {
+            Some((acc, last)) => vec![
+                // 弾のイベントも撃った次のフレームから数える
+                BulletEvent {
+                    at: 1,
+                    event_ty: BulletEventType::SetAcc(Vector::from_deg_and_mag(deg, acc)),
+                },
+                BulletEvent {
+                    at: 1,
+                    event_ty: BulletEventType::SpeedClamp {
+                        min: speed.min(last),
+                        max: speed.max(last),
+                    },
+                },
+            ],
+            None => vec![],
+        };
+        Self {
+            at,
+            event_ty: EnemyEventType::Shot {
+                offset,
+                deg,
+                speed,
+                aimed,
+                bullet: BulletSpec::new(events),
+            },
+        }
+    }
+
+    fn problem(&self) -> Option<String> {
+        match &self.event_ty {
+            EnemyEventType::Nways {
+                n,
+                wide_deg,
+                center_deg,
+                ..
+            } => {
+                if *n == 0 {
+                    Some("Nways needs n of 1 or more".to_string())
+                } else if !(wide_deg.is_finite() && (0.0..=360.0).contains(wide_deg)) {
+                    Some(format!(
+                        "Nways wide_deg must be between 0 and 360 (got {})",
+                        wide_deg
+                    ))
+                } else if !(center_deg.is_finite() && (-360.0..=360.0).contains(center_deg)) {
+                    Some(format!(
+                        "Nways center_deg must be between -360 and 360 (got {})",
+                        center_deg
+                    ))
+                } else {
+                    None
+                }
+            }
+            EnemyEventType::AimedNways {
+                n, wide_deg, lead, ..
+            } => {
+                if *n == 0 {
+                    Some("AimedNways needs n of 1 or more".to_string())
+                } else if !(wide_deg.is_finite() && (0.0..=360.0).contains(wide_deg)) {
+                    Some(format!(
+                        "AimedNways wide_deg must be between 0 and 360 (got {})",
+                        wide_deg
+                    ))
+                } else if !(lead.is_finite() && *lead >= 0.0) {
+                    Some(format!("AimedNways lead must be 0 or more (got {})", lead))
+                } else {
+                    None
+                }
+            }
+            EnemyEventType::SummonFamiliars(familiars) => {
+                familiars.iter().find_map(|spec| match spec.offset {
+                    Offset::Fixed(offset) if !offset.is_finite() => {
+                        Some("familiar offset is not a number".to_string())
+                    }
+                    Offset::Orbit {
+                        radius,
+                        start_deg,
+                        deg_per_frame,
+                    } if !(radius.is_finite()
+                        && start_deg.is_finite()
+                        && deg_per_frame.is_finite()) =>
+                    {
+                        Some("familiar orbit is not a number".to_string())
+                    }
+                    _ => None,
+                })
+            }
+            EnemyEventType::SpawnEnemy { definition, offset } => {
+                if !offset.is_finite() {
+                    Some("SpawnEnemy offset is not a number".to_string())
+                } else if !definition.vel.is_finite() {
+                    Some("SpawnEnemy velocity is not a number".to_string())
+                } else {
+                    None
+                }
+            }
+            EnemyEventType::Level(LevelEvent::RotateAllBullets(deg)) if !deg.is_finite() => {
+                Some(format!("RotateAllBullets angle {} is not a number", deg))
+            }
+            EnemyEventType::Level(_) => None,
+            EnemyEventType::AimShot => None,
+            EnemyEventType::Shot {
+                offset, deg, speed, ..
+            } => {
+                if !(offset.is_finite() && deg.is_finite()) {
+                    Some("Shot position or angle is not a number".to_string())
+                } else if !(speed.is_finite() && *speed > 0.0) {
+                    Some(format!("Shot speed must be more than 0 (got {})", speed))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    use crate::{
+        constants::ticks,
+        engine::{DrawCall, RecordingRenderer},
+    };
+
+    const SOAK_WAVES: usize = 5; // 弾の波を出す回数。1回で基準の速さのおよそ1500フレーム進む
+    const SOAK_BULLETS: usize = 2000; // 1回の波で出す弾の数
+    const SOAK_WAVE_FRAMES: u32 = ticks(60.0); // 1回の波の弾が消えきるまでに待つ最大のフレーム数
+
+    /// 弾の波を何度も出しては消えきるまで進め、場の実体が最初の数に戻るか確かめる。
+    /// 入れ物は最初の波で広がったあとは同じ大きさを使い回し、波ごとに増えていかない
+    #[test]
+    fn soak_returns_to_baseline() {
+        let mut level = Level::benchmark(1);
+        let baseline = level.memory_usage();
+        let mut settled_bytes = None;
+        for wave in 0..SOAK_WAVES {
+            level.fill_bullets(SOAK_BULLETS);
+            let mut frames = 0;
+            while level.memory_usage().bullets > 0 {
+                assert!(
+                    frames < SOAK_WAVE_FRAMES,
+                    "wave {}: bullets did not clear",
+                    wave
+                );
+                level.update(&[Actions::default()]);
+                frames += 1;
+            }
+            let usage = level.memory_usage();
+            assert_eq!(
+                (usage.bullets, usage.enemies, usage.items, usage.events),
+                (
+                    baseline.bullets,
+                    baseline.enemies,
+                    baseline.items,
+                    baseline.events
+                ),
+                "wave {}",
+                wave
+            );
+            let bytes = *settled_bytes.get_or_insert(usage.bytes);
+            assert_eq!(usage.bytes, bytes, "wave {}: pools kept growing", wave);
+        }
+    }
+
+    /// 予告は弾を撃つイベントの前にだけ出し、使い魔を出すだけのイベントは飛ばして次に撃つときを見る
+    #[test]
+    fn telegraph_skips_events_without_bullets() {
+        let summon = EnemyEvent {
+            at: 10,
+            event_ty: EnemyEventType::SummonFamiliars(Vec::new().into()),
+        };
+        let mut enemy = Enemy::new(
+            Point { x: 0.0, y: 0.0 },
+            Vector::zero(),
+            vec![summon, EnemyEvent::aim_shot(100)],
+        );
+        assert_eq!(enemy.frames_until_next_shot(), Some(frames(100)));
+
+        let telegraphs = |enemy: &Enemy| {
+            let renderer = RecordingRenderer::default();
+            enemy.decorate(&renderer);
+            renderer.take().len()
+        };
+        enemy.frame = frames(10) - 1;
+        assert_eq!(telegraphs(&enemy), 0);
+        enemy.frame = frames(100) - 1;
+        assert!(telegraphs(&enemy) > 0);
+    }
+
+    /// スナップショットに戻せば同じように進み、乱数を変えれば違う弾が出る（練習でのスペルのやり直し）
+    #[test]
+    fn retry_from_snapshot_with_new_seed() {
+        let run = |level: &mut Level| {
+            for _ in 0..ticks(1.0) {
+                level.fill_bullets(100);
+                level.update(&[Actions::default()]);
+            }
+            level.state_hash()
+        };
+        let mut level = Level::benchmark(1);
+        let snapshot = level.snapshot();
+        let first = run(&mut level);
+        level.restore(&snapshot);
+        assert_eq!(run(&mut level), first);
+        level.restore(&snapshot);
+        level.reseed(2);
+        assert_eq!(level.seed(), 2);
+        assert_ne!(run(&mut level), first);
+    }
+
+    const SNAPSHOT_BULLETS: usize = 2000; // 測る場面に出しておく弾の数
+    const SNAPSHOT_SAMPLES: u32 = 100; // 時間を測るのに繰り返す回数
+    const ROLLBACK_FRAMES: usize = 8; // 巻き戻して進め直すフレーム数（通信の遅れの分）
+
+    /// 弾がたくさん出ている場面
+    fn crowded_level() -> Level {
+        let mut level = Level::benchmark(1);
+        level.fill_bullets(SNAPSHOT_BULLETS);
+        for _ in 0..ticks(1.0) {
+            level.update(&[Actions::default()]);
+        }
+        level
+    }
+
+    /// 巻き戻してから進め直した状態は、巻き戻さずに進めた状態と一致する
+    #[test]
+    fn rollback_matches_straight_run() {
+        let mut level = crowded_level();
+        let inputs = [[Actions::default(); MAX_PLAYERS]; ROLLBACK_FRAMES];
+        let snapshot = level.snapshot();
+        for actions in &inputs {
+            level.update(actions);
+        }
+        let expected = level.state_hash();
+        level.resimulate(&snapshot, &inputs);
+        assert_eq!(level.state_hash(), expected);
+    }
+
+    /// 弾がたくさん出ている場面で、スナップショットの保存と復元、巻き戻してからの進め直しにかかる時間を表示する。
+    /// `cargo test --release snapshot_cost -- --ignored --nocapture`で動かす
+    #[test]
+    #[ignore = "prints timings only"]
+    fn snapshot_cost() {
+        let mut level = crowded_level();
+        let start = Instant::now();
+        for _ in 0..SNAPSHOT_SAMPLES {
+            let snapshot = level.snapshot();
+            level.restore(&snapshot);
+        }
+        let snapshot_ms = start.elapsed().as_secs_f64() * 1000.0 / SNAPSHOT_SAMPLES as f64;
+
+        let inputs = [[Actions::default(); MAX_PLAYERS]; ROLLBACK_FRAMES];
+        let snapshot = level.snapshot();
+        let start = Instant::now();
+        for _ in 0..SNAPSHOT_SAMPLES {
+            level.resimulate(&snapshot, &inputs);
+        }
+        let rollback_ms = start.elapsed().as_secs_f64() * 1000.0 / SNAPSHOT_SAMPLES as f64;
+
+        println!(
+            "{} bullets: snapshot and restore {:.3}ms, rollback of {} frames {:.3}ms",
+            level.memory_usage().bullets,
+            snapshot_ms,
+            ROLLBACK_FRAMES,
+            rollback_ms
+        );
+    }
+
+    /// 残機とボムの表示は、横向きでは上の帯に、縦向きではプレイフィールドの下の帯に並べる
+    #[test]
+    fn hud_status_follows_layout() {
+        let field = Playfield::MAIN;
+        let level = Level::new(
+            1,
+            &[0, 1],
+            field,
+            Modifiers::default(),
+            BombType::default(),
+            ShotType::default(),
+        );
+        for (layout, y) in [
+            (Layout::Landscape, 22.0),
+            (Layout::Portrait, field.bottom() + 24.0),
+        ] {
+            let renderer = RecordingRenderer::default();
+            level.draw(
+                &renderer,
+                &Settings::default(),
+                Device::default(),
+                Quality::High,
+                layout,
+            );
+            let calls = renderer.take();
+            let status = |text: &str, x: f32| DrawCall::Text(text.to_string(), Point { x, y });
+            assert!(calls.contains(&status("1P L3 B3", field.left())));
+            assert!(calls.contains(&status("2P L3 B3", field.right() - 150.0)));
+        }
+    }
+}
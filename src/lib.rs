@@ -5,16 +5,85 @@ use wasm_bindgen::prelude::*;
 
 #[macro_use]
 mod browser;
+mod ambient;
+mod background;
+mod banner;
+mod behavior;
+mod benchmark;
+mod boss;
+mod bot;
+mod camping;
+mod chain;
+mod collision;
+mod command;
+mod component;
+mod console;
+mod constants;
+mod damage;
+mod danmakufu;
+mod defeat;
+mod dialog;
+mod difficulty;
+mod effects;
+mod ending;
 mod engine;
+mod entity;
+mod event;
+#[cfg(test)]
+mod fixture;
+#[cfg(test)]
+mod fuzz;
 mod game;
+mod guide;
+mod haptics;
+mod hazard;
+mod heatmap;
+mod history;
+mod input;
+mod item;
+mod lanes;
+mod layout;
 mod level;
 mod math;
+mod menu;
+mod name_entry;
+mod netplay;
+mod options;
+mod pack;
+mod palette;
+#[cfg(test)]
+mod pattern_check;
 mod player;
+mod playfield;
+mod profile;
+mod quality;
+mod render_list;
+mod replay;
+mod risk;
+mod rng;
+mod scene;
+mod settings;
+mod shot;
+mod spectate;
+mod spellbook;
+mod stage;
+mod suspend;
+mod tas;
+mod timeline;
+mod timing;
+mod title;
+mod tweak;
+mod version;
+mod worker;
 
 // This is like the `main` function, except for JavaScript.
 #[wasm_bindgen(start)]
 pub fn main_js() -> Result<(), JsValue> {
     console_error_panic_hook::set_once();
+    // Web Workerの中でも読み込まれるが、そこでは`worker_main`から始める
+    if browser::is_worker() {
+        return Ok(());
+    }
 
     browser::spawn_local(async move {
         let game = StgGame::new();
@@ -25,3 +94,10 @@ pub fn main_js() -> Result<(), JsValue> {
 
     Ok(())
 }
+
+// The entry point of the web worker started by `?worker` (see `js/worker.js`).
+#[wasm_bindgen]
+pub fn worker_main() -> Result<(), JsValue> {
+    console_error_panic_hook::set_once();
+    worker::serve().map_err(|err| JsValue::from_str(&format!("{:#?}", err)))
+}
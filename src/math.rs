@@ -1,5 +1,34 @@
-use std::{f32::consts::PI, ops::AddAssign};
+use std::{
+    f32::consts::PI,
+    ops::{AddAssign, Mul},
+    sync::OnceLock,
+};
 
+// 整数の角度は表から引く。-360度から720度までを持つ
+const TABLE_MIN_DEG: i32 = -360;
+const TABLE_MAX_DEG: i32 = 720;
+
+/// `deg`度の(cos, sin)。
+/// 表の値は同じ式で計算しておいたものなので、表を使っても使わなくても結果は同じになる（リプレイがずれない）。
+fn cos_sin(deg: f32) -> (f32, f32) {
+    fn compute(deg: f32) -> (f32, f32) {
+        let rad = deg * PI / 180.0;
+        (rad.cos(), rad.sin())
+    }
+
+    static TABLE: OnceLock<Vec<(f32, f32)>> = OnceLock::new();
+    if deg.fract() == 0.0 && (TABLE_MIN_DEG as f32..=TABLE_MAX_DEG as f32).contains(&deg) {
+        let table = TABLE.get_or_init(|| {
+            (TABLE_MIN_DEG..=TABLE_MAX_DEG)
+                .map(|deg| compute(deg as f32))
+                .collect()
+        });
+        return table[(deg as i32 - TABLE_MIN_DEG) as usize];
+    }
+    compute(deg)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -7,7 +36,37 @@ pub struct Rect {
     pub height: f32,
 }
 
-#[derive(Clone, Copy)]
+impl Rect {
+    /// 両方を囲む最小の矩形
+    pub fn union(&self, other: &Rect) -> Rect {
+        let left = self.x.min(other.x);
+        let top = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Rect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    /// 重なっている部分。重ならなければ`None`
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        (right > left && bottom > top).then_some(Rect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Point {
     pub x: f32,
     pub y: f32,
@@ -43,18 +102,82 @@ impl Vector {
     }
 
     pub fn from_deg_and_mag(deg: f32, mag: f32) -> Self {
-        let rad = deg * PI / 180.0;
+        let (cos, sin) = cos_sin(deg);
+        Self {
+            x: mag * cos,
+            y: mag * sin,
+        }
+    }
+
+    /// `from`から`to`へ向かうベクトル
+    pub fn between(from: &Point, to: &Point) -> Self {
+        Self {
+            x: to.x - from.x,
+            y: to.y - from.y,
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.x.hypot(self.y)
+    }
+
+    /// 向きを変えずに長さを`length`にする。長さが0のときはそのまま。
+    pub fn with_length(&self, length: f32) -> Self {
+        let current = self.length();
+        if current == 0.0 {
+            return *self;
+        }
+        *self * (length / current)
+    }
+
+    /// x成分の向きを反転する
+    pub fn flip_x(&self) -> Self {
+        Self {
+            x: -self.x,
+            y: self.y,
+        }
+    }
+
+    /// y成分の向きを反転する
+    pub fn flip_y(&self) -> Self {
         Self {
-            x: mag * rad.cos(),
-            y: mag * rad.sin(),
+            x: self.x,
+            y: -self.y,
         }
     }
 
+    /// x成分とy成分
+    pub fn components(&self) -> (f32, f32) {
+        (self.x, self.y)
+    }
+
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite()
+    }
+
+    /// この向きから`other`の向きまで回る角度（度数法、-180から180）。どちらかの長さが0なら0
+    pub fn deg_to(&self, other: &Vector) -> f32 {
+        let cross = self.x * other.y - self.y * other.x;
+        let dot = self.x * other.x + self.y * other.y;
+        cross.atan2(dot).to_degrees()
+    }
+
     pub fn rotate(&self, deg: f32) -> Self {
-        let rad = deg * PI / 180.0;
+        let (cos, sin) = cos_sin(deg);
         Self {
-            x: self.x * rad.cos() - self.y * rad.sin(),
-            y: self.x * rad.sin() + self.y * rad.cos(),
+            x: self.x * cos - self.y * sin,
+            y: self.x * sin + self.y * cos,
+        }
+    }
+}
+
+impl Mul<f32> for Vector {
+    type Output = Self;
+
+    fn mul(self, rhs: f32) -> Self {
+        Self {
+            x: self.x * rhs,
+            y: self.y * rhs,
         }
     }
 }
@@ -65,3 +188,57 @@ impl AddAssign for Vector {
         self.y += rhs.y;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{hint::black_box, time::Instant};
+
+    use super::*;
+
+    const BURST: i32 = 120; // 1度に撃つ弾の数（3度おきに1周）
+    const BURSTS: u32 = 2000; // 時間を測るのに撃つ回数
+
+    /// 表を使わずに計算した(cos, sin)
+    fn direct(deg: f32) -> (f32, f32) {
+        let rad = deg * PI / 180.0;
+        (rad.cos(), rad.sin())
+    }
+
+    /// 表の値は、表の範囲のどの角度でも`sin`と`cos`で計算した値とビット単位で一致する
+    #[test]
+    fn table_matches_sin_cos() {
+        for deg in TABLE_MIN_DEG..=TABLE_MAX_DEG {
+            let (cos, sin) = cos_sin(deg as f32);
+            let (expected_cos, expected_sin) = direct(deg as f32);
+            assert_eq!(cos.to_bits(), expected_cos.to_bits(), "cos {}", deg);
+            assert_eq!(sin.to_bits(), expected_sin.to_bits(), "sin {}", deg);
+        }
+        // 表の外や整数でない角度は、そのまま計算する
+        for deg in [TABLE_MIN_DEG as f32 - 1.0, TABLE_MAX_DEG as f32 + 1.0, 12.5] {
+            assert_eq!(cos_sin(deg).0.to_bits(), direct(deg).0.to_bits());
+            assert_eq!(cos_sin(deg).1.to_bits(), direct(deg).1.to_bits());
+        }
+    }
+
+    /// 1周に弾を並べる撃ち方を何度も繰り返し、表を引く場合とその場で計算する場合の時間を比べる。
+    /// `cargo test --release trig_table_timing -- --ignored --nocapture`で時間を表示する
+    #[test]
+    #[ignore = "prints timings only"]
+    fn trig_table_timing() {
+        let time = |cos_sin: fn(f32) -> (f32, f32)| {
+            let start = Instant::now();
+            for _ in 0..BURSTS {
+                for i in 0..BURST {
+                    black_box(cos_sin(black_box((i * 360 / BURST) as f32)));
+                }
+            }
+            start.elapsed().as_secs_f64() * 1000.0
+        };
+        cos_sin(0.0); // 表を作る時間は含めない
+        let (table_ms, direct_ms) = (time(cos_sin), time(direct));
+        println!(
+            "{} bursts of {} bullets: table {:.3}ms, sin/cos {:.3}ms",
+            BURSTS, BURST, table_ms, direct_ms
+        );
+    }
+}
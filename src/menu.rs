@@ -0,0 +1,147 @@
+use crate::{
+    engine::Renderer,
+    input::Actions,
+    math::{Point, Rect},
+    palette::Color,
+};
+
+const REPEAT_DELAY: u16 = 20; // 押しっぱなしにしてから繰り返し始めるまでのフレーム数
+const REPEAT_INTERVAL: u16 = 5; // 繰り返すときの間隔（フレーム数）
+const CURSOR_X: f32 = 80.0;
+const LABEL_X: f32 = 100.0;
+const VALUE_X: f32 = 320.0;
+const SLIDER_WIDTH: f32 = 100.0;
+const SLIDER_HEIGHT: f32 = 10.0;
+
+/// 方向を押しっぱなしにしたとき、少し待ってから一定の間隔で押し直したことにする
+#[derive(Default)]
+pub struct HoldRepeat {
+    held_frames: u16,          // 同じ方向を押し続けているフレーム数
+    held_direction: [bool; 4], // 押し続けている方向（上下左右）
+}
+
+impl HoldRepeat {
+    /// 押し始めた方向と、押しっぱなしで繰り返した方向を合わせて返す
+    pub fn update(&mut self, pressed: &Actions, held: &Actions) -> Actions {
+        let direction = [held.up, held.down, held.left, held.right];
+        if direction == self.held_direction && direction.contains(&true) {
+            self.held_frames = self.held_frames.saturating_add(1);
+        } else {
+            self.held_frames = 0;
+            self.held_direction = direction;
+        }
+        let repeat = self.held_frames >= REPEAT_DELAY
+            && (self.held_frames - REPEAT_DELAY).is_multiple_of(REPEAT_INTERVAL);
+        Actions {
+            up: pressed.up || (repeat && held.up),
+            down: pressed.down || (repeat && held.down),
+            left: pressed.left || (repeat && held.left),
+            right: pressed.right || (repeat && held.right),
+            ..*pressed
+        }
+    }
+}
+
+/// 縦に並んだ項目を上下で選ぶメニュー。端では反対側に回り込み、押しっぱなしにすると繰り返し動く。
+#[derive(Default)]
+pub struct ListMenu {
+    cursor: usize,
+    repeat: HoldRepeat,
+}
+
+impl ListMenu {
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn reset(&mut self) {
+        self.cursor = 0;
+    }
+
+    /// `HoldRepeat`で繰り返しを加えた操作を返し、上下で`len`個の項目の中を動く
+    pub fn update(&mut self, pressed: &Actions, held: &Actions, len: usize) -> Actions {
+        let moved = self.repeat.update(pressed, held);
+        if len > 0 {
+            if moved.up {
+                self.cursor = (self.cursor + len - 1) % len;
+            }
+            if moved.down {
+                self.cursor = (self.cursor + 1) % len;
+            }
+        }
+        moved
+    }
+
+    /// 項目`index`を`widget`で描く。`y`は項目の文字の高さ
+    pub fn draw_row(
+        &self,
+        renderer: &dyn Renderer,
+        index: usize,
+        y: f32,
+        label: &str,
+        widget: &Widget,
+    ) {
+        renderer.set_color(Color::Text);
+        if index == self.cursor {
+            renderer.draw_text(">", &Point { x: CURSOR_X, y });
+        }
+        renderer.draw_text(label, &Point { x: LABEL_X, y });
+        widget.draw(renderer, y, index == self.cursor);
+    }
+}
+
+/// メニューの1項目の値の見せ方
+pub enum Widget<'a> {
+    Toggle(bool),    // ONとOFF
+    Choice(&'a str), // 左右で選ぶ値
+    Slider {
+        index: usize, // 左から何番目の値か
+        count: usize, // 値の数
+        name: &'a str,
+    },
+    Field(&'a str), // 入力中の文字
+    Link(&'a str),  // 別のページへ移る
+}
+
+impl Widget<'_> {
+    /// 読み上げにも使う値の文字
+    pub fn text(&self) -> String {
+        match self {
+            Widget::Toggle(true) => "ON".to_string(),
+            Widget::Toggle(false) => "OFF".to_string(),
+            Widget::Choice(text) | Widget::Field(text) | Widget::Link(text) => text.to_string(),
+            Widget::Slider { name, .. } => name.to_string(),
+        }
+    }
+
+    fn draw(&self, renderer: &dyn Renderer, y: f32, selected: bool) {
+        match self {
+            Widget::Slider { index, count, name } => {
+                let bar = Rect {
+                    x: VALUE_X,
+                    y: y - SLIDER_HEIGHT - 2.0,
+                    width: SLIDER_WIDTH,
+                    height: SLIDER_HEIGHT,
+                };
+                renderer.draw_rect(&bar);
+                let filled = (*index + 1) as f32 / (*count).max(1) as f32;
+                renderer.fill_rect(&Rect {
+                    width: bar.width * filled,
+                    ..bar
+                });
+                renderer.draw_text(
+                    name,
+                    &Point {
+                        x: VALUE_X + SLIDER_WIDTH + 10.0,
+                        y,
+                    },
+                );
+            }
+            // 選んでいる間は、入力できることがわかるように末尾に印を付ける
+            Widget::Field(text) if selected => {
+                renderer.draw_text(&format!("{}_", text), &Point { x: VALUE_X, y });
+            }
+            widget => renderer.draw_text(&widget.text(), &Point { x: VALUE_X, y }),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{
+    engine::Renderer,
+    input::{Actions, Device, Prompt, TextKey},
+    math::{Point, Rect},
+    menu::HoldRepeat,
+    palette::Color,
+    playfield::Playfield,
+};
+
+pub const NAME_LENGTH: usize = 3;
+const CHARACTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.-";
+const COLUMNS: usize = 10;
+const CELL_SIZE: f32 = 40.0;
+const GRID_TOP: f32 = 270.0; // 升目の1行目の高さ（プレイフィールドの上端から）
+
+/// 升目の1つ分
+#[derive(Clone, Copy)]
+enum Cell {
+    Char(char),
+    Erase, // 最後の1文字を消す
+    End,   // 入力を終える
+}
+
+impl Cell {
+    fn label(self) -> String {
+        match self {
+            Cell::Char(c) => c.to_string(),
+            Cell::Erase => "DEL".to_string(),
+            Cell::End => "END".to_string(),
+        }
+    }
+}
+
+fn cells() -> Vec<Cell> {
+    CHARACTERS
+        .chars()
+        .map(Cell::Char)
+        .chain([Cell::Erase, Cell::End])
+        .collect()
+}
+
+pub enum EntryEvent {
+    Changed,
+    Done(String), // 入力を終えた。中身は入力された名前
+}
+
+/// 最高スコアを出したときの名前入力。升目をカーソルで選ぶほか、数字はキーで直接打てる。
+/// 文字のキーは移動に使っているので、アルファベットは升目から選ぶ。
+pub struct NameEntry {
+    name: String,
+    cursor: usize,
+    repeat: HoldRepeat,
+    score: u64,
+    custom: bool,                    // ルール変更ありの記録か
+    previous: Option<(u64, String)>, // 塗り替えた記録とその名前
+}
+
+impl NameEntry {
+    pub fn new(score: u64, custom: bool, previous: Option<(u64, String)>) -> Self {
+        Self {
+            name: String::new(),
+            cursor: 0,
+            repeat: HoldRepeat::default(),
+            score,
+            custom,
+            previous,
+        }
+    }
+
+    pub fn is_custom(&self) -> bool {
+        self.custom
+    }
+
+    pub fn update(
+        &mut self,
+        pressed: &Actions,
+        held: &Actions,
+        typed: Option<TextKey>,
+    ) -> Option<EntryEvent> {
+        let cells = cells();
+        let pressed = self.repeat.update(pressed, held);
+        match typed {
+            Some(TextKey::Digit(digit)) => return self.push(char::from(b'0' + digit)),
+            Some(TextKey::Backspace) => return self.erase(),
+            Some(TextKey::Enter) => return Some(self.finish()),
+            None => {}
+        }
+
+        let before = self.cursor;
+        if pressed.left {
+            self.cursor = (self.cursor + cells.len() - 1) % cells.len();
+        }
+        if pressed.right {
+            self.cursor = (self.cursor + 1) % cells.len();
+        }
+        if pressed.up {
+            self.cursor = (self.cursor + cells.len() - COLUMNS) % cells.len();
+        }
+        if pressed.down {
+            self.cursor = (self.cursor + COLUMNS) % cells.len();
+        }
+        if pressed.bomb {
+            return self.erase();
+        }
+        if pressed.shot {
+            return match cells[self.cursor] {
+                Cell::Char(c) => self.push(c),
+                Cell::Erase => self.erase(),
+                Cell::End => Some(self.finish()),
+            };
+        }
+        (self.cursor != before).then_some(EntryEvent::Changed)
+    }
+
+    fn push(&mut self, c: char) -> Option<EntryEvent> {
+        if self.name.len() >= NAME_LENGTH {
+            return None;
+        }
+        self.name.push(c);
+        // 埋まったら、すぐに終えられるようにカーソルを終わりの升目に置く
+        if self.name.len() == NAME_LENGTH {
+            self.cursor = cells().len() - 1;
+        }
+        Some(EntryEvent::Changed)
+    }
+
+    fn erase(&mut self) -> Option<EntryEvent> {
+        self.name.pop().map(|_| EntryEvent::Changed)
+    }
+
+    fn finish(&self) -> EntryEvent {
+        if self.name.is_empty() {
+            EntryEvent::Done("???".to_string())
+        } else {
+            EntryEvent::Done(self.name.clone())
+        }
+    }
+
+    /// スクリーンリーダーに読ませる今の状態
+    pub fn describe(&self) -> String {
+        let name = if self.name.is_empty() {
+            "empty".to_string()
+        } else {
+            self.name.clone()
+        };
+        format!("Name {}. {}", name, cells()[self.cursor].label())
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, device: Device) {
+        let field = Playfield::MAIN;
+        let x = field.center_x();
+        renderer.set_color(Color::Background);
+        renderer.fill_rect(&Rect {
+            x: field.left() + 10.0,
+            y: field.top() + 40.0,
+            width: field.rect().width - 20.0,
+            height: 440.0,
+        });
+        renderer.set_color(Color::Text);
+        renderer.draw_large_text_centered(
+            "NEW HIGH SCORE",
+            &Point {
+                x,
+                y: field.top() + 90.0,
+            },
+        );
+        let board = if self.custom {
+            "Custom rules"
+        } else {
+            "Standard rules"
+        };
+        renderer.draw_text_centered(
+            &format!("{}  {}", board, self.score),
+            &Point {
+                x,
+                y: field.top() + 130.0,
+            },
+        );
+        if let Some((score, name)) = &self.previous {
+            renderer.draw_text_centered(
+                &format!("Previous {} {}", score, name),
+                &Point {
+                    x,
+                    y: field.top() + 160.0,
+                },
+            );
+        }
+        let shown: String = self
+            .name
+            .chars()
+            .chain(std::iter::repeat('_'))
+            .take(NAME_LENGTH)
+            .flat_map(|c| [c, ' '])
+            .collect();
+        renderer.draw_large_text_centered(
+            shown.trim_end(),
+            &Point {
+                x,
+                y: field.top() + 210.0,
+            },
+        );
+
+        let left = x - CELL_SIZE * COLUMNS as f32 / 2.0;
+        for (i, cell) in cells().iter().enumerate() {
+            let column = (i % COLUMNS) as f32;
+            let row = (i / COLUMNS) as f32;
+            let center = Point {
+                x: left + CELL_SIZE * (column + 0.5),
+                y: field.top() + GRID_TOP + CELL_SIZE * row,
+            };
+            renderer.draw_text_centered(&cell.label(), &center);
+            if i == self.cursor {
+                renderer.draw_rect(&Rect {
+                    x: center.x - CELL_SIZE / 2.0,
+                    y: center.y - CELL_SIZE / 2.0 - 6.0,
+                    width: CELL_SIZE,
+                    height: CELL_SIZE,
+                });
+            }
+        }
+
+        renderer.draw_text_centered(
+            &format!(
+                "{}: pick  {}: erase",
+                device.glyph(Prompt::Pick),
+                device.glyph(Prompt::Erase)
+            ),
+            &Point {
+                x,
+                y: field.top() + 450.0,
+            },
+        );
+    }
+}
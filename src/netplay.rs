@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::{
+    browser::{Socket, SocketState},
+    constants::{ticks, HASH_INTERVAL},
+    input::{Actions, MAX_PLAYERS},
+    level::Level,
+};
+
+const INPUT_DELAY: u32 = 3; // 自分の入力を何フレーム後に反映するか（通信の遅れを吸収する）
+                            // 相手の入力とハッシュを、今のフレームから前後何フレームまで受け付けるか。
+                            // ロックステップでは相手は数フレームしか離れないので、それより遠いフレームのものは溜めずに捨てる
+const FRAME_WINDOW: u32 = ticks(2.0);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum NetStatus {
+    Connecting,
+    WaitingForPeer,
+    Playing,
+    Desynced(u32), // ずれを見つけたフレーム
+    Disconnected,
+}
+
+/// WebSocketの中継サーバーを通して2人で協力プレイする（実験的）。
+///
+/// 両方の環境で同じ入力から同じステージを進める（ロックステップ）。
+/// 自分の入力は`INPUT_DELAY`フレーム先の分として送り、相手の入力が届くまではステージを止める。
+/// ホスト（1P）がシードを決めて、ゲスト（2P）に送る。
+///
+/// メッセージは空白区切りのテキスト:
+/// - `HELLO <slot>`: 接続したことを相手に知らせる
+/// - `SEED <seed>`: ホストが決めたシード。これを受け取ったら開始する
+/// - `I <frame> <bits>`: そのフレームの入力
+/// - `HASH <frame> <hash>`: そのフレームを進めた後の状態のハッシュ
+/// - `BYE`: 切断する
+pub struct Netplay {
+    socket: Socket,
+    slot: usize, // 自分が何番目のプレイヤーか（0ならホスト）
+    status: NetStatus,
+    seed: u64,
+    started: bool,                    // 開始したことを`take_start`でまだ伝えていないか
+    hello_sent: bool,                 // 接続後に`HELLO`を送ったか
+    frame: u32,                       // 次に進めるフレーム
+    sent_frame: u32,                  // 次に自分の入力を送るフレーム
+    local: HashMap<u32, u16>,         // まだ使っていない自分の入力
+    remote: HashMap<u32, u16>,        // まだ使っていない相手の入力
+    local_hashes: HashMap<u32, u64>,  // 相手のハッシュが届くまで覚えておく自分のハッシュ
+    remote_hashes: HashMap<u32, u64>, // 自分がまだそのフレームに達していない相手のハッシュ
+}
+
+impl Netplay {
+    /// `seed`はホストのときだけ使う
+    pub fn connect(url: &str, slot: usize, seed: u64) -> Result<Self> {
+        Ok(Self {
+            socket: Socket::connect(url)?,
+            slot,
+            status: NetStatus::Connecting,
+            seed,
+            started: false,
+            hello_sent: false,
+            frame: 0,
+            sent_frame: 0,
+            local: HashMap::new(),
+            remote: HashMap::new(),
+            local_hashes: HashMap::new(),
+            remote_hashes: HashMap::new(),
+        })
+    }
+
+    pub fn status(&self) -> NetStatus {
+        self.status
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// 届いたメッセージを処理する。毎フレーム最初に呼ぶ。
+    pub fn poll(&mut self) {
+        if self.socket.state() == SocketState::Closed {
+            self.status = NetStatus::Disconnected;
+        }
+        if matches!(
+            self.status,
+            NetStatus::Disconnected | NetStatus::Desynced(_)
+        ) {
+            return;
+        }
+
+        if self.socket.state() == SocketState::Open && !self.hello_sent {
+            self.hello_sent = true;
+            self.status = NetStatus::WaitingForPeer;
+            self.send(&format!("HELLO {}", self.slot));
+        }
+
+        while let Some(message) = self.socket.receive() {
+            self.handle(&message);
+        }
+    }
+
+    fn handle(&mut self, message: &str) {
+        let mut words = message.split_whitespace();
+        let command = words.next();
+        let mut number = || words.next().and_then(|word| word.parse::<u64>().ok());
+        match command {
+            // ゲストが先に繋いでいた場合に備えて、ホストの挨拶には挨拶を返す
+            Some("HELLO") if number() == Some(0) && self.slot != 0 => {
+                self.send(&format!("HELLO {}", self.slot));
+            }
+            Some("HELLO") if self.slot == 0 && self.status == NetStatus::WaitingForPeer => {
+                self.send(&format!("SEED {}", self.seed));
+                self.start();
+            }
+            Some("SEED") if self.slot != 0 && self.status == NetStatus::WaitingForPeer => {
+                if let Some(seed) = number() {
+                    self.seed = seed;
+                    self.start();
+                }
+            }
+            Some("I") => {
+                if let (Some(frame), Some(bits)) = (number(), number()) {
+                    // 使い終わったフレームの入力は、届いてももう使わない
+                    if frame >= self.frame as u64 && self.is_near(frame) {
+                        self.remote.insert(frame as u32, bits as u16);
+                    }
+                }
+            }
+            Some("HASH") => {
+                if let (Some(frame), Some(hash)) = (number(), number()) {
+                    if self.is_near(frame) {
+                        self.remote_hashes.insert(frame as u32, hash);
+                        self.compare_hashes(frame as u32);
+                    }
+                }
+            }
+            Some("BYE") => self.status = NetStatus::Disconnected,
+            _ => {
+                log!("Unknown netplay message: {}", message);
+            }
+        }
+    }
+
+    /// `frame`が今のフレームから`FRAME_WINDOW`以内か
+    fn is_near(&self, frame: u64) -> bool {
+        frame.abs_diff(self.frame as u64) <= FRAME_WINDOW as u64
+    }
+
+    /// もう使わない入力と、相手と比べられなくなったハッシュを捨てる
+    fn prune(&mut self) {
+        let frame = self.frame;
+        let oldest = frame.saturating_sub(FRAME_WINDOW);
+        self.remote.retain(|&at, _| at >= frame);
+        self.local_hashes.retain(|&at, _| at >= oldest);
+        self.remote_hashes.retain(|&at, _| at >= oldest);
+    }
+
+    fn start(&mut self) {
+        self.status = NetStatus::Playing;
+        self.started = true;
+        // 最初の`INPUT_DELAY`フレームはどちらも何も押していないことにする
+        for frame in 0..INPUT_DELAY {
+            self.local.insert(frame, 0);
+            self.remote.insert(frame, 0);
+        }
+        self.sent_frame = INPUT_DELAY;
+    }
+
+    /// 開始したときに一度だけ、両方で使うシードを返す
+    pub fn take_start(&mut self) -> Option<u64> {
+        std::mem::take(&mut self.started).then_some(self.seed)
+    }
+
+    /// 自分の入力を渡し、両方の入力が揃っていればこのフレームの全員の操作を返す
+    pub fn tick(&mut self, local: Actions) -> Option<[Actions; MAX_PLAYERS]> {
+        if self.status != NetStatus::Playing {
+            return None;
+        }
+
+        if self.sent_frame <= self.frame + INPUT_DELAY {
+            let bits = local.to_bits();
+            self.local.insert(self.sent_frame, bits);
+            self.send(&format!("I {} {}", self.sent_frame, bits));
+            self.sent_frame += 1;
+        }
+
+        if !self.remote.contains_key(&self.frame) {
+            return None;
+        }
+        let local = Actions::from_bits(self.local.remove(&self.frame)?);
+        let remote = Actions::from_bits(self.remote.remove(&self.frame)?);
+        self.frame += 1;
+        Some(std::array::from_fn(|player| {
+            if player == self.slot {
+                local
+            } else {
+                remote
+            }
+        }))
+    }
+
+    /// `tick`で進めた後のステージの状態を、ときどき相手と比べる
+    pub fn check_sync(&mut self, level: &Level) {
+        if self.status != NetStatus::Playing || !self.frame.is_multiple_of(HASH_INTERVAL) {
+            return;
+        }
+        self.prune();
+        let hash = level.state_hash();
+        self.local_hashes.insert(self.frame, hash);
+        self.send(&format!("HASH {} {}", self.frame, hash));
+        self.compare_hashes(self.frame);
+    }
+
+    fn compare_hashes(&mut self, frame: u32) {
+        if let (Some(local), Some(remote)) = (
+            self.local_hashes.get(&frame),
+            self.remote_hashes.get(&frame),
+        ) {
+            if local != remote {
+                self.status = NetStatus::Desynced(frame);
+            }
+            self.local_hashes.remove(&frame);
+            self.remote_hashes.remove(&frame);
+        }
+    }
+
+    fn send(&mut self, text: &str) {
+        if let Err(err) = self.socket.send(text) {
+            log!("Could not send netplay message: {:#?}", err);
+            self.status = NetStatus::Disconnected;
+        }
+    }
+}
+
+impl Drop for Netplay {
+    fn drop(&mut self) {
+        if self.socket.state() == SocketState::Open {
+            let _ = self.socket.send("BYE");
+        }
+    }
+}
@@ -0,0 +1,374 @@
+use crate::{
+    engine::Renderer,
+    input::{Actions, Device, Prompt, TextKey},
+    math::Point,
+    menu::{ListMenu, Widget},
+    palette::{Color, Palette},
+    settings::{
+        BombType, GameSpeed, HeatmapView, Modifiers, PlayMode, QualitySetting, RenderScale,
+        Settings, ShotType,
+    },
+};
+
+/// 挑戦用のルール変更の項目
+#[derive(Clone, Copy)]
+enum Modifier {
+    NoBombs,
+    OneLife,
+    FastBullets,
+    NearSight,
+    AntiCamping,
+}
+
+impl Modifier {
+    fn flag(self, modifiers: &mut Modifiers) -> &mut bool {
+        match self {
+            Modifier::NoBombs => &mut modifiers.no_bombs,
+            Modifier::OneLife => &mut modifiers.one_life,
+            Modifier::FastBullets => &mut modifiers.fast_bullets,
+            Modifier::NearSight => &mut modifiers.near_sight,
+            Modifier::AntiCamping => &mut modifiers.anti_camping,
+        }
+    }
+}
+
+/// オプション画面の項目
+#[derive(Clone, Copy)]
+enum OptionItem {
+    Autofire,
+    ToggleFocus,
+    Palette,
+    ReduceFlashing,
+    Vibration,
+    GameSpeed,
+    ShowHitboxes,
+    Heatmap,
+    Quality,
+    RenderScale,
+    FrameSkip,
+    Practice,
+    Casual,
+    Mode,
+    Bomb,
+    Shot,
+    Tas,
+    Seed,
+    CustomGame, // ルール変更のページへ移る
+    History,    // これまでのプレイの記録を見る
+    Spells,     // 出会ったスペルの記録を見る
+    Modifier(Modifier),
+    Back, // ルール変更のページから戻る
+}
+
+const ITEMS: [OptionItem; 21] = [
+    OptionItem::Autofire,
+    OptionItem::ToggleFocus,
+    OptionItem::Palette,
+    OptionItem::ReduceFlashing,
+    OptionItem::Vibration,
+    OptionItem::GameSpeed,
+    OptionItem::ShowHitboxes,
+    OptionItem::Heatmap,
+    OptionItem::Quality,
+    OptionItem::RenderScale,
+    OptionItem::FrameSkip,
+    OptionItem::Practice,
+    OptionItem::Casual,
+    OptionItem::Mode,
+    OptionItem::Bomb,
+    OptionItem::Shot,
+    OptionItem::Tas,
+    OptionItem::Seed,
+    OptionItem::CustomGame,
+    OptionItem::History,
+    OptionItem::Spells,
+];
+
+const CUSTOM_ITEMS: [OptionItem; 6] = [
+    OptionItem::Modifier(Modifier::NoBombs),
+    OptionItem::Modifier(Modifier::OneLife),
+    OptionItem::Modifier(Modifier::FastBullets),
+    OptionItem::Modifier(Modifier::NearSight),
+    OptionItem::Modifier(Modifier::AntiCamping),
+    OptionItem::Back,
+];
+
+const MAX_SEED_DIGITS: usize = 19; // u64に収まる桁数
+const ROW_SPACING: f32 = 19.0; // 項目の行の間隔
+
+/// オプション画面での操作の結果
+pub enum MenuEvent {
+    Changed,      // 選択や設定が変わった
+    Restart(u64), // 入力されたシードで最初からやり直す
+    ShowHistory,  // プレイの記録の画面を開く
+    ShowSpells,   // スペルの記録の画面を開く
+}
+
+impl OptionItem {
+    fn label(self) -> &'static str {
+        match self {
+            OptionItem::Autofire => "Autofire",
+            OptionItem::ToggleFocus => "Toggle focus",
+            OptionItem::Palette => "Palette",
+            OptionItem::ReduceFlashing => "Reduce flashing",
+            OptionItem::Vibration => "Vibration",
+            OptionItem::GameSpeed => "Game speed",
+            OptionItem::ShowHitboxes => "Show hitboxes",
+            OptionItem::Heatmap => "Heatmap",
+            OptionItem::Quality => "Quality",
+            OptionItem::RenderScale => "Resolution",
+            OptionItem::FrameSkip => "Frame skip",
+            OptionItem::Practice => "Practice mode",
+            OptionItem::Casual => "Casual mode",
+            OptionItem::Mode => "Players",
+            OptionItem::Bomb => "Bomb",
+            OptionItem::Shot => "Shot",
+            OptionItem::Tas => "TAS mode",
+            OptionItem::Seed => "Seed",
+            OptionItem::CustomGame => "Custom game",
+            OptionItem::History => "Run history",
+            OptionItem::Spells => "Spell cards",
+            OptionItem::Modifier(Modifier::NoBombs) => "No bombs",
+            OptionItem::Modifier(Modifier::OneLife) => "1 life",
+            OptionItem::Modifier(Modifier::FastBullets) => "Double bullet speed",
+            OptionItem::Modifier(Modifier::NearSight) => "Bullets only near you",
+            OptionItem::Modifier(Modifier::AntiCamping) => "No camping",
+            OptionItem::Back => "Back",
+        }
+    }
+
+    fn widget<'a>(self, settings: &Settings, seed_entry: &'a str) -> Widget<'a> {
+        let mut modifiers = settings.modifiers;
+        match self {
+            OptionItem::Autofire => Widget::Toggle(settings.autofire),
+            OptionItem::ToggleFocus => Widget::Toggle(settings.toggle_focus),
+            OptionItem::Palette => Widget::Choice(settings.palette.name()),
+            OptionItem::ReduceFlashing => Widget::Toggle(settings.reduce_flashing),
+            OptionItem::Vibration => Widget::Toggle(!settings.no_vibration),
+            OptionItem::GameSpeed => Widget::Slider {
+                index: position(&GameSpeed::ALL, settings.game_speed),
+                count: GameSpeed::ALL.len(),
+                name: settings.game_speed.name(),
+            },
+            OptionItem::ShowHitboxes => Widget::Toggle(settings.show_hitboxes),
+            OptionItem::Heatmap => Widget::Choice(settings.heatmap.name()),
+            OptionItem::Quality => Widget::Choice(settings.quality.name()),
+            OptionItem::RenderScale => Widget::Choice(settings.render_scale.name()),
+            OptionItem::FrameSkip => Widget::Toggle(settings.frame_skip),
+            OptionItem::Practice => Widget::Toggle(settings.practice),
+            OptionItem::Casual => Widget::Toggle(settings.casual),
+            OptionItem::Mode => Widget::Choice(settings.mode.name()),
+            OptionItem::Bomb => Widget::Choice(settings.bomb.name()),
+            OptionItem::Shot => Widget::Choice(settings.shot.name()),
+            OptionItem::Tas => Widget::Toggle(settings.tas),
+            OptionItem::Seed => Widget::Field(seed_entry),
+            OptionItem::CustomGame if settings.modifiers.is_custom() => Widget::Link("ON >"),
+            OptionItem::CustomGame => Widget::Link("OFF >"),
+            OptionItem::Modifier(modifier) => Widget::Toggle(*modifier.flag(&mut modifiers)),
+            OptionItem::History | OptionItem::Spells => Widget::Link(">"),
+            OptionItem::Back => Widget::Link("<"),
+        }
+    }
+
+    /// 値を`step`（-1か1）だけ切り替える
+    fn change(self, settings: &mut Settings, step: isize) {
+        match self {
+            OptionItem::Autofire => settings.autofire = !settings.autofire,
+            OptionItem::ToggleFocus => settings.toggle_focus = !settings.toggle_focus,
+            OptionItem::Palette => {
+                settings.palette = cycle(&Palette::ALL, settings.palette, step);
+            }
+            OptionItem::ReduceFlashing => settings.reduce_flashing = !settings.reduce_flashing,
+            OptionItem::Vibration => settings.no_vibration = !settings.no_vibration,
+            OptionItem::GameSpeed => {
+                settings.game_speed = cycle(&GameSpeed::ALL, settings.game_speed, step);
+            }
+            OptionItem::ShowHitboxes => settings.show_hitboxes = !settings.show_hitboxes,
+            OptionItem::Heatmap => {
+                settings.heatmap = cycle(&HeatmapView::ALL, settings.heatmap, step);
+            }
+            OptionItem::Quality => {
+                settings.quality = cycle(&QualitySetting::ALL, settings.quality, step);
+            }
+            OptionItem::RenderScale => {
+                settings.render_scale = cycle(&RenderScale::ALL, settings.render_scale, step);
+            }
+            OptionItem::FrameSkip => settings.frame_skip = !settings.frame_skip,
+            OptionItem::Practice => settings.practice = !settings.practice,
+            OptionItem::Casual => settings.casual = !settings.casual,
+            OptionItem::Mode => settings.mode = cycle(&PlayMode::ALL, settings.mode, step),
+            OptionItem::Bomb => settings.bomb = cycle(&BombType::ALL, settings.bomb, step),
+            OptionItem::Shot => settings.shot = cycle(&ShotType::ALL, settings.shot, step),
+            OptionItem::Tas => settings.tas = !settings.tas,
+            OptionItem::Modifier(modifier) => {
+                let flag = modifier.flag(&mut settings.modifiers);
+                *flag = !*flag;
+            }
+            OptionItem::Seed
+            | OptionItem::CustomGame
+            | OptionItem::History
+            | OptionItem::Spells
+            | OptionItem::Back => {}
+        }
+    }
+}
+
+/// `values`の中で`current`が何番目か
+fn position<T: PartialEq>(values: &[T], current: T) -> usize {
+    values.iter().position(|v| *v == current).unwrap_or(0)
+}
+
+/// `values`の中で`current`から`step`だけずらした値を返す（端では反対側に回り込む）
+fn cycle<T: Copy + PartialEq>(values: &[T], current: T, step: isize) -> T {
+    let index = position(values, current) as isize;
+    let len = values.len() as isize;
+    values[(index + step).rem_euclid(len) as usize]
+}
+
+/// ゲーム中に開くオプション画面（ポーズ画面を兼ねる）
+pub struct OptionsMenu {
+    custom: bool, // ルール変更のページを開いているか
+    list: ListMenu,
+    seed: u64,          // 今のプレイのシード
+    seed_entry: String, // 入力中のシード
+}
+
+impl OptionsMenu {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            custom: false,
+            list: ListMenu::default(),
+            seed,
+            seed_entry: seed.to_string(),
+        }
+    }
+
+    /// 今開いているページの項目
+    fn items(&self) -> &'static [OptionItem] {
+        if self.custom {
+            &CUSTOM_ITEMS
+        } else {
+            &ITEMS
+        }
+    }
+
+    /// 選択中の項目とその値を読み上げ用の文章にする
+    pub fn describe(&self, settings: &Settings) -> String {
+        let item = self.items()[self.list.cursor()];
+        format!(
+            "{}: {}",
+            item.label(),
+            item.widget(settings, &self.seed_entry).text()
+        )
+    }
+
+    /// シード欄での文字入力。Enterで入力したシードからやり直す。
+    fn edit_seed(&mut self, key: TextKey) -> Option<MenuEvent> {
+        match key {
+            TextKey::Digit(digit) => {
+                if self.seed_entry.len() < MAX_SEED_DIGITS {
+                    self.seed_entry.push(char::from(b'0' + digit));
+                }
+                Some(MenuEvent::Changed)
+            }
+            TextKey::Backspace => {
+                self.seed_entry.pop();
+                Some(MenuEvent::Changed)
+            }
+            TextKey::Enter => self.seed_entry.parse().ok().map(MenuEvent::Restart),
+        }
+    }
+
+    /// `pressed`はこのフレームで押し始めた操作、`held`は押している操作、`typed`は押し始めた文字入力キー
+    pub fn update(
+        &mut self,
+        pressed: &Actions,
+        held: &Actions,
+        typed: Option<TextKey>,
+        settings: &mut Settings,
+    ) -> Option<MenuEvent> {
+        let items = self.items();
+        let item = items[self.list.cursor()];
+        if let (OptionItem::Seed, Some(key)) = (item, typed) {
+            return self.edit_seed(key);
+        }
+
+        let pressed = self.list.update(pressed, held, items.len());
+        if let (OptionItem::CustomGame | OptionItem::Back, true) =
+            (item, pressed.left || pressed.right)
+        {
+            self.custom = !self.custom;
+            self.list.reset();
+            return Some(MenuEvent::Changed);
+        }
+        if let (OptionItem::History, true) = (item, pressed.left || pressed.right) {
+            return Some(MenuEvent::ShowHistory);
+        }
+        if let (OptionItem::Spells, true) = (item, pressed.left || pressed.right) {
+            return Some(MenuEvent::ShowSpells);
+        }
+        if pressed.left {
+            item.change(settings, -1);
+        }
+        if pressed.right {
+            item.change(settings, 1);
+        }
+        if pressed.up || pressed.down || pressed.left || pressed.right {
+            Some(MenuEvent::Changed)
+        } else {
+            None
+        }
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, settings: &Settings, device: Device) {
+        renderer.set_color(Color::Text);
+        let title = if self.custom {
+            "CUSTOM GAME"
+        } else {
+            "OPTIONS"
+        };
+        renderer.draw_text(title, &Point { x: 100.0, y: 80.0 });
+        renderer.draw_text(
+            &format!("Current seed: {}", self.seed),
+            &Point { x: 100.0, y: 110.0 },
+        );
+        // 項目が多いので、下の案内と重ならないよう行を少し詰める
+        for (i, item) in self.items().iter().enumerate() {
+            let widget = item.widget(settings, &self.seed_entry);
+            self.list.draw_row(
+                renderer,
+                i,
+                140.0 + ROW_SPACING * i as f32,
+                item.label(),
+                &widget,
+            );
+        }
+        renderer.set_color(Color::Text);
+        if settings.modifiers.is_custom() {
+            renderer.draw_text(
+                "Custom games are scored on a separate board",
+                &Point { x: 100.0, y: 524.0 },
+            );
+        }
+        if !settings.game_speed.is_ranked() {
+            renderer.draw_text(
+                "Slowed runs are not saved to scores or replays",
+                &Point { x: 100.0, y: 544.0 },
+            );
+        }
+        // シードはキーボードでしか入力できないので、ゲームパッドでは案内しない
+        let hint = match device {
+            Device::Keyboard => format!(
+                "{}: change  0-9+Enter: seed  {}: back",
+                device.glyph(Prompt::Change),
+                device.glyph(Prompt::Back)
+            ),
+            Device::Gamepad => format!(
+                "{}: change  {}: back",
+                device.glyph(Prompt::Change),
+                device.glyph(Prompt::Back)
+            ),
+        };
+        renderer.draw_text(&hint, &Point { x: 100.0, y: 568.0 });
+    }
+}
@@ -0,0 +1,397 @@
+use std::rc::Rc;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    constants::{frames, per_tick, ticks, MIDBOSS_ARRIVAL_FRAME},
+    danmakufu,
+    level::{self, EnemyEvent, PATTERNS},
+    math::{Point, Vector},
+    playfield::Playfield,
+    timeline::Timed,
+};
+
+const MAX_NAME_LENGTH: usize = 24;
+const MAX_TITLE_LENGTH: usize = 40;
+const MAX_ROAD_SECONDS: f32 = 300.0; // 道中の最大の長さ
+const MAX_WAVES: usize = 200;
+const MAX_MOVE_SPEED: f32 = 600.0; // 道中の敵が動ける最大の速さ（1秒に何ピクセルか）
+
+/// 遊ぶ人が用意した、道中の敵の出し方を差し替えるデータ。組み込みのステージの道中（`static/stage1.txt`）も同じ形で書く。
+/// 敵に撃たせられるのは名前付きパターン（`PATTERNS`）と、パックの中で書いたイベント列や弾幕風スクリプトを訳したものだけで、
+/// どれも読み込むときに確かめるので、おかしな弾は出せない。
+///
+/// 1行に1つずつ、`#`で始まる行と空行は読み飛ばす。
+/// - `name <名前>`：記録を分けるための名前（英数字と`-`と`_`のみ）
+/// - `title <見出し>`：ステージの始まりに出す見出し（なくてもよい）
+/// - `road <秒>`：中ボスが出るまでの道中の長さ（なければ元のステージと同じ）
+/// - `events <パターンの名前>`：次の`end`だけの行までを1行に1つの敵のイベントとして読み、この名前のパターンにする。
+///   フレーム数と速さは基準の速さ（1秒に60フレーム）で書く
+///   - `<フレーム> nways <発数> <幅> <中心の向き>`：決まった向きへの扇（角度は度）
+///   - `<フレーム> nways <発数> <幅> aim`：一番近いプレイヤーに弾が届くときの予想位置を中心にした扇
+///   - `<フレーム> aim`：一番近いプレイヤーへの1発
+///   - `<フレーム> shot <向き> <速さ>`：決まった向きへ、1フレームにこのピクセル数だけ進む1発
+/// - `script <パターンの名前>`：次の`end`だけの行までを弾幕風のスクリプトとして読み、この名前のパターンにする
+/// - `wave <秒> <横> <縦> <パターンの名前>`：道中の敵。位置はプレイフィールドの左上が0、右下が1
+/// - `move <向き> <速さ>`：直前の`wave`の敵を、度で表した向き（0が右、90が下）へ1秒にこのピクセル数だけ動かし続ける
+#[derive(Clone)]
+pub struct Pack {
+    name: String,
+    title: Option<String>,
+    road_frames: u32,
+    waves: Vec<Wave>,
+}
+
+/// `at`フレーム目に、パターンを撃つ敵を出す予定
+#[derive(Clone)]
+pub struct Wave {
+    at: u32,
+    x: f32,
+    y: f32,
+    vel: Vector, // 1フレームに動く量（ピクセル）
+    events: Rc<[EnemyEvent]>,
+}
+
+/// パックの中のスクリプトを訳したパターン
+struct Script {
+    name: String,
+    events: Rc<[EnemyEvent]>,
+}
+
+impl Timed for Wave {
+    fn at(&self) -> u32 {
+        self.at
+    }
+}
+
+impl Wave {
+    /// 基準の速さでの`at`フレーム目に、標準のプレイフィールド（`Playfield::MAIN`）の左上から`(x, y)`の位置に出す敵。
+    /// ほかの大きさのプレイフィールドでは、同じ割合の位置に出す
+    pub fn new(at: u32, x: f32, y: f32, events: Vec<EnemyEvent>) -> Self {
+        let rect = Playfield::MAIN.rect();
+        Self {
+            at: frames(at),
+            x: x / rect.width,
+            y: y / rect.height,
+            vel: Vector::zero(),
+            events: events.into(),
+        }
+    }
+
+    pub fn position(&self, playfield: &Playfield) -> Point {
+        let rect = playfield.rect();
+        Point {
+            x: rect.x + rect.width * self.x,
+            y: rect.y + rect.height * self.y,
+        }
+    }
+
+    pub fn velocity(&self) -> Vector {
+        self.vel
+    }
+
+    pub fn events(&self) -> Vec<EnemyEvent> {
+        self.events.to_vec()
+    }
+
+    /// `<秒> <横> <縦> <パターンの名前>`。パックのスクリプトは同じ名前の名前付きパターンより優先する
+    fn parse(text: &str, scripts: &[Script]) -> Result<Self> {
+        let mut fields = text.splitn(4, ' ');
+        let mut next = |what: &str| {
+            fields
+                .next()
+                .filter(|field| !field.is_empty())
+                .ok_or_else(|| anyhow!("wave needs {}", what))
+        };
+        let seconds = next("a time")?.parse::<f32>()?;
+        let x = next("a position")?.parse::<f32>()?;
+        let y = next("a position")?.parse::<f32>()?;
+        let name = next("a pattern")?;
+        if !(0.0..=MAX_ROAD_SECONDS).contains(&seconds) {
+            return Err(anyhow!("wave time {} is out of range", seconds));
+        }
+        if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+            return Err(anyhow!(
+                "wave position {} {} is outside the playfield",
+                x,
+                y
+            ));
+        }
+        let events = match scripts.iter().find(|script| script.name == name) {
+            Some(script) => script.events.clone(),
+            None => PATTERNS
+                .iter()
+                .find(|(pattern, _)| *pattern == name)
+                .map(|(_, pattern)| pattern().into())
+                .ok_or_else(|| anyhow!("unknown pattern \"{}\"", name))?,
+        };
+        Ok(Self {
+            at: ticks(seconds),
+            x,
+            y,
+            vel: Vector::zero(),
+            events,
+        })
+    }
+
+    /// `<向き> <速さ>`で、この敵が動き続ける速さを決める
+    fn parse_move(&mut self, text: &str) -> Result<()> {
+        let (deg, speed) = text
+            .split_once(' ')
+            .ok_or_else(|| anyhow!("move needs a direction and a speed"))?;
+        let deg = deg.parse::<f32>()?;
+        let speed = speed.trim().parse::<f32>()?;
+        if !deg.is_finite() || !(0.0..=MAX_MOVE_SPEED).contains(&speed) {
+            return Err(anyhow!(
+                "move speed must be 0 to {} pixels per second",
+                MAX_MOVE_SPEED
+            ));
+        }
+        self.vel = Vector::from_deg_and_mag(deg, per_tick(speed));
+        Ok(())
+    }
+}
+
+impl Pack {
+    /// 誤りがあれば、何行目のどこがおかしいかをすべて並べたエラーを返す
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut name = None;
+        let mut title = None;
+        let mut road_frames = MIDBOSS_ARRIVAL_FRAME;
+        let mut waves: Vec<Wave> = Vec::new();
+        let mut scripts = Vec::new();
+        let mut problems = Vec::new();
+        let mut lines = text.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let rest = rest.trim();
+            let problem = match key {
+                "name" if is_valid_name(rest) => {
+                    name = Some(rest.to_string());
+                    None
+                }
+                "name" => Some(format!(
+                    "name \"{}\" must be 1 to {} letters, digits, - or _",
+                    rest, MAX_NAME_LENGTH
+                )),
+                "title" if rest.chars().count() <= MAX_TITLE_LENGTH => {
+                    title = Some(rest.to_string());
+                    None
+                }
+                "title" => Some(format!(
+                    "title is longer than {} characters",
+                    MAX_TITLE_LENGTH
+                )),
+                "road" => match rest.parse::<f32>() {
+                    Ok(seconds) if (1.0..=MAX_ROAD_SECONDS).contains(&seconds) => {
+                        road_frames = ticks(seconds);
+                        None
+                    }
+                    _ => Some(format!("road must be 1 to {} seconds", MAX_ROAD_SECONDS)),
+                },
+                "events" | "script" => {
+                    let source = lines
+                        .by_ref()
+                        .map(|(_, line)| line)
+                        .take_while(|line| line.trim() != "end")
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let events = if key == "events" {
+                        parse_events(&source)
+                    } else {
+                        danmakufu::import(&source)
+                    };
+                    match events {
+                        Ok(events) => {
+                            let owner = format!("line {}: {} \"{}\"", index + 1, key, rest);
+                            level::validate_enemy_events(&owner, &events, &mut problems);
+                            scripts.push(Script {
+                                name: rest.to_string(),
+                                events: events.into(),
+                            });
+                            None
+                        }
+                        Err(err) => Some(format!("{} \"{}\": {:#}", key, rest, err)),
+                    }
+                }
+                "wave" => match Wave::parse(rest, &scripts) {
+                    Ok(wave) if waves.last().is_some_and(|last| wave.at < last.at) => {
+                        Some("wave comes before the previous wave".to_string())
+                    }
+                    Ok(wave) => {
+                        waves.push(wave);
+                        None
+                    }
+                    Err(err) => Some(err.to_string()),
+                },
+                "move" => match waves.last_mut() {
+                    Some(wave) => wave.parse_move(rest).err().map(|err| err.to_string()),
+                    None => Some("move comes before any wave".to_string()),
+                },
+                _ => Some(format!("unknown key \"{}\"", key)),
+            };
+            if let Some(problem) = problem {
+                problems.push(format!("line {}: {}", index + 1, problem));
+            }
+        }
+        if name.is_none() {
+            problems.push("the pack has no name".to_string());
+        }
+        if waves.is_empty() {
+            problems.push("the pack has no waves".to_string());
+        }
+        if waves.len() > MAX_WAVES {
+            problems.push(format!("the pack has more than {} waves", MAX_WAVES));
+        }
+        if waves.last().is_some_and(|last| last.at >= road_frames) {
+            problems.push("a wave comes after the road has ended".to_string());
+        }
+        match name {
+            Some(name) if problems.is_empty() => Ok(Self {
+                name,
+                title,
+                road_frames,
+                waves,
+            }),
+            _ => Err(anyhow!("Invalid content pack:\n{}", problems.join("\n"))),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// ステージの始まりに出す見出し。なければ名前を使う
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn road_frames(&self) -> u32 {
+        self.road_frames
+    }
+
+    pub fn waves(&self) -> &[Wave] {
+        &self.waves
+    }
+}
+
+/// `events`の中身を、1行に1つずつ敵のイベントにする。`#`で始まる行と空行は読み飛ばす
+fn parse_events(source: &str) -> Result<Vec<EnemyEvent>> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| parse_event(line).map_err(|err| anyhow!("\"{}\": {}", line, err)))
+        .collect()
+}
+
+/// `<フレーム> <撃ち方> ...`
+fn parse_event(line: &str) -> Result<EnemyEvent> {
+    let fields = line.split_whitespace().collect::<Vec<_>>();
+    let at = fields[0]
+        .parse::<u16>()
+        .map_err(|_| anyhow!("the frame must be 0 to {}", u16::MAX))?;
+    let number = |field: &str| {
+        field
+            .parse::<f32>()
+            .map_err(|_| anyhow!("\"{}\" is not a number", field))
+    };
+    match fields[1..] {
+        ["nways", n, wide, "aim"] => Ok(EnemyEvent::aimed_nways(at, n.parse()?, number(wide)?)),
+        ["nways", n, wide, center] => Ok(EnemyEvent::nways(
+            at,
+            n.parse()?,
+            number(wide)?,
+            number(center)?,
+        )),
+        ["aim"] => Ok(EnemyEvent::aim_shot(at)),
+        ["shot", deg, speed] => Ok(EnemyEvent::shot(
+            at,
+            Vector::zero(),
+            number(deg)?,
+            number(speed)?,
+            false,
+            None,
+        )),
+        _ => Err(anyhow!("unknown event (expected nways, aim or shot)")),
+    }
+}
+
+/// 保存データの名前に使えるか
+fn is_valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LENGTH).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        input::Actions,
+        level::Level,
+        settings::{BombType, Modifiers, ShotType},
+    };
+
+    const STAGE_FILE: &str = include_str!("../static/stage1.txt");
+    const SAMPLE_PACK: &str = include_str!("../static/packs/sample.txt");
+
+    /// ステージのデータファイルの道中が、ゲームに組み込んである道中と同じに動くか
+    #[test]
+    fn stage_file_matches_builtin_road() {
+        let stage = Pack::parse(STAGE_FILE).unwrap();
+        let new_level = || {
+            Level::new(
+                1,
+                &[0],
+                Playfield::MAIN,
+                Modifiers::default(),
+                BombType::default(),
+                ShotType::default(),
+            )
+        };
+        let mut builtin = new_level();
+        let mut from_file = new_level().with_stage(&stage);
+        for frame in 1..=MIDBOSS_ARRIVAL_FRAME {
+            builtin.update(&[Actions::default()]);
+            from_file.update(&[Actions::default()]);
+            assert_eq!(
+                builtin.state_hash(),
+                from_file.state_hash(),
+                "The stage file differs from the built-in road at frame {}",
+                frame
+            );
+        }
+    }
+
+    #[test]
+    fn sample_pack_parses() {
+        let pack = Pack::parse(SAMPLE_PACK).unwrap();
+        assert_eq!(pack.name(), "sample");
+        assert_eq!(pack.waves().len(), 8);
+    }
+
+    #[test]
+    fn events_become_a_pattern() {
+        let pack = Pack::parse(
+            "name events\nevents burst\n30 nways 5 60 aim\n# コメント\n60 shot 90 2.5\nend\nwave 1 0.5 0.2 burst\n",
+        )
+        .unwrap();
+        assert_eq!(pack.waves()[0].events().len(), 2);
+    }
+
+    #[test]
+    fn bad_events_are_reported() {
+        let err =
+            Pack::parse("name bad\nevents burst\n30 spiral 5\n20 aim\nend\nwave 1 0.5 0.2 burst\n")
+                .err()
+                .unwrap()
+                .to_string();
+        assert!(err.contains("unknown event"), "{}", err);
+    }
+}
@@ -0,0 +1,173 @@
+/// 描画に使う色の役割。実際の色はパレットによって決まる。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    Background,      // 背景
+    Border,          // 画面枠
+    Text,            // 文字
+    Player,          // プレイヤー（通常状態）
+    Player2,         // 2人プレイ時の2P（通常状態）
+    PlayerBombing,   // プレイヤー（ボム中）
+    PlayerReloading, // プレイヤー（被弾からの復帰中）
+    PlayerShot,      // 自機が撃つ弾
+    Enemy,           // 敵
+    EnemyBullet,     // 敵弾
+    Flash,           // 画面全体のフラッシュ
+    Telegraph,       // 敵が弾を撃つ前の予告
+    Debug,           // 当たり判定などのデバッグ表示
+    Heat,            // ヒートマップの滞在マス
+    Death,           // ヒートマップの被弾位置
+    Item,            // 敵が落とすアイテム
+    Hazard,          // ステージに置かれた障害物
+    Ambient,         // 背景に舞う花びら
+    Explosion,       // ボスを倒したときの爆発
+}
+
+/// 色覚特性などに合わせて選べる配色
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Palette {
+    #[default]
+    Default,
+    Deuteranopia, // 2型色覚向け（Okabe-Itoの配色を元にしている）
+    HighContrast, // 黒背景の高コントラスト
+}
+
+/// ステージごとの色合い。選んでいる配色のうち、背景や敵などの役割の色を差し替える。
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Theme {
+    #[default]
+    Plain, // 配色をそのまま使う
+    Dusk, // 夕暮れの橙と紫
+}
+
+impl Theme {
+    pub const ALL: [Theme; 2] = [Theme::Plain, Theme::Dusk];
+
+    /// 差し替える色。見分けやすさのための配色を選んでいるときは、そちらを優先して差し替えない。
+    fn css(self, palette: Palette, color: Color) -> Option<&'static str> {
+        if palette != Palette::Default {
+            return None;
+        }
+        match (self, color) {
+            (Theme::Plain, _) => None,
+            (Theme::Dusk, Color::Background) => Some("#FFF3E6"),
+            (Theme::Dusk, Color::Border) => Some("#C07850"),
+            (Theme::Dusk, Color::Enemy) => Some("#E0609A"),
+            (Theme::Dusk, Color::EnemyBullet) => Some("#4B2A5C"),
+            (Theme::Dusk, _) => None,
+        }
+    }
+}
+
+impl Color {
+    pub const ALL: [Color; 19] = [
+        Color::Background,
+        Color::Border,
+        Color::Text,
+        Color::Player,
+        Color::Player2,
+        Color::PlayerBombing,
+        Color::PlayerReloading,
+        Color::PlayerShot,
+        Color::Enemy,
+        Color::EnemyBullet,
+        Color::Flash,
+        Color::Telegraph,
+        Color::Debug,
+        Color::Heat,
+        Color::Death,
+        Color::Item,
+        Color::Hazard,
+        Color::Ambient,
+        Color::Explosion,
+    ];
+}
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [
+        Palette::Default,
+        Palette::Deuteranopia,
+        Palette::HighContrast,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Default => "Default",
+            Palette::Deuteranopia => "Deuteranopia",
+            Palette::HighContrast => "High Contrast",
+        }
+    }
+
+    /// `theme`で差し替えたうえで、役割に対応するCSSの色を返す
+    pub fn themed_css(self, theme: Theme, color: Color) -> &'static str {
+        theme.css(self, color).unwrap_or_else(|| self.css(color))
+    }
+
+    /// 役割に対応するCSSの色を返す
+    pub fn css(self, color: Color) -> &'static str {
+        match self {
+            Palette::Default => match color {
+                Color::Background => "white",
+                Color::Border => "gray",
+                Color::Text => "black",
+                Color::Player => "red",
+                Color::Player2 => "green",
+                Color::PlayerBombing => "blue",
+                Color::PlayerReloading => "yellow",
+                Color::PlayerShot => "tomato",
+                Color::Enemy => "pink",
+                Color::EnemyBullet => "black",
+                Color::Flash => "white",
+                Color::Telegraph => "orange",
+                Color::Debug => "lime",
+                Color::Heat => "red",
+                Color::Death => "black",
+                Color::Item => "purple",
+                Color::Hazard => "saddlebrown",
+                Color::Ambient => "hotpink",
+                Color::Explosion => "orangered",
+            },
+            Palette::Deuteranopia => match color {
+                Color::Background => "white",
+                Color::Border => "gray",
+                Color::Text => "black",
+                Color::Player => "#0072B2",
+                Color::Player2 => "#009E73",
+                Color::PlayerBombing => "#56B4E9",
+                Color::PlayerReloading => "#F0E442",
+                Color::PlayerShot => "#56B4E9",
+                Color::Enemy => "#E69F00",
+                Color::EnemyBullet => "#D55E00",
+                Color::Flash => "white",
+                Color::Telegraph => "#009E73",
+                Color::Debug => "#CC79A7",
+                Color::Heat => "#0072B2",
+                Color::Death => "black",
+                Color::Item => "#CC79A7",
+                Color::Hazard => "dimgray",
+                Color::Ambient => "#CC79A7",
+                Color::Explosion => "#D55E00",
+            },
+            Palette::HighContrast => match color {
+                Color::Background => "black",
+                Color::Border => "white",
+                Color::Text => "white",
+                Color::Player => "#00FFFF",
+                Color::Player2 => "#FF8080",
+                Color::PlayerBombing => "#00FF00",
+                Color::PlayerReloading => "#FFFFFF",
+                Color::PlayerShot => "#80FFFF",
+                Color::Enemy => "#FF00FF",
+                Color::EnemyBullet => "#FFFF00",
+                Color::Flash => "white",
+                Color::Telegraph => "#FF8000",
+                Color::Debug => "#00FF00",
+                Color::Heat => "#FF8000",
+                Color::Death => "white",
+                Color::Item => "#80FF80",
+                Color::Hazard => "#C0C0C0",
+                Color::Ambient => "#FF80C0",
+                Color::Explosion => "#FFFF80",
+            },
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! 名前付きパターンの弾の動きが、記録しておいたものから変わっていないかを`cargo test`で確かめる。
+//! パターンや弾の動きを意図して変えたときは、失敗したテストが書き出す記録で`fixtures/patterns.txt`を作り直す。
+//! 記録は基準の速さで取る。`high_refresh`の機能を付けたビルドでは、同じ秒数での弾の位置が記録と近いかを確かめる。
+
+use crate::{
+    constants::frames,
+    input::Actions,
+    level::{Level, PATTERNS},
+};
+
+// 記録しておいた、名前付きパターンの弾の動き
+const FIXTURE: &str = include_str!("../fixtures/patterns.txt");
+const FORMAT_VERSION: u32 = 1; // 記録の書き方を変えたら上げて、記録を作り直す
+const SNAPSHOT_SEED: u64 = 1;
+const CHECKPOINTS: [u32; 4] = [45, 90, 180, 300]; // 弾の位置を記録するフレーム（基準の速さで）
+
+/// 名前付きパターン1つを決まったシードで`CHECKPOINTS`の最後まで動かし、各時点の弾の位置を1行ずつ並べる。
+/// 位置は小数第1位まで丸める（ブラウザごとの三角関数の細かな誤差で食い違わないように）。
+fn snapshot(pattern: usize) -> Vec<String> {
+    let mut level = Level::pattern_sandbox(SNAPSHOT_SEED, pattern);
+    let mut lines = vec![format!("pattern {}", PATTERNS[pattern].0)];
+    let mut frame = 0;
+    for checkpoint in CHECKPOINTS {
+        while frame < frames(checkpoint) {
+            level.update(&[Actions::default()]);
+            frame += 1;
+        }
+        let positions = level
+            .bullet_positions()
+            .map(|pos| format!(" {:.1},{:.1}", pos.x, pos.y))
+            .collect::<String>();
+        lines.push(format!("{}:{}", checkpoint, positions));
+    }
+    lines
+}
+
+/// すべての名前付きパターンの記録。`fixtures/patterns.txt`の中身になる
+fn record() -> String {
+    let mut lines = vec![format!("version {}", FORMAT_VERSION)];
+    for pattern in 0..PATTERNS.len() {
+        lines.extend(snapshot(pattern));
+    }
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture::assert_matches_fixture, math::Point};
+
+    // 速さの違うビルドで、同じ秒数での弾の位置がどれだけずれてよいか（ピクセル）。
+    // 1フレームごとに速度を足していくので、加速する弾は細かく刻むほど少しだけ先へ進む
+    const RATE_TOLERANCE: f32 = 4.0;
+
+    /// 記録の1行（`<フレーム>: x,y x,y ...`）の弾の位置
+    fn positions(line: &str) -> Vec<Point> {
+        let (_, positions) = line.split_once(':').unwrap();
+        positions
+            .split_whitespace()
+            .map(|pos| {
+                let (x, y) = pos.split_once(',').unwrap();
+                Point {
+                    x: x.parse().unwrap(),
+                    y: y.parse().unwrap(),
+                }
+            })
+            .collect()
+    }
+
+    /// 名前付きパターンを動かし直して、記録と食い違っていないか確かめる。
+    /// 食い違っていれば、最初に食い違った行と作り直した記録を書き出して失敗する
+    #[test]
+    #[cfg_attr(
+        feature = "high_refresh",
+        ignore = "the fixture is recorded at the standard tick rate"
+    )]
+    fn patterns_match_fixture() {
+        assert_matches_fixture(&record(), FIXTURE, "pattern snapshots");
+    }
+
+    /// 基準の速さで記録した弾の位置と、このビルドで同じ秒数だけ動かした弾の位置が近いか確かめる。
+    /// 基準の速さのビルドではそのまま一致し、`cargo test --features high_refresh`では60と120の速さを比べる
+    #[test]
+    fn positions_match_at_equal_seconds() {
+        let actual = record();
+        for (actual, expected) in actual.lines().zip(FIXTURE.lines()) {
+            if !expected.contains(':') {
+                assert_eq!(actual, expected);
+                continue;
+            }
+            let (actual_positions, expected_positions) = (positions(actual), positions(expected));
+            assert_eq!(
+                actual_positions.len(),
+                expected_positions.len(),
+                "Bullet count differs at equal seconds:\nexpected: {}\n  actual: {}",
+                expected,
+                actual
+            );
+            for (a, e) in actual_positions.iter().zip(&expected_positions) {
+                assert!(
+                    (a.x - e.x).abs() <= RATE_TOLERANCE && (a.y - e.y).abs() <= RATE_TOLERANCE,
+                    "Bullet moved differently at equal seconds:\nexpected: {}\n  actual: {}",
+                    expected,
+                    actual
+                );
+            }
+        }
+    }
+}
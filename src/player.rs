@@ -1,58 +1,255 @@
 use crate::{
-    engine::{KeyState, Renderer},
-    level::Bullet,
-    math::Point,
+    collision::{Body, Layer},
+    constants::{PLAYER_SPEED, SHOT_INTERVAL},
+    engine::Renderer,
+    input::Actions,
+    math::{Point, Vector},
+    palette::Color,
+    playfield::Playfield,
+    settings::{BombType, Modifiers, ShotType},
 };
 
+pub use self::player_states::HITBOX_RADIUS;
 use self::player_states::*;
 
+const INITIAL_LIVES: u8 = 3;
+const INITIAL_BOMBS: u8 = 3;
+const MAX_LIVES: u8 = 8;
+const MAX_BOMBS: u8 = 8;
+pub const BOMB_FRAGMENTS: u8 = 3; // ボムが1つ増えるのに必要なかけらの数
+pub const LIFE_FRAGMENTS: u8 = 3; // 残機が1つ増えるのに必要なかけらの数
+const SEAL_RADIUS: f32 = 60.0; // 封印ボムで敵弾を得点アイテムに変える輪の半径
+const SHIELD_RADIUS: f32 = 18.0; // シールドを持っている間、自機の周りに描く輪の半径
+
+#[derive(Clone)]
 pub struct Player {
+    number: usize, // 何人目のプレイヤーか（0なら1P）
     state_machine: PlayerStateMachine,
+    lives: u8,          // 残機。0になったらこのプレイヤーはゲームオーバー
+    bombs: u8,          // 残りボム数。被弾すると元に戻る
+    initial_bombs: u8,  // 最初と被弾したあとのボム数（ボムなしのルールでは0）
+    bomb_fragments: u8, // 集めたボムのかけら。被弾しても減らない
+    life_fragments: u8, // 集めた残機のかけら
+    shot_cooldown: u8,  // 次のショットを撃てるまでの残りフレーム数
+    bomb_type: BombType,
+    shot_type: ShotType,
 }
 
 impl Player {
-    pub fn new() -> Self {
+    /// `number`は何人目のプレイヤーか、`spawn`は出現・復帰する位置
+    pub fn new(
+        number: usize,
+        spawn: Point,
+        playfield: Playfield,
+        modifiers: Modifiers,
+        bomb_type: BombType,
+        shot_type: ShotType,
+    ) -> Self {
+        let color = if number == 0 {
+            Color::Player
+        } else {
+            Color::Player2
+        };
+        let initial_bombs = if modifiers.no_bombs { 0 } else { INITIAL_BOMBS };
         Self {
-            state_machine: PlayerStateMachine::Alive(PlayerState::new()),
+            number,
+            state_machine: PlayerStateMachine::Alive(PlayerState::new(spawn, color, playfield)),
+            lives: if modifiers.one_life { 1 } else { INITIAL_LIVES },
+            bombs: initial_bombs,
+            initial_bombs,
+            bomb_fragments: 0,
+            life_fragments: 0,
+            shot_cooldown: 0,
+            bomb_type,
+            shot_type,
         }
     }
 
-    pub fn draw(&self, renderer: &Renderer) {
-        self.state_machine.draw(renderer);
+    pub fn draw(&self, renderer: &dyn Renderer) {
+        if !self.is_out() {
+            self.state_machine.draw(renderer);
+        }
+        if self.is_shielded() {
+            renderer.set_color(Color::Item);
+            renderer.draw_circle(&self.position(), SHIELD_RADIUS);
+        }
+        if let Some(radius) = self.seal_radius() {
+            renderer.set_color(Color::PlayerBombing);
+            renderer.draw_circle(&self.position(), radius);
+        }
     }
 
     pub fn update(&mut self, vx: f32, vy: f32) {
+        self.shot_cooldown = self.shot_cooldown.saturating_sub(1);
         self.state_machine = self.state_machine.update().set_velocity(vx, vy);
     }
 
-    pub fn bomb(&mut self) {
+    /// 壁が動いてプレイフィールドの動ける範囲が変わったことを伝える。次の更新から新しい範囲に収める
+    pub fn set_playfield(&mut self, playfield: Playfield) {
+        self.state_machine = self.state_machine.set_playfield(playfield);
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.is_out() && matches!(self.state_machine, PlayerStateMachine::Alive(_))
+    }
+
+    /// 残機がなくなったか
+    pub fn is_out(&self) -> bool {
+        self.lives == 0
+    }
+
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    pub fn lives(&self) -> u8 {
+        self.lives
+    }
+
+    pub fn bombs(&self) -> u8 {
+        self.bombs
+    }
+
+    /// ボムを最大数まで戻す
+    pub fn refill_bombs(&mut self) {
+        if !self.is_out() {
+            self.bombs = self.initial_bombs;
+        }
+    }
+
+    /// アイテムで残機を1つ増やす
+    pub fn add_life(&mut self) {
+        if !self.is_out() {
+            self.lives = (self.lives + 1).min(MAX_LIVES);
+        }
+    }
+
+    /// アイテムでボムを1つ増やす。ボムなしのルールでは増えない。
+    pub fn add_bomb(&mut self) {
+        if !self.is_out() && self.initial_bombs > 0 {
+            self.bombs = (self.bombs + 1).min(MAX_BOMBS);
+        }
+    }
+
+    pub fn bomb_fragments(&self) -> u8 {
+        self.bomb_fragments
+    }
+
+    pub fn life_fragments(&self) -> u8 {
+        self.life_fragments
+    }
+
+    /// アイテムでシールドを付ける。いくつ取っても防げるのは1度だけ
+    pub fn add_shield(&mut self) {
+        if !self.is_out() {
+            self.state_machine = self.state_machine.map_context(PlayerContext::shield);
+        }
+    }
+
+    pub fn is_shielded(&self) -> bool {
+        !self.is_out() && self.state_machine.context().is_shielded()
+    }
+
+    /// シールドが壊れた直後で、まだ被弾しない間か
+    pub fn is_protected(&self) -> bool {
+        self.state_machine.context().is_protected()
+    }
+
+    /// ボムのかけらを1つ集める。`BOMB_FRAGMENTS`個そろったらボムが1つ増える。
+    pub fn add_bomb_fragment(&mut self) {
+        if self.is_out() {
+            return;
+        }
+        self.bomb_fragments += 1;
+        if self.bomb_fragments >= BOMB_FRAGMENTS {
+            self.bomb_fragments = 0;
+            self.add_bomb();
+        }
+    }
+
+    /// 残機のかけらを1つ集める。`LIFE_FRAGMENTS`個そろったら残機が1つ増える。
+    pub fn add_life_fragment(&mut self) {
+        if self.is_out() {
+            return;
+        }
+        self.life_fragments += 1;
+        if self.life_fragments >= LIFE_FRAGMENTS {
+            self.life_fragments = 0;
+            self.add_life();
+        }
+    }
+
+    /// ボムを使う。使えたら`true`を返す。
+    pub fn bomb(&mut self) -> bool {
+        if !self.is_alive() || self.bombs == 0 {
+            return false;
+        }
+        self.bombs -= 1;
         self.state_machine = self.state_machine.transition(PlayerEvent::Bomb);
+        true
+    }
+
+    /// ショットを撃つ。前に撃ってから`SHOT_INTERVAL`フレーム経っていれば`true`を返す。
+    /// 被弾からの復帰中は撃てない
+    pub fn shoot(&mut self) -> bool {
+        let reloading = matches!(self.state_machine, PlayerStateMachine::Reloading(_));
+        if self.is_out() || reloading || self.shot_cooldown > 0 {
+            return false;
+        }
+        self.shot_cooldown = SHOT_INTERVAL;
+        true
+    }
+
+    /// 封印ボムの最中なら、敵弾を得点アイテムに変える輪の半径を返す
+    pub fn seal_radius(&self) -> Option<f32> {
+        let bombing =
+            !self.is_out() && matches!(self.state_machine, PlayerStateMachine::Bombing(_));
+        (bombing && self.bomb_type == BombType::Seal).then_some(SEAL_RADIUS)
     }
 
-    pub fn hit(&mut self) {
+    /// 被弾させる。シールドがあれば代わりにシールドを壊して`false`を返し、残機が減ったら`true`を返す
+    pub fn hit(&mut self) -> bool {
+        if !self.is_alive() {
+            return false;
+        }
+        if self.is_shielded() {
+            self.state_machine = self.state_machine.map_context(PlayerContext::break_shield);
+            return false;
+        }
+        self.lives -= 1;
+        self.bombs = self.initial_bombs;
         self.state_machine = self.state_machine.transition(PlayerEvent::Hit);
+        true
     }
 
-    pub fn is_collided(&self, bullet: &Bullet) -> bool {
-        self.state_machine
-            .context()
-            .is_collided(&bullet.pos(), 10.0)
+    /// ステージの中で`index`番目のプレイヤーとしての当たり判定
+    pub fn hurtbox(&self, index: usize) -> Body {
+        Body::circle(Layer::PlayerHurtbox, index, self.position(), HITBOX_RADIUS)
     }
 
-    pub fn get_aim_rad(&self, point: &Point) -> f32 {
-        self.state_machine.context().get_aim_rad(point)
+    pub fn position(&self) -> Point {
+        self.state_machine.context().position()
     }
 
-    pub fn calc_velocity(keystate: &KeyState) -> (f32, f32) {
-        let w = keystate.is_pressed("KeyW");
-        let a = keystate.is_pressed("KeyA");
-        let s = keystate.is_pressed("KeyS");
-        let d = keystate.is_pressed("KeyD");
-        let slow_factor = if keystate.is_pressed("KeyK") {
-            0.6
-        } else {
-            1.0
-        };
+    pub fn velocity(&self) -> Vector {
+        self.state_machine.context().velocity()
+    }
+
+    pub fn shot_type(&self) -> ShotType {
+        self.shot_type
+    }
+
+    /// デバッグ表示用に当たり判定を描く
+    pub fn draw_hitbox(&self, renderer: &dyn Renderer) {
+        renderer.draw_circle(&self.state_machine.context().position(), HITBOX_RADIUS);
+    }
+
+    pub fn calc_velocity(actions: &Actions) -> (f32, f32) {
+        let w = actions.up;
+        let a = actions.left;
+        let s = actions.down;
+        let d = actions.right;
+        let slow_factor = if actions.focus { 0.6 } else { 1.0 };
         let x_direction = match (a, d) {
             (true, true) | (false, false) => 0.0,
             (true, false) => -1.0,
@@ -69,8 +266,8 @@ impl Player {
         } else {
             1.0
         };
-        let velocity_x = 6.0 * x_direction * diag_factor * slow_factor;
-        let velocity_y = 6.0 * y_direction * diag_factor * slow_factor;
+        let velocity_x = PLAYER_SPEED * x_direction * diag_factor * slow_factor;
+        let velocity_y = PLAYER_SPEED * y_direction * diag_factor * slow_factor;
         (velocity_x, velocity_y)
     }
 }
@@ -101,10 +298,11 @@ impl From<PlayerState<Reloading>> for PlayerStateMachine {
 }
 
 pub enum PlayerEvent {
-    Bomb,           // ボム
-    Hit,            // 被弾
-    Update,         // フレームごとの更新
-    Move(f32, f32), // プレイヤー速度の更新
+    Bomb,                 // ボム
+    Hit,                  // 被弾
+    Update,               // フレームごとの更新
+    Move(f32, f32),       // プレイヤー速度の更新
+    Constrain(Playfield), // 動ける範囲の更新
 }
 
 impl PlayerStateMachine {
@@ -129,6 +327,17 @@ impl PlayerStateMachine {
             (PlayerStateMachine::Bombing(state), PlayerEvent::Update) => state.update().into(),
             (PlayerStateMachine::Reloading(state), PlayerEvent::Update) => state.update().into(),
 
+            // 動ける範囲の更新も、すべての状態に行う。
+            (PlayerStateMachine::Alive(state), PlayerEvent::Constrain(playfield)) => {
+                state.set_playfield(playfield).into()
+            }
+            (PlayerStateMachine::Bombing(state), PlayerEvent::Constrain(playfield)) => {
+                state.set_playfield(playfield).into()
+            }
+            (PlayerStateMachine::Reloading(state), PlayerEvent::Constrain(playfield)) => {
+                state.set_playfield(playfield).into()
+            }
+
             // 他の場合は状態を変えない。
             _ => self,
         }
@@ -136,9 +345,9 @@ impl PlayerStateMachine {
 
     fn context(&self) -> &PlayerContext {
         match self {
-            PlayerStateMachine::Alive(state) => &state.context(),
-            PlayerStateMachine::Bombing(state) => &state.context(),
-            PlayerStateMachine::Reloading(state) => &state.context(),
+            PlayerStateMachine::Alive(state) => state.context(),
+            PlayerStateMachine::Bombing(state) => state.context(),
+            PlayerStateMachine::Reloading(state) => state.context(),
         }
     }
 
@@ -150,7 +359,20 @@ impl PlayerStateMachine {
         self.transition(PlayerEvent::Move(vx, vy))
     }
 
-    fn draw(&self, renderer: &Renderer) {
+    fn set_playfield(self, playfield: Playfield) -> Self {
+        self.transition(PlayerEvent::Constrain(playfield))
+    }
+
+    /// 状態は変えずに、持っている情報だけを書き換える
+    fn map_context(self, f: impl FnOnce(PlayerContext) -> PlayerContext) -> Self {
+        match self {
+            PlayerStateMachine::Alive(state) => state.map_context(f).into(),
+            PlayerStateMachine::Bombing(state) => state.map_context(f).into(),
+            PlayerStateMachine::Reloading(state) => state.map_context(f).into(),
+        }
+    }
+
+    fn draw(&self, renderer: &dyn Renderer) {
         match self {
             PlayerStateMachine::Alive(state) => state.draw(renderer),
             PlayerStateMachine::Bombing(state) => state.draw(renderer),
@@ -163,15 +385,16 @@ mod player_states {
     use std::marker::PhantomData;
 
     use crate::{
+        constants::{BOMB_FRAMES, PLAYER_BLINK_FRAMES, RESPAWN_FRAMES},
         engine::Renderer,
-        math::{Point, Rect},
+        math::{Point, Rect, Vector},
+        palette::Color,
+        playfield::Playfield,
     };
 
     use super::PlayerStateMachine;
-    const FLOOR: f32 = 475.0;
-    const NORMAL_LOOP: u8 = 30;
-    const RELOAD_TIME: u8 = 120;
-    const BOMB_TIME: u8 = 60;
+    pub const HITBOX_RADIUS: f32 = 3.0; // 当たり判定の半径
+    const SHIELD_GRACE_FRAMES: u8 = 60; // シールドが壊れてから、次に被弾するようになるまでのフレーム数
 
     #[derive(Clone, Copy)]
     pub struct PlayerState<S> {
@@ -184,6 +407,11 @@ mod player_states {
         frame: u8,
         position: Point,
         velocity: Point,
+        spawn: Point, // 出現・復帰する位置
+        color: Color, // 通常状態の色
+        playfield: Playfield,
+        shielded: bool,   // 次の被弾を1度だけ防ぐシールドを持っているか
+        shield_grace: u8, // シールドが壊れてから、被弾しない残りフレーム数
     }
 
     impl PlayerContext {
@@ -196,22 +424,32 @@ mod player_states {
             self.position.x += self.velocity.x;
             self.position.y += self.velocity.y;
 
-            if self.position.x < 50.0 {
-                self.position.x = 50.0;
-            }
-            if self.position.x > 550.0 {
-                self.position.x = 550.0;
-            }
-            if self.position.y < 30.0 {
-                self.position.y = 30.0;
-            }
-            if self.position.y > 570.0 {
-                self.position.y = 570.0;
-            }
+            self.position = self.playfield.clamp(self.position);
+            self.shield_grace = self.shield_grace.saturating_sub(1);
 
             self
         }
 
+        pub fn shield(mut self) -> Self {
+            self.shielded = true;
+            self
+        }
+
+        /// シールドを壊し、同じ弾や障害物に続けて当たらないよう少しの間は被弾しないようにする
+        pub fn break_shield(mut self) -> Self {
+            self.shielded = false;
+            self.shield_grace = SHIELD_GRACE_FRAMES;
+            self
+        }
+
+        pub fn is_shielded(&self) -> bool {
+            self.shielded
+        }
+
+        pub fn is_protected(&self) -> bool {
+            self.shield_grace > 0
+        }
+
         fn set_pos(mut self, point: Point) -> Self {
             self.position.x = point.x;
             self.position.y = point.y;
@@ -223,18 +461,12 @@ mod player_states {
             self
         }
 
-        pub fn is_collided(&self, point: &Point, radius: f32) -> bool {
-            let dx = self.position.x - point.x;
-            let dy = self.position.y - point.y;
-            let distance = dx * dx + dy * dy;
-            let r = radius + 3.0;
-            distance < r * r
+        pub fn position(&self) -> Point {
+            self.position
         }
 
-        pub fn get_aim_rad(&self, point: &Point) -> f32 {
-            let dx = self.position.x - point.x;
-            let dy = self.position.y - point.y;
-            dy.atan2(dx)
+        pub fn velocity(&self) -> Vector {
+            Vector::new(self.velocity.x, self.velocity.y)
         }
     }
 
@@ -255,15 +487,30 @@ mod player_states {
             self.context.velocity.y = vy;
             self
         }
+
+        pub fn set_playfield(mut self, playfield: Playfield) -> Self {
+            self.context.playfield = playfield;
+            self
+        }
+
+        pub fn map_context(mut self, f: impl FnOnce(PlayerContext) -> PlayerContext) -> Self {
+            self.context = f(self.context);
+            self
+        }
     }
 
     impl PlayerState<Alive> {
-        pub fn new() -> Self {
+        pub fn new(spawn: Point, color: Color, playfield: Playfield) -> Self {
             PlayerState {
                 context: PlayerContext {
                     frame: 0,
-                    position: Point { x: 300.0, y: FLOOR },
+                    position: spawn,
                     velocity: Point { x: 0.0, y: 0.0 },
+                    spawn,
+                    color,
+                    playfield,
+                    shielded: false,
+                    shield_grace: 0,
                 },
                 _state: PhantomData,
             }
@@ -284,12 +531,12 @@ mod player_states {
         }
 
         pub fn update(mut self) -> Self {
-            self.context = self.context.update(NORMAL_LOOP);
+            self.context = self.context.update(PLAYER_BLINK_FRAMES);
             self
         }
 
-        pub fn draw(&self, renderer: &Renderer) {
-            renderer.set_color("red");
+        pub fn draw(&self, renderer: &dyn Renderer) {
+            renderer.set_color(self.context.color);
             let center = &Point {
                 x: self.context.position.x,
                 y: self.context.position.y,
@@ -301,16 +548,16 @@ mod player_states {
                 height: -20.0 - self.context.frame as f32,
             });
 
-            renderer.draw_circle(center, 3.0);
+            renderer.draw_circle(center, HITBOX_RADIUS);
         }
     }
 
     impl PlayerState<Bombing> {
         pub fn update(mut self) -> BombingEndState {
-            self.context = self.context.update(BOMB_TIME);
+            self.context = self.context.update(BOMB_FRAMES);
 
-            // `BOMB_TIME`経過したら通常状態へ。そうでないならまだボム中。
-            if self.context.frame >= BOMB_TIME {
+            // `BOMB_FRAMES`経過したら通常状態へ。そうでないならまだボム中。
+            if self.context.frame >= BOMB_FRAMES {
                 BombingEndState::Complete(self.end_bomb())
             } else {
                 BombingEndState::Bombing(self)
@@ -324,8 +571,8 @@ mod player_states {
             }
         }
 
-        pub fn draw(&self, renderer: &Renderer) {
-            renderer.set_color("blue");
+        pub fn draw(&self, renderer: &dyn Renderer) {
+            renderer.set_color(Color::PlayerBombing);
             let center = &Point {
                 x: self.context.position.x,
                 y: self.context.position.y,
@@ -337,7 +584,7 @@ mod player_states {
                 height: -20.0 - self.context.frame as f32,
             });
 
-            renderer.draw_circle(center, 3.0);
+            renderer.draw_circle(center, HITBOX_RADIUS);
         }
     }
 
@@ -348,10 +595,10 @@ mod player_states {
 
     impl PlayerState<Reloading> {
         pub fn update(mut self) -> ReloadEndState {
-            self.context = self.context.update(RELOAD_TIME);
+            self.context = self.context.update(RESPAWN_FRAMES);
 
-            // `RELOAD_TIME`経過したら通常状態へ。そうでないならまだ復帰中。
-            if self.context.frame >= RELOAD_TIME {
+            // `RESPAWN_FRAMES`経過したら通常状態へ。そうでないならまだ復帰中。
+            if self.context.frame >= RESPAWN_FRAMES {
                 ReloadEndState::Complete(self.end_reload())
             } else {
                 ReloadEndState::Reloading(self)
@@ -360,19 +607,16 @@ mod player_states {
 
         pub fn end_reload(self) -> PlayerState<Alive> {
             PlayerState {
-                context: self
-                    .context
-                    .reset_frame()
-                    .set_pos(Point { x: 300.0, y: FLOOR }),
+                context: self.context.reset_frame().set_pos(self.context.spawn),
                 _state: PhantomData,
             }
         }
 
-        pub fn draw(&self, renderer: &Renderer) {
-            renderer.set_color("yellow");
+        pub fn draw(&self, renderer: &dyn Renderer) {
+            renderer.set_color(Color::PlayerReloading);
             let center = &Point {
-                x: 300.0,
-                y: FLOOR + (RELOAD_TIME - self.context.frame) as f32,
+                x: self.context.spawn.x,
+                y: self.context.spawn.y + (RESPAWN_FRAMES - self.context.frame) as f32,
             };
             renderer.draw_rect(&Rect {
                 x: center.x - 10.0,
@@ -381,7 +625,7 @@ mod player_states {
                 height: -20.0,
             });
 
-            renderer.draw_circle(center, 3.0);
+            renderer.draw_circle(center, HITBOX_RADIUS);
         }
     }
 
@@ -408,3 +652,95 @@ mod player_states {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::RESPAWN_FRAMES,
+        engine::{DrawCall, RecordingRenderer},
+        math::Rect,
+        palette::{Palette, Theme},
+    };
+
+    const SPAWN: Point = Point { x: 300.0, y: 500.0 };
+
+    fn player() -> Player {
+        Player::new(
+            0,
+            SPAWN,
+            Playfield::MAIN,
+            Modifiers::default(),
+            BombType::default(),
+            ShotType::default(),
+        )
+    }
+
+    /// 自機を描いたときの描画命令
+    fn draw(player: &Player) -> Vec<DrawCall> {
+        let renderer = RecordingRenderer::default();
+        player.draw(&renderer);
+        renderer.take()
+    }
+
+    /// 描画命令のうち、`color`を選んでから描いた四角
+    fn rects_in(calls: &[DrawCall], color: Color) -> Vec<Rect> {
+        let mut current = None;
+        calls
+            .iter()
+            .filter_map(|call| match call {
+                DrawCall::SetColor(selected) => {
+                    current = Some(*selected);
+                    None
+                }
+                DrawCall::DrawRect(rect) if current == Some(color) => Some(*rect),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 被弾して復帰中の自機は、被弾した位置ではなく復帰する位置の列に黄色で描く
+    #[test]
+    fn reloading_player_draws_yellow_at_respawn_column() {
+        let mut player = player();
+        for _ in 0..30 {
+            player.update(PLAYER_SPEED, 0.0);
+        }
+        assert!(player.position().x > SPAWN.x);
+        assert!(player.hit());
+
+        let calls = draw(&player);
+        let rects = rects_in(&calls, Color::PlayerReloading);
+        assert_eq!(rects.len(), 1, "{:?}", calls);
+        assert_eq!(rects[0].x + rects[0].width / 2.0, SPAWN.x);
+        assert!(rects_in(&calls, Color::Player).is_empty());
+        assert_eq!(
+            Palette::default().themed_css(Theme::default(), Color::PlayerReloading),
+            "yellow"
+        );
+    }
+
+    /// ボムの最中はボムの色で描き、終われば自機の色に戻る
+    #[test]
+    fn bombing_player_draws_in_bomb_color() {
+        let mut player = player();
+        assert_eq!(rects_in(&draw(&player), Color::Player).len(), 1);
+        assert!(player.bomb());
+        let calls = draw(&player);
+        assert_eq!(rects_in(&calls, Color::PlayerBombing).len(), 1);
+        assert!(rects_in(&calls, Color::Player).is_empty());
+    }
+
+    /// 残機がなくなった自機は描かない
+    #[test]
+    fn player_out_of_lives_draws_nothing() {
+        let mut player = player();
+        while !player.is_out() {
+            assert!(player.hit());
+            for _ in 0..=RESPAWN_FRAMES {
+                player.update(0.0, 0.0);
+            }
+        }
+        assert!(draw(&player).is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::math::{Point, Rect};
+
+const DRAW_MARGIN: f32 = 16.0; // プレイフィールドの外でも描く幅（枠の上にはみ出して見える分）
+
+/// 左右から迫り出して、プレイフィールドを一時的に狭める壁の厚さ
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Walls {
+    pub left: f32,
+    pub right: f32,
+}
+
+/// プレイヤーや弾が動ける範囲。画面上のどこに置くかもこれで決まる。
+/// 壁が迫り出している間は、その内側だけがプレイヤーの動ける範囲で、弾も壁の中には入れない。
+#[derive(Clone, Copy)]
+pub struct Playfield {
+    rect: Rect,
+    walls: Walls,
+}
+
+impl Playfield {
+    /// 1つの画面で遊ぶときの標準のプレイフィールド
+    pub const MAIN: Playfield = Playfield {
+        rect: Rect {
+            x: 50.0,
+            y: 30.0,
+            width: 500.0,
+            height: 540.0,
+        },
+        walls: Walls {
+            left: 0.0,
+            right: 0.0,
+        },
+    };
+
+    /// 対戦モードで画面を左右に分けたときのプレイフィールド
+    pub const VERSUS: [Playfield; 2] = [
+        Playfield {
+            rect: Rect {
+                x: 10.0,
+                y: 30.0,
+                width: 285.0,
+                height: 540.0,
+            },
+            walls: Walls {
+                left: 0.0,
+                right: 0.0,
+            },
+        },
+        Playfield {
+            rect: Rect {
+                x: 305.0,
+                y: 30.0,
+                width: 285.0,
+                height: 540.0,
+            },
+            walls: Walls {
+                left: 0.0,
+                right: 0.0,
+            },
+        },
+    ];
+
+    pub fn rect(&self) -> &Rect {
+        &self.rect
+    }
+
+    /// 壁を`walls`の厚さにしたプレイフィールド。左右の壁が重ならないよう、厚さは合わせて幅までに抑える
+    pub fn with_walls(mut self, walls: Walls) -> Self {
+        let left = walls.left.clamp(0.0, self.rect.width);
+        self.walls = Walls {
+            left,
+            right: walls.right.clamp(0.0, self.rect.width - left),
+        };
+        self
+    }
+
+    pub fn walls(&self) -> Walls {
+        self.walls
+    }
+
+    /// 壁に塞がれていない、動ける範囲
+    pub fn open_rect(&self) -> Rect {
+        Rect {
+            x: self.open_left(),
+            y: self.rect.y,
+            width: self.open_right() - self.open_left(),
+            height: self.rect.height,
+        }
+    }
+
+    /// 壁の内側の左端
+    pub fn open_left(&self) -> f32 {
+        self.left() + self.walls.left
+    }
+
+    /// 壁の内側の右端
+    pub fn open_right(&self) -> f32 {
+        self.right() - self.walls.right
+    }
+
+    pub fn left(&self) -> f32 {
+        self.rect.x
+    }
+
+    pub fn right(&self) -> f32 {
+        self.rect.x + self.rect.width
+    }
+
+    pub fn top(&self) -> f32 {
+        self.rect.y
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.rect.y + self.rect.height
+    }
+
+    pub fn center_x(&self) -> f32 {
+        self.rect.x + self.rect.width / 2.0
+    }
+
+    pub fn center_y(&self) -> f32 {
+        self.rect.y + self.rect.height / 2.0
+    }
+
+    /// `point`が壁に塞がれていない範囲にあるか
+    pub fn contains(&self, point: &Point) -> bool {
+        point.x >= self.open_left()
+            && point.x <= self.open_right()
+            && point.y >= self.top()
+            && point.y <= self.bottom()
+    }
+
+    /// `point`を中心とする半径`radius`の円が、描いて見える範囲に少しでも入っているか。
+    /// 入っていないものは描画命令を出さずに済ませる。
+    pub fn is_drawn(&self, point: &Point, radius: f32) -> bool {
+        let reach = radius + DRAW_MARGIN;
+        point.x >= self.left() - reach
+            && point.x <= self.right() + reach
+            && point.y >= self.top() - reach
+            && point.y <= self.bottom() + reach
+    }
+
+    /// `point`をプレイフィールドの壁の内側に収める
+    pub fn clamp(&self, point: Point) -> Point {
+        Point {
+            x: point.x.clamp(self.open_left(), self.open_right()),
+            y: point.y.clamp(self.top(), self.bottom()),
+        }
+    }
+}
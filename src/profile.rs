@@ -0,0 +1,152 @@
+use anyhow::{anyhow, Result};
+
+use crate::{browser, constants::rate_key, version};
+
+const STORAGE_KEY: &str = "profile";
+// 保存形式の版
+//   0: 版の印がない。スコア、名前、見た演出の欄は後から加えたので、ない場合がある
+//   1: 先頭に版の印を付けた。すべての欄がそろう
+const FORMAT_VERSION: u32 = 1;
+
+/// 飛ばせる演出。一度最後まで見たか飛ばしたものは、確かめずに飛ばせる
+#[derive(Clone, Copy)]
+pub enum Scene {
+    Credits, // 最後のステージのあとのスタッフロール
+}
+
+impl Scene {
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// プレイをまたいで残す記録
+#[derive(Clone, Default)]
+pub struct Profile {
+    clears: u32,              // 記録対象の条件でクリアした回数
+    one_cc: bool,             // コンティニューなしでクリアしたことがあるか
+    best_score: u64,          // ルール変更なしでの最高スコア
+    custom_best_score: u64,   // ルール変更ありでの最高スコア
+    best_name: String,        // ルール変更なしでの最高スコアを出した人の名前（空白を含まない）
+    custom_best_name: String, // ルール変更ありでの最高スコアを出した人の名前
+    seen_scenes: u32,         // 見たことのある演出（`Scene`ごとのビット）
+}
+
+impl Profile {
+    pub fn clears(&self) -> u32 {
+        self.clears
+    }
+
+    pub fn has_one_cc(&self) -> bool {
+        self.one_cc
+    }
+
+    /// プレイの最終スコアを、ルール変更の有無で分けた記録に反映する。更新したら`true`を返す。
+    pub fn record_score(&mut self, score: u64, custom: bool) -> bool {
+        let best = if custom {
+            &mut self.custom_best_score
+        } else {
+            &mut self.best_score
+        };
+        let improved = score > *best;
+        *best = (*best).max(score);
+        improved
+    }
+
+    /// ルール変更の有無で分けた最高スコアと、それを出した人の名前。まだ記録がなければ`None`
+    pub fn best(&self, custom: bool) -> Option<(u64, String)> {
+        let (score, name) = if custom {
+            (self.custom_best_score, &self.custom_best_name)
+        } else {
+            (self.best_score, &self.best_name)
+        };
+        (score > 0).then(|| (score, name.clone()))
+    }
+
+    /// 最高スコアを出した人の名前を付ける
+    pub fn set_best_name(&mut self, custom: bool, name: &str) {
+        // 保存の形式では空白で区切るので、名前には空白を入れない
+        let name = name.replace(' ', "");
+        if custom {
+            self.custom_best_name = name;
+        } else {
+            self.best_name = name;
+        }
+    }
+
+    pub fn has_seen(&self, scene: Scene) -> bool {
+        self.seen_scenes & scene.bit() != 0
+    }
+
+    pub fn mark_seen(&mut self, scene: Scene) {
+        self.seen_scenes |= scene.bit();
+    }
+
+    pub fn record_clear(&mut self, one_cc: bool) {
+        self.clears = self.clears.saturating_add(1);
+        self.one_cc |= one_cc;
+    }
+
+    /// `v<版> クリア回数 1ccしたか(0か1) 最高スコア ルール変更ありの最高スコア 名前 ルール変更ありの名前 見た演出`の形の文字列
+    pub fn serialize(&self) -> String {
+        let body = format!(
+            "{} {} {} {} {} {} {}",
+            self.clears,
+            self.one_cc as u8,
+            self.best_score,
+            self.custom_best_score,
+            self.best_name,
+            self.custom_best_name,
+            self.seen_scenes
+        );
+        version::tag(FORMAT_VERSION, &body)
+    }
+
+    pub fn deserialize(text: &str) -> Result<Self> {
+        let (version, text) = version::split("profile", text, FORMAT_VERSION)?;
+        let mut fields = text.split(' ');
+        let mut next = || fields.next().ok_or_else(|| anyhow!("Malformed profile"));
+        let clears = next()?.parse()?;
+        let one_cc = next()? == "1";
+        // 後から加えた欄は、版0の保存データにはないことがある
+        let mut later = |default: &'static str| match next() {
+            Ok(field) => Ok(field),
+            Err(_) if version == 0 => Ok(default),
+            Err(err) => Err(err),
+        };
+        let best_score = later("0")?.parse()?;
+        let custom_best_score = later("0")?.parse()?;
+        let best_name = later("")?.to_string();
+        let custom_best_name = later("")?.to_string();
+        let seen_scenes = later("0")?.parse()?;
+        Ok(Self {
+            clears,
+            one_cc,
+            best_score,
+            custom_best_score,
+            best_name,
+            custom_best_name,
+            seen_scenes,
+        })
+    }
+
+    /// コンテンツパックで遊ぶときは、パックの名前ごとに別の記録を残す。基準と違う速さのビルドでも別に残す
+    fn storage_key(pack: Option<&str>) -> String {
+        rate_key(match pack {
+            Some(pack) => format!("{}.pack.{}", STORAGE_KEY, pack),
+            None => STORAGE_KEY.to_string(),
+        })
+    }
+
+    /// 保存された記録を読み込む。まだなければ空の記録を返す。
+    pub fn load(pack: Option<&str>) -> Result<Self> {
+        Ok(browser::storage_get(&Self::storage_key(pack))?
+            .map(|text| Self::deserialize(&text))
+            .transpose()?
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, pack: Option<&str>) -> Result<()> {
+        browser::storage_set(&Self::storage_key(pack), &self.serialize())
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+
+use crate::settings::QualitySetting;
+
+const WINDOW_FRAMES: usize = 120; // 平均をとる直近のフレーム数
+const MAX_SAMPLE_MS: f32 = 250.0; // これより長い間隔はタブを隠していたなどとみなして数えない
+const DEGRADE_FPS: f32 = 55.0; // 平均がこれを下回ったら軽くする
+const RECOVER_FPS: f32 = 59.0; // 軽くしたあと、平均がこれを上回ったら元に戻す
+
+/// 実際に使う描画の品質
+#[derive(Clone, Copy, PartialEq)]
+pub enum Quality {
+    High,
+    Low, // 背景の飾りや浮かぶ数字を省き、弾をまとめて描く
+}
+
+/// 直近のフレームの間隔を測り、60fpsを保てているかを見る
+#[derive(Default)]
+pub struct FrameMonitor {
+    samples: VecDeque<f32>, // フレームの間隔（ミリ秒）
+    degraded: bool,         // 自動で軽くしているか
+}
+
+impl FrameMonitor {
+    pub fn record(&mut self, delta_ms: f32) {
+        if delta_ms > MAX_SAMPLE_MS {
+            return;
+        }
+        self.samples.push_back(delta_ms);
+        if self.samples.len() > WINDOW_FRAMES {
+            self.samples.pop_front();
+        }
+        if self.samples.len() < WINDOW_FRAMES {
+            return;
+        }
+        let average = self.samples.iter().sum::<f32>() / self.samples.len() as f32;
+        let fps = 1000.0 / average;
+        // 切り替えた直後の揺れで行ったり来たりしないよう、戻す条件を厳しくしておく
+        let threshold = if self.degraded {
+            RECOVER_FPS
+        } else {
+            DEGRADE_FPS
+        };
+        let degraded = fps < threshold;
+        if degraded != self.degraded {
+            self.degraded = degraded;
+            self.samples.clear();
+        }
+    }
+
+    pub fn quality(&self, setting: QualitySetting) -> Quality {
+        match setting {
+            QualitySetting::Auto if self.degraded => Quality::Low,
+            QualitySetting::Auto | QualitySetting::High => Quality::High,
+            QualitySetting::Low => Quality::Low,
+        }
+    }
+}
@@ -0,0 +1,398 @@
+//! 1フレーム分の描画命令の並び。ゲームはいったんここに描き、まとめ直してから実際の描画先で描く。
+//! 文字列にもでき、文字列では1行に1命令で、先頭の記号が命令の種類を表す。
+//! ステージをWeb Workerで進めるとき（`worker`）は、この文字列をWorkerからメインスレッドに送る。
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    engine::{DrawCall, Renderer},
+    math::{Point, Rect},
+    palette::{Color, Palette, Theme},
+};
+
+fn encode_call(call: &DrawCall) -> String {
+    let index = |palette: Palette| Palette::ALL.iter().position(|p| *p == palette);
+    match call {
+        DrawCall::SetPalette(palette) => format!("P {}", index(*palette).unwrap_or(0)),
+        DrawCall::SetTheme(theme) => format!(
+            "H {}",
+            Theme::ALL.iter().position(|t| t == theme).unwrap_or(0)
+        ),
+        DrawCall::Clear(rect) => format!("C {}", encode_rect(rect)),
+        DrawCall::FillRect(rect) => format!("F {}", encode_rect(rect)),
+        DrawCall::DrawRect(rect) => format!("R {}", encode_rect(rect)),
+        DrawCall::Line(start, end) => format!("L {} {} {} {}", start.x, start.y, end.x, end.y),
+        DrawCall::Triangle(p1, p2, p3) => {
+            format!("V {} {} {} {} {} {}", p1.x, p1.y, p2.x, p2.y, p3.x, p3.y)
+        }
+        DrawCall::Circle(center, radius) => format!("O {} {} {}", center.x, center.y, radius),
+        DrawCall::Circles(circles) => {
+            let circles = circles
+                .iter()
+                .map(|(center, radius)| format!("{} {} {}", center.x, center.y, radius))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!("M {}", circles)
+        }
+        DrawCall::Save => "S".to_string(),
+        DrawCall::Restore => "Z".to_string(),
+        DrawCall::Translate(dx, dy) => format!("G {} {}", dx, dy),
+        DrawCall::SetAlpha(alpha) => format!("A {}", alpha),
+        // 文字列には空白が入るので、行の最後に置く
+        DrawCall::Text(text, at) => format!("T {} {} {}", at.x, at.y, text),
+        DrawCall::TextCentered(text, at) => format!("X {} {} {}", at.x, at.y, text),
+        DrawCall::LargeTextCentered(text, at) => format!("Y {} {} {}", at.x, at.y, text),
+        DrawCall::SetColor(color) => format!(
+            "K {}",
+            Color::ALL.iter().position(|c| c == color).unwrap_or(0)
+        ),
+    }
+}
+
+fn encode_rect(rect: &Rect) -> String {
+    format!("{} {} {} {}", rect.x, rect.y, rect.width, rect.height)
+}
+
+pub fn encode(calls: &[DrawCall]) -> String {
+    calls.iter().map(encode_call).collect::<Vec<_>>().join("\n")
+}
+
+fn decode_call(line: &str) -> Result<DrawCall> {
+    let (op, rest) = line.split_once(' ').unwrap_or((line, ""));
+    // 文字列を含む命令は、座標の2つだけを先に切り出す
+    let text = |rest: &str| -> Result<(Point, String)> {
+        let mut parts = rest.splitn(3, ' ');
+        let mut next = || {
+            parts
+                .next()
+                .ok_or_else(|| anyhow!("Malformed draw call: {}", line))
+        };
+        let x = next()?.parse()?;
+        let y = next()?.parse()?;
+        let text = parts.next().unwrap_or("").to_string();
+        Ok((Point { x, y }, text))
+    };
+    let numbers = || -> Result<Vec<f32>> {
+        Ok(rest
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?)
+    };
+    let rect = || -> Result<Rect> {
+        match numbers()?[..] {
+            [x, y, width, height] => Ok(Rect {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(anyhow!("Malformed rect: {}", line)),
+        }
+    };
+    let index = || -> Result<usize> { Ok(rest.trim().parse()?) };
+    let call = match op {
+        "P" => DrawCall::SetPalette(
+            *Palette::ALL
+                .get(index()?)
+                .ok_or_else(|| anyhow!("Unknown palette: {}", line))?,
+        ),
+        "H" => DrawCall::SetTheme(
+            *Theme::ALL
+                .get(index()?)
+                .ok_or_else(|| anyhow!("Unknown theme: {}", line))?,
+        ),
+        "C" => DrawCall::Clear(rect()?),
+        "F" => DrawCall::FillRect(rect()?),
+        "R" => DrawCall::DrawRect(rect()?),
+        "L" => match numbers()?[..] {
+            [x1, y1, x2, y2] => DrawCall::Line(Point { x: x1, y: y1 }, Point { x: x2, y: y2 }),
+            _ => return Err(anyhow!("Malformed line: {}", line)),
+        },
+        "V" => match numbers()?[..] {
+            [x1, y1, x2, y2, x3, y3] => DrawCall::Triangle(
+                Point { x: x1, y: y1 },
+                Point { x: x2, y: y2 },
+                Point { x: x3, y: y3 },
+            ),
+            _ => return Err(anyhow!("Malformed triangle: {}", line)),
+        },
+        "O" => match numbers()?[..] {
+            [x, y, radius] => DrawCall::Circle(Point { x, y }, radius),
+            _ => return Err(anyhow!("Malformed circle: {}", line)),
+        },
+        "M" => {
+            let numbers = numbers()?;
+            if !numbers.len().is_multiple_of(3) {
+                return Err(anyhow!("Malformed circles: {}", line));
+            }
+            DrawCall::Circles(
+                numbers
+                    .chunks(3)
+                    .map(|c| (Point { x: c[0], y: c[1] }, c[2]))
+                    .collect(),
+            )
+        }
+        "S" => DrawCall::Save,
+        "Z" => DrawCall::Restore,
+        "G" => match numbers()?[..] {
+            [dx, dy] => DrawCall::Translate(dx, dy),
+            _ => return Err(anyhow!("Malformed translate: {}", line)),
+        },
+        "A" => match numbers()?[..] {
+            [alpha] => DrawCall::SetAlpha(alpha),
+            _ => return Err(anyhow!("Malformed alpha: {}", line)),
+        },
+        "T" => {
+            let (at, text) = text(rest)?;
+            DrawCall::Text(text, at)
+        }
+        "X" => {
+            let (at, text) = text(rest)?;
+            DrawCall::TextCentered(text, at)
+        }
+        "Y" => {
+            let (at, text) = text(rest)?;
+            DrawCall::LargeTextCentered(text, at)
+        }
+        "K" => DrawCall::SetColor(
+            *Color::ALL
+                .get(index()?)
+                .ok_or_else(|| anyhow!("Unknown color: {}", line))?,
+        ),
+        _ => return Err(anyhow!("Unknown draw call: {}", line)),
+    };
+    Ok(call)
+}
+
+pub fn decode(text: &str) -> Result<Vec<DrawCall>> {
+    text.lines()
+        .filter(|line| !line.is_empty())
+        .map(decode_call)
+        .collect()
+}
+
+/// 続けて描く円を1つの命令にまとめる。間に色などの変更がないので、見た目は変わらない。
+pub fn batch(calls: Vec<DrawCall>) -> Vec<DrawCall> {
+    let mut batched: Vec<DrawCall> = Vec::with_capacity(calls.len());
+    for call in calls {
+        let circles = match call {
+            DrawCall::Circle(center, radius) => vec![(center, radius)],
+            DrawCall::Circles(circles) => circles,
+            call => {
+                batched.push(call);
+                continue;
+            }
+        };
+        match batched.last_mut() {
+            Some(DrawCall::Circles(last)) => last.extend(circles),
+            _ => batched.push(DrawCall::Circles(circles)),
+        }
+    }
+    batched
+}
+
+const LINE_MARGIN: f32 = 2.0; // 線の太さの分だけ範囲を広げる
+const TEXT_SIZE: f32 = 20.0;
+const LARGE_TEXT_SIZE: f32 = 36.0;
+
+// 文字の幅は測れないので、1文字が正方形に収まるとして大きめに見積もる
+fn text_bounds(text: &str, at: Point, size: f32, centered: bool) -> Rect {
+    let width = text.chars().count() as f32 * size;
+    Rect {
+        x: if centered { at.x - width / 2.0 } else { at.x },
+        y: at.y - size,
+        width,
+        height: size * 1.5,
+    }
+}
+
+fn circle_bounds(center: Point, radius: f32) -> Rect {
+    Rect {
+        x: center.x - radius,
+        y: center.y - radius,
+        width: radius * 2.0,
+        height: radius * 2.0,
+    }
+}
+
+fn points_bounds(points: &[Point]) -> Rect {
+    let (first, rest) = points.split_first().expect("at least one point");
+    rest.iter().fold(
+        Rect {
+            x: first.x,
+            y: first.y,
+            width: 0.0,
+            height: 0.0,
+        },
+        |bounds, p| {
+            bounds.union(&Rect {
+                x: p.x,
+                y: p.y,
+                width: 0.0,
+                height: 0.0,
+            })
+        },
+    )
+}
+
+/// 描画命令が描く範囲を囲む矩形。何も描かなければ`None`
+pub fn bounds(calls: &[DrawCall]) -> Option<Rect> {
+    let mut offsets = vec![(0.0, 0.0)]; // `Save`と`Restore`で戻せるように積んでおく
+    let mut bounds: Option<Rect> = None;
+    for call in calls {
+        let drawn = match call {
+            DrawCall::FillRect(rect) | DrawCall::DrawRect(rect) => *rect,
+            DrawCall::Line(start, end) => points_bounds(&[*start, *end]),
+            DrawCall::Triangle(p1, p2, p3) => points_bounds(&[*p1, *p2, *p3]),
+            DrawCall::Circle(center, radius) => circle_bounds(*center, *radius),
+            DrawCall::Circles(circles) => match circles
+                .iter()
+                .map(|(center, radius)| circle_bounds(*center, *radius))
+                .reduce(|a, b| a.union(&b))
+            {
+                Some(rect) => rect,
+                None => continue,
+            },
+            DrawCall::Text(text, at) => text_bounds(text, *at, TEXT_SIZE, false),
+            DrawCall::TextCentered(text, at) => text_bounds(text, *at, TEXT_SIZE, true),
+            DrawCall::LargeTextCentered(text, at) => text_bounds(text, *at, LARGE_TEXT_SIZE, true),
+            DrawCall::Save => {
+                offsets.push(*offsets.last().unwrap_or(&(0.0, 0.0)));
+                continue;
+            }
+            DrawCall::Restore => {
+                if offsets.len() > 1 {
+                    offsets.pop();
+                }
+                continue;
+            }
+            DrawCall::Translate(dx, dy) => {
+                if let Some((x, y)) = offsets.last_mut() {
+                    *x += dx;
+                    *y += dy;
+                }
+                continue;
+            }
+            DrawCall::SetPalette(_)
+            | DrawCall::SetTheme(_)
+            | DrawCall::Clear(_)
+            | DrawCall::SetAlpha(_)
+            | DrawCall::SetColor(_) => continue,
+        };
+        let (dx, dy) = *offsets.last().unwrap_or(&(0.0, 0.0));
+        let drawn = Rect {
+            x: drawn.x + dx - LINE_MARGIN,
+            y: drawn.y + dy - LINE_MARGIN,
+            width: drawn.width + LINE_MARGIN * 2.0,
+            height: drawn.height + LINE_MARGIN * 2.0,
+        };
+        bounds = Some(bounds.map_or(drawn, |b| b.union(&drawn)));
+    }
+    bounds
+}
+
+/// 前のフレームの描画命令を覚えておき、変わったときだけ描き直す。
+/// 描き直すときも、前のフレームと今のフレームで何かを描いた範囲だけを消す。
+#[derive(Default)]
+pub struct Presenter {
+    last: Vec<DrawCall>,
+    last_bounds: Option<Rect>,
+}
+
+impl Presenter {
+    pub fn present(&mut self, calls: Vec<DrawCall>, renderer: &dyn Renderer) {
+        if calls == self.last {
+            return;
+        }
+        let current = bounds(&calls);
+        let dirty = match (current, self.last_bounds) {
+            (Some(a), Some(b)) => Some(a.union(&b)),
+            (a, b) => a.or(b),
+        };
+        for call in &calls {
+            match call {
+                // 消すのは描いた範囲と重なる部分だけでよい（それ以外はもう空になっている）
+                DrawCall::Clear(rect) => {
+                    if let Some(rect) = dirty.and_then(|dirty| rect.intersection(&dirty)) {
+                        renderer.clear(&rect);
+                    }
+                }
+                call => replay(std::slice::from_ref(call), renderer),
+            }
+        }
+        self.last = calls;
+        self.last_bounds = current;
+    }
+}
+
+/// 描画命令を`renderer`で描く
+pub fn replay(calls: &[DrawCall], renderer: &dyn Renderer) {
+    for call in calls {
+        match call {
+            DrawCall::SetPalette(palette) => renderer.set_palette(*palette),
+            DrawCall::SetTheme(theme) => renderer.set_theme(*theme),
+            DrawCall::Clear(rect) => renderer.clear(rect),
+            DrawCall::FillRect(rect) => renderer.fill_rect(rect),
+            DrawCall::DrawRect(rect) => renderer.draw_rect(rect),
+            DrawCall::Line(start, end) => renderer.draw_line(start, end),
+            DrawCall::Triangle(p1, p2, p3) => renderer.draw_triangle(p1, p2, p3),
+            DrawCall::Circle(center, radius) => renderer.draw_circle(center, *radius),
+            DrawCall::Circles(circles) => renderer.draw_circles(circles),
+            DrawCall::Save => renderer.save(),
+            DrawCall::Restore => renderer.restore(),
+            DrawCall::Translate(dx, dy) => renderer.translate(*dx, *dy),
+            DrawCall::SetAlpha(alpha) => renderer.set_alpha(*alpha),
+            DrawCall::Text(text, at) => renderer.draw_text(text, at),
+            DrawCall::TextCentered(text, at) => renderer.draw_text_centered(text, at),
+            DrawCall::LargeTextCentered(text, at) => renderer.draw_large_text_centered(text, at),
+            DrawCall::SetColor(color) => renderer.set_color(*color),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::frames,
+        engine::RecordingRenderer,
+        fixture::assert_matches_fixture,
+        input::{Actions, Device},
+        layout::Layout,
+        level::Level,
+        quality::Quality,
+        settings::Settings,
+    };
+
+    // 記録しておいた、決まった場面の描画命令の並び
+    const FIXTURE: &str = include_str!("../fixtures/draw_list.txt");
+    const SNAPSHOT_SEED: u64 = 1;
+    const SNAPSHOT_PATTERN: usize = 0; // 扇状に撃つパターン
+    const SNAPSHOT_FRAME: u32 = 60; // 描くフレーム（基準の速さで）。弾が撃たれて広がったところ
+
+    /// 名前付きパターンを撃つ敵を置いたステージを進めて描いた描画命令が、記録と同じか確かめる。
+    /// 描画を意図して変えたときは、失敗したテストが書き出す並びで`fixtures/draw_list.txt`を作り直す
+    #[test]
+    #[cfg_attr(
+        feature = "high_refresh",
+        ignore = "the fixture is recorded at the standard tick rate"
+    )]
+    fn sandbox_draw_list_matches_fixture() {
+        let mut level = Level::pattern_sandbox(SNAPSHOT_SEED, SNAPSHOT_PATTERN);
+        for _ in 0..frames(SNAPSHOT_FRAME) {
+            level.update(&[Actions::default()]);
+        }
+        let renderer = RecordingRenderer::default();
+        level.draw(
+            &renderer,
+            &Settings::default(),
+            Device::default(),
+            Quality::High,
+            Layout::default(),
+        );
+        let calls = renderer.take();
+        assert_matches_fixture(&encode(&calls), FIXTURE, "draw list");
+        // 記録の文字列から戻した命令も、描いた命令と同じになる
+        assert_eq!(decode(FIXTURE).unwrap(), calls);
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    browser,
+    constants::{rate_key, MAX_REPLAY_FRAMES},
+    input::{Actions, MAX_PLAYERS},
+    settings::{BombType, Modifiers, PlayMode, ShotType},
+    version,
+};
+
+// 保存形式の版。欄を増やしたり意味を変えたりしたら上げて、前の版を読めるようにしておく
+//   0: 版の印がない。ボムの種類は後から加えたので、ない場合がある
+//   1: 先頭に版の印を付けた。ボムの種類は必ずある
+//   2: ショットの種類を加えた。それより前の版はまっすぐのショットで遊んだもの
+const FORMAT_VERSION: u32 = 2;
+
+/// シードと毎フレームの入力だけを記録したプレイ。
+/// シミュレーションは決定的なので、これだけで同じプレイを再現できる。
+pub struct Replay {
+    seed: u64,
+    mode: PlayMode,
+    modifiers: Modifiers,
+    bomb: BombType,
+    shot: ShotType,
+    inputs: Vec<[Actions; MAX_PLAYERS]>,
+}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        mode: PlayMode,
+        modifiers: Modifiers,
+        bomb: BombType,
+        shot: ShotType,
+        inputs: Vec<[Actions; MAX_PLAYERS]>,
+    ) -> Self {
+        Self {
+            seed,
+            mode,
+            modifiers,
+            bomb,
+            shot,
+            inputs,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    pub fn bomb(&self) -> BombType {
+        self.bomb
+    }
+
+    pub fn shot(&self) -> ShotType {
+        self.shot
+    }
+
+    pub fn inputs(&self) -> &[[Actions; MAX_PLAYERS]] {
+        &self.inputs
+    }
+
+    /// 1フレーム分の入力を記録に足す
+    pub fn push(&mut self, actions: [Actions; MAX_PLAYERS]) {
+        self.inputs.push(actions);
+    }
+
+    /// 1行目に`v<版> <シード> <形式の番号> <ルール変更のビット列> <ボムの種類の番号> <ショットの種類の番号>`、2行目に空白区切りで各フレームの`<1Pの入力>,<2Pの入力>`
+    pub fn serialize(&self) -> String {
+        let mode = PlayMode::ALL
+            .iter()
+            .position(|mode| *mode == self.mode)
+            .unwrap_or(0);
+        let bomb = BombType::ALL
+            .iter()
+            .position(|bomb| *bomb == self.bomb)
+            .unwrap_or(0);
+        let shot = ShotType::ALL
+            .iter()
+            .position(|shot| *shot == self.shot)
+            .unwrap_or(0);
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|actions| {
+                actions
+                    .iter()
+                    .map(|actions| actions.to_bits().to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let body = format!(
+            "{} {} {} {} {}\n{}",
+            self.seed,
+            mode,
+            self.modifiers.to_bits(),
+            bomb,
+            shot,
+            inputs
+        );
+        version::tag(FORMAT_VERSION, &body)
+    }
+
+    pub fn deserialize(text: &str) -> Result<Self> {
+        let (version, text) = version::split("replay", text, FORMAT_VERSION)?;
+        let (header, inputs) = text.split_once('\n').unwrap_or((text, ""));
+        let mut fields = header.split(' ');
+        let mut next = || {
+            fields
+                .next()
+                .ok_or_else(|| anyhow!("Malformed replay header"))
+        };
+        let seed = next()?.parse()?;
+        let mode = *PlayMode::ALL
+            .get(next()?.parse::<usize>()?)
+            .ok_or_else(|| anyhow!("Unknown play mode in replay"))?;
+        let modifiers = Modifiers::from_bits(next()?.parse()?);
+        // ボムの種類は後から加えたので、版0のリプレイにはないことがある
+        let bomb = match next() {
+            Ok(field) => *BombType::ALL
+                .get(field.parse::<usize>()?)
+                .ok_or_else(|| anyhow!("Unknown bomb in replay"))?,
+            Err(_) if version == 0 => BombType::default(),
+            Err(err) => return Err(err),
+        };
+        // ショットの種類は版2で加えたので、それより前のリプレイにはない
+        let shot = if version < 2 {
+            ShotType::default()
+        } else {
+            *ShotType::ALL
+                .get(next()?.parse::<usize>()?)
+                .ok_or_else(|| anyhow!("Unknown shot in replay"))?
+        };
+        // 長すぎるリプレイは最後まで読まずに断る
+        let inputs: Vec<_> = inputs
+            .split_whitespace()
+            .take(MAX_REPLAY_FRAMES + 1)
+            .map(|frame| {
+                let mut bits = frame
+                    .split(',')
+                    .map(|bits| bits.parse().map(Actions::from_bits));
+                let mut actions = [Actions::default(); MAX_PLAYERS];
+                for slot in actions.iter_mut() {
+                    *slot = bits.next().transpose()?.unwrap_or_default();
+                }
+                Ok(actions)
+            })
+            .collect::<Result<_>>()?;
+        if inputs.len() > MAX_REPLAY_FRAMES {
+            return Err(anyhow!(
+                "Replay is longer than {} frames",
+                MAX_REPLAY_FRAMES
+            ));
+        }
+        Ok(Self {
+            seed,
+            mode,
+            modifiers,
+            bomb,
+            shot,
+            inputs,
+        })
+    }
+
+    fn storage_key(stage: &str) -> String {
+        rate_key(format!("replay.{}", stage))
+    }
+
+    pub fn save(&self, stage: &str) -> Result<()> {
+        browser::storage_set(&Self::storage_key(stage), &self.serialize())
+    }
+}
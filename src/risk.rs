@@ -0,0 +1,44 @@
+//! プレイフィールドの上の端に沿った帯（リスクゾーン）。敵弾の濃い上の方へ踏み込むほど稼げるよう、
+//! この中で敵を倒したり得点アイテムを取ったりすると得点を倍にする。
+
+use crate::{
+    engine::Renderer,
+    math::{Point, Rect},
+    palette::Color,
+    playfield::Playfield,
+};
+
+const DEPTH: f32 = 150.0; // プレイフィールドの上の端から、帯の下の端までの長さ
+const MULTIPLIER: u64 = 2;
+const BANDS: u32 = 6; // 帯を塗り分ける段の数。上の段ほど濃くする
+const MAX_ALPHA: f32 = 0.12;
+
+/// `point`がリスクゾーンの中にあるか
+pub fn contains(playfield: &Playfield, point: &Point) -> bool {
+    point.y <= playfield.top() + DEPTH
+}
+
+/// `point`にいるプレイヤーが稼いだ得点に掛ける倍率
+pub fn multiplier(playfield: &Playfield, point: &Point) -> u64 {
+    if contains(playfield, point) {
+        MULTIPLIER
+    } else {
+        1
+    }
+}
+
+/// 下に向かって薄れていく帯として描く
+pub fn draw(renderer: &dyn Renderer, playfield: &Playfield) {
+    let band = DEPTH / BANDS as f32;
+    renderer.set_color(Color::Item);
+    for i in 0..BANDS {
+        renderer.set_alpha(MAX_ALPHA * (1.0 - i as f32 / BANDS as f32));
+        renderer.fill_rect(&Rect {
+            x: playfield.left(),
+            y: playfield.top() + band * i as f32,
+            width: playfield.rect().width,
+            height: band,
+        });
+    }
+    renderer.set_alpha(1.0);
+}
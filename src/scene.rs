@@ -0,0 +1,56 @@
+/// 重ねて出す画面の1枚が、下にあるものをどう扱うか
+pub trait Layer {
+    /// 下の層とゲームも動かし続けるか
+    fn updates_below(&self) -> bool {
+        false
+    }
+
+    /// 下の層とゲームも描くか（半透明の帯などを重ねるだけの画面）
+    fn draws_below(&self) -> bool {
+        true
+    }
+}
+
+/// ゲームの上に積み重ねる画面。一番上のものだけが入力を受け取る。
+pub struct SceneStack<L> {
+    layers: Vec<L>, // 下から順
+}
+
+impl<L: Layer> SceneStack<L> {
+    pub fn new() -> Self {
+        Self { layers: Vec::new() }
+    }
+
+    pub fn push(&mut self, layer: L) {
+        self.layers.push(layer);
+    }
+
+    pub fn pop(&mut self) -> Option<L> {
+        self.layers.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.layers.clear();
+    }
+
+    pub fn top(&self) -> Option<&L> {
+        self.layers.last()
+    }
+
+    pub fn top_mut(&mut self) -> Option<&mut L> {
+        self.layers.last_mut()
+    }
+
+    /// 一番下のゲームを動かしてよいか
+    pub fn updates_game(&self) -> bool {
+        self.layers.iter().all(Layer::updates_below)
+    }
+
+    /// ゲームを描くかと、描く層（下から順）。下を隠す層があれば、それより下は描かない。
+    pub fn visible(&self) -> (bool, &[L]) {
+        match self.layers.iter().rposition(|layer| !layer.draws_below()) {
+            Some(index) => (false, &self.layers[index..]),
+            None => (true, &self.layers),
+        }
+    }
+}
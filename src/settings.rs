@@ -0,0 +1,261 @@
+use crate::palette::Palette;
+
+/// ゲーム速度。100%以外はスコアやリプレイの記録対象外にする。
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum GameSpeed {
+    #[default]
+    Full,
+    Ninety,
+    SeventyFive,
+}
+
+impl GameSpeed {
+    pub const ALL: [GameSpeed; 3] = [GameSpeed::SeventyFive, GameSpeed::Ninety, GameSpeed::Full];
+
+    pub fn factor(self) -> f32 {
+        match self {
+            GameSpeed::Full => 1.0,
+            GameSpeed::Ninety => 0.9,
+            GameSpeed::SeventyFive => 0.75,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameSpeed::Full => "100%",
+            GameSpeed::Ninety => "90% (unranked)",
+            GameSpeed::SeventyFive => "75% (unranked)",
+        }
+    }
+
+    /// リーダーボードやリプレイに記録してよい速度か
+    pub fn is_ranked(self) -> bool {
+        self == GameSpeed::Full
+    }
+}
+
+/// プレイフィールドに重ねるヒートマップ
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum HeatmapView {
+    #[default]
+    Off,
+    Current,  // 今回のプレイ
+    Previous, // 前回までに保存されたプレイ
+}
+
+impl HeatmapView {
+    pub const ALL: [HeatmapView; 3] = [
+        HeatmapView::Off,
+        HeatmapView::Current,
+        HeatmapView::Previous,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HeatmapView::Off => "OFF",
+            HeatmapView::Current => "This run",
+            HeatmapView::Previous => "Last run",
+        }
+    }
+}
+
+/// ボムの種類
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum BombType {
+    #[default]
+    Standard, // 一定時間無敵になる
+    Seal, // 一定時間、自機のまわりの輪に入った敵弾を得点アイテムに変える
+}
+
+impl BombType {
+    pub const ALL: [BombType; 2] = [BombType::Standard, BombType::Seal];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BombType::Standard => "Standard",
+            BombType::Seal => "Seal",
+        }
+    }
+}
+
+/// 自機のショットの種類
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum ShotType {
+    #[default]
+    Straight, // まっすぐ上に撃つ
+    Homing,   // 一番近い敵を追いかける。そのぶん1発の傷は少ない
+    Piercing, // 敵を貫いて進む。貫くごとに傷が減る
+    Bouncing, // 左右に開いて撃ち、プレイフィールドの端で1度だけ跳ね返る
+}
+
+impl ShotType {
+    pub const ALL: [ShotType; 4] = [
+        ShotType::Straight,
+        ShotType::Homing,
+        ShotType::Piercing,
+        ShotType::Bouncing,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShotType::Straight => "Straight",
+            ShotType::Homing => "Homing",
+            ShotType::Piercing => "Piercing",
+            ShotType::Bouncing => "Bouncing",
+        }
+    }
+}
+
+/// 描画の品質。自動では、60fpsを保てないときだけ軽くする。
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum QualitySetting {
+    #[default]
+    Auto,
+    High,
+    Low,
+}
+
+impl QualitySetting {
+    pub const ALL: [QualitySetting; 3] = [
+        QualitySetting::Auto,
+        QualitySetting::High,
+        QualitySetting::Low,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QualitySetting::Auto => "Auto",
+            QualitySetting::High => "High",
+            QualitySetting::Low => "Low",
+        }
+    }
+}
+
+/// 内部で描く解像度。キャンバスより小さく描いて引き伸ばし、描画の重い端末でも軽くする
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum RenderScale {
+    #[default]
+    Full,
+    ThreeQuarters,
+    Half,
+}
+
+impl RenderScale {
+    pub const ALL: [RenderScale; 3] = [
+        RenderScale::Full,
+        RenderScale::ThreeQuarters,
+        RenderScale::Half,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderScale::Full => "1x",
+            RenderScale::ThreeQuarters => "0.75x",
+            RenderScale::Half => "0.5x",
+        }
+    }
+
+    /// キャンバスの大きさに掛ける値
+    pub fn factor(self) -> f32 {
+        match self {
+            RenderScale::Full => 1.0,
+            RenderScale::ThreeQuarters => 0.75,
+            RenderScale::Half => 0.5,
+        }
+    }
+}
+
+/// 遊ぶ人数と形式
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum PlayMode {
+    #[default]
+    Single,
+    CoOp,   // 同じプレイフィールドで2人協力
+    Versus, // 画面を左右に分けて2人対戦
+}
+
+impl PlayMode {
+    pub const ALL: [PlayMode; 3] = [PlayMode::Single, PlayMode::CoOp, PlayMode::Versus];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PlayMode::Single => "1",
+            PlayMode::CoOp => "2 (co-op)",
+            PlayMode::Versus => "2 (versus)",
+        }
+    }
+}
+
+/// 挑戦用のルール変更。どれかを使ったプレイのスコアは別の記録として残す。
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Modifiers {
+    pub no_bombs: bool,     // ボムなし
+    pub one_life: bool,     // 残機1
+    pub fast_bullets: bool, // 敵弾の速さが2倍
+    pub near_sight: bool,   // 敵弾はプレイヤーの近くにあるときしか見えない
+    pub anti_camping: bool, // 同じ場所に居座ると、警告のあとで狙い撃ちされる
+}
+
+impl Modifiers {
+    /// ルール変更を1つでも使っているか
+    pub fn is_custom(self) -> bool {
+        self != Modifiers::default()
+    }
+
+    /// 敵弾の速さに掛ける値
+    pub fn bullet_speed(self) -> f32 {
+        if self.fast_bullets {
+            2.0
+        } else {
+            1.0
+        }
+    }
+
+    /// リプレイや配信に記録するためのビット列
+    pub fn to_bits(self) -> u8 {
+        [
+            self.no_bombs,
+            self.one_life,
+            self.fast_bullets,
+            self.near_sight,
+            self.anti_camping,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, &on)| bits | (on as u8) << i)
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let bit = |i: u8| bits & (1 << i) != 0;
+        Self {
+            no_bombs: bit(0),
+            one_life: bit(1),
+            fast_bullets: bit(2),
+            near_sight: bit(3),
+            anti_camping: bit(4),
+        }
+    }
+}
+
+/// プレイヤーが変更できる設定
+#[derive(Clone, Copy, Default)]
+pub struct Settings {
+    pub autofire: bool,            // ショットキーを押さなくても常に撃ち続ける
+    pub toggle_focus: bool,        // 低速キーを押すたびに低速モードを切り替える（押しっぱなし不要）
+    pub palette: Palette,          // 配色
+    pub reduce_flashing: bool,     // フラッシュや画面揺れを弱める（光過敏性への配慮）
+    pub no_vibration: bool,        // ゲームパッドや携帯端末を震わせない
+    pub game_speed: GameSpeed,     // ゲーム速度
+    pub show_hitboxes: bool,       // 当たり判定と弾の速度を表示する（練習・開発用）
+    pub heatmap: HeatmapView,      // プレイヤー位置のヒートマップ表示
+    pub quality: QualitySetting,   // 描画の品質
+    pub render_scale: RenderScale, // 内部で描く解像度
+    pub frame_skip: bool,          // 処理が遅れたら描画を1回おきに飛ばす（ゲームの速さは保つ）
+    pub practice: bool,            // 練習モード（被弾した弾の表示とやり直し）
+    pub casual: bool, // カジュアルモード（ゲームオーバーになったら最後のチェックポイントから続ける）
+    pub mode: PlayMode, // 遊ぶ人数と形式
+    pub bomb: BombType, // ボムの種類
+    pub shot: ShotType, // ショットの種類
+    pub tas: bool,    // TASモード（セーブステートとコマ送り）
+    pub modifiers: Modifiers, // 挑戦用のルール変更
+}
@@ -0,0 +1,342 @@
+//! 自機が撃つ弾（ショット）。敵に当たると傷を与えて消え、プレイフィールドの上に出ると消える。
+//! 遠くまで飛んだ弾や、敵を貫いたあとの弾は与える傷が減る。
+//! 追いかける弾は一番近い敵へ少しずつ曲がり、狙った敵がいなくなれば次に近い敵を狙い直す。
+//! 貫く弾は決まった数の敵を貫き、跳ね返る弾はプレイフィールドの端で1度だけ跳ね返る。
+
+use crate::{
+    component::{Collider, Components, Kinematics, Renderable, Shape, Transform},
+    constants::per_tick,
+    entity::EntityId,
+    math::{Point, Vector},
+    palette::Color,
+    playfield::Playfield,
+    settings::ShotType,
+};
+
+const SHOT_SPEED: f32 = per_tick(900.0); // 真上に進む速さ
+const SHOT_RADIUS: f32 = 5.0;
+const SHOT_DAMAGE: u32 = 20;
+const HOMING_SPEED: f32 = per_tick(600.0); // 追いかける弾の速さ
+const HOMING_TURN_DEG: f32 = per_tick(480.0); // 追いかける弾が1フレームに曲がれる角度
+const HOMING_DAMAGE: u32 = 12; // 必ず当たる代わりに、まっすぐの弾より少なくする
+const BOUNCE_SPREAD_DEG: f32 = 15.0; // 跳ね返る弾を、真上から左右へ開いて撃つ角度
+const SHOT_GAP: f32 = 8.0; // 1度に撃つ2発の、自機の中心から左右への離れ具合
+const SHOT_AHEAD: f32 = 12.0; // 自機の中心からどれだけ上から撃ち出すか
+const VOLLEY_FALLOFF: Falloff = Falloff {
+    near: 200.0,
+    far: 450.0,
+    min_scale: 0.5,
+    pierce: 0,
+    pierce_scale: 0.7,
+};
+const PIERCE_FALLOFF: Falloff = Falloff {
+    pierce: 2,
+    ..VOLLEY_FALLOFF
+};
+
+/// ショットの種類ごとの撃ち方
+struct ShotSpec {
+    speed: f32,
+    damage: u32,
+    falloff: Falloff,
+    spread_deg: f32, // 真上から左右へ開く角度
+    homing: bool,
+    bounces: u8, // プレイフィールドの端で跳ね返る回数
+}
+
+impl ShotSpec {
+    fn of(shot_type: ShotType) -> Self {
+        let straight = Self {
+            speed: SHOT_SPEED,
+            damage: SHOT_DAMAGE,
+            falloff: VOLLEY_FALLOFF,
+            spread_deg: 0.0,
+            homing: false,
+            bounces: 0,
+        };
+        match shot_type {
+            ShotType::Straight => straight,
+            ShotType::Homing => Self {
+                speed: HOMING_SPEED,
+                damage: HOMING_DAMAGE,
+                homing: true,
+                ..straight
+            },
+            ShotType::Piercing => Self {
+                falloff: PIERCE_FALLOFF,
+                ..straight
+            },
+            ShotType::Bouncing => Self {
+                spread_deg: BOUNCE_SPREAD_DEG,
+                bounces: 1,
+                ..straight
+            },
+        }
+    }
+}
+
+/// 弾が与える傷の減り方
+#[derive(Clone, Copy)]
+pub struct Falloff {
+    pub near: f32,         // 撃ち出した位置からこの距離までは減らない
+    pub far: f32,          // この距離で一番少なくなり、その先は変わらない
+    pub min_scale: f32,    // 一番少なくなったときの倍率
+    pub pierce: u8,        // 消えずに貫ける敵の数
+    pub pierce_scale: f32, // 敵を1体貫くごとに掛ける倍率
+}
+
+impl Falloff {
+    /// `distance`だけ飛び、`pierced`体の敵を貫いたあとに与える傷の倍率
+    fn scale(&self, distance: f32, pierced: usize) -> f32 {
+        let t = ((distance - self.near) / (self.far - self.near).max(1.0)).clamp(0.0, 1.0);
+        (1.0 + (self.min_scale - 1.0) * t) * self.pierce_scale.powi(pierced as i32)
+    }
+}
+
+#[derive(Clone)]
+pub struct PlayerShot {
+    pub owner: usize, // 撃ったプレイヤー（ステージの中での番号）
+    pub transform: Transform,
+    pub kinematics: Kinematics,
+    pub collider: Collider,
+    origin: Point, // 撃ち出した位置
+    falloff: Falloff,
+    damage: u32,              // 減る前の傷
+    homing: bool,             // 敵を追いかけるか
+    target: Option<EntityId>, // 追いかけている敵
+    bounces: u8,              // プレイフィールドの端であと何回跳ね返るか
+    hits: Vec<EntityId>, // もう傷を与えた敵。貫いている間に同じ敵へ何度も当たらないように覚えておく
+    renderable: Renderable,
+}
+
+impl PlayerShot {
+    /// `owner`のプレイヤーが`from`にいるときに撃つ、左右に並んだ2発
+    pub fn volley(owner: usize, from: Point, shot_type: ShotType) -> [Self; 2] {
+        let spec = ShotSpec::of(shot_type);
+        [-SHOT_GAP, SHOT_GAP].map(|dx| {
+            let pos = Point {
+                x: from.x + dx,
+                y: from.y - SHOT_AHEAD,
+            };
+            Self {
+                owner,
+                transform: Transform { pos },
+                kinematics: Kinematics::new(
+                    Vector::new(0.0, -spec.speed).rotate(spec.spread_deg * dx.signum()),
+                ),
+                collider: Collider {
+                    radius: SHOT_RADIUS,
+                },
+                origin: pos,
+                falloff: spec.falloff,
+                damage: spec.damage,
+                homing: spec.homing,
+                target: None,
+                bounces: spec.bounces,
+                hits: Vec::new(),
+                renderable: Renderable::circle(Color::PlayerShot, SHOT_RADIUS),
+            }
+        })
+    }
+
+    /// 1フレームのうち、位置を進める前の処理。追いかける弾なら向きを変える。
+    /// `enemies`は場にいる敵の番号と位置
+    pub fn steer(&mut self, enemies: &[(EntityId, Point)]) {
+        if self.homing {
+            self.turn(enemies);
+        }
+    }
+
+    /// 1フレームのうち、位置を進めた後の処理。プレイフィールドの外に出て消えるなら`false`を返す
+    pub fn settle(&mut self, playfield: &Playfield) -> bool {
+        if self.bounces > 0 && !playfield.contains(&self.transform.pos) {
+            self.bounce(playfield);
+        }
+        playfield.is_drawn(&self.transform.pos, self.collider.radius)
+    }
+
+    /// はみ出した端で向きを反転し、プレイフィールドの中へ戻す
+    fn bounce(&mut self, playfield: &Playfield) {
+        self.bounces -= 1;
+        let pos = self.transform.pos;
+        if pos.x < playfield.open_left() || pos.x > playfield.open_right() {
+            self.kinematics.vel = self.kinematics.vel.flip_x();
+        }
+        if pos.y < playfield.top() || pos.y > playfield.bottom() {
+            self.kinematics.vel = self.kinematics.vel.flip_y();
+        }
+        self.transform.pos = playfield.clamp(pos);
+    }
+
+    /// 追いかけている敵へ、曲がれる角度の分だけ向きを変える。
+    /// その敵がいなくなっていれば、まだ傷を与えていない敵のうち一番近いものを狙い直す
+    fn turn(&mut self, enemies: &[(EntityId, Point)]) {
+        let pos = self.transform.pos;
+        let target = self
+            .target
+            .and_then(|target| enemies.iter().find(|(id, _)| *id == target))
+            .or_else(|| {
+                enemies
+                    .iter()
+                    .filter(|(id, _)| !self.hits.contains(id))
+                    .min_by(|(_, a), (_, b)| {
+                        let distance = |to: &Point| Vector::between(&pos, to).length();
+                        distance(a).total_cmp(&distance(b))
+                    })
+            });
+        self.target = target.map(|(id, _)| *id);
+        if let Some((_, to)) = target {
+            let turn = self
+                .kinematics
+                .vel
+                .deg_to(&Vector::between(&pos, to))
+                .clamp(-HOMING_TURN_DEG, HOMING_TURN_DEG);
+            self.kinematics.vel = self.kinematics.vel.rotate(turn);
+        }
+    }
+
+    /// `enemy`に当たったときに与える傷。もう傷を与えた敵や、貫ける数を使い切った後なら`None`
+    pub fn strike(&mut self, enemy: EntityId) -> Option<u32> {
+        if self.is_spent() || self.hits.contains(&enemy) {
+            return None;
+        }
+        let distance = Vector::between(&self.origin, &self.transform.pos).length();
+        let scale = self.falloff.scale(distance, self.hits.len());
+        self.hits.push(enemy);
+        Some((self.damage as f32 * scale).round() as u32)
+    }
+
+    /// 貫ける数より多くの敵に当たって、消えるところか
+    pub fn is_spent(&self) -> bool {
+        self.hits.len() > self.falloff.pierce as usize
+    }
+}
+
+impl Components for PlayerShot {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    fn transform_mut(&mut self) -> &mut Transform {
+        &mut self.transform
+    }
+
+    fn kinematics(&self) -> Option<&Kinematics> {
+        Some(&self.kinematics)
+    }
+
+    fn hitbox(&self) -> Shape {
+        Shape::Circle(self.collider)
+    }
+
+    fn renderable(&self) -> &Renderable {
+        &self.renderable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::slice;
+
+    use crate::{component, entity::EntityIds};
+
+    const FROM: Point = Point { x: 300.0, y: 500.0 };
+
+    /// `Level`と同じ順で1フレーム進める
+    fn update(shot: &mut PlayerShot, playfield: &Playfield, enemies: &[(EntityId, Point)]) -> bool {
+        shot.steer(enemies);
+        component::integrate(slice::from_mut(shot), 1.0);
+        shot.settle(playfield)
+    }
+
+    /// 追いかける弾は一番近い敵の方へ、1フレームに曲がれる角度だけ曲がる
+    #[test]
+    fn homing_turns_towards_nearest_enemy() {
+        let mut ids = EntityIds::default();
+        let (near, far) = (ids.allocate(), ids.allocate());
+        let enemies = [
+            (far, Point { x: 100.0, y: 100.0 }),
+            (near, Point { x: 500.0, y: 400.0 }),
+        ];
+        let [mut shot, _] = PlayerShot::volley(0, FROM, ShotType::Homing);
+        let before = shot.kinematics.vel;
+        assert!(update(&mut shot, &Playfield::MAIN, &enemies));
+        assert!(shot.target == Some(near));
+        assert!(shot.transform.pos.x > FROM.x - SHOT_GAP);
+        let turned = before.deg_to(&shot.kinematics.vel);
+        assert!((turned - HOMING_TURN_DEG).abs() < 0.01, "turned {}", turned);
+    }
+
+    /// 狙っていた敵がいなくなれば、残っている敵を狙い直す
+    #[test]
+    fn homing_retargets_when_target_is_gone() {
+        let mut ids = EntityIds::default();
+        let (first, second) = (ids.allocate(), ids.allocate());
+        let [mut shot, _] = PlayerShot::volley(0, FROM, ShotType::Homing);
+        update(
+            &mut shot,
+            &Playfield::MAIN,
+            &[
+                (first, Point { x: 300.0, y: 300.0 }),
+                (second, Point { x: 100.0, y: 100.0 }),
+            ],
+        );
+        assert!(shot.target == Some(first));
+        update(
+            &mut shot,
+            &Playfield::MAIN,
+            &[(second, Point { x: 100.0, y: 100.0 })],
+        );
+        assert!(shot.target == Some(second));
+    }
+
+    /// まっすぐの弾は敵がいても曲がらず、追いかける弾より1発の傷が多い
+    #[test]
+    fn straight_shots_ignore_enemies() {
+        let mut ids = EntityIds::default();
+        let enemy = ids.allocate();
+        let [mut straight, _] = PlayerShot::volley(0, FROM, ShotType::Straight);
+        update(
+            &mut straight,
+            &Playfield::MAIN,
+            &[(enemy, Point { x: 500.0, y: 400.0 })],
+        );
+        assert_eq!(straight.transform.pos.x, FROM.x - SHOT_GAP);
+        let [mut homing, _] = PlayerShot::volley(0, FROM, ShotType::Homing);
+        assert!(straight.strike(enemy) > homing.strike(enemy));
+    }
+
+    /// 貫く弾は2体まで貫き、3体目に当たると消える。貫くごとに傷が減る
+    #[test]
+    fn piercing_shots_pass_through_enemies() {
+        let mut ids = EntityIds::default();
+        let [mut shot, _] = PlayerShot::volley(0, FROM, ShotType::Piercing);
+        let damage = [ids.allocate(), ids.allocate(), ids.allocate()].map(|enemy| {
+            let damage = shot.strike(enemy);
+            (damage, shot.is_spent())
+        });
+        assert!(damage[0] == (Some(SHOT_DAMAGE), false));
+        assert!(damage[1].0 < damage[0].0 && !damage[1].1);
+        assert!(damage[2].0 < damage[1].0 && damage[2].1);
+    }
+
+    /// 跳ね返る弾は端で1度だけ跳ね返り、2度目は外へ出て消える
+    #[test]
+    fn bouncing_shots_bounce_once() {
+        let playfield = Playfield::MAIN;
+        let [_, mut shot] = PlayerShot::volley(0, FROM, ShotType::Bouncing);
+        assert!(shot.kinematics.vel.deg_to(&Vector::new(0.0, -1.0)) < 0.0);
+        let mut frames = 0;
+        while shot.bounces > 0 {
+            assert!(update(&mut shot, &playfield, &[]));
+            frames += 1;
+            assert!(frames < 1000, "the shot never reached an edge");
+        }
+        assert!(playfield.contains(&shot.transform.pos));
+        while update(&mut shot, &playfield, &[]) {
+            frames += 1;
+            assert!(frames < 2000, "the shot never left after bouncing");
+        }
+    }
+}
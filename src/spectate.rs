@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use crate::{
+    browser::{Socket, SocketState},
+    input::{Actions, MAX_PLAYERS},
+    settings::{BombType, Modifiers, PlayMode, ShotType},
+};
+
+const BUFFER_FRAMES: usize = 30; // 観戦側で再生を始める前に溜めておく入力のフレーム数
+const MAX_STEPS_PER_FRAME: usize = 60; // 遅れを取り戻すときに1フレームで進める最大のフレーム数
+
+/// 配信しているプレイの始め方（シード、形式、ルール変更、ボムとショットの種類）
+type Run = (u64, PlayMode, Modifiers, BombType, ShotType);
+
+/// 観戦者に送るプレイの内容
+pub enum StreamEvent {
+    // このシードと形式、ルール変更、ボムとショットの種類で最初から始めた
+    Run {
+        seed: u64,
+        mode: PlayMode,
+        modifiers: Modifiers,
+        bomb: BombType,
+        shot: ShotType,
+    },
+    Tick([Actions; MAX_PLAYERS]), // 1フレーム進めた
+}
+
+impl StreamEvent {
+    /// `RUN <形式> <シード> <ルール変更> <ボムの種類> <ショットの種類>`か`I <1Pの入力>,<2Pの入力>`（入力は複数フレーム分並べてもよい）
+    fn encode_run(run: Run) -> String {
+        let (seed, mode, modifiers, bomb, shot) = run;
+        let mode = PlayMode::ALL.iter().position(|m| *m == mode).unwrap_or(0);
+        let bomb = BombType::ALL.iter().position(|b| *b == bomb).unwrap_or(0);
+        let shot = ShotType::ALL.iter().position(|s| *s == shot).unwrap_or(0);
+        format!(
+            "RUN {} {} {} {} {}",
+            mode,
+            seed,
+            modifiers.to_bits(),
+            bomb,
+            shot
+        )
+    }
+
+    fn encode_ticks(ticks: &[[u16; MAX_PLAYERS]]) -> String {
+        let ticks = ticks
+            .iter()
+            .map(|tick| format!("{},{}", tick[0], tick[1]))
+            .collect::<Vec<_>>()
+            .join(" ");
+        format!("I {}", ticks)
+    }
+
+    pub fn decode(message: &str, events: &mut VecDeque<StreamEvent>) {
+        let mut words = message.split_whitespace();
+        match words.next() {
+            Some("RUN") => {
+                let mode = words.next().and_then(|word| word.parse::<usize>().ok());
+                let seed = words.next().and_then(|word| word.parse::<u64>().ok());
+                // ルール変更のない配信元はこの欄を送ってこない
+                let modifiers = words
+                    .next()
+                    .and_then(|word| word.parse::<u8>().ok())
+                    .map(Modifiers::from_bits)
+                    .unwrap_or_default();
+                // ボムの種類も後から加えたので、古い配信元は送ってこない
+                let bomb = words
+                    .next()
+                    .and_then(|word| word.parse::<usize>().ok())
+                    .and_then(|b| BombType::ALL.get(b).copied())
+                    .unwrap_or_default();
+                // ショットの種類も同じく、古い配信元は送ってこない
+                let shot = words
+                    .next()
+                    .and_then(|word| word.parse::<usize>().ok())
+                    .and_then(|s| ShotType::ALL.get(s).copied())
+                    .unwrap_or_default();
+                if let (Some(mode), Some(seed)) = (mode.and_then(|m| PlayMode::ALL.get(m)), seed) {
+                    events.push_back(StreamEvent::Run {
+                        seed,
+                        mode: *mode,
+                        modifiers,
+                        bomb,
+                        shot,
+                    });
+                }
+            }
+            Some("I") => {
+                for tick in words {
+                    let mut bits = tick
+                        .split(',')
+                        .map(|bits| Actions::from_bits(bits.parse().unwrap_or(0)));
+                    events.push_back(StreamEvent::Tick(std::array::from_fn(|_| {
+                        bits.next().unwrap_or_default()
+                    })));
+                }
+            }
+            _ => {
+                log!("Unknown stream message: {}", message);
+            }
+        }
+    }
+}
+
+/// 今のプレイの入力を中継サーバーに流し続ける。
+/// 途中から観戦を始めた人のために、今のプレイの最初からの入力を覚えておく。
+pub struct Broadcaster {
+    socket: Socket,
+    run: Option<Run>,
+    ticks: Vec<[u16; MAX_PLAYERS]>, // 今のプレイの最初からの入力
+}
+
+impl Broadcaster {
+    pub fn connect(url: &str) -> Result<Self> {
+        Ok(Self {
+            socket: Socket::connect(url)?,
+            run: None,
+            ticks: Vec::new(),
+        })
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.socket.state() == SocketState::Open
+    }
+
+    /// 観戦者が来ていたら、今のプレイを最初から送る
+    pub fn poll(&mut self) {
+        while let Some(message) = self.socket.receive() {
+            if message == "WATCH" {
+                if let Some(run) = self.run {
+                    self.send(&StreamEvent::encode_run(run));
+                    if !self.ticks.is_empty() {
+                        self.send(&StreamEvent::encode_ticks(&self.ticks));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn start_run(
+        &mut self,
+        seed: u64,
+        mode: PlayMode,
+        modifiers: Modifiers,
+        bomb: BombType,
+        shot: ShotType,
+    ) {
+        let run = (seed, mode, modifiers, bomb, shot);
+        self.run = Some(run);
+        self.ticks.clear();
+        self.send(&StreamEvent::encode_run(run));
+    }
+
+    pub fn tick(&mut self, actions: &[Actions; MAX_PLAYERS]) {
+        let tick = actions.map(|actions| actions.to_bits());
+        self.ticks.push(tick);
+        self.send(&StreamEvent::encode_ticks(&[tick]));
+    }
+
+    fn send(&self, text: &str) {
+        if !self.is_live() {
+            return;
+        }
+        if let Err(err) = self.socket.send(text) {
+            log!("Could not send stream message: {:#?}", err);
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WatchStatus {
+    Connecting,
+    Buffering, // 入力が溜まるのを待っている
+    Watching,
+    Ended, // 配信が切れて、届いた分も全部再生した
+}
+
+/// 配信された入力を受け取り、手元で同じようにステージを進めて観戦する
+pub struct Spectator {
+    socket: Socket,
+    requested: bool, // 配信者に最初からの入力を頼んだか
+    buffering: bool,
+    events: VecDeque<StreamEvent>,
+}
+
+impl Spectator {
+    pub fn connect(url: &str) -> Result<Self> {
+        Ok(Self {
+            socket: Socket::connect(url)?,
+            requested: false,
+            buffering: true,
+            events: VecDeque::new(),
+        })
+    }
+
+    pub fn status(&self) -> WatchStatus {
+        match self.socket.state() {
+            SocketState::Connecting => WatchStatus::Connecting,
+            SocketState::Closed if self.events.is_empty() => WatchStatus::Ended,
+            _ if self.buffering => WatchStatus::Buffering,
+            _ => WatchStatus::Watching,
+        }
+    }
+
+    /// 届いた入力を取り込み、このフレームで再生する分を返す
+    pub fn poll(&mut self) -> Vec<StreamEvent> {
+        if self.socket.state() == SocketState::Open && !self.requested {
+            self.requested = true;
+            if let Err(err) = self.socket.send("WATCH") {
+                log!("Could not request stream: {:#?}", err);
+            }
+        }
+        while let Some(message) = self.socket.receive() {
+            StreamEvent::decode(&message, &mut self.events);
+        }
+
+        let buffered = self.buffered_ticks();
+        let closed = self.socket.state() == SocketState::Closed;
+        if buffered == 0 && !closed {
+            self.buffering = true;
+        }
+        if buffered >= BUFFER_FRAMES || closed {
+            self.buffering = false;
+        }
+        if self.buffering {
+            return Vec::new();
+        }
+
+        // 溜まりすぎたら早送りして、遅れを`BUFFER_FRAMES`くらいに保つ
+        let steps = if buffered > BUFFER_FRAMES * 2 {
+            (buffered - BUFFER_FRAMES).min(MAX_STEPS_PER_FRAME)
+        } else {
+            1
+        };
+        let mut played = Vec::new();
+        let mut ticks = 0;
+        while let Some(event) = self.events.pop_front() {
+            if matches!(event, StreamEvent::Tick(_)) {
+                ticks += 1;
+            }
+            played.push(event);
+            if ticks >= steps {
+                break;
+            }
+        }
+        played
+    }
+
+    fn buffered_ticks(&self) -> usize {
+        self.events
+            .iter()
+            .filter(|event| matches!(event, StreamEvent::Tick(_)))
+            .count()
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    browser,
+    engine::Renderer,
+    input::{Actions, Device, Prompt, TextKey},
+    level,
+    math::Point,
+    menu::ListMenu,
+    palette::Color,
+    version,
+};
+
+const STORAGE_KEY: &str = "spells";
+// 保存形式の版
+//   1: 最初の形式
+const FORMAT_VERSION: u32 = 1;
+const VISIBLE_ROWS: usize = 14; // 一覧に一度に出す行の数
+const ROW_HEIGHT: f32 = 24.0;
+
+/// ボスのスペル1つの記録
+#[derive(Clone)]
+pub struct SpellRecord {
+    name: String,
+    seen: u32,     // 出会った回数
+    captured: u32, // 取得した回数
+    best: u64,     // 取得したときのスコアの最高（まだ取得していなければ0）
+}
+
+impl SpellRecord {
+    /// 一覧の1行。名前と、取得した回数／出会った回数
+    fn summary(&self) -> String {
+        format!("{:<24}  {:>3}/{:<3}", self.name, self.captured, self.seen)
+    }
+
+    /// 選んでいるスペルの詳しい内容
+    fn details(&self) -> String {
+        if self.captured == 0 {
+            format!("Seen {}  Not captured yet", self.seen)
+        } else {
+            format!(
+                "Seen {}  Captured {}  Best {}",
+                self.seen, self.captured, self.best
+            )
+        }
+    }
+
+    /// `出会った回数 取得した回数 最高スコア 名前`。名前には空白が入るので最後に置く
+    fn serialize(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.seen, self.captured, self.best, self.name
+        )
+    }
+
+    fn deserialize(line: &str) -> Result<Self> {
+        let mut fields = line.splitn(4, ' ');
+        let mut next = || {
+            fields
+                .next()
+                .ok_or_else(|| anyhow!("Malformed spell record: {}", line))
+        };
+        let seen = next()?.parse()?;
+        let captured = next()?.parse()?;
+        let best = next()?.parse()?;
+        let name = next()?.to_string();
+        Ok(Self {
+            name,
+            seen,
+            captured,
+            best,
+        })
+    }
+}
+
+/// これまでに出会ったスペルの記録。初めて出会った順に並べる
+#[derive(Default)]
+pub struct SpellBook {
+    spells: Vec<SpellRecord>,
+}
+
+impl SpellBook {
+    /// 保存された記録を読み込む。まだなければ空の記録を返す。
+    pub fn load() -> Result<Self> {
+        browser::storage_get(STORAGE_KEY)?
+            .map(|text| Self::deserialize(&text))
+            .transpose()
+            .map(Option::unwrap_or_default)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        browser::storage_set(STORAGE_KEY, &self.serialize())
+    }
+
+    /// 版の印に続けて、1行に1つずつスペルの記録を並べた文字列
+    pub fn serialize(&self) -> String {
+        let body = self
+            .spells
+            .iter()
+            .map(SpellRecord::serialize)
+            .collect::<Vec<_>>()
+            .join("\n");
+        version::tag(FORMAT_VERSION, &body)
+    }
+
+    pub fn deserialize(text: &str) -> Result<Self> {
+        let (_, text) = version::split("spells", text, FORMAT_VERSION)?;
+        let spells = text
+            .lines()
+            .map(SpellRecord::deserialize)
+            .collect::<Result<_>>()?;
+        Ok(Self { spells })
+    }
+
+    /// `name`のスペルの記録。初めて出会ったなら一覧の最後に加える
+    fn entry(&mut self, name: &str) -> &mut SpellRecord {
+        let index = match self.spells.iter().position(|spell| spell.name == name) {
+            Some(index) => index,
+            None => {
+                self.spells.push(SpellRecord {
+                    name: name.to_string(),
+                    seen: 0,
+                    captured: 0,
+                    best: 0,
+                });
+                self.spells.len() - 1
+            }
+        };
+        &mut self.spells[index]
+    }
+
+    /// `name`のスペルが始まった
+    pub fn record_seen(&mut self, name: &str) {
+        let spell = self.entry(name);
+        spell.seen = spell.seen.saturating_add(1);
+    }
+
+    /// `name`のスペルを取得した。`score`は取得のボーナスを足したあとのスコア
+    pub fn record_capture(&mut self, name: &str, score: u64) {
+        let spell = self.entry(name);
+        spell.captured = spell.captured.saturating_add(1);
+        spell.best = spell.best.max(score);
+    }
+
+    pub fn spells(&self) -> &[SpellRecord] {
+        &self.spells
+    }
+
+    /// 一覧の`index`番目のスペルの名前
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.spells.get(index).map(|spell| spell.name.as_str())
+    }
+}
+
+/// スペルの一覧での操作の結果
+pub enum SpellBookEvent {
+    Changed,         // 選んでいるスペルが変わった
+    Practice(usize), // 選んだスペル（一覧での番号）を練習する
+}
+
+/// 出会ったスペルを眺め、選んだスペルの練習を始める画面
+#[derive(Default)]
+pub struct SpellBookView {
+    list: ListMenu,
+}
+
+impl SpellBookView {
+    /// 上下で選ぶスペルを変え、決定で練習を始める。今のステージにないスペルは練習できない
+    pub fn update(
+        &mut self,
+        pressed: &Actions,
+        held: &Actions,
+        typed: Option<TextKey>,
+        book: &SpellBook,
+    ) -> Option<SpellBookEvent> {
+        let moved = self.list.update(pressed, held, book.spells().len());
+        if typed == Some(TextKey::Enter) || pressed.shot {
+            return self
+                .selected(book)
+                .filter(|spell| level::can_practice_spell(&spell.name))
+                .map(|_| SpellBookEvent::Practice(self.list.cursor()));
+        }
+        (moved.up || moved.down).then_some(SpellBookEvent::Changed)
+    }
+
+    /// 選んでいるスペルの読み上げ用の文章
+    pub fn describe(&self, book: &SpellBook) -> String {
+        match self.selected(book) {
+            Some(spell) => format!("{}. {}", spell.name, spell.details()),
+            None => "No spells yet".to_string(),
+        }
+    }
+
+    fn selected<'a>(&self, book: &'a SpellBook) -> Option<&'a SpellRecord> {
+        book.spells().get(self.list.cursor())
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, book: &SpellBook, device: Device) {
+        renderer.set_color(Color::Text);
+        renderer.draw_text("SPELL CARDS", &Point { x: 100.0, y: 100.0 });
+
+        // 選んでいる行が見えるように、一覧を行単位でずらす
+        let first = self.list.cursor().saturating_sub(VISIBLE_ROWS - 1);
+        for (i, spell) in book
+            .spells()
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(VISIBLE_ROWS)
+        {
+            let y = 140.0 + ROW_HEIGHT * (i - first) as f32;
+            if i == self.list.cursor() {
+                renderer.draw_text(">", &Point { x: 80.0, y });
+            }
+            renderer.draw_text(&spell.summary(), &Point { x: 100.0, y });
+        }
+        match self.selected(book) {
+            Some(spell) => {
+                renderer.draw_text(&spell.details(), &Point { x: 100.0, y: 488.0 });
+                if !level::can_practice_spell(&spell.name) {
+                    renderer.draw_text(
+                        "Not in this stage",
+                        &Point {
+                            x: 100.0,
+                            y: 488.0 + ROW_HEIGHT,
+                        },
+                    );
+                }
+            }
+            None => renderer.draw_text("No spells yet", &Point { x: 100.0, y: 488.0 }),
+        }
+        renderer.draw_text(
+            &format!(
+                "{}: select  {}: practice  {}: back",
+                device.glyph(Prompt::Change),
+                device.glyph(Prompt::Confirm),
+                device.glyph(Prompt::Back)
+            ),
+            &Point { x: 100.0, y: 552.0 },
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! 道中の敵の出し方を、Rustのコードの中に読みやすく書くためのマクロ。
+//! コンテンツパックと同じ`Wave`の列になるが、書き間違いは読み込むときではなくコンパイルするときにエラーになる。
+
+/// `with`で撃つフレームを省いたときに、敵が出てから撃つまでのフレーム数（名前付きパターンと同じ）
+pub const FIRE_DELAY: u16 = 30;
+
+/// 道中の敵の出し方を書き、`Vec<Wave>`にする。1行に敵1体で、出すフレームの順に並べる。
+/// フレーム数は基準の速さ（`STANDARD_TICK_RATE`）で書く。
+///
+/// ```text
+/// stage! {
+///     at 120 => spawn fairy at (100, 50) with nways(5, 60 deg, aim);
+///     at 300 => spawn fairy at (250, 80) with nways(4, 90 deg, 90 deg) after 60, aim after 90;
+/// }
+/// ```
+/// - `at <フレーム>`：敵を出すフレーム（ステージの始まりから数える）
+/// - `spawn <敵の種類> at (<横>, <縦>)`：標準のプレイフィールドの左上からの位置。敵の種類は今は`fairy`だけ
+/// - `with <撃ち方> after <フレーム>, ...`：出てから何フレーム目に撃つか。`after`を省くと`FIRE_DELAY`
+///   - `nways(<発数>, <幅> deg, <中心の向き> deg)`：決まった向きへの扇
+///   - `nways(<発数>, <幅> deg, aim)`：一番近いプレイヤーに弾が届くときの予想位置を中心にした扇
+///   - `aim`：一番近いプレイヤーへの1発
+macro_rules! stage {
+    (@after) => {
+        $crate::stage::FIRE_DELAY
+    };
+    (@after $after:literal) => {
+        $after
+    };
+    (@shot $at:expr, nways($n:literal, $wide:literal deg, aim)) => {
+        $crate::level::EnemyEvent::aimed_nways($at, $n, $wide as f32)
+    };
+    (@shot $at:expr, nways($n:literal, $wide:literal deg, $center:literal deg)) => {
+        $crate::level::EnemyEvent::nways($at, $n, $wide as f32, $center as f32)
+    };
+    (@shot $at:expr, aim) => {
+        $crate::level::EnemyEvent::aim_shot($at)
+    };
+    ($(
+        at $at:literal => spawn fairy at ($x:literal, $y:literal)
+            with $($shot:ident $(($($arg:tt)*))? $(after $after:literal)?),+;
+    )*) => {
+        vec![$(
+            $crate::pack::Wave::new(
+                $at,
+                $x as f32,
+                $y as f32,
+                vec![$(
+                    $crate::stage::stage!(
+                        @shot $crate::stage::stage!(@after $($after)?),
+                        $shot $(($($arg)*))?
+                    )
+                ),+],
+            )
+        ),*]
+    };
+}
+
+pub(crate) use stage;
@@ -0,0 +1,77 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::Result;
+
+use crate::{
+    browser,
+    constants::rate_key,
+    input::{Actions, MAX_PLAYERS},
+    replay::Replay,
+};
+
+/// 中断セーブ。シミュレーションは決定的なので、リプレイと同じくシードと入力だけを残し、
+/// 再開するときには最初から入力を流し直して同じ状態に戻す。
+/// ページを閉じるときにも保存できるよう、記録はページのイベントと共有する。
+#[derive(Clone)]
+pub struct Suspend {
+    run: Rc<RefCell<Option<Replay>>>, // 中断できるプレイの最初からの記録。中断できないときは`None`
+    stage: &'static str,
+}
+
+impl Suspend {
+    /// ページを閉じるときに、中断できるプレイがあれば保存するようにする
+    pub fn install(stage: &'static str) -> Result<Self> {
+        let suspend = Self {
+            run: Rc::new(RefCell::new(None)),
+            stage,
+        };
+        let on_hide = suspend.clone();
+        browser::on_page_hide(move || {
+            if let Err(err) = on_hide.save() {
+                log!("Could not suspend run: {:#?}", err);
+            }
+        })?;
+        Ok(suspend)
+    }
+
+    fn storage_key(stage: &str) -> String {
+        rate_key(format!("suspend.{}", stage))
+    }
+
+    /// `replay`の続きから記録する
+    pub fn start(&self, replay: Replay) {
+        *self.run.borrow_mut() = Some(replay);
+    }
+
+    /// 今のプレイは中断できない（終わった、チートを使ったなど）。それまでの記録を返す。
+    pub fn stop(&self) -> Option<Replay> {
+        self.run.borrow_mut().take()
+    }
+
+    pub fn record(&self, actions: [Actions; MAX_PLAYERS]) {
+        if let Some(run) = self.run.borrow_mut().as_mut() {
+            run.push(actions);
+        }
+    }
+
+    /// 中断できるプレイがあれば保存する。保存したら`true`を返す。
+    pub fn save(&self) -> Result<bool> {
+        let Some(run) = &*self.run.borrow() else {
+            return Ok(false);
+        };
+        browser::storage_set(&Self::storage_key(self.stage), &run.serialize())?;
+        Ok(true)
+    }
+
+    /// 保存された中断セーブを読み込む
+    pub fn load(&self) -> Result<Option<Replay>> {
+        browser::storage_get(&Self::storage_key(self.stage))?
+            .map(|text| Replay::deserialize(&text))
+            .transpose()
+    }
+
+    /// 中断セーブを消す。再開したあとに同じところからやり直せないようにする。
+    pub fn discard(&self) -> Result<()> {
+        browser::storage_remove(&Self::storage_key(self.stage))
+    }
+}
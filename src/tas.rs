@@ -0,0 +1,116 @@
+use crate::{
+    input::{Actions, MAX_PLAYERS},
+    level::{Level, Snapshot},
+    replay::Replay,
+    settings::{BombType, Modifiers, PlayMode, ShotType},
+};
+
+pub const SLOTS: usize = 4; // セーブステートの枠の数
+
+/// ある時点のプレイ全体の状態
+struct SaveState {
+    level: Snapshot,
+    rival: Option<Snapshot>,
+    inputs: usize, // この時点までに記録した入力のフレーム数
+}
+
+/// TAS（ツールを使った記録作り）のための状態。
+/// 入力をフレーム単位で記録し、セーブステートに戻ったらそこから先を記録し直す。
+pub struct Tas {
+    seed: u64,
+    mode: PlayMode,
+    modifiers: Modifiers,
+    bomb: BombType,
+    shot: ShotType,
+    inputs: Vec<[Actions; MAX_PLAYERS]>, // 最初からの入力
+    slots: [Option<SaveState>; SLOTS],
+    slot: usize,  // 選んでいる枠
+    paused: bool, // 止めている間はコマ送りしたときだけ進める
+}
+
+impl Tas {
+    pub fn new(
+        seed: u64,
+        mode: PlayMode,
+        modifiers: Modifiers,
+        bomb: BombType,
+        shot: ShotType,
+    ) -> Self {
+        Self {
+            seed,
+            mode,
+            modifiers,
+            bomb,
+            shot,
+            inputs: Vec::new(),
+            slots: std::array::from_fn(|_| None),
+            slot: 0,
+            paused: true,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        if slot < SLOTS {
+            self.slot = slot;
+        }
+    }
+
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    pub fn frame(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_filled(&self, slot: usize) -> bool {
+        self.slots[slot].is_some()
+    }
+
+    /// 1フレーム進めたときの入力を記録する
+    pub fn record(&mut self, actions: [Actions; MAX_PLAYERS]) {
+        self.inputs.push(actions);
+    }
+
+    pub fn save(&mut self, level: &Level, rival: Option<&Level>) {
+        self.slots[self.slot] = Some(SaveState {
+            level: level.snapshot(),
+            rival: rival.map(Level::snapshot),
+            inputs: self.inputs.len(),
+        });
+    }
+
+    /// 選んでいる枠の状態に戻し、それより後の入力を捨てる。枠が空なら何もしない。
+    pub fn load(&mut self, level: &mut Level, rival: Option<&mut Level>) -> bool {
+        let Some(state) = &self.slots[self.slot] else {
+            return false;
+        };
+        level.restore(&state.level);
+        if let (Some(rival), Some(snapshot)) = (rival, &state.rival) {
+            rival.restore(snapshot);
+        }
+        self.inputs.truncate(state.inputs);
+        self.paused = true;
+        true
+    }
+
+    /// ここまでの入力をリプレイにする
+    pub fn to_replay(&self) -> Replay {
+        Replay::new(
+            self.seed,
+            self.mode,
+            self.modifiers,
+            self.bomb,
+            self.shot,
+            self.inputs.clone(),
+        )
+    }
+}
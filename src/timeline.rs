@@ -0,0 +1,124 @@
+use std::rc::Rc;
+
+/// 決まったフレームに起こる出来事
+pub trait Timed {
+    /// 列の始まりから何フレーム目に起こるか
+    fn at(&self) -> u32;
+}
+
+/// `at`の順に並んだ出来事の列を、時間に合わせて1つずつ取り出す。
+/// 同じフレームの出来事はいくつあってもすべて起こる。繰り返す設定なら、最後まで起こしたら同じ列を最初から起こし直す。
+/// 出来事は`at`のフレーム以降で最初に`advance`したときに起こるので、フレームを飛ばして進めても落ちない。
+#[derive(Clone)]
+pub struct Timeline<E> {
+    events: Rc<[E]>,
+    next: usize,         // 次に起こる出来事の番号
+    start: u32,          // 今の周の始まりのフレーム
+    period: Option<u32>, // 繰り返すなら1周のフレーム数
+}
+
+impl<E: Timed + Clone> Timeline<E> {
+    pub fn new(events: impl Into<Rc<[E]>>) -> Self {
+        Self {
+            events: events.into(),
+            next: 0,
+            start: 0,
+            period: None,
+        }
+    }
+
+    /// `period`フレームごとに、同じ列を最初から起こし直す
+    #[allow(dead_code)]
+    pub fn looping(mut self, period: u32) -> Self {
+        self.period = Some(period.max(1));
+        self
+    }
+
+    /// 出来事を`at`の順を崩さない位置に加える。同じフレームの出来事より後に起こる。
+    pub fn push(&mut self, event: E) {
+        let mut events = self.events.to_vec();
+        let index =
+            self.next + events[self.next..].partition_point(|other| other.at() <= event.at());
+        events.insert(index, event);
+        self.events = events.into();
+    }
+
+    /// 繰り返す前の、元の出来事の列
+    pub fn events(&self) -> &[E] {
+        &self.events
+    }
+
+    /// 次に起こる出来事
+    pub fn peek(&self) -> Option<&E> {
+        self.events.get(self.next)
+    }
+
+    /// 今の周でまだ起こしていない出来事を、起こるフレームと一緒に順に返す
+    pub fn upcoming(&self) -> impl Iterator<Item = (u32, &E)> {
+        self.events[self.next..]
+            .iter()
+            .map(|event| (self.start + event.at(), event))
+    }
+
+    /// 次の出来事が起こるフレーム
+    pub fn next_at(&self) -> Option<u32> {
+        self.peek().map(|event| self.start + event.at())
+    }
+
+    /// `frame`までに起こるはずの出来事を、まだ起こしていないものから1つ取り出す
+    pub fn advance(&mut self, frame: u32) -> Option<E> {
+        if self.next == self.events.len() {
+            let period = self.period.filter(|_| !self.events.is_empty())?;
+            self.start += period;
+            self.next = 0;
+        }
+        if self.next_at()? > frame {
+            return None;
+        }
+        self.next += 1;
+        Some(self.events[self.next - 1].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Timed for u32 {
+        fn at(&self) -> u32 {
+            *self
+        }
+    }
+
+    /// 飛ばしたフレームの出来事も、次に進めたときに順に起こる
+    #[test]
+    fn advance_catches_up_on_skipped_frames() {
+        let mut timeline = Timeline::new(vec![2, 3, 3, 7]);
+        assert_eq!(timeline.advance(1), None);
+        let due = std::iter::from_fn(|| timeline.advance(5)).collect::<Vec<_>>();
+        assert_eq!(due, [2, 3, 3]);
+        assert_eq!(timeline.next_at(), Some(7));
+    }
+
+    /// 繰り返す列は、最後まで起こしたら1周のフレーム数だけずらして最初から起こし直す
+    #[test]
+    fn looping_restarts_after_period() {
+        let mut timeline = Timeline::new(vec![1, 4]).looping(10);
+        let mut fired = Vec::new();
+        for frame in 0..=25 {
+            while timeline.advance(frame).is_some() {
+                fired.push(frame);
+            }
+        }
+        assert_eq!(fired, [1, 4, 11, 14, 21, 24]);
+        assert_eq!(timeline.next_at(), Some(31));
+
+        let mut once = Timeline::new(vec![1, 4]);
+        assert_eq!(
+            (0..=25)
+                .filter(|&frame| once.advance(frame).is_some())
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::engine::PhaseTimes;
+
+const BUCKET_MS: f32 = 0.5; // 1つの枠の幅
+const BUCKETS: usize = 100; // 50msまでを枠に分け、それより長いものは最後の枠にまとめる
+const PHASES: [&str; 4] = ["frame", "update", "draw", "present"]; // 描画の間隔と、処理の段
+
+/// 1つの段でかかった時間の分布
+#[derive(Clone)]
+struct Histogram {
+    counts: Vec<u32>, // `BUCKETS`個の枠と、それより長いものの枠
+    total_ms: f64,
+    max_ms: f32,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKETS + 1],
+            total_ms: 0.0,
+            max_ms: 0.0,
+        }
+    }
+}
+
+impl Histogram {
+    fn record(&mut self, ms: f32) {
+        let ms = ms.max(0.0);
+        let bucket = ((ms / BUCKET_MS) as usize).min(BUCKETS);
+        self.counts[bucket] += 1;
+        self.total_ms += ms as f64;
+        self.max_ms = self.max_ms.max(ms);
+    }
+
+    fn samples(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    fn mean_ms(&self) -> f64 {
+        self.total_ms / self.samples().max(1) as f64
+    }
+
+    /// 短い方から`ratio`の割合のところが入っている枠の上の端（枠の外なら一番長かった時間）
+    fn percentile_ms(&self, ratio: f32) -> f32 {
+        let wanted = (self.samples() as f32 * ratio).ceil().max(1.0) as u32;
+        let mut seen = 0;
+        for (bucket, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= wanted {
+                return if bucket < BUCKETS {
+                    (bucket + 1) as f32 * BUCKET_MS
+                } else {
+                    self.max_ms
+                };
+            }
+        }
+        0.0
+    }
+}
+
+/// 遊んでいる間の、描画1回ごとの間隔と処理の段ごとの時間の分布。
+/// 重いという報告に数字を添えてもらえるよう、デバッグ表示からJSONかCSVで書き出せる。
+#[derive(Clone, Default)]
+pub struct FrameTimings {
+    phases: [Histogram; 4], // `PHASES`の順
+}
+
+impl FrameTimings {
+    pub fn record_interval(&mut self, delta_ms: f32) {
+        self.phases[0].record(delta_ms);
+    }
+
+    pub fn record_phases(&mut self, times: PhaseTimes) {
+        for (histogram, ms) in
+            self.phases[1..]
+                .iter_mut()
+                .zip([times.update_ms, times.draw_ms, times.present_ms])
+        {
+            histogram.record(ms);
+        }
+    }
+
+    /// デバッグ表示に出す1行。段ごとの中央値と95パーセンタイル
+    pub fn summary(&self) -> String {
+        PHASES
+            .iter()
+            .zip(self.phases.iter())
+            .map(|(name, histogram)| {
+                format!(
+                    "{} {:.1}/{:.1}",
+                    &name[..1].to_uppercase(),
+                    histogram.percentile_ms(0.5),
+                    histogram.percentile_ms(0.95)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// 枠ごとの数を1行ずつ並べたCSV。列は枠の始まりと終わり（ミリ秒）と、段ごとの数
+    pub fn to_csv(&self) -> String {
+        let mut lines = vec![format!("start_ms,end_ms,{}", PHASES.join(","))];
+        for bucket in 0..=BUCKETS {
+            let end = if bucket < BUCKETS {
+                format!("{}", (bucket + 1) as f32 * BUCKET_MS)
+            } else {
+                String::new()
+            };
+            let counts = self
+                .phases
+                .iter()
+                .map(|histogram| histogram.counts[bucket].to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            lines.push(format!("{},{},{}", bucket as f32 * BUCKET_MS, end, counts));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// 段ごとの数、平均、最大、枠ごとの数をまとめたJSON。最後の枠は`BUCKETS`×`bucket_ms`より長かったもの
+    pub fn to_json(&self) -> String {
+        let phases = PHASES
+            .iter()
+            .zip(self.phases.iter())
+            .map(|(name, histogram)| {
+                let counts = histogram
+                    .counts
+                    .iter()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!(
+                    "\"{}\":{{\"samples\":{},\"mean_ms\":{:.3},\"max_ms\":{:.3},\"counts\":[{}]}}",
+                    name,
+                    histogram.samples(),
+                    histogram.mean_ms(),
+                    histogram.max_ms,
+                    counts
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"bucket_ms\":{},\"phases\":{{{}}}}}\n",
+            BUCKET_MS, phases
+        )
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::Result;
+
+use crate::{
+    browser,
+    constants::{ticks, STANDARD_TICK_RATE, TICK_RATE},
+    engine::Renderer,
+    input::{Device, Prompt},
+    layout::Layout,
+    level::Level,
+    math::Point,
+    palette::Color,
+    playfield::Playfield,
+    quality::Quality,
+    replay::Replay,
+    settings::Settings,
+};
+
+// 操作役に1分遊ばせて記録したもの。シミュレーションの動きを変えたら作り直す
+const DEMO_REPLAY: &str = include_str!("../static/demo.txt");
+const ATTRACT_DELAY: u32 = ticks(30.0); // タイトル画面で何も押されないまま、デモを始めるまでの長さ
+const BLINK_PERIOD: u32 = 30; // 「DEMO」の表示を点滅させる間隔（フレーム数）
+const HAS_DEMO: bool = TICK_RATE == STANDARD_TICK_RATE; // デモは基準の速さで記録したので、違う速さのビルドでは流さない
+
+/// デモとして同梱のリプレイを流しているステージ
+struct Demo {
+    level: Level,
+    replay: Replay,
+    frame: usize,
+}
+
+impl Demo {
+    fn load() -> Result<Self> {
+        let replay = Replay::deserialize(DEMO_REPLAY)?;
+        let level = Level::new(
+            replay.seed(),
+            &[0],
+            Playfield::MAIN,
+            replay.modifiers(),
+            replay.bomb(),
+            replay.shot(),
+        );
+        Ok(Self {
+            level,
+            replay,
+            frame: 0,
+        })
+    }
+
+    /// 1フレーム進める。最後まで流したら`false`を返す
+    fn update(&mut self) -> bool {
+        let Some(actions) = self.replay.inputs().get(self.frame) else {
+            return false;
+        };
+        self.level.update(actions);
+        self.frame += 1;
+        !self.level.is_game_over() && !self.level.is_cleared()
+    }
+}
+
+/// 遊び始める前のタイトル画面。しばらく何も押されなければ、デモを流して待つ（アトラクトモード）。
+/// デモの間に何か押されたら、遊び始めずにタイトル画面に戻る。
+#[derive(Default)]
+pub struct Title {
+    idle: u32, // 何も押されていないフレーム数
+    demo: Option<Demo>,
+}
+
+impl Title {
+    /// `active`は何かのキーかボタンを押しているか、`start`は遊び始める操作をしたか。
+    /// 遊び始めるなら`true`を返す
+    pub fn update(&mut self, active: bool, start: bool) -> bool {
+        if let Some(demo) = &mut self.demo {
+            if active || !demo.update() {
+                self.demo = None;
+                self.idle = 0;
+                let _ = browser::announce("Title");
+            }
+            return false;
+        }
+        if start {
+            return true;
+        }
+        self.idle = if active { 0 } else { self.idle + 1 };
+        if HAS_DEMO && self.idle >= ATTRACT_DELAY {
+            self.idle = 0;
+            match Demo::load() {
+                Ok(demo) => {
+                    self.demo = Some(demo);
+                    let _ = browser::announce("Demo");
+                }
+                Err(err) => {
+                    log!("Could not load demo replay: {:#?}", err);
+                }
+            }
+        }
+        false
+    }
+
+    pub fn draw(
+        &self,
+        renderer: &dyn Renderer,
+        settings: &Settings,
+        device: Device,
+        layout: Layout,
+    ) {
+        let field = Playfield::MAIN;
+        if let Some(demo) = &self.demo {
+            demo.level
+                .draw(renderer, settings, device, Quality::High, layout);
+            if (demo.frame as u32 / BLINK_PERIOD).is_multiple_of(2) {
+                renderer.set_color(Color::Text);
+                renderer.draw_large_text_centered(
+                    "DEMO",
+                    &Point {
+                        x: field.center_x(),
+                        y: field.center_y(),
+                    },
+                );
+            }
+            return;
+        }
+        renderer.set_color(Color::Border);
+        renderer.draw_rect(field.rect());
+        renderer.set_color(Color::Text);
+        renderer.draw_large_text_centered(
+            "wasm_game",
+            &Point {
+                x: field.center_x(),
+                y: field.center_y() - 40.0,
+            },
+        );
+        renderer.draw_text_centered(
+            &format!("{}: start", device.glyph(Prompt::Confirm)),
+            &Point {
+                x: field.center_x(),
+                y: field.center_y() + 40.0,
+            },
+        );
+    }
+}
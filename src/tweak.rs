@@ -0,0 +1,99 @@
+use crate::{engine::Renderer, input::Actions, math::Point, palette::Color};
+
+const STEP: f32 = 0.1; // 左右を1回押して変える倍率の幅
+const MIN_FACTOR: f32 = 0.1;
+const MAX_FACTOR: f32 = 3.0;
+const NAMES: [&str; 3] = ["player speed", "bullet speed", "boss health"];
+
+/// 作り直さずに釣り合いを試せるよう、実行中に変えられるゲームの調整値の倍率。どれも1.0なら元の値のまま
+#[derive(Clone, Copy, PartialEq)]
+pub struct Tweaks {
+    pub player_speed: f32, // 自機の速さ
+    pub bullet_speed: f32, // 敵弾の速さ（ルール変更の倍率にさらに掛ける）
+    pub boss_health: f32,  // ボスのスペルごとの体力（次のスペルから効く）
+}
+
+impl Default for Tweaks {
+    fn default() -> Self {
+        Self {
+            player_speed: 1.0,
+            bullet_speed: 1.0,
+            boss_health: 1.0,
+        }
+    }
+}
+
+impl Tweaks {
+    /// `NAMES`と同じ順の倍率
+    fn factors(&self) -> [f32; 3] {
+        [self.player_speed, self.bullet_speed, self.boss_health]
+    }
+
+    fn factor_mut(&mut self, index: usize) -> &mut f32 {
+        match index {
+            0 => &mut self.player_speed,
+            1 => &mut self.bullet_speed,
+            _ => &mut self.boss_health,
+        }
+    }
+}
+
+/// 開発中だけ使える、調整値の倍率を変える画面。開いている間はゲームを止める。
+/// 上下で項目を選び、左右で倍率を変える。
+pub struct TweakPanel {
+    tweaks: Tweaks,
+    selected: usize, // 選んでいる項目（`NAMES`の番号）
+}
+
+impl TweakPanel {
+    pub fn new(tweaks: Tweaks) -> Self {
+        Self {
+            tweaks,
+            selected: 0,
+        }
+    }
+
+    /// `pressed`で押された方向に従って項目を選ぶか倍率を変え、倍率が変わったら新しい値を返す
+    pub fn update(&mut self, pressed: &Actions) -> Option<Tweaks> {
+        if pressed.up {
+            self.selected = (self.selected + NAMES.len() - 1) % NAMES.len();
+        }
+        if pressed.down {
+            self.selected = (self.selected + 1) % NAMES.len();
+        }
+        let step = match (pressed.left, pressed.right) {
+            (true, false) => -STEP,
+            (false, true) => STEP,
+            _ => return None,
+        };
+        let factor = self.tweaks.factor_mut(self.selected);
+        // 0.1ずつ足し引きしても誤差が溜まらないよう、10分の1の位で丸める
+        *factor = ((*factor + step) * 10.0)
+            .round()
+            .clamp(MIN_FACTOR * 10.0, MAX_FACTOR * 10.0)
+            / 10.0;
+        Some(self.tweaks)
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer) {
+        renderer.set_color(Color::Text);
+        renderer.draw_text("TWEAKS", &Point { x: 60.0, y: 60.0 });
+        for (i, (name, factor)) in NAMES.iter().zip(self.tweaks.factors()).enumerate() {
+            let cursor = if i == self.selected { ">" } else { " " };
+            renderer.draw_text(
+                &format!("{} {}: x{:.1}", cursor, name, factor),
+                &Point {
+                    x: 60.0,
+                    y: 86.0 + 24.0 * i as f32,
+                },
+            );
+        }
+        renderer.draw_text(
+            "Up/Down: select  Left/Right: change  T: close",
+            &Point {
+                x: 60.0,
+                y: 86.0 + 24.0 * NAMES.len() as f32,
+            },
+        );
+    }
+}
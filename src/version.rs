@@ -0,0 +1,95 @@
+use anyhow::{anyhow, Result};
+
+/// 保存データの先頭の版の印`v<番号>`を付ける
+pub fn tag(version: u32, body: &str) -> String {
+    format!("v{} {}", version, body)
+}
+
+/// 保存データの先頭の版の印を読み取り、版と残りを返す。印を付ける前の古いデータは版0として扱う。
+/// このビルドより新しい版のデータは、読み違えて壊さないようにエラーにする。
+pub fn split<'a>(kind: &str, text: &'a str, current: u32) -> Result<(u32, &'a str)> {
+    let Some(rest) = text.strip_prefix('v') else {
+        return Ok((0, text));
+    };
+    let (number, rest) = rest.split_once([' ', '\n']).unwrap_or((rest, ""));
+    let version = number
+        .parse::<u32>()
+        .map_err(|_| anyhow!("Malformed {} version: {}", kind, number))?;
+    if version > current {
+        return Err(anyhow!(
+            "{} version {} is newer than this game can read (up to {})",
+            kind,
+            version,
+            current
+        ));
+    }
+    Ok((version, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        history::History,
+        profile::Profile,
+        replay::Replay,
+        settings::{BombType, ShotType},
+    };
+
+    // 前の版の形式で保存されたデータの見本。形式を変えたら、変える前の版の見本を`fixtures`に加える
+    const REPLAYS: [(&str, &str); 2] = [
+        ("replay v0", include_str!("../fixtures/replay.v0.txt")),
+        ("replay v1", include_str!("../fixtures/replay.v1.txt")),
+    ];
+    const PROFILES: [(&str, &str); 2] = [
+        ("profile v0", include_str!("../fixtures/profile.v0.txt")),
+        ("profile v1", include_str!("../fixtures/profile.v1.txt")),
+    ];
+    const HISTORIES: [(&str, &str); 2] = [
+        ("history v0", include_str!("../fixtures/history.v0.txt")),
+        ("history v1", include_str!("../fixtures/history.v1.txt")),
+    ];
+
+    #[test]
+    fn old_replays_load() {
+        for (name, text) in REPLAYS {
+            let replay = Replay::deserialize(text)
+                .unwrap_or_else(|err| panic!("Could not read fixture \"{}\": {:#}", name, err));
+            // 版0はボムを選べる前のリプレイなので、標準のボムになる
+            let bomb = if name.ends_with("v0") {
+                BombType::Standard
+            } else {
+                BombType::Seal
+            };
+            assert_eq!(replay.seed(), 12345, "{}", name);
+            assert_eq!(replay.len(), 4, "{}", name);
+            assert!(replay.bomb() == bomb, "{}: wrong bomb", name);
+            // どちらもショットを選べる前のリプレイなので、まっすぐのショットになる
+            assert!(replay.shot() == ShotType::Straight, "{}: wrong shot", name);
+        }
+    }
+
+    #[test]
+    fn old_profiles_load() {
+        for (name, text) in PROFILES {
+            let profile = Profile::deserialize(text)
+                .unwrap_or_else(|err| panic!("Could not read fixture \"{}\": {:#}", name, err));
+            assert_eq!(profile.clears(), 3, "{}", name);
+            assert!(profile.has_one_cc(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn old_histories_load() {
+        for (name, text) in HISTORIES {
+            let history = History::deserialize(text)
+                .unwrap_or_else(|err| panic!("Could not read fixture \"{}\": {:#}", name, err));
+            assert_eq!(history.runs().len(), 2, "{}", name);
+        }
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        assert!(super::split("replay", "v99 1 2 3", 1).is_err());
+        assert_eq!(super::split("replay", "1 2 3", 1).unwrap(), (0, "1 2 3"));
+    }
+}
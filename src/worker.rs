@@ -0,0 +1,335 @@
+//! ステージをWeb Workerで進めるモード。URLに`?worker`を付けて開くと始まる。
+//! Workerは`Level`を進めて描画命令の並び（`render_list`の文字列）を返し、
+//! メインスレッドは入力を送って、返ってきた並びをキャンバスに描くだけになる。
+//! Workerからは保存（`localStorage`）やゲームパッドの振動が使えないので、メニューも記録もなく、1人でステージを遊ぶだけにしている。
+//!
+//! メッセージはテキストで、1行目が種類を表す:
+//! - `START <seed>`: ステージを作り直す。2行目からはステージのデータファイルの中身（空なら組み込みの道中）
+//! - `STEP <bits> <bits> ...`: 1Pの1フレームごとの入力（`Actions::to_bits`）。この分だけ進めて描く
+//! - `READY`: Workerがメッセージを受け取れるようになった（Workerから）
+//! - `FRAME`: 進めた後の描画命令の並び。2行目から並びが続く（Workerから）
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use crate::{
+    browser::{self, WorkerThread},
+    engine::{DrawCall, Game, KeyState, RecordingRenderer, Renderer},
+    input::{Actions, Device, Input},
+    layout::Layout,
+    level::Level,
+    math::Rect,
+    pack::Pack,
+    playfield::Playfield,
+    quality::Quality,
+    render_list,
+    settings::{BombType, Modifiers, Settings, ShotType},
+};
+
+const WORKER_URL: &str = "worker.js"; // webpackが`js/worker.js`から作るWorkerのスクリプト
+
+/// メインスレッドからWorkerへのメッセージ
+enum Request {
+    Start { seed: u64, stage: String },
+    Step(Vec<u16>),
+}
+
+impl Request {
+    fn encode(&self) -> String {
+        match self {
+            Request::Start { seed, stage } => format!("START {}\n{}", seed, stage),
+            Request::Step(inputs) => {
+                let inputs = inputs
+                    .iter()
+                    .map(|bits| bits.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!("STEP {}", inputs)
+            }
+        }
+    }
+
+    fn decode(message: &str) -> Result<Self> {
+        let (head, body) = message.split_once('\n').unwrap_or((message, ""));
+        let mut words = head.split_whitespace();
+        match words.next() {
+            Some("START") => {
+                let seed = words
+                    .next()
+                    .and_then(|word| word.parse().ok())
+                    .ok_or_else(|| anyhow!("Malformed start: {}", head))?;
+                Ok(Request::Start {
+                    seed,
+                    stage: body.to_string(),
+                })
+            }
+            Some("STEP") => Ok(Request::Step(
+                words
+                    .map(|word| word.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| anyhow!("Malformed step: {}", head))?,
+            )),
+            _ => Err(anyhow!("Unknown worker request: {}", head)),
+        }
+    }
+}
+
+/// Workerからメインスレッドへのメッセージ
+enum Reply {
+    Ready,
+    Frame(String),
+}
+
+impl Reply {
+    fn encode(&self) -> String {
+        match self {
+            Reply::Ready => "READY".to_string(),
+            Reply::Frame(calls) => format!("FRAME\n{}", calls),
+        }
+    }
+
+    fn decode(message: &str) -> Result<Self> {
+        match message.split_once('\n').unwrap_or((message, "")) {
+            ("READY", _) => Ok(Reply::Ready),
+            ("FRAME", calls) => Ok(Reply::Frame(calls.to_string())),
+            (head, _) => Err(anyhow!("Unknown worker reply: {}", head)),
+        }
+    }
+}
+
+/// Workerの中で進めるステージ
+struct Simulation {
+    seed: u64,
+    stage: Option<Pack>,
+    level: Level,
+    settings: Settings,
+}
+
+impl Simulation {
+    /// `stage`はステージのデータファイルの中身。空なら組み込みの道中で遊ぶ
+    fn new(seed: u64, stage: &str) -> Result<Self> {
+        let stage = if stage.trim().is_empty() {
+            None
+        } else {
+            Some(Pack::parse(stage)?)
+        };
+        Ok(Self {
+            seed,
+            level: Self::fresh_level(seed, stage.as_ref()),
+            stage,
+            settings: Settings::default(),
+        })
+    }
+
+    fn fresh_level(seed: u64, stage: Option<&Pack>) -> Level {
+        let level = Level::new(
+            seed,
+            &[0],
+            Playfield::MAIN,
+            Modifiers::default(),
+            BombType::default(),
+            ShotType::default(),
+        );
+        match stage {
+            Some(stage) => level.with_stage(stage),
+            None => level,
+        }
+    }
+
+    /// 1フレームごとの入力で進め、進めた後の描画命令の並びを文字列にして返す
+    fn step(&mut self, inputs: &[u16]) -> String {
+        for &bits in inputs {
+            let actions = Actions::from_bits(bits);
+            // 残機がなくなったら、やり直しの操作で同じシードから始め直す
+            if self.level.is_game_over() && actions.retry {
+                self.level = Self::fresh_level(self.seed, self.stage.as_ref());
+            }
+            self.level.update(&[actions]);
+        }
+        let renderer = RecordingRenderer::default();
+        renderer.clear(&Rect {
+            x: 0.0,
+            y: 0.0,
+            width: 600.0,
+            height: 600.0,
+        });
+        self.level.draw(
+            &renderer,
+            &self.settings,
+            Device::default(),
+            Quality::High,
+            Layout::Landscape,
+        );
+        render_list::encode(&renderer.take())
+    }
+}
+
+/// Workerの入口。メインスレッドからのメッセージを待ち、`STEP`が来るたびに進めて描画命令の並びを返す
+pub fn serve() -> Result<()> {
+    let mut simulation: Option<Simulation> = None;
+    browser::on_main_thread_message(move |message| {
+        let reply = match Request::decode(&message) {
+            Ok(Request::Start { seed, stage }) => {
+                simulation = Simulation::new(seed, &stage)
+                    .map_err(|err| log!("Could not start simulation: {:#?}", err))
+                    .ok();
+                None
+            }
+            Ok(Request::Step(inputs)) => simulation
+                .as_mut()
+                .map(|simulation| Reply::Frame(simulation.step(&inputs))),
+            Err(err) => {
+                log!("{:#?}", err);
+                None
+            }
+        };
+        if let Some(reply) = reply {
+            if let Err(err) = browser::post_to_main_thread(&reply.encode()) {
+                log!("{:#?}", err);
+            }
+        }
+    })?;
+    browser::post_to_main_thread(&Reply::Ready.encode())
+}
+
+/// メインスレッドの側。入力を1フレームずつ溜めて送り、Workerから届いた一番新しい描画命令の並びを描く。
+/// 前に送った入力の返事が届くまでは次を送らず、その間の入力は溜めておいて次にまとめて送る
+pub struct WorkerGame {
+    thread: WorkerThread,
+    input: Input,
+    settings: Settings,
+    start: Request,        // Workerの準備ができたら送る`START`
+    ready: bool,           // `START`を送ったか
+    waiting: bool,         // 送った入力の返事を待っているか
+    pending: Vec<u16>,     // まだ送っていない入力
+    latest: Vec<DrawCall>, // 最後に届いた描画命令の並び
+}
+
+impl WorkerGame {
+    /// `stage`はステージのデータファイルの中身。空なら組み込みの道中で遊ぶ
+    pub fn start(seed: u64, stage: String) -> Result<Self> {
+        log!("Running the stage in a worker (seed {})", seed);
+        Ok(Self {
+            thread: WorkerThread::spawn(WORKER_URL)?,
+            input: Input::new(),
+            settings: Settings::default(),
+            start: Request::Start { seed, stage },
+            ready: false,
+            waiting: false,
+            pending: Vec::new(),
+            latest: Vec::new(),
+        })
+    }
+
+    fn send(&self, request: &Request) {
+        if let Err(err) = self.thread.send(&request.encode()) {
+            log!("{:#?}", err);
+        }
+    }
+
+    /// 届いた返事を処理する
+    fn poll(&mut self) {
+        while let Some(message) = self.thread.receive() {
+            match Reply::decode(&message) {
+                Ok(Reply::Ready) => {
+                    self.send(&self.start);
+                    self.ready = true;
+                }
+                Ok(Reply::Frame(calls)) => {
+                    self.waiting = false;
+                    match render_list::decode(&calls) {
+                        Ok(calls) => self.latest = calls,
+                        Err(err) => log!("Could not decode frame from worker: {:#?}", err),
+                    }
+                }
+                Err(err) => log!("{:#?}", err),
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Game for WorkerGame {
+    async fn initialize(&self) -> Result<Box<dyn Game>> {
+        Err(anyhow!("Error: WorkerGame is already initialized!"))
+    }
+
+    fn update(&mut self, keystate: &KeyState) {
+        self.poll();
+        let [actions, ..] = self.input.update(keystate, &self.settings);
+        // 始まる前の入力は送らない
+        if !self.ready {
+            return;
+        }
+        self.pending.push(actions.to_bits());
+        if !self.waiting {
+            let inputs = std::mem::take(&mut self.pending);
+            self.send(&Request::Step(inputs));
+            self.waiting = true;
+        }
+    }
+
+    fn draw(&self, renderer: &dyn Renderer) {
+        render_list::replay(&self.latest, renderer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let stage = "wave 0\nenemy 300 40";
+        match Request::decode(
+            &Request::Start {
+                seed: 42,
+                stage: stage.to_string(),
+            }
+            .encode(),
+        ) {
+            Ok(Request::Start { seed: 42, stage: s }) => assert_eq!(s, stage),
+            _ => panic!("START did not round trip"),
+        }
+        match Request::decode(&Request::Step(vec![0, 5, 511]).encode()) {
+            Ok(Request::Step(inputs)) => assert_eq!(inputs, [0, 5, 511]),
+            _ => panic!("STEP did not round trip"),
+        }
+        match Reply::decode(&Reply::Frame("S\nZ".to_string()).encode()) {
+            Ok(Reply::Frame(calls)) => assert_eq!(calls, "S\nZ"),
+            _ => panic!("FRAME did not round trip"),
+        }
+        assert!(matches!(Reply::decode("READY"), Ok(Reply::Ready)));
+        assert!(Request::decode("STEP 1 x").is_err());
+    }
+
+    /// Workerが返す描画命令の並びは、同じ入力でその場でステージを進めて描いたものと同じになる
+    #[test]
+    fn simulation_draws_like_the_level() {
+        let mut simulation = Simulation::new(7, "").unwrap();
+        let inputs = [Actions {
+            left: true,
+            shot: true,
+            ..Actions::default()
+        }
+        .to_bits(); 30];
+        simulation.step(&inputs[..10]);
+        let frame = simulation.step(&inputs[10..]);
+
+        let mut level = Simulation::fresh_level(7, None);
+        for &bits in &inputs {
+            level.update(&[Actions::from_bits(bits)]);
+        }
+        let renderer = RecordingRenderer::default();
+        level.draw(
+            &renderer,
+            &Settings::default(),
+            Device::default(),
+            Quality::High,
+            Layout::Landscape,
+        );
+        let calls = render_list::decode(&frame).unwrap();
+        assert!(matches!(calls.first(), Some(DrawCall::Clear(_))));
+        assert_eq!(calls[1..], renderer.take());
+    }
+}
@@ -9,6 +9,8 @@ module.exports = {
   mode: "production",
   entry: {
     index: "./js/index.js",
+    // `?worker`で開いたときに、ステージを進めるWeb Worker（`src/worker.rs`）
+    worker: { import: "./js/worker.js", chunkLoading: "import-scripts" },
   },
   output: {
     path: dist,
@@ -22,6 +24,7 @@ module.exports = {
 
     new WasmPackPlugin({
       crateDirectory: __dirname,
+      extraArgs: process.env.HIGH_REFRESH ? "--features high_refresh" : "",
     }),
   ],
 };